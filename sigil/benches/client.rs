@@ -0,0 +1,97 @@
+//! The cost of commanding a node through its `SwarmClient`, and of gossip between nodes running in
+//! this process.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sigil::client::SwarmClient;
+use sigil::event::NodeEvent;
+use sigil::testing::TestNetwork;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+
+/// How many messages each iteration of the gossip benchmark publishes.
+const MESSAGES: u64 = 100;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build runtime")
+}
+
+/// A command's trip to the node's event loop and back, which every client call makes.
+fn command_round_trip(c: &mut Criterion) {
+    let runtime = runtime();
+    let network = runtime.block_on(async { TestNetwork::start(1).expect("failed to start node") });
+    let client = network.nodes()[0].client.clone();
+    c.bench_function("command_round_trip", |b| {
+        b.to_async(&runtime).iter(|| async {
+            client.connected_peers().await.expect("node is running");
+        })
+    });
+    runtime.block_on(network.shutdown());
+}
+
+/// Messages published by one node until the last of the others has received them all.
+fn gossip_throughput(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("gossip_throughput");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.sample_size(10);
+    for size in [2, 4, 8] {
+        let network = runtime.block_on(async {
+            let network = TestNetwork::start(size).expect("failed to start network");
+            network
+                .wait_for_connections(Duration::from_secs(30))
+                .await
+                .expect("nodes did not connect");
+            network
+        });
+        let clients = network.clients();
+        let topic = network.nodes()[0].config.topics.application[0].clone();
+        let publisher = clients[0].clone();
+        let receiver = clients[size - 1].clone();
+        runtime.block_on(wait_for_subscribers(&publisher, &topic));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.to_async(&runtime).iter(|| async {
+                let mut events = receiver.subscribe_events();
+                for i in 0..MESSAGES {
+                    publisher
+                        .gossipsub_publish(&topic, i.to_be_bytes().to_vec())
+                        .await
+                        .expect("publish failed");
+                }
+                receive(&mut events, MESSAGES).await;
+            })
+        });
+        runtime.block_on(network.shutdown());
+    }
+    group.finish();
+}
+
+/// Publish until gossipsub knows of peers subscribed to `topic`, which it learns just after
+/// connecting.
+async fn wait_for_subscribers(publisher: &SwarmClient, topic: &str) {
+    while publisher
+        .gossipsub_publish(topic, b"warm up".to_vec())
+        .await
+        .is_err()
+    {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+async fn receive(events: &mut broadcast::Receiver<NodeEvent>, count: u64) {
+    let mut received = 0;
+    while received < count {
+        match events.recv().await {
+            Ok(NodeEvent::Message(_)) => received += 1,
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(missed)) => received += missed,
+            Err(broadcast::error::RecvError::Closed) => panic!("receiver stopped"),
+        }
+    }
+}
+
+criterion_group!(benches, command_round_trip, gossip_throughput);
+criterion_main!(benches);
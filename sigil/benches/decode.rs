@@ -0,0 +1,55 @@
+//! The decoding of the messages peers gossip, which every received message goes through before it
+//! is validated.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use sigil::batch::{Batch, BatchLimits};
+use sigil::block::BlockHash;
+use sigil::envelope::Envelope;
+use sigil::mempool::Transaction;
+use sigil::version::node_name;
+
+fn envelope(c: &mut Criterion) {
+    let encoded = Envelope::new(vec![7; 1024]).encode();
+    let mut group = c.benchmark_group("envelope");
+    group.throughput(Throughput::Bytes(encoded.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| Envelope::decode(black_box(&encoded)).expect("envelope decodes"))
+    });
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    let batch = Batch {
+        number: 1,
+        parent_hash: BlockHash::of(b"parent"),
+        l1_origin: 1,
+        timestamp: 0,
+        transactions: (0..100)
+            .map(|nonce| Transaction {
+                nonce,
+                data: vec![nonce as u8; 256],
+            })
+            .collect(),
+    };
+    let encoded = batch.encode();
+    let limits = BatchLimits {
+        max_size: usize::MAX,
+        max_decompressed_size: usize::MAX,
+    };
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Bytes(encoded.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| Batch::decode(black_box(&encoded), limits).expect("batch decodes"))
+    });
+    group.finish();
+}
+
+fn agent_version(c: &mut Criterion) {
+    let agent_version = "sigil/0.1.0 (sequencer-eu-1)";
+    c.bench_function("agent_version/node_name", |b| {
+        b.iter(|| node_name(black_box(agent_version)))
+    });
+}
+
+criterion_group!(benches, envelope, batch, agent_version);
+criterion_main!(benches);
@@ -1,4 +1,11 @@
 use std::env;
+use std::fs;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The dependencies whose versions are reported alongside the build, as they shape how the node
+/// talks to its peers and clients.
+const REPORTED_DEPENDENCIES: &[&str] = &["libp2p", "libp2p-gossipsub", "jsonrpsee", "tokio"];
 
 fn main() {
     match env::var("BUILD_SCRIPT_USED") {
@@ -7,4 +14,92 @@ fn main() {
             panic!("Please build using the provided `build` script!");
         }
     }
+
+    // Record the commit being built, which builds outside a checkout, such as in Docker, may pass
+    // in through `SIGIL_GIT_HASH`.
+    println!("cargo:rerun-if-env-changed=SIGIL_GIT_HASH");
+    let hash = env::var("SIGIL_GIT_HASH").ok().or_else(|| {
+        let git_dir = git(&["rev-parse", "--git-dir"])?;
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/refs/heads");
+        git(&["rev-parse", "--short=12", "HEAD"])
+    });
+    println!(
+        "cargo:rustc-env=SIGIL_GIT_HASH={}",
+        hash.as_deref().unwrap_or("unknown")
+    );
+
+    // Record when the binary was built, which reproducible builds pin through `SOURCE_DATE_EPOCH`.
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let built = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("the clock is past the epoch")
+                .as_secs()
+        });
+    println!("cargo:rustc-env=SIGIL_BUILD_TIMESTAMP={}", rfc3339(built));
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command(&rustc, &["--version"]);
+    println!(
+        "cargo:rustc-env=SIGIL_RUSTC_VERSION={}",
+        rustc_version.as_deref().unwrap_or("unknown")
+    );
+
+    // Record the locked versions of the reported dependencies.
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    for name in REPORTED_DEPENDENCIES {
+        println!(
+            "cargo:rustc-env=SIGIL_DEPENDENCY_{}={}",
+            name.to_uppercase().replace('-', "_"),
+            locked_version(&lock, name).unwrap_or("unknown")
+        );
+    }
+}
+
+/// The version of the package `name` in a `Cargo.lock`.
+fn locked_version<'a>(lock: &'a str, name: &str) -> Option<&'a str> {
+    let mut lines = lock.lines();
+    lines.find(|line| *line == format!("name = \"{name}\""))?;
+    lines
+        .next()?
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+}
+
+/// Seconds since the epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(secs: u64) -> String {
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Convert days since the epoch to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    command("git", args)
+}
+
+fn command(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
@@ -0,0 +1,15 @@
+//! The node name that peers append to the agent version they identify with.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigil::version::node_name;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(agent_version) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Some(name) = node_name(agent_version) {
+        assert!(!name.is_empty());
+        assert!(agent_version.ends_with(&format!(" ({name})")));
+    }
+});
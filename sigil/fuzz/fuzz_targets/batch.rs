@@ -0,0 +1,21 @@
+//! Sealed batches, whose compressed size and claimed decompressed size both come from the sender.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigil::batch::{Batch, BatchLimits};
+
+const LIMITS: BatchLimits = BatchLimits {
+    max_size: 64 * 1024,
+    max_decompressed_size: 256 * 1024,
+};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(batch) = Batch::decode(data, LIMITS) {
+        let unlimited = BatchLimits {
+            max_size: usize::MAX,
+            max_decompressed_size: usize::MAX,
+        };
+        let decoded = Batch::decode(&batch.encode(), unlimited).expect("encoded batches decode");
+        assert_eq!(decoded.hash(), batch.hash());
+    }
+});
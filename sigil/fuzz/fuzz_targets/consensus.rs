@@ -0,0 +1,12 @@
+//! Sequenced consensus messages, whose session and sequence numbers order a sender's messages.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigil::consensus::SequencedMessage;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(message) = SequencedMessage::decode(data) {
+        let decoded = SequencedMessage::decode(&message.encode()).expect("encoded messages decode");
+        assert_eq!(decoded.encode(), message.encode());
+    }
+});
@@ -0,0 +1,17 @@
+//! Envelopes and acknowledgements, which every application message and ack arrives in.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigil::envelope::{Ack, Envelope};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(envelope) = Envelope::decode(data) {
+        let decoded = Envelope::decode(&envelope.encode()).expect("encoded envelopes decode");
+        assert_eq!(decoded.payload, envelope.payload);
+        assert_eq!(decoded.nonce, envelope.nonce);
+    }
+    if let Ok(ack) = Ack::decode(data) {
+        let decoded = Ack::decode(&ack.encode()).expect("encoded acks decode");
+        assert_eq!((decoded.id, decoded.publisher), (ack.id, ack.publisher));
+    }
+});
@@ -0,0 +1,14 @@
+//! Transactions gossiped into the mempool.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigil::mempool::Transaction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transaction) = Transaction::decode(data) {
+        let decoded =
+            Transaction::decode(&transaction.encode()).expect("encoded transactions decode");
+        assert_eq!(decoded, transaction);
+        assert_eq!(decoded.hash(), transaction.hash());
+    }
+});
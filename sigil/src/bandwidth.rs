@@ -0,0 +1,252 @@
+use crate::config::{BandwidthConfig, ByteRateConfig};
+use futures::{ready, AsyncRead, AsyncWrite};
+use libp2p::core::muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::Sleep;
+
+/// A token bucket of bytes, which allows bursts of up to `burst_bytes` and refills at
+/// `bytes_per_second`. Streams sharing a bucket may overdraw it by one read or write each, which
+/// later transfers wait off.
+#[derive(Debug)]
+pub struct ByteBucket {
+    rate: ByteRateConfig,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl ByteBucket {
+    pub fn new(rate: ByteRateConfig) -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                tokens: rate.burst_bytes as f64,
+                updated: Instant::now(),
+            }),
+            rate,
+        }
+    }
+
+    /// How many bytes may pass now, or how long until some may.
+    pub fn available(&self, now: Instant) -> Result<usize, Duration> {
+        let mut state = self.state.lock().expect("bucket lock is never poisoned");
+        let elapsed = now.saturating_duration_since(state.updated).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate.bytes_per_second as f64)
+            .min(self.rate.burst_bytes.max(1) as f64);
+        state.updated = now;
+        if state.tokens >= 1.0 {
+            Ok(state.tokens as usize)
+        } else {
+            let rate = self.rate.bytes_per_second.max(1) as f64;
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / rate))
+        }
+    }
+
+    /// Take `bytes` that have passed from the bucket.
+    pub fn consume(&self, bytes: usize) {
+        self.state
+            .lock()
+            .expect("bucket lock is never poisoned")
+            .tokens -= bytes as f64;
+    }
+}
+
+/// Caps on the bytes the node's connections carry, applied to every substream of every
+/// connection the transport yields.
+#[derive(Clone, Debug, Default)]
+pub struct Bandwidth {
+    inbound: Option<Arc<ByteBucket>>,
+    outbound: Option<Arc<ByteBucket>>,
+    inbound_per_connection: Option<ByteRateConfig>,
+    outbound_per_connection: Option<ByteRateConfig>,
+}
+
+impl Bandwidth {
+    pub fn new(config: &BandwidthConfig) -> Self {
+        Self {
+            inbound: bucket(&config.inbound),
+            outbound: bucket(&config.outbound),
+            inbound_per_connection: config.inbound_per_connection.clone(),
+            outbound_per_connection: config.outbound_per_connection.clone(),
+        }
+    }
+
+    /// `muxer` with its substreams throttled, or `muxer` itself if bandwidth is unlimited.
+    pub fn throttle(&self, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        let buckets = Buckets {
+            inbound: self
+                .inbound
+                .iter()
+                .cloned()
+                .chain(bucket(&self.inbound_per_connection))
+                .collect(),
+            outbound: self
+                .outbound
+                .iter()
+                .cloned()
+                .chain(bucket(&self.outbound_per_connection))
+                .collect(),
+        };
+        if buckets.inbound.is_empty() && buckets.outbound.is_empty() {
+            return muxer;
+        }
+        StreamMuxerBox::new(Throttled {
+            inner: muxer,
+            buckets,
+        })
+    }
+}
+
+fn bucket(rate: &Option<ByteRateConfig>) -> Option<Arc<ByteBucket>> {
+    rate.clone().map(ByteBucket::new).map(Arc::new)
+}
+
+/// The buckets that a connection's reads and writes are taken from.
+#[derive(Clone)]
+struct Buckets {
+    inbound: Vec<Arc<ByteBucket>>,
+    outbound: Vec<Arc<ByteBucket>>,
+}
+
+struct Throttled {
+    inner: StreamMuxerBox,
+    buckets: Buckets,
+}
+
+impl StreamMuxer for Throttled {
+    type Substream = ThrottledStream<<StreamMuxerBox as StreamMuxer>::Substream>;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let stream = ready!(Pin::new(&mut this.inner).poll_inbound(cx))?;
+        Poll::Ready(Ok(ThrottledStream::new(stream, this.buckets.clone())))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let stream = ready!(Pin::new(&mut this.inner).poll_outbound(cx))?;
+        Poll::Ready(Ok(ThrottledStream::new(stream, this.buckets.clone())))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll(cx)
+    }
+}
+
+/// A substream whose reads and writes wait for their buckets to allow them.
+pub struct ThrottledStream<S> {
+    inner: S,
+    buckets: Buckets,
+    read_delay: Option<Pin<Box<Sleep>>>,
+    write_delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> ThrottledStream<S> {
+    fn new(inner: S, buckets: Buckets) -> Self {
+        Self {
+            inner,
+            buckets,
+            read_delay: None,
+            write_delay: None,
+        }
+    }
+}
+
+/// Wait until every bucket has bytes to spare, returning how many all of them allow.
+fn poll_allowance(
+    buckets: &[Arc<ByteBucket>],
+    delay: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut Context<'_>,
+) -> Poll<usize> {
+    loop {
+        if let Some(sleep) = delay {
+            ready!(sleep.as_mut().poll(cx));
+            *delay = None;
+        }
+        let now = Instant::now();
+        let mut allowance = usize::MAX;
+        let mut wait = Duration::ZERO;
+        for bucket in buckets {
+            match bucket.available(now) {
+                Ok(available) => allowance = allowance.min(available),
+                Err(until) => wait = wait.max(until),
+            }
+        }
+        if wait.is_zero() {
+            return Poll::Ready(allowance);
+        }
+        *delay = Some(Box::pin(tokio::time::sleep(wait)));
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ThrottledStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let allowance = ready!(poll_allowance(
+            &this.buckets.inbound,
+            &mut this.read_delay,
+            cx
+        ));
+        let limit = buf.len().min(allowance);
+        let read = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[..limit]))?;
+        for bucket in &this.buckets.inbound {
+            bucket.consume(read);
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ThrottledStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let allowance = ready!(poll_allowance(
+            &this.buckets.outbound,
+            &mut this.write_delay,
+            cx
+        ));
+        let limit = buf.len().min(allowance);
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..limit]))?;
+        for bucket in &this.buckets.outbound {
+            bucket.consume(written);
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
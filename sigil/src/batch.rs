@@ -0,0 +1,86 @@
+use crate::block::BlockHash;
+use crate::mempool::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A sealed batch of L2 transactions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    pub number: u64,
+    pub parent_hash: BlockHash,
+
+    /// The number of the L1 block this batch derives from.
+    pub l1_origin: u64,
+    pub timestamp: u64,
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Debug)]
+pub enum BatchError {
+    /// The batch, compressed or not, exceeds the size limit.
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    Decompress(snap::Error),
+    Malformed(serde_json::Error),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::TooLarge { size, limit } => {
+                write!(
+                    f,
+                    "batch of {size} bytes exceeds the limit of {limit} bytes"
+                )
+            }
+            BatchError::Decompress(e) => write!(f, "failed to decompress batch: {e}"),
+            BatchError::Malformed(e) => write!(f, "malformed batch: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// The most bytes a batch may occupy on the wire and once decompressed.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchLimits {
+    pub max_size: usize,
+    pub max_decompressed_size: usize,
+}
+
+impl Batch {
+    /// Encode this batch for gossip, snappy-compressed.
+    pub fn encode(&self) -> Vec<u8> {
+        let json = serde_json::to_vec(self).expect("batch serialization is infallible");
+        snap::raw::Encoder::new()
+            .compress_vec(&json)
+            .expect("batch compression is infallible")
+    }
+
+    /// Decode a batch, refusing to decompress any that would exceed `limits`.
+    pub fn decode(bytes: &[u8], limits: BatchLimits) -> Result<Self, BatchError> {
+        if bytes.len() > limits.max_size {
+            return Err(BatchError::TooLarge {
+                size: bytes.len(),
+                limit: limits.max_size,
+            });
+        }
+        let size = snap::raw::decompress_len(bytes).map_err(BatchError::Decompress)?;
+        if size > limits.max_decompressed_size {
+            return Err(BatchError::TooLarge {
+                size,
+                limit: limits.max_decompressed_size,
+            });
+        }
+        let json = snap::raw::Decoder::new()
+            .decompress_vec(bytes)
+            .map_err(BatchError::Decompress)?;
+        serde_json::from_slice(&json).map_err(BatchError::Malformed)
+    }
+
+    pub fn hash(&self) -> BlockHash {
+        BlockHash::of(&serde_json::to_vec(self).expect("batch serialization is infallible"))
+    }
+}
@@ -0,0 +1,435 @@
+//! Runs dozens of nodes in this process for hours, replacing them one at a time while they gossip,
+//! and fails as soon as they lose messages, keep connections to nodes that left, or let commands
+//! and events pile up on their channels.
+
+use clap::Parser;
+use libp2p::PeerId;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
+use prometheus_client::registry::Registry;
+use rand::Rng;
+use sigil::client::SwarmClient;
+use sigil::event::NodeEvent;
+use sigil::testing::TestNetwork;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+/// How often delivery, connections and channel depths are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Soak and churn test for sigil.
+#[derive(Parser)]
+struct Args {
+    /// How many nodes run at once.
+    #[arg(long, default_value_t = 24)]
+    nodes: usize,
+
+    /// How long to run, in seconds.
+    #[arg(long, default_value_t = 4 * 3600)]
+    duration_secs: u64,
+
+    /// How often, in seconds, a random node leaves and a new one joins in its place.
+    #[arg(long, default_value_t = 30)]
+    churn_secs: u64,
+
+    /// How many messages are published each second, each by a random node.
+    #[arg(long, default_value_t = 20)]
+    messages_per_second: u32,
+
+    /// How long, in seconds, a node may be up before it must receive every message, giving it time
+    /// to join the mesh.
+    #[arg(long, default_value_t = 30)]
+    settle_secs: u64,
+
+    /// How long, in seconds, a message has to reach every settled node, and the nodes have to
+    /// close their connections to a node that left.
+    #[arg(long, default_value_t = 10)]
+    grace_secs: u64,
+
+    /// The most commands or events that may wait on any of a node's channels.
+    #[arg(long, default_value_t = 256)]
+    max_channel_depth: usize,
+
+    /// How often, in seconds, a report is printed.
+    #[arg(long, default_value_t = 60)]
+    report_secs: u64,
+
+    /// Connect the nodes in memory rather than over TCP on localhost.
+    #[arg(long)]
+    memory: bool,
+
+    /// A file to write the soak's metrics to with each report, in the Prometheus text format, such
+    /// as for node_exporter's textfile collector.
+    #[arg(long)]
+    metrics_file: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Metrics {
+    published: Counter,
+    publish_failures: Counter,
+    delivered: Counter,
+    lost: Counter,
+    joins: Counter,
+    leaves: Counter,
+    nodes: Gauge,
+    connections: Gauge,
+    max_channel_depth: Gauge,
+}
+
+impl Metrics {
+    fn register(&self, registry: &mut Registry) {
+        let registry = registry.sub_registry_with_prefix("soak");
+        registry.register("published", "Messages published", self.published.clone());
+        registry.register(
+            "publish_failures",
+            "Messages that could not be published",
+            self.publish_failures.clone(),
+        );
+        registry.register(
+            "delivered",
+            "Deliveries of messages to settled nodes",
+            self.delivered.clone(),
+        );
+        registry.register(
+            "lost",
+            "Messages that never reached a settled node",
+            self.lost.clone(),
+        );
+        registry.register("joins", "Nodes that joined", self.joins.clone());
+        registry.register("leaves", "Nodes that left", self.leaves.clone());
+        registry.register("nodes", "Nodes running", self.nodes.clone());
+        registry.register(
+            "connections",
+            "Connections between the running nodes, counted from both ends",
+            self.connections.clone(),
+        );
+        registry.register(
+            "max_channel_depth",
+            "The most commands or events waiting on any node's channel",
+            self.max_channel_depth.clone(),
+        );
+    }
+}
+
+/// A published message that not every node is known to have received yet.
+struct Published {
+    id: u64,
+    at: Instant,
+
+    /// The nodes that had settled when it was published, which must all receive it unless they
+    /// leave first.
+    expected: Vec<PeerId>,
+}
+
+/// The messages each running node has received, by their ids.
+type Received = Arc<Mutex<HashMap<PeerId, HashSet<u64>>>>;
+
+/// A node the soak runs, with the task recording what it receives.
+struct Member {
+    joined: Instant,
+    client: SwarmClient,
+    recorder: JoinHandle<()>,
+}
+
+struct Soak {
+    args: Args,
+    network: TestNetwork,
+    members: HashMap<PeerId, Member>,
+    received: Received,
+    pending: VecDeque<Published>,
+
+    /// Nodes that left, by when, until the others have had time to drop their connections.
+    departed: HashMap<PeerId, Instant>,
+    next_id: u64,
+    metrics: Metrics,
+    registry: Registry,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(args).await {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let network = if args.memory {
+        TestNetwork::simulate(0)?
+    } else {
+        TestNetwork::start(0)?
+    };
+    let metrics = Metrics::default();
+    let mut registry = Registry::default();
+    metrics.register(&mut registry);
+    let mut soak = Soak {
+        network,
+        members: HashMap::new(),
+        received: Received::default(),
+        pending: VecDeque::new(),
+        departed: HashMap::new(),
+        next_id: 0,
+        metrics,
+        registry,
+        args,
+    };
+    for _ in 0..soak.args.nodes {
+        soak.join()?;
+    }
+    println!(
+        "Soaking {} nodes for {}s",
+        soak.args.nodes, soak.args.duration_secs
+    );
+
+    let end = Instant::now() + Duration::from_secs(soak.args.duration_secs);
+    let mut publish = interval(Duration::from_secs(1) / soak.args.messages_per_second.max(1));
+    let mut churn = interval(Duration::from_secs(soak.args.churn_secs.max(1)));
+    let mut check = interval(CHECK_INTERVAL);
+    let mut report = interval(Duration::from_secs(soak.args.report_secs.max(1)));
+    let deadline = time::sleep_until(end);
+    tokio::pin!(deadline);
+    let result = loop {
+        let step = tokio::select! {
+            _ = &mut deadline => break Ok(()),
+            _ = publish.tick() => {
+                soak.publish().await;
+                Ok(())
+            }
+            _ = churn.tick() => soak.churn().await,
+            _ = check.tick() => soak.check().await,
+            _ = report.tick() => soak.report(),
+        };
+        if let Err(e) = step {
+            break Err(e);
+        }
+    };
+    soak.report()?;
+    result?;
+    println!("Soak passed");
+    soak.network.shutdown().await;
+    Ok(())
+}
+
+/// An interval whose first tick is a period away.
+fn interval(period: Duration) -> time::Interval {
+    let mut interval = time::interval_at(Instant::now() + period, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+impl Soak {
+    /// Start a node, dialing every node running, and record what it receives.
+    fn join(&mut self) -> Result<(), Box<dyn Error>> {
+        let node = self.network.join()?;
+        let peer_id = node.peer_id;
+        let client = node.client.clone();
+        let recorder = tokio::spawn(record(
+            peer_id,
+            client.subscribe_events(),
+            self.received.clone(),
+        ));
+        self.members.insert(
+            peer_id,
+            Member {
+                joined: Instant::now(),
+                client,
+                recorder,
+            },
+        );
+        self.metrics.joins.inc();
+        Ok(())
+    }
+
+    /// Replace a random node with a new one.
+    async fn churn(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.network.nodes().is_empty() {
+            let index = rand::thread_rng().gen_range(0..self.network.nodes().len());
+            let peer_id = self.network.leave(index).await;
+            if let Some(member) = self.members.remove(&peer_id) {
+                member.recorder.abort();
+            }
+            self.received
+                .lock()
+                .expect("received lock is never poisoned")
+                .remove(&peer_id);
+            self.departed.insert(peer_id, Instant::now());
+            self.metrics.leaves.inc();
+        }
+        self.join()
+    }
+
+    /// Publish the next message from a random node, expecting it at every other settled node.
+    async fn publish(&mut self) {
+        let nodes = self.network.nodes();
+        if nodes.is_empty() {
+            return;
+        }
+        let publisher = &nodes[rand::thread_rng().gen_range(0..nodes.len())];
+        let topic = &publisher.config.topics.application[0];
+        let id = self.next_id;
+        self.next_id += 1;
+        let result = publisher
+            .client
+            .gossipsub_publish(topic, id.to_be_bytes().to_vec())
+            .await;
+        if result.is_err() {
+            // The publisher may not have found peers on the topic yet, having only just joined.
+            self.metrics.publish_failures.inc();
+            return;
+        }
+        let now = Instant::now();
+        let settle = Duration::from_secs(self.args.settle_secs);
+        let expected = self
+            .members
+            .iter()
+            .filter(|(peer_id, member)| {
+                **peer_id != publisher.peer_id && now.duration_since(member.joined) >= settle
+            })
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        self.pending.push_back(Published {
+            id,
+            at: now,
+            expected,
+        });
+        self.metrics.published.inc();
+    }
+
+    /// Check every invariant, failing on the first that is broken.
+    async fn check(&mut self) -> Result<(), Box<dyn Error>> {
+        let now = Instant::now();
+        let grace = Duration::from_secs(self.args.grace_secs);
+
+        // Every message reached every settled node that is still running.
+        let mut received = self
+            .received
+            .lock()
+            .expect("received lock is never poisoned");
+        while let Some(published) = self.pending.front() {
+            if now.duration_since(published.at) < grace {
+                break;
+            }
+            let published = self.pending.pop_front().expect("a message is pending");
+            for peer_id in &published.expected {
+                if !self.members.contains_key(peer_id) {
+                    continue;
+                }
+                if received
+                    .get(peer_id)
+                    .is_some_and(|ids| ids.contains(&published.id))
+                {
+                    self.metrics.delivered.inc();
+                } else {
+                    self.metrics.lost.inc();
+                    return Err(format!(
+                        "message {} never reached {peer_id} within {grace:?}",
+                        published.id
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Forget the messages that have been checked, or that arrived after their check.
+        let oldest = self
+            .pending
+            .front()
+            .map_or(self.next_id, |published| published.id);
+        for ids in received.values_mut() {
+            ids.retain(|id| *id >= oldest);
+        }
+        drop(received);
+
+        // No node kept a connection to a node that left, once it had time to close it.
+        self.departed
+            .retain(|_, left| now.duration_since(*left) < grace * 2);
+        let mut connections = 0;
+        let mut max_depth = 0;
+        for (peer_id, member) in &self.members {
+            let peers = member.client.connected_peers().await?;
+            connections += peers.len();
+            if let Some(leaked) = peers.iter().find(|peer| {
+                !self.members.contains_key(peer)
+                    && self
+                        .departed
+                        .get(peer)
+                        .map_or(true, |left| now.duration_since(*left) >= grace)
+            }) {
+                return Err(format!("{peer_id} is still connected to {leaked}, which left").into());
+            }
+
+            // No channel grew past its bound.
+            let depths = member.client.channel_depths();
+            let depth = depths
+                .control
+                .max(depths.consensus)
+                .max(depths.bulk)
+                .max(depths.events);
+            max_depth = max_depth.max(depth);
+            if depth > self.args.max_channel_depth {
+                return Err(format!("{peer_id}'s channels grew too deep: {depths:?}").into());
+            }
+        }
+        self.metrics.nodes.set(self.members.len() as i64);
+        self.metrics.connections.set(connections as i64);
+        self.metrics.max_channel_depth.set(max_depth as i64);
+        Ok(())
+    }
+
+    fn report(&self) -> Result<(), Box<dyn Error>> {
+        let metrics = &self.metrics;
+        println!(
+            "{} nodes, {} connections: {} published ({} failed), {} delivered, {} lost; {} joins, \
+             {} leaves; deepest channel {}",
+            metrics.nodes.get(),
+            metrics.connections.get(),
+            metrics.published.get(),
+            metrics.publish_failures.get(),
+            metrics.delivered.get(),
+            metrics.lost.get(),
+            metrics.joins.get(),
+            metrics.leaves.get(),
+            metrics.max_channel_depth.get(),
+        );
+        if let Some(path) = &self.args.metrics_file {
+            let mut text = String::new();
+            encode(&mut text, &self.registry)?;
+            fs::write(path, text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record the ids of the messages `peer_id` receives, until it stops.
+async fn record(peer_id: PeerId, mut events: broadcast::Receiver<NodeEvent>, received: Received) {
+    loop {
+        match events.recv().await {
+            Ok(NodeEvent::Message(message)) => {
+                let Ok(id) = <[u8; 8]>::try_from(&message.entry.payload[..]) else {
+                    continue;
+                };
+                received
+                    .lock()
+                    .expect("received lock is never poisoned")
+                    .entry(peer_id)
+                    .or_default()
+                    .insert(u64::from_be_bytes(id));
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                println!("WARNING: {peer_id} missed {missed} events; its messages may seem lost");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use crate::config::BlobConfig;
+use crate::event::NodeEvent;
+use libp2p::{request_response, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot};
+
+/// The request-response protocol used to transfer blobs too large for gossip, such as state
+/// snapshots. A sender offers a blob, and the recipient then pulls it in chunks, so an interrupted
+/// transfer resumes from the last chunk received.
+pub const BLOB_PROTOCOL: &str = "/sigil/blob/1.0.0";
+
+pub type BlobBehaviour = request_response::cbor::Behaviour<BlobRequest, BlobResponse>;
+
+/// How often transfers that have gone idle for longer than `blob.idle_secs` are forgotten.
+pub const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BlobRequest {
+    Offer { id: u64, name: String, size: u64 },
+    Fetch { id: u64, offset: u64, length: u64 },
+    Complete { id: u64 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BlobResponse {
+    Accepted,
+    Rejected,
+    Chunk { data: Vec<u8> },
+    Unknown,
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobError {
+    /// The recipient declined our offer, such as because the blob exceeds its size limit.
+    Rejected,
+    /// No blob with this id has been offered to us by this peer, or the sender has forgotten it.
+    Unknown,
+    /// A download of this blob is already in progress.
+    InProgress,
+    /// The transfer was interrupted; fetching again resumes where it stopped.
+    Interrupted(String),
+}
+
+impl fmt::Display for BlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobError::Rejected => write!(f, "blob offer was rejected"),
+            BlobError::Unknown => write!(f, "unknown blob"),
+            BlobError::InProgress => write!(f, "blob download already in progress"),
+            BlobError::Interrupted(e) => write!(f, "blob transfer interrupted: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BlobError {}
+
+struct Outgoing {
+    peer: PeerId,
+    data: Vec<u8>,
+    /// When the recipient last fetched a chunk, or when we offered the blob.
+    active_at: Instant,
+}
+
+struct Incoming {
+    size: u64,
+    data: Vec<u8>,
+    fetcher: Option<oneshot::Sender<Result<Vec<u8>, BlobError>>>,
+    /// When we last received a chunk, or when the blob was offered.
+    active_at: Instant,
+}
+
+enum Pending {
+    Offer(u64, oneshot::Sender<Result<u64, BlobError>>),
+    Fetch(PeerId, u64),
+    Complete,
+}
+
+/// The state of every blob transfer this node is part of, in either direction.
+pub struct BlobTransfers {
+    chunk_size: u64,
+    max_size: u64,
+    max_offers_per_peer: usize,
+    max_offers: usize,
+    idle_timeout: Duration,
+    next_id: u64,
+    outgoing: HashMap<u64, Outgoing>,
+    incoming: HashMap<(PeerId, u64), Incoming>,
+    pending: HashMap<request_response::OutboundRequestId, Pending>,
+    events: broadcast::Sender<NodeEvent>,
+}
+
+impl BlobTransfers {
+    pub fn new(config: &BlobConfig, events: broadcast::Sender<NodeEvent>) -> Self {
+        Self {
+            chunk_size: config.chunk_size.max(1),
+            max_size: config.max_size,
+            max_offers_per_peer: config.max_offers_per_peer,
+            max_offers: config.max_offers,
+            idle_timeout: config.idle_timeout(),
+            next_id: rand::random(),
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
+            pending: HashMap::new(),
+            events,
+        }
+    }
+
+    /// Offer `data` to `peer`, answering `sender` with the blob's id once the peer accepts.
+    pub fn offer(
+        &mut self,
+        behaviour: &mut BlobBehaviour,
+        peer: PeerId,
+        name: String,
+        data: Vec<u8>,
+        sender: oneshot::Sender<Result<u64, BlobError>>,
+    ) {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let request = BlobRequest::Offer {
+            id,
+            name,
+            size: data.len() as u64,
+        };
+        self.outgoing.insert(
+            id,
+            Outgoing {
+                peer,
+                data,
+                active_at: Instant::now(),
+            },
+        );
+        let request_id = behaviour.send_request(&peer, request);
+        self.pending.insert(request_id, Pending::Offer(id, sender));
+    }
+
+    /// Download, or resume downloading, a blob that `peer` offered us.
+    pub fn fetch(
+        &mut self,
+        behaviour: &mut BlobBehaviour,
+        peer: PeerId,
+        id: u64,
+        sender: oneshot::Sender<Result<Vec<u8>, BlobError>>,
+    ) {
+        let Some(incoming) = self.incoming.get_mut(&(peer, id)) else {
+            let _ = sender.send(Err(BlobError::Unknown));
+            return;
+        };
+        if incoming.fetcher.is_some() {
+            let _ = sender.send(Err(BlobError::InProgress));
+            return;
+        }
+        incoming.fetcher = Some(sender);
+        incoming.active_at = Instant::now();
+        self.request_chunk(behaviour, peer, id);
+    }
+
+    /// Forget the transfers that have made no progress for the idle timeout, other than
+    /// downloads waiting on a chunk, which end when the request does.
+    pub fn expire(&mut self, now: Instant) {
+        let idle_timeout = self.idle_timeout;
+        self.outgoing
+            .retain(|_, outgoing| now.duration_since(outgoing.active_at) < idle_timeout);
+        self.incoming.retain(|_, incoming| {
+            incoming.fetcher.is_some() || now.duration_since(incoming.active_at) < idle_timeout
+        });
+    }
+
+    /// Forget every transfer to or from `peer`, which has disconnected, failing its downloads.
+    pub fn forget_peer(&mut self, peer: &PeerId) {
+        self.outgoing.retain(|_, outgoing| outgoing.peer != *peer);
+        let ids: Vec<_> = self
+            .incoming
+            .keys()
+            .filter(|(from, _)| from == peer)
+            .map(|(_, id)| *id)
+            .collect();
+        for id in ids {
+            self.drop_incoming(
+                *peer,
+                id,
+                BlobError::Interrupted("peer disconnected".to_string()),
+            );
+        }
+    }
+
+    pub fn handle_event(
+        &mut self,
+        behaviour: &mut BlobBehaviour,
+        event: request_response::Event<BlobRequest, BlobResponse>,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => {
+                let response = self.handle_request(peer, request);
+                if behaviour.send_response(channel, response).is_err() {
+                    tracing::warn!(%peer, "Failed to respond to blob request");
+                }
+            }
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+            } => {
+                if let Some(pending) = self.pending.remove(&request_id) {
+                    self.handle_response(behaviour, peer, pending, response);
+                }
+            }
+            request_response::Event::OutboundFailure {
+                request_id, error, ..
+            } => match self.pending.remove(&request_id) {
+                Some(Pending::Offer(id, sender)) => {
+                    self.outgoing.remove(&id);
+                    let _ = sender.send(Err(BlobError::Interrupted(error.to_string())));
+                }
+                Some(Pending::Fetch(peer, id)) => {
+                    self.fail_fetch(peer, id, BlobError::Interrupted(error.to_string()));
+                }
+                Some(Pending::Complete) | None => {}
+            },
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::warn!(%peer, %error, "Failed to serve blob request");
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    fn handle_request(&mut self, peer: PeerId, request: BlobRequest) -> BlobResponse {
+        match request {
+            BlobRequest::Offer { id, name, size } => {
+                if size > self.max_size {
+                    return BlobResponse::Rejected;
+                }
+                if !self.incoming.contains_key(&(peer, id)) {
+                    let from_peer = self.incoming.keys().filter(|(p, _)| *p == peer).count();
+                    if from_peer >= self.max_offers_per_peer
+                        || self.incoming.len() >= self.max_offers
+                    {
+                        return BlobResponse::Rejected;
+                    }
+                }
+                self.incoming.insert(
+                    (peer, id),
+                    Incoming {
+                        size,
+                        data: Vec::new(),
+                        fetcher: None,
+                        active_at: Instant::now(),
+                    },
+                );
+                let _ = self.events.send(NodeEvent::BlobOffered {
+                    peer,
+                    id,
+                    name,
+                    size,
+                });
+                BlobResponse::Accepted
+            }
+            BlobRequest::Fetch { id, offset, length } => {
+                let Some(outgoing) = self.outgoing.get_mut(&id).filter(|o| o.peer == peer) else {
+                    return BlobResponse::Unknown;
+                };
+                outgoing.active_at = Instant::now();
+                let size = outgoing.data.len() as u64;
+                let start = offset.min(size) as usize;
+                let end = offset.saturating_add(length.min(self.chunk_size)).min(size) as usize;
+                BlobResponse::Chunk {
+                    data: outgoing.data[start..end].to_vec(),
+                }
+            }
+            BlobRequest::Complete { id } => {
+                if self.outgoing.get(&id).is_some_and(|o| o.peer == peer) {
+                    self.outgoing.remove(&id);
+                    let _ = self.events.send(NodeEvent::BlobSent { peer, id });
+                }
+                BlobResponse::Done
+            }
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        behaviour: &mut BlobBehaviour,
+        peer: PeerId,
+        pending: Pending,
+        response: BlobResponse,
+    ) {
+        match (pending, response) {
+            (Pending::Offer(id, sender), BlobResponse::Accepted) => {
+                let _ = sender.send(Ok(id));
+            }
+            (Pending::Offer(id, sender), _) => {
+                self.outgoing.remove(&id);
+                let _ = sender.send(Err(BlobError::Rejected));
+            }
+            (Pending::Fetch(peer, id), BlobResponse::Chunk { data }) => {
+                let Some(incoming) = self.incoming.get_mut(&(peer, id)) else {
+                    return;
+                };
+                if data.is_empty() && (incoming.data.len() as u64) < incoming.size {
+                    self.fail_fetch(
+                        peer,
+                        id,
+                        BlobError::Interrupted("sender returned an empty chunk".to_string()),
+                    );
+                    return;
+                }
+                if incoming.data.len() as u64 + data.len() as u64 > incoming.size {
+                    self.drop_incoming(
+                        peer,
+                        id,
+                        BlobError::Interrupted("sender returned more than it offered".to_string()),
+                    );
+                    return;
+                }
+                incoming.data.extend_from_slice(&data);
+                incoming.active_at = Instant::now();
+                let received = incoming.data.len() as u64;
+                let size = incoming.size;
+                let _ = self.events.send(NodeEvent::BlobProgress {
+                    peer,
+                    id,
+                    received,
+                    size,
+                });
+                if received < size {
+                    self.request_chunk(behaviour, peer, id);
+                    return;
+                }
+
+                let Some(incoming) = self.incoming.remove(&(peer, id)) else {
+                    return;
+                };
+                let request_id = behaviour.send_request(&peer, BlobRequest::Complete { id });
+                self.pending.insert(request_id, Pending::Complete);
+                let _ = self.events.send(NodeEvent::BlobReceived { peer, id, size });
+                if let Some(fetcher) = incoming.fetcher {
+                    let _ = fetcher.send(Ok(incoming.data));
+                }
+            }
+            (Pending::Fetch(_, id), _) => {
+                // The sender no longer has the blob, so it can never be resumed.
+                self.drop_incoming(peer, id, BlobError::Unknown);
+            }
+            (Pending::Complete, _) => {}
+        }
+    }
+
+    fn request_chunk(&mut self, behaviour: &mut BlobBehaviour, peer: PeerId, id: u64) {
+        let Some(incoming) = self.incoming.get(&(peer, id)) else {
+            return;
+        };
+        let request = BlobRequest::Fetch {
+            id,
+            offset: incoming.data.len() as u64,
+            length: self.chunk_size,
+        };
+        let request_id = behaviour.send_request(&peer, request);
+        self.pending.insert(request_id, Pending::Fetch(peer, id));
+    }
+
+    fn fail_fetch(&mut self, peer: PeerId, id: u64, error: BlobError) {
+        if let Some(fetcher) = self
+            .incoming
+            .get_mut(&(peer, id))
+            .and_then(|incoming| incoming.fetcher.take())
+        {
+            let _ = fetcher.send(Err(error));
+        }
+    }
+
+    /// Forget the blob `id` that `peer` offered us, failing its download with `error`.
+    fn drop_incoming(&mut self, peer: PeerId, id: u64, error: BlobError) {
+        if let Some(fetcher) = self
+            .incoming
+            .remove(&(peer, id))
+            .and_then(|incoming| incoming.fetcher)
+        {
+            let _ = fetcher.send(Err(error));
+        }
+    }
+}
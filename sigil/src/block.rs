@@ -0,0 +1,302 @@
+#[cfg(feature = "kademlia")]
+use libp2p::kad::{self, store::MemoryStore};
+use libp2p::request_response;
+#[cfg(feature = "kademlia")]
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+#[cfg(feature = "kademlia")]
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "kademlia")]
+use tokio::sync::oneshot;
+
+/// The request-response protocol used to fetch a content-addressed block from a peer that
+/// advertised it on the DHT.
+pub const BLOCK_PROTOCOL: &str = "/sigil/block/1.0.0";
+
+/// The Kademlia protocol that sigil nodes advertise block providers on, kept separate from the
+/// public IPFS DHT.
+pub const KAD_PROTOCOL: &str = "/sigil/kad/1.0.0";
+
+pub type BlockBehaviour = request_response::cbor::Behaviour<BlockRequest, BlockResponse>;
+
+/// The SHA-256 digest identifying a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockHash(pub [u8; 32]);
+
+impl BlockHash {
+    pub fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+
+    #[cfg(feature = "kademlia")]
+    fn key(&self) -> kad::RecordKey {
+        kad::RecordKey::new(&self.0)
+    }
+}
+
+impl fmt::Display for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl FromStr for BlockHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(format!("block hash must be 64 hex characters: {s}"));
+        }
+        let mut hash = [0; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+                .map_err(|e| format!("invalid block hash {s}: {e}"))?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockRequest(pub BlockHash);
+
+/// The requested block, or `None` if the peer does not have it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockResponse(pub Option<Vec<u8>>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    /// No provider of the block could be found, or none of them served it.
+    NotFound(BlockHash),
+    /// The block was stored locally but could not be advertised on the DHT.
+    Advertise(String),
+    /// The node was built without the `kademlia` feature, which blocks are advertised on.
+    Disabled,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::NotFound(hash) => write!(f, "block {hash} not found"),
+            BlockError::Advertise(e) => write!(f, "failed to advertise block: {e}"),
+            BlockError::Disabled => write!(f, "the DHT is not compiled into this node"),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// The blocks this node holds, keyed by hash.
+#[derive(Default)]
+pub struct BlockStore {
+    blocks: HashMap<BlockHash, Vec<u8>>,
+}
+
+impl BlockStore {
+    pub fn get(&self, hash: &BlockHash) -> Option<&Vec<u8>> {
+        self.blocks.get(hash)
+    }
+
+    pub fn insert(&mut self, data: Vec<u8>) -> BlockHash {
+        let hash = BlockHash::of(&data);
+        self.blocks.insert(hash, data);
+        hash
+    }
+}
+
+#[cfg(feature = "kademlia")]
+type BlockSender = oneshot::Sender<Result<Vec<u8>, BlockError>>;
+
+/// A block being searched for, with everyone waiting on it.
+#[cfg(feature = "kademlia")]
+#[derive(Default)]
+struct PendingGet {
+    senders: Vec<BlockSender>,
+    providers: VecDeque<PeerId>,
+    tried: HashSet<PeerId>,
+    requesting: bool,
+    searching: bool,
+}
+
+/// Stores blocks, advertises them on the DHT, and fetches missing blocks from their providers.
+#[cfg(feature = "kademlia")]
+#[derive(Default)]
+pub struct BlockExchange {
+    store: BlockStore,
+    gets: HashMap<BlockHash, PendingGet>,
+    queries: HashMap<kad::QueryId, BlockHash>,
+    requests: HashMap<request_response::OutboundRequestId, BlockHash>,
+}
+
+#[cfg(feature = "kademlia")]
+impl BlockExchange {
+    /// Store `data` and advertise this node as its provider, returning its hash.
+    pub fn put(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        data: Vec<u8>,
+    ) -> Result<BlockHash, BlockError> {
+        let hash = self.store.insert(data);
+        kad.start_providing(hash.key())
+            .map_err(|e| BlockError::Advertise(e.to_string()))?;
+        Ok(hash)
+    }
+
+    /// Answer `sender` with the block `hash`, searching the DHT for providers if we lack it.
+    pub fn get(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        hash: BlockHash,
+        sender: BlockSender,
+    ) {
+        if let Some(data) = self.store.get(&hash) {
+            let _ = sender.send(Ok(data.clone()));
+            return;
+        }
+        let pending = self.gets.entry(hash).or_default();
+        pending.senders.push(sender);
+        if !pending.searching && !pending.requesting {
+            pending.searching = true;
+            self.queries.insert(kad.get_providers(hash.key()), hash);
+        }
+    }
+
+    pub fn handle_kad_event(
+        &mut self,
+        block: &mut BlockBehaviour,
+        local_peer_id: PeerId,
+        event: kad::Event,
+    ) {
+        let kad::Event::OutboundQueryProgressed {
+            id, result, step, ..
+        } = event
+        else {
+            return;
+        };
+        let kad::QueryResult::GetProviders(result) = result else {
+            return;
+        };
+        let Some(&hash) = self.queries.get(&id) else {
+            return;
+        };
+        if step.last {
+            self.queries.remove(&id);
+        }
+        let Some(pending) = self.gets.get_mut(&hash) else {
+            return;
+        };
+        if let Ok(kad::GetProvidersOk::FoundProviders { providers, .. }) = result {
+            for provider in providers {
+                if provider != local_peer_id
+                    && !pending.tried.contains(&provider)
+                    && !pending.providers.contains(&provider)
+                {
+                    pending.providers.push_back(provider);
+                }
+            }
+        }
+        if step.last {
+            pending.searching = false;
+        }
+        self.request_next(block, hash);
+    }
+
+    pub fn handle_block_event(
+        &mut self,
+        block: &mut BlockBehaviour,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        event: request_response::Event<BlockRequest, BlockResponse>,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => {
+                let response = BlockResponse(self.store.get(&request.0).cloned());
+                if block.send_response(channel, response).is_err() {
+                    tracing::warn!(%peer, block = %request.0, "Failed to serve block");
+                }
+            }
+            request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => {
+                let Some(hash) = self.requests.remove(&request_id) else {
+                    return;
+                };
+                // Providers are untrusted, so a block is only accepted if it matches its hash.
+                match response.0.filter(|data| BlockHash::of(data) == hash) {
+                    Some(data) => {
+                        if let Err(e) = self.put(kad, data.clone()) {
+                            tracing::warn!(
+                                block = %hash,
+                                error = %e,
+                                "Failed to advertise fetched block"
+                            );
+                        }
+                        if let Some(pending) = self.gets.remove(&hash) {
+                            for sender in pending.senders {
+                                let _ = sender.send(Ok(data.clone()));
+                            }
+                        }
+                    }
+                    None => {
+                        if let Some(pending) = self.gets.get_mut(&hash) {
+                            pending.requesting = false;
+                        }
+                        self.request_next(block, hash);
+                    }
+                }
+            }
+            request_response::Event::OutboundFailure { request_id, .. } => {
+                if let Some(hash) = self.requests.remove(&request_id) {
+                    if let Some(pending) = self.gets.get_mut(&hash) {
+                        pending.requesting = false;
+                    }
+                    self.request_next(block, hash);
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::warn!(%peer, %error, "Failed to serve block request");
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// Ask the next untried provider for `hash`, failing the get once none remain.
+    fn request_next(&mut self, block: &mut BlockBehaviour, hash: BlockHash) {
+        let Some(pending) = self.gets.get_mut(&hash) else {
+            return;
+        };
+        if pending.requesting {
+            return;
+        }
+        match pending.providers.pop_front() {
+            Some(provider) => {
+                pending.tried.insert(provider);
+                pending.requesting = true;
+                let request_id = block.send_request(&provider, BlockRequest(hash));
+                self.requests.insert(request_id, hash);
+            }
+            None if !pending.searching => {
+                if let Some(pending) = self.gets.remove(&hash) {
+                    for sender in pending.senders {
+                        let _ = sender.send(Err(BlockError::NotFound(hash)));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::BundleConfig;
+use crate::envelope::{Bundle, Envelope};
+use crate::mempool::{MempoolError, TxHash};
+use libp2p::gossipsub;
+use std::collections::{HashMap, HashSet};
+use std::{io, mem};
+use tokio::sync::oneshot;
+
+/// The bytes a bundle adds around its envelopes, each of which is counted with a separator.
+const BUNDLE_OVERHEAD: usize = r#"{"envelopes":[]}"#.len();
+
+/// Answers a publisher with the id of the gossip message that carried its message.
+pub type PublishSender = oneshot::Sender<Result<gossipsub::MessageId, gossipsub::PublishError>>;
+
+/// A client waiting to hear how the bundle carrying its message was published.
+pub enum Publisher {
+    /// A publish on a topic, answered with the id of the bundle's gossip message.
+    Message(PublishSender),
+    /// A transaction submitted to the mempool, answered with its hash.
+    Transaction(TxHash, oneshot::Sender<Result<TxHash, MempoolError>>),
+}
+
+impl Publisher {
+    /// Tell the client how its bundle was published.
+    pub fn answer(self, result: &Result<gossipsub::MessageId, gossipsub::PublishError>) {
+        match self {
+            Publisher::Message(sender) => {
+                let _ = sender.send(match result {
+                    Ok(id) => Ok(id.clone()),
+                    Err(e) => Err(copy_publish_error(e)),
+                });
+            }
+            Publisher::Transaction(hash, sender) => {
+                let _ = sender.send(match result {
+                    Ok(_) => Ok(hash),
+                    Err(e) => Err(MempoolError::Publish(copy_publish_error(e))),
+                });
+            }
+        }
+    }
+}
+
+/// A bundle ready to publish, with the publishers waiting to hear how it went.
+pub struct ReadyBundle {
+    pub topic: gossipsub::IdentTopic,
+    pub bundle: Bundle,
+    pub publishers: Vec<Publisher>,
+}
+
+#[derive(Default)]
+struct PendingBundle {
+    bundle: Bundle,
+    publishers: Vec<Publisher>,
+
+    /// The bytes the bundle takes once encoded.
+    size: usize,
+}
+
+/// Collects the messages published on bundled topics until their bundle is full or the node's
+/// scheduler flushes it.
+pub struct Bundler {
+    topics: HashSet<gossipsub::TopicHash>,
+    max_messages: usize,
+    max_bytes: usize,
+    pending: HashMap<gossipsub::TopicHash, (gossipsub::IdentTopic, PendingBundle)>,
+}
+
+impl Bundler {
+    pub fn new(config: &BundleConfig) -> Self {
+        Self {
+            topics: config
+                .topics
+                .iter()
+                .map(|topic| gossipsub::IdentTopic::new(topic).hash())
+                .collect(),
+            max_messages: config.max_messages.max(1),
+            max_bytes: config.max_bytes,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Whether messages on `topic` are bundled.
+    pub fn bundles(&self, topic: &gossipsub::TopicHash) -> bool {
+        self.topics.contains(topic)
+    }
+
+    /// Add `envelope` to its topic's bundle, returning the bundles it filled. A message too large
+    /// for the bundle so far is bundled alone after it, however large.
+    pub fn push(
+        &mut self,
+        topic: gossipsub::IdentTopic,
+        envelope: Envelope,
+        publisher: Option<Publisher>,
+    ) -> Vec<ReadyBundle> {
+        let mut ready = Vec::new();
+        let size = envelope.encode().len() + 1;
+        let (_, pending) = self
+            .pending
+            .entry(topic.hash())
+            .or_insert_with(|| (topic.clone(), PendingBundle::default()));
+        if !pending.bundle.envelopes.is_empty()
+            && BUNDLE_OVERHEAD + pending.size + size > self.max_bytes
+        {
+            ready.push(take(&topic, pending));
+        }
+        pending.bundle.envelopes.push(envelope);
+        pending.publishers.extend(publisher);
+        pending.size += size;
+        if pending.bundle.envelopes.len() >= self.max_messages
+            || BUNDLE_OVERHEAD + pending.size >= self.max_bytes
+        {
+            ready.push(take(&topic, pending));
+        }
+        ready
+    }
+
+    /// Take every bundle that holds a message.
+    pub fn flush(&mut self) -> Vec<ReadyBundle> {
+        self.pending
+            .values_mut()
+            .filter(|(_, pending)| !pending.bundle.envelopes.is_empty())
+            .map(|(topic, pending)| take(topic, pending))
+            .collect()
+    }
+
+    /// How many messages wait in bundles.
+    pub fn len(&self) -> usize {
+        self.pending
+            .values()
+            .map(|(_, pending)| pending.bundle.envelopes.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn take(topic: &gossipsub::IdentTopic, pending: &mut PendingBundle) -> ReadyBundle {
+    let pending = mem::take(pending);
+    ReadyBundle {
+        topic: topic.clone(),
+        bundle: pending.bundle,
+        publishers: pending.publishers,
+    }
+}
+
+/// A copy of `error` for each publisher of a bundle that failed, as publish errors cannot be
+/// cloned. Errors carrying a cause keep only its description.
+pub fn copy_publish_error(error: &gossipsub::PublishError) -> gossipsub::PublishError {
+    match error {
+        gossipsub::PublishError::Duplicate => gossipsub::PublishError::Duplicate,
+        gossipsub::PublishError::InsufficientPeers => gossipsub::PublishError::InsufficientPeers,
+        gossipsub::PublishError::MessageTooLarge => gossipsub::PublishError::MessageTooLarge,
+        e => gossipsub::PublishError::TransformFailed(io::Error::other(format!("{e:?}"))),
+    }
+}
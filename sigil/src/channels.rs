@@ -0,0 +1,147 @@
+use crate::client::Priority;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::{counter::Counter, family::Family, gauge::Gauge};
+use prometheus_client::registry::Registry;
+
+/// A queue between the node's tasks, by the label its metrics carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// The command channels from `SwarmClient`s, one per priority.
+    Control,
+    Consensus,
+    Bulk,
+    /// Received messages being validated, and validation results waiting for the event loop.
+    Validation,
+    /// Events waiting for the slowest subscriber.
+    Events,
+    /// Received messages waiting to be posted to the webhook.
+    Webhook,
+}
+
+impl Channel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Control => "control",
+            Channel::Consensus => "consensus",
+            Channel::Bulk => "bulk",
+            Channel::Validation => "validation",
+            Channel::Events => "events",
+            Channel::Webhook => "webhook",
+        }
+    }
+}
+
+impl From<Priority> for Channel {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::Control => Channel::Control,
+            Priority::Consensus => Channel::Consensus,
+            Priority::Bulk => Channel::Bulk,
+        }
+    }
+}
+
+/// How many items the node's event loop takes from any one source in a turn.
+pub const TURN_BUDGET: u32 = 64;
+
+/// What the node's event loop takes its next item from, in the order it polls them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Control,
+    Validation,
+    Consensus,
+    Swarm,
+    Bulk,
+    Jobs,
+}
+
+/// Bounds how many items the event loop takes from each source in a turn. Sources are polled in
+/// priority order, and one that has used its budget waits until the turn ends, which it does once
+/// no source with budget left has anything ready. A busy source therefore cannot starve those
+/// polled after it.
+#[derive(Clone, Debug, Default)]
+pub struct Turn {
+    taken: [u32; 6],
+}
+
+impl Turn {
+    /// Whether `source` has budget left this turn.
+    pub fn allows(&self, source: Source) -> bool {
+        self.taken[source as usize] < TURN_BUDGET
+    }
+
+    /// Record that an item was taken from `source`.
+    pub fn take(&mut self, source: Source) {
+        self.taken[source as usize] += 1;
+    }
+
+    /// Whether some source has used its budget, so that the turn should end when nothing else is
+    /// ready.
+    pub fn is_limited(&self) -> bool {
+        self.taken.iter().any(|&taken| taken >= TURN_BUDGET)
+    }
+
+    /// Start the next turn, restoring every source's budget.
+    pub fn end(&mut self) {
+        self.taken = [0; 6];
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ChannelLabels {
+    channel: &'static str,
+}
+
+/// How deep each of the node's internal channels is, and how often a send found one full, so that
+/// operators can see the event loop falling behind before it stalls. Clones share the metrics, so
+/// that the tasks at both ends of a channel can record them.
+#[derive(Clone, Debug, Default)]
+pub struct ChannelMetrics {
+    depth: Family<ChannelLabels, Gauge>,
+    blocked: Family<ChannelLabels, Counter>,
+}
+
+impl ChannelMetrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let metrics = Self::default();
+        registry.register(
+            "channel_depth",
+            "Items queued on each internal channel",
+            metrics.depth.clone(),
+        );
+        registry.register(
+            "channel_blocked_sends",
+            "Sends that found an internal channel full, and waited or dropped their item",
+            metrics.blocked.clone(),
+        );
+        metrics
+    }
+
+    /// Record that `depth` items are queued on `channel`.
+    pub fn set_depth(&self, channel: Channel, depth: usize) {
+        self.depth
+            .get_or_create(&Self::labels(channel))
+            .set(depth as i64);
+    }
+
+    /// Record a send that found `channel` full.
+    pub fn blocked(&self, channel: Channel) {
+        self.blocked.get_or_create(&Self::labels(channel)).inc();
+    }
+
+    /// The depth last recorded for `channel`.
+    pub fn depth(&self, channel: Channel) -> usize {
+        self.depth.get_or_create(&Self::labels(channel)).get() as usize
+    }
+
+    /// How many sends have found `channel` full.
+    pub fn blocked_sends(&self, channel: Channel) -> u64 {
+        self.blocked.get_or_create(&Self::labels(channel)).get()
+    }
+
+    fn labels(channel: Channel) -> ChannelLabels {
+        ChannelLabels {
+            channel: channel.name(),
+        }
+    }
+}
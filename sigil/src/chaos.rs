@@ -0,0 +1,53 @@
+use crate::config::ChaosConfig;
+use rand::Rng;
+use std::time::Duration;
+
+/// A part of the node that chaos can tear down, leaving the node's own upkeep to rebuild it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restart {
+    /// Leave and rejoin every subscribed topic, so that the mesh is grafted again.
+    Gossipsub,
+    /// Empty the DHT's routing table, which identify and bootstrapping fill again.
+    Kademlia,
+    /// Close every relay reservation, which the relay check requests again.
+    Relays,
+}
+
+impl Restart {
+    pub const ALL: [Restart; 3] = [Restart::Gossipsub, Restart::Kademlia, Restart::Relays];
+}
+
+/// What chaos does on one strike.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Strike {
+    /// Close the connections to a random peer.
+    pub disconnect: bool,
+
+    /// Stall the event loop for this long.
+    pub stall: Option<Duration>,
+    pub restart: Option<Restart>,
+}
+
+impl Strike {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl ChaosConfig {
+    /// Decide what a strike does, each disruption by its chance.
+    pub fn strike(&self, rng: &mut impl Rng) -> Strike {
+        let disconnect = rng.gen_bool(self.disconnect_rate.clamp(0.0, 1.0));
+        let stall = rng
+            .gen_bool(self.stall_rate.clamp(0.0, 1.0))
+            .then(|| Duration::from_millis(rng.gen_range(0..=self.max_stall_millis)));
+        let restart = rng
+            .gen_bool(self.restart_rate.clamp(0.0, 1.0))
+            .then(|| Restart::ALL[rng.gen_range(0..Restart::ALL.len())]);
+        Strike {
+            disconnect,
+            stall,
+            restart,
+        }
+    }
+}
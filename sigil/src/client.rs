@@ -0,0 +1,867 @@
+use crate::batch::Batch;
+use crate::blob::BlobError;
+use crate::block::{BlockError, BlockHash};
+use crate::channels::{Channel, ChannelMetrics};
+use crate::consensus::ConsensusMessage;
+use crate::delivery::DeliveryError;
+use crate::dht::{DhtError, RoutingTableEntry};
+use crate::direct::DirectMessage;
+use crate::event::{EventLog, LoggedEvent, NodeEvent, EVENT_LOG_SIZE};
+use crate::faults::Faults;
+use crate::journal::JournalEntry;
+use crate::mempool::{MempoolError, MempoolStatus, PooledTransaction, Transaction, TxHash};
+use crate::node::{NodeInfo, NodeState, NodeStatus};
+use crate::peers::PeerInfo;
+use crate::relay::{RelayInfo, RelaySnapshot};
+use crate::stats::{SessionStats, TopicStats};
+use crate::sync::SyncError;
+use bytes::Bytes;
+use libp2p::connection_limits::ConnectionLimits;
+use libp2p::swarm::DialError;
+use libp2p::{gossipsub, request_response, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+/// Commands sent from a `SwarmClient` to be executed on the node's event loop.
+#[derive(Debug)]
+pub enum SwarmCommand {
+    GossipsubPublish {
+        topic: String,
+        data: Bytes,
+        sender: oneshot::Sender<Result<gossipsub::MessageId, gossipsub::PublishError>>,
+    },
+    PublishReliable {
+        topic: String,
+        data: Bytes,
+        recipients: Vec<PeerId>,
+        sender: oneshot::Sender<Result<(), DeliveryError>>,
+    },
+    RecentMessages {
+        topic: String,
+        limit: usize,
+        sender: oneshot::Sender<Vec<JournalEntry>>,
+    },
+    TopicStats {
+        sender: oneshot::Sender<Vec<TopicStats>>,
+    },
+    ConnectedPeers {
+        sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    Peers {
+        sender: oneshot::Sender<Vec<PeerInfo>>,
+    },
+    RoutingTablePeers {
+        sender: oneshot::Sender<HashMap<PeerId, Vec<Multiaddr>>>,
+    },
+    RoutingTable {
+        sender: oneshot::Sender<Vec<RoutingTableEntry>>,
+    },
+    SendDirect {
+        peer_id: PeerId,
+        message: DirectMessage,
+        sender: oneshot::Sender<Result<(), request_response::OutboundFailure>>,
+    },
+    OfferBlob {
+        peer_id: PeerId,
+        name: String,
+        data: Vec<u8>,
+        sender: oneshot::Sender<Result<u64, BlobError>>,
+    },
+    FetchBlob {
+        peer_id: PeerId,
+        id: u64,
+        sender: oneshot::Sender<Result<Vec<u8>, BlobError>>,
+    },
+    PutBlock {
+        data: Vec<u8>,
+        sender: oneshot::Sender<Result<BlockHash, BlockError>>,
+    },
+    GetBlock {
+        hash: BlockHash,
+        sender: oneshot::Sender<Result<Vec<u8>, BlockError>>,
+    },
+    PublishBatch {
+        batch: Batch,
+        sender: oneshot::Sender<Result<gossipsub::MessageId, gossipsub::PublishError>>,
+    },
+    PublishConsensus {
+        message: ConsensusMessage,
+        sender: oneshot::Sender<Result<gossipsub::MessageId, gossipsub::PublishError>>,
+    },
+    SubmitTransaction {
+        transaction: Transaction,
+        sender: oneshot::Sender<Result<TxHash, MempoolError>>,
+    },
+    MempoolContent {
+        limit: usize,
+        sender: oneshot::Sender<Vec<PooledTransaction>>,
+    },
+    MempoolStatus {
+        sender: oneshot::Sender<MempoolStatus>,
+    },
+    SyncState {
+        peer_id: PeerId,
+        sender: oneshot::Sender<Result<u64, SyncError>>,
+    },
+    AddPeer {
+        address: Multiaddr,
+        sender: oneshot::Sender<Result<(), DialError>>,
+    },
+    RemovePeer {
+        peer_id: PeerId,
+        sender: oneshot::Sender<bool>,
+    },
+    BanPeer {
+        peer_id: PeerId,
+        sender: oneshot::Sender<()>,
+    },
+    DisconnectPeer {
+        peer_id: PeerId,
+        sender: oneshot::Sender<bool>,
+    },
+    NodeInfo {
+        sender: oneshot::Sender<NodeInfo>,
+    },
+    NodeStatus {
+        sender: oneshot::Sender<NodeStatus>,
+    },
+    NodeState {
+        sender: oneshot::Sender<NodeState>,
+    },
+    SessionStats {
+        sender: oneshot::Sender<SessionStats>,
+    },
+    SubscribeTopic {
+        topic: String,
+        sender: oneshot::Sender<Result<bool, ClientError>>,
+    },
+    UnsubscribeTopic {
+        topic: String,
+        sender: oneshot::Sender<Result<bool, ClientError>>,
+    },
+    ListTopics {
+        sender: oneshot::Sender<Vec<String>>,
+    },
+    SetPeerLimits {
+        limits: ConnectionLimits,
+        sender: oneshot::Sender<()>,
+    },
+    SetTopicScoring {
+        topics: Vec<(String, gossipsub::TopicScoreParams)>,
+        sender: oneshot::Sender<()>,
+    },
+    Shutdown {
+        sender: oneshot::Sender<()>,
+    },
+    KadPut {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        sender: oneshot::Sender<Result<(), DhtError>>,
+    },
+    KadGet {
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<Vec<u8>, DhtError>>,
+    },
+    KadProvide {
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<(), DhtError>>,
+    },
+    KadProviders {
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<Vec<PeerId>, DhtError>>,
+    },
+    Dial {
+        address: Multiaddr,
+        sender: oneshot::Sender<Result<PeerId, DialError>>,
+    },
+    DialPeer {
+        peer_id: PeerId,
+        sender: oneshot::Sender<Result<PeerId, DialError>>,
+    },
+}
+
+/// The class of a command, which decides the order the node's event loop services them in when
+/// commands are queued faster than they can be executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Network control traffic, such as relay discovery and acknowledgements, and the management
+    /// of the node's peers and subscriptions.
+    Control,
+    Consensus,
+    /// Everything else, such as application data and queries of the node's state.
+    #[default]
+    Bulk,
+}
+
+/// The failure of a request issued through a `SwarmClient`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The node's event loop is no longer running.
+    NodeUnavailable,
+    Publish(gossipsub::PublishError),
+    Delivery(DeliveryError),
+    Direct(request_response::OutboundFailure),
+    Blob(BlobError),
+    Block(BlockError),
+    Sync(SyncError),
+    Mempool(MempoolError),
+    Dial(DialError),
+    Dht(DhtError),
+    Subscription(gossipsub::SubscriptionError),
+    /// The topic is one the node relies on, so its subscription cannot be changed.
+    ReservedTopic(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::NodeUnavailable => write!(f, "node is unavailable"),
+            ClientError::Publish(e) => write!(f, "publish failed: {e}"),
+            ClientError::Delivery(e) => write!(f, "delivery failed: {e}"),
+            ClientError::Direct(e) => write!(f, "direct message failed: {e}"),
+            ClientError::Blob(e) => write!(f, "{e}"),
+            ClientError::Block(e) => write!(f, "{e}"),
+            ClientError::Sync(e) => write!(f, "{e}"),
+            ClientError::Mempool(e) => write!(f, "{e}"),
+            ClientError::Dial(e) => write!(f, "dial failed: {e}"),
+            ClientError::Dht(e) => write!(f, "{e}"),
+            ClientError::Subscription(e) => write!(f, "subscription failed: {e:?}"),
+            ClientError::ReservedTopic(topic) => write!(f, "topic {topic} is reserved"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// How many items are waiting on each of a node's channels.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ChannelDepths {
+    pub control: usize,
+    pub consensus: usize,
+    pub bulk: usize,
+    pub events: usize,
+}
+
+/// A cheaply-cloneable handle for issuing commands to a running `P2pNode`.
+#[derive(Clone, Debug)]
+pub struct SwarmClient {
+    control: mpsc::Sender<SwarmCommand>,
+    consensus: mpsc::Sender<SwarmCommand>,
+    bulk: mpsc::Sender<SwarmCommand>,
+    events: broadcast::Sender<NodeEvent>,
+    event_log: Arc<Mutex<EventLog>>,
+    faults: Faults,
+    relays: RelaySnapshot,
+    channels: ChannelMetrics,
+}
+
+impl SwarmClient {
+    pub(crate) fn new(
+        control: mpsc::Sender<SwarmCommand>,
+        consensus: mpsc::Sender<SwarmCommand>,
+        bulk: mpsc::Sender<SwarmCommand>,
+        events: broadcast::Sender<NodeEvent>,
+        faults: Faults,
+        relays: RelaySnapshot,
+        channels: ChannelMetrics,
+    ) -> Self {
+        Self {
+            control,
+            consensus,
+            bulk,
+            event_log: EventLog::spawn(&events, EVENT_LOG_SIZE),
+            events,
+            faults,
+            relays,
+            channels,
+        }
+    }
+
+    /// The faults injected into the node's connections, which can be changed while it runs. They
+    /// only take effect in builds with the `fault-injection` feature.
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    /// Receive every `NodeEvent` the node emits from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Significant events the node has emitted, as described by `EventLog::events`.
+    pub fn recent_events(&self, limit: usize, since: Option<u64>) -> Vec<LoggedEvent> {
+        self.event_log
+            .lock()
+            .expect("event log lock is never poisoned")
+            .events(limit, since)
+    }
+
+    /// The latest `limit` significant events, or `None` if the event log is locked, so that a
+    /// panic hook can read it without risking a deadlock on the thread that holds it.
+    pub fn try_recent_events(&self, limit: usize) -> Option<Vec<LoggedEvent>> {
+        let log = self.event_log.try_lock().ok()?;
+        Some(log.events(limit, None))
+    }
+
+    /// Publish `data` to `topic` as bulk traffic, returning the id gossipsub assigned the message.
+    /// A `Vec<u8>` or `Bytes` is handed to the node without being copied.
+    pub async fn gossipsub_publish(
+        &self,
+        topic: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Result<gossipsub::MessageId, ClientError> {
+        self.gossipsub_publish_with_priority(topic, data, Priority::Bulk)
+            .await
+    }
+
+    /// Publish `data` to `topic`, queued behind only commands of a higher `priority`.
+    pub async fn gossipsub_publish_with_priority(
+        &self,
+        topic: impl Into<String>,
+        data: impl Into<Bytes>,
+        priority: Priority,
+    ) -> Result<gossipsub::MessageId, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::GossipsubPublish {
+                topic: topic.into(),
+                data: data.into(),
+                sender,
+            },
+            priority,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Publish)
+    }
+
+    /// Publish `data` to `topic` and wait until every one of `recipients` has acknowledged it,
+    /// republishing until the retry budget is exhausted. Use this for control messages that must
+    /// not be lost even if the mesh drops them.
+    pub async fn publish_reliable(
+        &self,
+        topic: impl Into<String>,
+        data: impl Into<Bytes>,
+        recipients: Vec<PeerId>,
+    ) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::PublishReliable {
+            topic: topic.into(),
+            data: data.into(),
+            recipients,
+            sender,
+        })
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Delivery)
+    }
+
+    /// Send `data` directly to `peer_id` over our encrypted connection to it, waiting for the
+    /// peer to confirm receipt. Use this for coordination that should not traverse the public
+    /// gossip mesh. The peer's node hands it to its event subscribers as
+    /// `NodeEvent::PrivateMessage`.
+    pub async fn send_private(&self, peer_id: PeerId, data: Vec<u8>) -> Result<(), ClientError> {
+        self.send_direct(
+            peer_id,
+            DirectMessage {
+                topic: None,
+                payload: data,
+            },
+        )
+        .await
+    }
+
+    /// Deliver a `topic` message only to the chosen `peers` rather than the whole mesh, such as
+    /// when responding to a single requester. Returns the outcome for each peer.
+    pub async fn publish_to(
+        &self,
+        topic: impl Into<String>,
+        data: Vec<u8>,
+        peers: Vec<PeerId>,
+    ) -> Vec<(PeerId, Result<(), ClientError>)> {
+        let topic = topic.into();
+        let sends = peers.into_iter().map(|peer_id| {
+            let message = DirectMessage {
+                topic: Some(topic.clone()),
+                payload: data.clone(),
+            };
+            async move { (peer_id, self.send_direct(peer_id, message).await) }
+        });
+        futures::future::join_all(sends).await
+    }
+
+    async fn send_direct(
+        &self,
+        peer_id: PeerId,
+        message: DirectMessage,
+    ) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::SendDirect {
+            peer_id,
+            message,
+            sender,
+        })
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Direct)
+    }
+
+    /// Offer `data` to `peer_id` as a blob named `name`, returning its id once the peer accepts
+    /// the offer. The peer then downloads it in chunks at its own pace.
+    pub async fn offer_blob(
+        &self,
+        peer_id: PeerId,
+        name: impl Into<String>,
+        data: Vec<u8>,
+    ) -> Result<u64, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::OfferBlob {
+                peer_id,
+                name: name.into(),
+                data,
+                sender,
+            },
+            Priority::Bulk,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Blob)
+    }
+
+    /// Download the blob `id` that `peer_id` offered us, as announced by a
+    /// `NodeEvent::BlobOffered`. If the transfer is interrupted, fetching it again resumes from
+    /// the last chunk received.
+    pub async fn fetch_blob(&self, peer_id: PeerId, id: u64) -> Result<Vec<u8>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::FetchBlob {
+                peer_id,
+                id,
+                sender,
+            },
+            Priority::Bulk,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Blob)
+    }
+
+    /// Store `data` as a content-addressed block and advertise this node as its provider,
+    /// returning the block's hash.
+    pub async fn put_block(&self, data: Vec<u8>) -> Result<BlockHash, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::PutBlock { data, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Block)
+    }
+
+    /// The block with this `hash`, from our own store or else fetched from a provider found on
+    /// the DHT.
+    pub async fn get_block(&self, hash: BlockHash) -> Result<Vec<u8>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::GetBlock { hash, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Block)
+    }
+
+    /// Propagate a sealed batch, compressed, ahead of bulk traffic.
+    pub async fn publish_batch(&self, batch: Batch) -> Result<gossipsub::MessageId, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::PublishBatch { batch, sender },
+            Priority::Consensus,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Publish)
+    }
+
+    /// Publish a consensus message, numbered so that every receiver hands our messages to its
+    /// consensus engine in the order we sent them.
+    pub async fn publish_consensus(
+        &self,
+        message: ConsensusMessage,
+    ) -> Result<gossipsub::MessageId, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::PublishConsensus { message, sender },
+            Priority::Consensus,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Publish)
+    }
+
+    /// Add `transaction` to our mempool and gossip it to peers, returning its hash. Where the
+    /// mempool topic is bundled, this returns once the transaction's bundle is published, and
+    /// fails if that bundle could not be.
+    pub async fn submit_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> Result<TxHash, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(
+            SwarmCommand::SubmitTransaction {
+                transaction,
+                sender,
+            },
+            Priority::Bulk,
+        )
+        .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Mempool)
+    }
+
+    /// Up to `limit` transactions in our mempool, oldest first.
+    pub async fn mempool_content(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PooledTransaction>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::MempoolContent { limit, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    pub async fn mempool_status(&self) -> Result<MempoolStatus, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::MempoolStatus { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Replace the local application state with a snapshot downloaded from `peer_id`, returning
+    /// the number of entries restored.
+    pub async fn sync_state(&self, peer_id: PeerId) -> Result<u64, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::SyncState { peer_id, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Sync)
+    }
+
+    /// The last `limit` messages this node received on `topic`, oldest first.
+    pub async fn recent_messages(
+        &self,
+        topic: impl Into<String>,
+        limit: usize,
+    ) -> Result<Vec<JournalEntry>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::RecentMessages {
+            topic: topic.into(),
+            limit,
+            sender,
+        })
+        .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Traffic statistics for every topic this node has received messages on.
+    pub async fn topic_stats(&self) -> Result<Vec<TopicStats>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::TopicStats { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// The peers this node currently has connections to.
+    pub async fn connected_peers(&self) -> Result<Vec<PeerId>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::ConnectedPeers { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// The peers this node currently has connections to, with what each identified itself as.
+    pub async fn peers(&self) -> Result<Vec<PeerInfo>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::Peers { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Every peer in the Kademlia routing table, with the addresses known for it.
+    pub async fn routing_table_peers(
+        &self,
+    ) -> Result<HashMap<PeerId, Vec<Multiaddr>>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::RoutingTablePeers { sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Every peer in the Kademlia routing table, with its addresses and bucket.
+    pub async fn routing_table(&self) -> Result<Vec<RoutingTableEntry>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::RoutingTable { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Dial `address`, adding it to the routing table if it names its peer. Returns once the dial
+    /// has started rather than once it connects.
+    pub async fn add_peer(&self, address: Multiaddr) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::AddPeer { address, sender })
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dial)
+    }
+
+    /// Forget a peer's addresses and disconnect from it, returning whether it was known.
+    pub async fn remove_peer(&self, peer_id: PeerId) -> Result<bool, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::RemovePeer { peer_id, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Disconnect from a peer and refuse any further connections with it.
+    pub async fn ban_peer(&self, peer_id: PeerId) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::BanPeer { peer_id, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Close every connection to a peer, returning whether there were any.
+    pub async fn disconnect_peer(&self, peer_id: PeerId) -> Result<bool, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::DisconnectPeer { peer_id, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    pub async fn node_info(&self) -> Result<NodeInfo, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::NodeInfo { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Uptime, connections, messages and hole punches since the node started.
+    pub async fn session_stats(&self) -> Result<SessionStats, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::SessionStats { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Peer, mesh and relay counts, external addresses, and bootstrap and NAT status, in one
+    /// round trip to the node.
+    pub async fn node_status(&self) -> Result<NodeStatus, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::NodeStatus { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// A snapshot of the node's internals, for diagnosing it.
+    pub async fn node_state(&self) -> Result<NodeState, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::NodeState { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// How many commands are queued on each of the node's channels, and events on its event
+    /// channel.
+    pub fn channel_depths(&self) -> ChannelDepths {
+        let depth = |sender: &mpsc::Sender<SwarmCommand>| sender.max_capacity() - sender.capacity();
+        ChannelDepths {
+            control: depth(&self.control),
+            consensus: depth(&self.consensus),
+            bulk: depth(&self.bulk),
+            events: self.events.len(),
+        }
+    }
+
+    /// Replace the limits on the node's connections. Existing connections over a new limit are
+    /// kept.
+    pub async fn set_peer_limits(&self, limits: ConnectionLimits) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::SetPeerLimits { limits, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Replace the score parameters of each topic, keyed by name. Peers' scores so far are kept.
+    pub async fn set_topic_scoring(
+        &self,
+        topics: Vec<(String, gossipsub::TopicScoreParams)>,
+    ) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::SetTopicScoring { topics, sender })
+            .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Stop the node, returning once it has disconnected from its peers and `run` has returned.
+    pub async fn shutdown(&self) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::Shutdown { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Store `value` under `key` on the DHT, returning once it has been replicated to a peer.
+    pub async fn kad_put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::KadPut { key, value, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dht)
+    }
+
+    /// The value stored under `key` on the DHT.
+    pub async fn kad_get(&self, key: Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::KadGet { key, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dht)
+    }
+
+    /// Advertise this node on the DHT as a provider of `key`.
+    pub async fn kad_provide(&self, key: Vec<u8>) -> Result<(), ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::KadProvide { key, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dht)
+    }
+
+    /// Every peer advertising itself on the DHT as a provider of `key`.
+    pub async fn kad_providers(&self, key: Vec<u8>) -> Result<Vec<PeerId>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_with_priority(SwarmCommand::KadProviders { key, sender }, Priority::Bulk)
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dht)
+    }
+
+    /// The relays this node has requested reservations from, and the state of each, read without
+    /// a round trip to the node.
+    pub fn my_relays(&self) -> Arc<[RelayInfo]> {
+        self.relays.get()
+    }
+
+    /// Dial `address`, returning the peer once connected.
+    pub async fn dial(&self, address: Multiaddr) -> Result<PeerId, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::Dial { address, sender })
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dial)
+    }
+
+    /// Dial `peer_id` at whichever addresses the node knows for it, returning once connected.
+    pub async fn dial_peer(&self, peer_id: PeerId) -> Result<PeerId, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::DialPeer { peer_id, sender })
+            .await?;
+        receiver
+            .await
+            .map_err(|_| ClientError::NodeUnavailable)?
+            .map_err(ClientError::Dial)
+    }
+
+    /// Subscribe to `topic`, returning false if already subscribed.
+    pub async fn subscribe_topic(&self, topic: impl Into<String>) -> Result<bool, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::SubscribeTopic {
+            topic: topic.into(),
+            sender,
+        })
+        .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)?
+    }
+
+    /// Unsubscribe from `topic`, returning false if not subscribed.
+    pub async fn unsubscribe_topic(&self, topic: impl Into<String>) -> Result<bool, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_control(SwarmCommand::UnsubscribeTopic {
+            topic: topic.into(),
+            sender,
+        })
+        .await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)?
+    }
+
+    /// The topics this node is subscribed to.
+    pub async fn list_topics(&self) -> Result<Vec<String>, ClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(SwarmCommand::ListTopics { sender }).await?;
+        receiver.await.map_err(|_| ClientError::NodeUnavailable)
+    }
+
+    /// Send a command that only reads or queries the node, which waits behind control and
+    /// consensus traffic.
+    async fn send(&self, command: SwarmCommand) -> Result<(), ClientError> {
+        self.send_with_priority(command, Priority::Bulk).await
+    }
+
+    /// Send a command that manages the node, its peers or its subscriptions.
+    async fn send_control(&self, command: SwarmCommand) -> Result<(), ClientError> {
+        self.send_with_priority(command, Priority::Control).await
+    }
+
+    async fn send_with_priority(
+        &self,
+        command: SwarmCommand,
+        priority: Priority,
+    ) -> Result<(), ClientError> {
+        let sender = match priority {
+            Priority::Control => &self.control,
+            Priority::Consensus => &self.consensus,
+            Priority::Bulk => &self.bulk,
+        };
+        let channel = Channel::from(priority);
+        match sender.try_send(command) {
+            Ok(()) => {}
+            Err(TrySendError::Full(command)) => {
+                self.channels.blocked(channel);
+                sender
+                    .send(command)
+                    .await
+                    .map_err(|_| ClientError::NodeUnavailable)?;
+            }
+            Err(TrySendError::Closed(_)) => return Err(ClientError::NodeUnavailable),
+        }
+        // Recorded here as well as by the node, so that the depth shows even if it has stalled.
+        self.channels
+            .set_depth(channel, sender.max_capacity() - sender.capacity());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use crate::config::ClockConfig;
+use crate::envelope::unix_millis;
+use crate::event::NodeEvent;
+use libp2p::{request_response, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+/// The request-response protocol nodes ask each other the time over, to measure how far apart
+/// their clocks are.
+pub const TIME_PROTOCOL: &str = "/sigil/time/1.0.0";
+
+pub type TimeBehaviour = request_response::cbor::Behaviour<TimeRequest, TimeResponse>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeRequest;
+
+/// The responder's clock, in milliseconds since the Unix epoch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeResponse(pub u64);
+
+/// How far ahead of our clock a peer's clock is, in milliseconds, given when we asked for its
+/// time, the time it answered with, and when the answer arrived. The answer is taken to have been
+/// made halfway through the round trip.
+pub fn estimate_skew(sent_at: u64, peer_time: u64, received_at: u64) -> i64 {
+    let midpoint = (sent_at + received_at.max(sent_at)) / 2;
+    peer_time as i64 - midpoint as i64
+}
+
+/// A change in whether a peer's clock is within the tolerated skew.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkewChange {
+    /// The peer's clock has drifted beyond the tolerated skew.
+    Exceeded,
+    /// The peer's clock is back within the tolerated skew.
+    Recovered,
+}
+
+/// Measures the clock skew of each connected peer whenever it identifies itself, warning of
+/// peers whose clocks diverge too far from ours.
+pub struct ClockSkew {
+    max_skew_millis: u64,
+    requests: HashMap<request_response::OutboundRequestId, u64>,
+    skews: HashMap<PeerId, i64>,
+    events: broadcast::Sender<NodeEvent>,
+}
+
+impl ClockSkew {
+    pub fn new(config: &ClockConfig, events: broadcast::Sender<NodeEvent>) -> Self {
+        Self {
+            max_skew_millis: config.max_skew_millis,
+            requests: HashMap::new(),
+            skews: HashMap::new(),
+            events,
+        }
+    }
+
+    /// Ask `peer` for its time.
+    pub fn measure(&mut self, behaviour: &mut TimeBehaviour, peer: PeerId) {
+        let request_id = behaviour.send_request(&peer, TimeRequest);
+        self.requests.insert(request_id, unix_millis());
+    }
+
+    /// The last measured skew of `peer`'s clock, in milliseconds ahead of ours.
+    pub fn skew(&self, peer: &PeerId) -> Option<i64> {
+        self.skews.get(peer).copied()
+    }
+
+    pub fn disconnected(&mut self, peer: &PeerId) {
+        self.skews.remove(peer);
+    }
+
+    /// Answer requests for our time, and record the skew of peers that answered ours, returning
+    /// the peer whose clock crossed the tolerated skew, if any.
+    pub fn handle_event(
+        &mut self,
+        behaviour: &mut TimeBehaviour,
+        event: request_response::Event<TimeRequest, TimeResponse>,
+    ) -> Option<(PeerId, SkewChange)> {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message: request_response::Message::Request { channel, .. },
+            } => {
+                if behaviour
+                    .send_response(channel, TimeResponse(unix_millis()))
+                    .is_err()
+                {
+                    tracing::warn!(%peer, "Failed to send our time");
+                }
+                None
+            }
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+            } => {
+                let sent_at = self.requests.remove(&request_id)?;
+                let skew = estimate_skew(sent_at, response.0, unix_millis());
+                self.record(peer, skew)
+            }
+            request_response::Event::OutboundFailure { request_id, .. } => {
+                self.requests.remove(&request_id);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Remember `peer`'s skew, returning whether it crossed the tolerated skew either way.
+    fn record(&mut self, peer: PeerId, skew: i64) -> Option<(PeerId, SkewChange)> {
+        let max_skew_millis = self.max_skew_millis;
+        let exceeds = |skew: i64| skew.unsigned_abs() > max_skew_millis;
+        let exceeded = self.skews.insert(peer, skew).is_some_and(exceeds);
+        match (exceeded, exceeds(skew)) {
+            (false, true) => {
+                tracing::warn!(
+                    %peer,
+                    skew_ms = skew,
+                    max_skew_ms = max_skew_millis,
+                    "Peer's clock is off ours beyond the tolerated skew"
+                );
+                let _ = self.events.send(NodeEvent::ClockSkewed {
+                    peer,
+                    skew_millis: skew,
+                });
+                Some((peer, SkewChange::Exceeded))
+            }
+            (true, false) => Some((peer, SkewChange::Recovered)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,1742 @@
+use crate::batch::BatchLimits;
+use crate::data_dir::{KEYPAIR_FILE, SNAPSHOT_FILE};
+use crate::keep_alive::ConnectionClass;
+use ipnet::IpNet;
+use libp2p::connection_limits::ConnectionLimits;
+use libp2p::gossipsub::{PeerScoreParams, PeerScoreThresholds, TopicHash, TopicScoreParams};
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use prometheus_client::metrics::histogram::exponential_buckets;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::EnvFilter;
+
+/// The configuration of a sigil node. Every field has a default, so an empty file is valid.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The deployment shape the node is configured for, which sets the defaults of the
+    /// `network` toggles and of `peer_limits`.
+    pub role: Role,
+
+    /// A bundle of settings for the environment the node runs in. Explicit settings override the
+    /// profile, which overrides the role.
+    pub profile: Option<Profile>,
+    pub network: NetworkConfig,
+    pub topics: TopicsConfig,
+    pub gossipsub: GossipsubConfig,
+    pub blob: BlobConfig,
+    pub sync: SyncConfig,
+    pub mempool: MempoolConfig,
+    pub batch: BatchConfig,
+    pub webhook: WebhookConfig,
+    pub rpc: RpcConfig,
+    pub relay: RelayConfig,
+    pub peer_limits: PeerLimitsConfig,
+    pub keep_alive: KeepAliveConfig,
+    pub dial_limits: DialLimitsConfig,
+    pub bandwidth: BandwidthConfig,
+    pub faults: FaultConfig,
+    pub chaos: ChaosConfig,
+    pub clock: ClockConfig,
+    pub log: LogConfig,
+    pub telemetry: TelemetryConfig,
+    pub identity: IdentityConfig,
+    pub snapshot: SnapshotConfig,
+    pub schedule: ScheduleConfig,
+    pub reload: ReloadConfig,
+
+    /// A file to write the process ID to while the node runs, for service managers.
+    pub pid_file: Option<PathBuf>,
+
+    /// The directory the node keeps its persistent state in, such as its keypair. It is created
+    /// at startup if it does not exist.
+    pub data_dir: Option<PathBuf>,
+
+    /// A name for the node, which peers show alongside its peer id. It is sent to every peer in
+    /// the node's agent version, so it must not be secret.
+    pub node_name: Option<String>,
+}
+
+/// The longest node name, which keeps agent versions short.
+pub const MAX_NODE_NAME_LEN: usize = 64;
+
+/// The prefix of environment variables that override configuration fields.
+pub const ENV_PREFIX: &str = "SIGIL_";
+
+/// The formats a configuration file may be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// The format of a file, judged by its extension and defaulting to TOML.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from a TOML, YAML or JSON file, or from defaults when there is
+    /// none, with fields overridden by `SIGIL_` environment variables.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let (contents, format) = match path {
+            Some(path) => (
+                fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read config {}: {e}", path.display()))?,
+                ConfigFormat::of(path),
+            ),
+            None => (String::new(), ConfigFormat::Toml),
+        };
+        Self::parse_with_env(&contents, format, std::env::vars()).map_err(|e| match path {
+            Some(path) => format!("failed to parse config {}: {e}", path.display()).into(),
+            None => e,
+        })
+    }
+
+    /// Whether to relay connections, as configured or as the role suggests.
+    pub fn relay(&self) -> bool {
+        self.network.relay.unwrap_or(self.role.relay())
+    }
+
+    pub fn kad_mode(&self) -> KadMode {
+        self.network.kad_mode.unwrap_or(self.role.kad_mode())
+    }
+
+    /// Whether to discover peers over mDNS, as configured or as the role suggests. Roles only
+    /// suggest it in builds with the `mdns` feature.
+    pub fn mdns(&self) -> bool {
+        self.network
+            .mdns
+            .unwrap_or(cfg!(feature = "mdns") && self.role.mdns())
+    }
+
+    /// The connection limits, each as configured or as the role suggests.
+    pub fn peer_limits(&self) -> ConnectionLimits {
+        self.peer_limits.or(&self.role.peer_limits()).limits()
+    }
+
+    /// Where the persistent artifact `name` is kept in the data directory, if one is configured.
+    pub fn data_path(&self, name: impl AsRef<Path>) -> Option<PathBuf> {
+        self.data_dir.as_ref().map(|dir| dir.join(name))
+    }
+
+    /// The identity settings, with the keypair kept in the data directory unless its path is
+    /// absolute. Relative keypair paths are resolved against the data directory.
+    /// The dev and test profiles use a fresh keypair for each run unless one is configured.
+    pub fn identity(&self) -> IdentityConfig {
+        let keypair_path = match &self.identity.keypair_path {
+            Some(path) if path.is_relative() => self.data_path(path).or(Some(path.clone())),
+            Some(path) => Some(path.clone()),
+            None if self.profile.is_some_and(Profile::ephemeral_keys) => None,
+            None => self.data_path(KEYPAIR_FILE),
+        };
+        IdentityConfig {
+            keypair_path,
+            ..self.identity.clone()
+        }
+    }
+
+    /// Where the network snapshot is kept, which is in the data directory unless its path is
+    /// absolute, or `None` if the node keeps none.
+    pub fn snapshot_path(&self) -> Option<PathBuf> {
+        if !self.snapshot.enabled {
+            return None;
+        }
+        match &self.snapshot.path {
+            Some(path) if path.is_relative() => self.data_path(path).or(Some(path.clone())),
+            Some(path) => Some(path.clone()),
+            None => self.data_path(SNAPSHOT_FILE),
+        }
+    }
+
+    /// Each topic the node scores its peers on, with that topic's score parameters.
+    pub fn scored_topics(&self) -> Vec<(&str, &TopicScoringConfig)> {
+        let topics = &self.topics;
+        let scoring = &self.gossipsub.scoring;
+        topics
+            .application
+            .iter()
+            .chain([&topics.ack, &topics.mempool, &topics.consensus])
+            .map(|topic| (topic.as_str(), &scoring.topic))
+            .chain([(topics.batch.as_str(), &self.batch.scoring)])
+            .collect()
+    }
+
+    /// The default configuration as TOML, with every setting commented, for operators to start
+    /// from.
+    pub fn default_toml() -> &'static str {
+        include_str!("default.toml")
+    }
+
+    /// Parse a configuration, overriding its fields with the given `SIGIL_` variables.
+    ///
+    /// Nested fields are separated by double underscores, so `SIGIL_RPC__RATE_LIMIT__BURST`
+    /// sets `rpc.rate_limit.burst`. Values are read as TOML where they parse as such, such as
+    /// numbers, booleans and arrays, and as strings otherwise; a string that looks like a number
+    /// must be quoted.
+    pub fn parse_with_env(
+        contents: &str,
+        format: ConfigFormat,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut value = match format {
+            // An empty file is a valid configuration in every format.
+            _ if contents.trim().is_empty() => serde_json::Value::Object(Default::default()),
+            ConfigFormat::Toml => serde_json::to_value(toml::from_str::<toml::Table>(contents)?)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+        };
+        for (name, override_value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let path = key.to_lowercase();
+            let mut segments = path.split("__").peekable();
+            let mut current = &mut value;
+            while let Some(segment) = segments.next() {
+                let table = current
+                    .as_object_mut()
+                    .ok_or_else(|| format!("{name} overrides a field that is not a table"))?;
+                if segments.peek().is_none() {
+                    table.insert(segment.to_string(), env_value(&override_value)?);
+                    break;
+                }
+                current = table
+                    .entry(segment)
+                    .or_insert_with(|| serde_json::Value::Object(Default::default()));
+            }
+        }
+        if let Some(profile) = value.get("profile") {
+            let profile = Profile::deserialize(profile)?;
+            let mut preset = profile.preset();
+            merge(&mut preset, value);
+            value = preset;
+        }
+        let config = Config::deserialize(value)?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Reject a malformed node name, and settings that the profile forbids.
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(name) = &self.node_name {
+            if name.is_empty() || name.len() > MAX_NODE_NAME_LEN {
+                return Err(
+                    format!("node_name must be 1 to {MAX_NODE_NAME_LEN} bytes long").into(),
+                );
+            }
+            if name.chars().any(|c| c.is_control() || c == '(' || c == ')') {
+                return Err("node_name must not contain parentheses or control characters".into());
+            }
+        }
+        if self.profile != Some(Profile::Prod) {
+            return Ok(());
+        }
+        if self.identity.secret_key_seed.is_some() {
+            return Err("the prod profile does not allow identity.secret_key_seed".into());
+        }
+        if self.identity().keypair_path.is_none() {
+            return Err(
+                "the prod profile requires a keypair file: set identity.keypair_path or data_dir"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Lay `overrides` over `base`, replacing the fields it sets and merging the tables within.
+fn merge(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// An environment variable's value as TOML, falling back to a plain string.
+fn env_value(value: &str) -> Result<serde_json::Value, serde_json::Error> {
+    let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+    serde_json::to_value(value)
+}
+
+/// The network the node joins, and the ports it listens on for peers. Each port is assigned by the
+/// OS when unset, so ports only need fixing where they are forwarded through a NAT, which may
+/// forward TCP and UDP differently.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The name of the network, which peers must share to connect. It is part of the identify
+    /// protocol version, such as `/sigil/0.1`.
+    pub name: String,
+
+    pub tcp_port: Option<u16>,
+
+    /// The UDP port QUIC listens on.
+    pub quic_port: Option<u16>,
+
+    /// Peers to dial at startup, to join the network through.
+    pub peers: Vec<Peer>,
+
+    /// Whether to relay connections for peers behind NATs. The role decides when unset.
+    pub relay: Option<bool>,
+
+    /// Whether to serve the DHT to peers. The role decides when unset.
+    pub kad_mode: Option<KadMode>,
+
+    /// Whether to discover peers on the local network over mDNS. The role decides when unset.
+    pub mdns: Option<bool>,
+}
+
+/// A preset of defaults for a common deployment shape. Settings given explicitly override it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// A publicly reachable node that relays connections for peers behind NATs.
+    Relay,
+
+    /// A publicly reachable entry point that serves the DHT to joining peers.
+    Bootnode,
+
+    /// A node that uses the network without serving the DHT, such as one behind a NAT.
+    Client,
+
+    /// A node that takes part in every protocol, limited only as configured.
+    #[default]
+    Full,
+}
+
+impl Role {
+    pub fn relay(self) -> bool {
+        self == Role::Relay
+    }
+
+    pub fn kad_mode(self) -> KadMode {
+        match self {
+            Role::Client => KadMode::Client,
+            Role::Relay | Role::Bootnode | Role::Full => KadMode::Server,
+        }
+    }
+
+    /// Public entry points have no use for local discovery.
+    pub fn mdns(self) -> bool {
+        matches!(self, Role::Client | Role::Full)
+    }
+
+    /// Public entry points accept many short-lived connections but few from any one peer, and
+    /// clients need only a handful.
+    pub fn peer_limits(self) -> PeerLimitsConfig {
+        match self {
+            Role::Relay | Role::Bootnode => PeerLimitsConfig {
+                max_established: Some(2048),
+                max_established_per_peer: Some(2),
+                max_pending_incoming: Some(256),
+                ..PeerLimitsConfig::default()
+            },
+            Role::Client => PeerLimitsConfig {
+                max_established: Some(64),
+                max_established_incoming: Some(16),
+                max_established_per_peer: Some(2),
+                ..PeerLimitsConfig::default()
+            },
+            Role::Full => PeerLimitsConfig::default(),
+        }
+    }
+}
+
+/// A bundle of settings for the environment a node runs in. Settings given explicitly override it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// A node on a developer's machine, which finds its peers over mDNS and floods its messages
+    /// to them.
+    Dev,
+
+    /// A node in a test network, which is joined through configured peers and does not score
+    /// them, as tests provoke the misbehaviour that scoring punishes.
+    Test,
+
+    /// A node in production, which keeps its keypair in a file and scores its peers.
+    Prod,
+}
+
+impl Profile {
+    /// The settings of the profile, in the shape of a configuration file.
+    pub fn preset(self) -> serde_json::Value {
+        match self {
+            Profile::Dev => serde_json::json!({
+                "network": { "mdns": true },
+                "gossipsub": { "flood_publish": true },
+            }),
+            Profile::Test => serde_json::json!({
+                "network": { "mdns": false },
+                "gossipsub": { "flood_publish": true, "scoring": { "enabled": false } },
+            }),
+            Profile::Prod => serde_json::json!({
+                "network": { "mdns": false },
+                "gossipsub": { "scoring": { "enabled": true } },
+            }),
+        }
+    }
+
+    /// Whether the node uses a fresh keypair for each run unless one is configured.
+    pub fn ephemeral_keys(self) -> bool {
+        self != Profile::Prod
+    }
+}
+
+/// Whether the node serves the DHT, or only queries it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KadMode {
+    Client,
+    Server,
+
+    /// Serve the DHT once the node has confirmed an external address.
+    Auto,
+}
+
+/// A peer to dial at startup, known by its id, its addresses, or both. Each of its addresses is
+/// tried, so a peer can be listed by every transport or name it is reachable through. A peer
+/// listed by id alone is dialed at whatever addresses discovery has found for it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Peer {
+    pub peer_id: Option<PeerId>,
+    pub addresses: Vec<Multiaddr>,
+}
+
+impl Peer {
+    /// The peer's id, as configured or as named by the `/p2p/` suffix of one of its addresses.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        self.peer_id.or_else(|| {
+            self.addresses
+                .iter()
+                .find_map(|address| match address.iter().last() {
+                    Some(Protocol::P2p(peer_id)) => Some(peer_id),
+                    _ => None,
+                })
+        })
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            name: "sigil".to_string(),
+            tcp_port: None,
+            quic_port: None,
+            peers: Vec::new(),
+            relay: None,
+            kad_mode: None,
+            mdns: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// The addresses to listen on, on every interface.
+    pub fn listen_addresses(&self) -> [Multiaddr; 2] {
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        [
+            Multiaddr::from(unspecified)
+                .with(Protocol::Udp(self.quic_port.unwrap_or(0)))
+                .with(Protocol::QuicV1),
+            Multiaddr::from(unspecified).with(Protocol::Tcp(self.tcp_port.unwrap_or(0))),
+        ]
+    }
+}
+
+/// The gossipsub topics the node uses. Networks sharing peers must pick distinct names so that
+/// their messages do not mix.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopicsConfig {
+    /// Topics subscribed to at startup for application messages. Lines read from stdin are
+    /// published to the first.
+    pub application: Vec<String>,
+
+    /// The topic that acknowledgements of reliable messages are published to.
+    pub ack: String,
+
+    /// The topic that pending transactions are gossiped on.
+    pub mempool: String,
+
+    /// The topic that sealed batches are propagated on, kept apart from the small, frequent
+    /// messages of other topics.
+    pub batch: String,
+
+    /// The topic that consensus messages are published to.
+    pub consensus: String,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            application: vec!["test-net".to_string()],
+            ack: "test-net-ack".to_string(),
+            mempool: "mempool".to_string(),
+            batch: "batches".to_string(),
+            consensus: "consensus".to_string(),
+        }
+    }
+}
+
+/// A snapshot of the node's view of the network, such as its peers and relays, written
+/// periodically and restored at startup so that a restarted node reconnects quickly.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+
+    /// The file the snapshot is kept in. It defaults to a file in the data directory, and no
+    /// snapshot is kept when neither is set.
+    pub path: Option<PathBuf>,
+
+    /// How often the snapshot is written, besides at shutdown.
+    pub interval_secs: u64,
+
+    /// The most peers the snapshot remembers, to dial at startup.
+    pub max_peers: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            interval_secs: 60,
+            max_peers: 64,
+        }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(1))
+    }
+}
+
+/// How often the node's recurring maintenance jobs run. A job whose interval is zero never runs.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// How often the DHT's routing table is refreshed by looking up the node's own id.
+    pub kad_bootstrap_interval_secs: u64,
+
+    /// How often reservations that relays have closed are requested again.
+    pub relay_check_interval_secs: u64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            kad_bootstrap_interval_secs: 300,
+            relay_check_interval_secs: 30,
+        }
+    }
+}
+
+impl ScheduleConfig {
+    pub fn kad_bootstrap_interval(&self) -> Option<Duration> {
+        (self.kad_bootstrap_interval_secs > 0)
+            .then(|| Duration::from_secs(self.kad_bootstrap_interval_secs))
+    }
+
+    pub fn relay_check_interval(&self) -> Option<Duration> {
+        (self.relay_check_interval_secs > 0)
+            .then(|| Duration::from_secs(self.relay_check_interval_secs))
+    }
+}
+
+/// Where the node's keypair, and so its peer id, comes from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdentityConfig {
+    /// A file holding the node's protobuf-encoded keypair, so that its peer id survives restarts.
+    /// It defaults to a file in the data directory, and a fresh keypair is used for each run when
+    /// neither is set.
+    pub keypair_path: Option<PathBuf>,
+
+    /// Generate a keypair and write it to `keypair_path` if the file does not exist yet.
+    pub generate_if_missing: bool,
+
+    /// Derive the keypair from this byte instead, for local testing only. Such keys are trivially
+    /// predictable, so a warning is logged whenever one is used.
+    #[serde(skip_serializing)]
+    pub secret_key_seed: Option<u8>,
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        Self {
+            keypair_path: None,
+            generate_if_missing: true,
+            secret_key_seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GossipsubConfig {
+    /// Publish our own messages to every subscribed peer rather than only our mesh peers, which
+    /// keeps delivery reliable in tiny networks before their meshes have formed.
+    pub flood_publish: bool,
+
+    /// Also speak the legacy floodsub protocol, for interoperating with floodsub-only peers.
+    pub support_floodsub: bool,
+
+    /// The prefix of the gossipsub protocol ids we negotiate, such as `/meshsub/1.1.0`.
+    pub protocol_id_prefix: String,
+
+    /// The newest gossipsub protocol version we negotiate. Version 1.2 and its IDONTWANT control
+    /// messages require a newer libp2p than sigil currently builds against.
+    pub protocol_version: GossipsubVersion,
+
+    /// Topics whose message ids hash only the payload, so that identical payloads are deduplicated
+    /// network-wide regardless of who published them.
+    pub content_addressed_topics: Vec<String>,
+
+    /// How many received messages may be validated at once.
+    pub validation_concurrency: usize,
+
+    /// How many received messages may await validation before further messages are ignored.
+    pub validation_queue_size: usize,
+
+    /// How long validating a message may take before it is ignored.
+    pub validation_timeout_millis: u64,
+
+    /// How far a message's timestamp may be from our clock before it is rejected as stale. Nonces
+    /// are remembered for this long to reject replays.
+    pub replay_window_secs: u64,
+
+    /// How many recently received messages to keep per topic for debugging.
+    pub journal_capacity: usize,
+
+    pub scoring: ScoringConfig,
+    pub bundle: BundleConfig,
+}
+
+impl Default for GossipsubConfig {
+    fn default() -> Self {
+        Self {
+            flood_publish: true,
+            support_floodsub: false,
+            protocol_id_prefix: "/meshsub".to_string(),
+            protocol_version: GossipsubVersion::default(),
+            content_addressed_topics: Vec::new(),
+            validation_concurrency: 64,
+            validation_queue_size: 1024,
+            validation_timeout_millis: 2000,
+            replay_window_secs: 60,
+            journal_capacity: 128,
+            scoring: ScoringConfig::default(),
+            bundle: BundleConfig::default(),
+        }
+    }
+}
+
+/// Bundling of small messages published on chatty topics, such as the mempool's, into one gossip
+/// message. Only nodes that understand bundles can receive them, so no topic is bundled unless
+/// listed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BundleConfig {
+    /// The topics whose messages are bundled.
+    pub topics: Vec<String>,
+
+    /// How long a message may wait for others to join its bundle.
+    pub flush_interval_millis: u64,
+
+    /// The most messages in a bundle.
+    pub max_messages: usize,
+
+    /// The largest bundle, in bytes. A message that would take a bundle past it starts the next
+    /// bundle instead.
+    pub max_bytes: usize,
+}
+
+impl Default for BundleConfig {
+    fn default() -> Self {
+        Self {
+            topics: Vec::new(),
+            flush_interval_millis: 50,
+            max_messages: 64,
+            max_bytes: 60 * 1024,
+        }
+    }
+}
+
+impl BundleConfig {
+    /// How often pending bundles are published, if any topic is bundled.
+    pub fn flush_interval(&self) -> Option<Duration> {
+        (!self.topics.is_empty()).then(|| Duration::from_millis(self.flush_interval_millis.max(1)))
+    }
+}
+
+/// Transfers of large blobs directly between two peers.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlobConfig {
+    /// How many bytes of a blob are requested at a time.
+    pub chunk_size: u64,
+
+    /// The largest blob offer we accept.
+    pub max_size: u64,
+
+    /// How many offers from one peer may wait to be fetched at once. Further offers are rejected.
+    pub max_offers_per_peer: usize,
+
+    /// How many offers from all peers together may wait to be fetched at once.
+    pub max_offers: usize,
+
+    /// How long a transfer may make no progress before it is forgotten, in either direction.
+    pub idle_secs: u64,
+}
+
+impl Default for BlobConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 256 * 1024,
+            max_size: 256 * 1024 * 1024,
+            max_offers_per_peer: 4,
+            max_offers: 64,
+            idle_secs: 300,
+        }
+    }
+}
+
+impl BlobConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_secs.max(1))
+    }
+}
+
+/// Synchronization of the application state when a node joins the network.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Download the state from the first sigil peer identified after startup.
+    pub on_join: bool,
+
+    /// How many state entries are requested at a time.
+    pub chunk_entries: usize,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            on_join: true,
+            chunk_entries: 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MempoolConfig {
+    /// How many pending transactions are pooled before the oldest are evicted.
+    pub capacity: usize,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self { capacity: 4096 }
+    }
+}
+
+/// Limits on the node's connections, which can be changed by reloading. Each is unlimited unless
+/// set or limited by the node's role.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerLimitsConfig {
+    pub max_established: Option<u32>,
+    pub max_established_incoming: Option<u32>,
+    pub max_established_outgoing: Option<u32>,
+    pub max_established_per_peer: Option<u32>,
+    pub max_pending_incoming: Option<u32>,
+    pub max_pending_outgoing: Option<u32>,
+}
+
+impl PeerLimitsConfig {
+    /// These limits, falling back to `defaults` for those that are unset.
+    pub fn or(&self, defaults: &PeerLimitsConfig) -> PeerLimitsConfig {
+        PeerLimitsConfig {
+            max_established: self.max_established.or(defaults.max_established),
+            max_established_incoming: self
+                .max_established_incoming
+                .or(defaults.max_established_incoming),
+            max_established_outgoing: self
+                .max_established_outgoing
+                .or(defaults.max_established_outgoing),
+            max_established_per_peer: self
+                .max_established_per_peer
+                .or(defaults.max_established_per_peer),
+            max_pending_incoming: self.max_pending_incoming.or(defaults.max_pending_incoming),
+            max_pending_outgoing: self.max_pending_outgoing.or(defaults.max_pending_outgoing),
+        }
+    }
+
+    pub fn limits(&self) -> ConnectionLimits {
+        ConnectionLimits::default()
+            .with_max_established(self.max_established)
+            .with_max_established_incoming(self.max_established_incoming)
+            .with_max_established_outgoing(self.max_established_outgoing)
+            .with_max_established_per_peer(self.max_established_per_peer)
+            .with_max_pending_incoming(self.max_pending_incoming)
+            .with_max_pending_outgoing(self.max_pending_outgoing)
+    }
+}
+
+/// How long the node keeps a connection open once nothing is using it, by what the peer is to the
+/// node. A connection belonging to several classes takes the first of relay, holepunched, mesh
+/// and DHT.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepAliveConfig {
+    /// For connections of no other class.
+    pub idle_secs: u64,
+
+    /// For connections to the relays the node holds reservations on.
+    pub relay_secs: u64,
+
+    /// For direct connections that hole punching established.
+    pub holepunched_secs: u64,
+
+    /// For connections to peers in one of the node's gossipsub meshes.
+    pub mesh_secs: u64,
+
+    /// For connections to peers in the DHT's routing table.
+    pub dht_secs: u64,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            idle_secs: 60,
+            relay_secs: 600,
+            holepunched_secs: 300,
+            mesh_secs: 120,
+            dht_secs: 30,
+        }
+    }
+}
+
+impl KeepAliveConfig {
+    pub fn timeout(&self, class: ConnectionClass) -> Duration {
+        Duration::from_secs(match class {
+            ConnectionClass::Relay => self.relay_secs,
+            ConnectionClass::Holepunched => self.holepunched_secs,
+            ConnectionClass::Mesh => self.mesh_secs,
+            ConnectionClass::Dht => self.dht_secs,
+            ConnectionClass::Other => self.idle_secs,
+        })
+    }
+
+    /// The shortest timeout of any class, which the swarm applies to every connection.
+    pub fn shortest(&self) -> Duration {
+        ConnectionClass::ALL
+            .into_iter()
+            .map(|class| self.timeout(class))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Limits on how often the node dials out, across all peers and to each peer, so that a long peer
+/// list or an overeager protocol cannot open connections in a storm. Dials over a limit fail,
+/// except those to configured and remembered peers, which wait until the limits allow them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DialLimitsConfig {
+    /// The limit applied to all dials together.
+    pub global: Option<RateConfig>,
+
+    /// The limit applied to each peer. Dials to an address without a peer id only count against
+    /// the global limit.
+    pub per_peer: Option<RateConfig>,
+}
+
+impl Default for DialLimitsConfig {
+    fn default() -> Self {
+        Self {
+            global: Some(RateConfig {
+                per_second: 20.0,
+                burst: 100,
+            }),
+            per_peer: Some(RateConfig {
+                per_second: 1.0,
+                burst: 5,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// The level of log lines to emit from modules without an override, such as `info`.
+    pub level: Option<String>,
+
+    /// Levels for individual modules, keyed by module path, such as `libp2p_gossipsub`.
+    pub modules: HashMap<String, String>,
+
+    /// Further directives in `RUST_LOG` syntax, which take precedence over `level` and `modules`.
+    /// That variable only applies when none of these three are set. All of them can be changed
+    /// by reloading.
+    pub filter: Option<String>,
+
+    /// How log lines are formatted, both on stdout and in files.
+    pub format: LogFormat,
+
+    /// Whether log lines are written to stdout.
+    pub stdout: bool,
+
+    /// A directory to also write log lines to, in files named after `file_prefix` and rotated
+    /// every `rotation`.
+    pub directory: Option<PathBuf>,
+    pub file_prefix: String,
+    pub rotation: LogRotation,
+
+    /// How many rotated files to keep, deleting the oldest beyond that. All are kept if unset.
+    pub max_files: Option<usize>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: None,
+            modules: HashMap::new(),
+            filter: None,
+            format: LogFormat::Full,
+            stdout: true,
+            directory: None,
+            file_prefix: "sigil.log".to_string(),
+            rotation: LogRotation::Daily,
+            max_files: None,
+        }
+    }
+}
+
+impl LogConfig {
+    /// The filter selecting which log lines are emitted.
+    pub fn env_filter(&self) -> Result<EnvFilter, ParseError> {
+        let directives: Vec<String> = self
+            .level
+            .iter()
+            .cloned()
+            .chain(
+                self.modules
+                    .iter()
+                    .map(|(module, level)| format!("{module}={level}")),
+            )
+            .chain(self.filter.iter().cloned())
+            .collect();
+        if directives.is_empty() {
+            return Ok(EnvFilter::from_default_env());
+        }
+        EnvFilter::try_new(directives.join(","))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines with every field.
+    #[default]
+    Full,
+
+    /// Shorter human-readable lines.
+    Compact,
+
+    /// Multi-line records, for reading during development.
+    Pretty,
+
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Export of the node's metrics.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Whether metrics are exported. Nothing is served or pushed when disabled.
+    pub enabled: bool,
+
+    /// The address Prometheus scrapes `/metrics` from. Metrics are not served when omitted.
+    pub metrics_listen: Option<SocketAddr>,
+
+    /// Whether gossipsub's own metrics are recorded, which add series for every topic.
+    pub gossipsub_metrics: bool,
+
+    /// The upper bounds, in seconds, of the buckets that RPC call durations are counted in.
+    pub rpc_duration_buckets: Vec<f64>,
+
+    pub otlp: OtlpConfig,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            metrics_listen: None,
+            gossipsub_metrics: true,
+            // From a millisecond to about 16 seconds.
+            rpc_duration_buckets: exponential_buckets(0.001, 2.0, 15).collect(),
+            otlp: OtlpConfig::default(),
+        }
+    }
+}
+
+/// Pushing metrics to an OpenTelemetry collector.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OtlpConfig {
+    /// The collector's OTLP/HTTP metrics endpoint. Metrics are not pushed when omitted.
+    pub endpoint: Option<String>,
+
+    /// Headers sent with every push, such as the collector's credentials.
+    #[serde(skip_serializing)]
+    pub headers: HashMap<String, String>,
+
+    /// How often metrics are pushed.
+    pub interval_secs: u64,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            headers: HashMap::new(),
+            interval_secs: 15,
+        }
+    }
+}
+
+impl OtlpConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Watching the configuration file, so that edits to the settings that can change while the node
+/// runs are applied without a signal.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadConfig {
+    /// Whether to reload the file whenever it is modified.
+    pub watch: bool,
+
+    /// How often the file is checked for modifications.
+    pub watch_interval_secs: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            watch: false,
+            watch_interval_secs: 5,
+        }
+    }
+}
+
+impl ReloadConfig {
+    pub fn watch_interval(&self) -> Duration {
+        Duration::from_secs(self.watch_interval_secs)
+    }
+}
+
+/// Measurement of peers' clocks, which are asked for the time whenever they identify themselves.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockConfig {
+    /// How far a peer's clock may be from ours, in milliseconds, before a warning is logged.
+    pub max_skew_millis: u64,
+
+    /// The gossipsub application score given to peers beyond the tolerated skew, which lowers
+    /// their peer score by `penalty` times `gossipsub.scoring.app_specific_weight`. Skewed peers
+    /// are only warned about when it is zero.
+    pub penalty: f64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_skew_millis: 2000,
+            penalty: 0.0,
+        }
+    }
+}
+
+/// Caps on the traffic of the node's connections to peers, so that one aggressive peer cannot
+/// saturate the node. Bandwidth is unlimited by default.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BandwidthConfig {
+    /// The limit on data received across all connections.
+    pub inbound: Option<ByteRateConfig>,
+
+    /// The limit on data sent across all connections.
+    pub outbound: Option<ByteRateConfig>,
+
+    /// The limit on data received over each connection.
+    pub inbound_per_connection: Option<ByteRateConfig>,
+
+    /// The limit on data sent over each connection.
+    pub outbound_per_connection: Option<ByteRateConfig>,
+
+    /// The most substreams a connection may have open at once, each carrying one protocol
+    /// exchange. Peers opening more are refused further substreams.
+    pub max_substreams_per_connection: u32,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            inbound: None,
+            outbound: None,
+            inbound_per_connection: None,
+            outbound_per_connection: None,
+            max_substreams_per_connection: 256,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ByteRateConfig {
+    /// The sustained number of bytes allowed each second.
+    pub bytes_per_second: u64,
+
+    /// How many bytes may pass at once before the sustained rate applies.
+    pub burst_bytes: u64,
+}
+
+/// Faults injected into the data the node sends to peers, to test it under poor network
+/// conditions. They only take effect in builds with the `fault-injection` feature.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    /// The faults of connections to peers not listed in `peers`.
+    pub default: LinkFaults,
+
+    /// The faults of connections to particular peers, keyed by peer id.
+    pub peers: HashMap<PeerId, LinkFaults>,
+}
+
+/// The conditions of the link to a peer. Connections are reliable streams, so a dropped write
+/// is resent after `retransmit_timeout_millis` rather than lost, as TCP would.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkFaults {
+    /// How long each write waits before it is sent.
+    pub latency_millis: u64,
+
+    /// The most further time, chosen at random, that each write waits.
+    pub jitter_millis: u64,
+
+    /// The chance, from 0 to 1, that a write is dropped and has to be resent.
+    pub drop_rate: f64,
+    pub retransmit_timeout_millis: u64,
+}
+
+impl Default for LinkFaults {
+    fn default() -> Self {
+        Self {
+            latency_millis: 0,
+            jitter_millis: 0,
+            drop_rate: 0.0,
+            retransmit_timeout_millis: 200,
+        }
+    }
+}
+
+/// Disruptions the node inflicts on itself at random, so that soak environments can check that it
+/// recovers from them. They only take effect in builds with the `chaos` feature.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChaosConfig {
+    /// How often chaos strikes, each time causing each disruption with its chance. Zero turns
+    /// chaos off.
+    pub interval_secs: u64,
+
+    /// The chance, from 0 to 1, that a strike closes the connections to a random peer.
+    pub disconnect_rate: f64,
+
+    /// The chance that a strike stalls the event loop, holding up commands and events as a slow
+    /// handler would, for a random time of up to `max_stall_millis`.
+    pub stall_rate: f64,
+    pub max_stall_millis: u64,
+
+    /// The chance that a strike restarts one of the gossip mesh, the DHT's routing table or the
+    /// relay reservations, chosen at random, leaving the node to rebuild it.
+    pub restart_rate: f64,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 0,
+            disconnect_rate: 0.5,
+            stall_rate: 0.2,
+            max_stall_millis: 2000,
+            restart_rate: 0.1,
+        }
+    }
+}
+
+impl ChaosConfig {
+    pub fn interval(&self) -> Option<Duration> {
+        (self.interval_secs > 0).then(|| Duration::from_secs(self.interval_secs))
+    }
+}
+
+/// Relays that this node reserves a slot on, so that peers can reach it from behind a NAT.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelayConfig {
+    /// The relays' addresses, each ending in the relay's `/p2p/` peer id.
+    pub reservations: Vec<Multiaddr>,
+}
+
+/// Propagation of sealed batches, which are far larger and rarer than other gossip.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchConfig {
+    /// The largest compressed batch we publish or accept.
+    pub max_size: usize,
+
+    /// The largest batch we accept once decompressed.
+    pub max_decompressed_size: usize,
+
+    /// Score parameters for the batch topic, in place of `gossipsub.scoring.topic`. The mesh
+    /// degree is shared by every topic, as gossipsub offers no per-topic mesh parameters.
+    pub scoring: TopicScoringConfig,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            max_decompressed_size: 10 * 1024 * 1024,
+            scoring: TopicScoringConfig::default(),
+        }
+    }
+}
+
+impl BatchConfig {
+    pub fn limits(&self) -> BatchLimits {
+        BatchLimits {
+            max_size: self.max_size,
+            max_decompressed_size: self.max_decompressed_size,
+        }
+    }
+}
+
+/// The JSON-RPC server.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// The address the server listens on for HTTP connections, and for WebSocket connections
+    /// too unless `ws` is set.
+    pub listen: SocketAddr,
+    pub auth: AuthConfig,
+
+    /// A separate listener for WebSocket connections, so that subscriptions can be firewalled
+    /// apart from plain requests.
+    pub ws: Option<WsConfig>,
+    pub cors: CorsConfig,
+    pub tls: TlsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub timeouts: TimeoutConfig,
+    pub limits: ServerLimitsConfig,
+
+    /// The networks that may call the server over HTTP and WebSocket, such as `10.0.0.0/8`.
+    /// Every address may when none are listed. It can be changed by reloading.
+    pub allowed_ips: Vec<IpNet>,
+
+    /// A unix socket to also serve RPC on, for tooling on the same host. Only the node's user may
+    /// connect, and its calls bypass authentication and rate limits.
+    pub ipc_path: Option<PathBuf>,
+
+    /// The network id reported by `net_version`, such as the rollup's chain id.
+    pub network_id: u64,
+
+    pub proxy: ProxyConfig,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([0, 0, 0, 0], 3030)),
+            auth: AuthConfig::default(),
+            ws: None,
+            cors: CorsConfig::default(),
+            tls: TlsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            limits: ServerLimitsConfig::default(),
+            allowed_ips: Vec::new(),
+            ipc_path: None,
+            network_id: 1,
+            proxy: ProxyConfig::default(),
+        }
+    }
+}
+
+/// Forwarding of calls in execution client namespaces to an op-geth endpoint, so that each node
+/// presents one RPC endpoint for both.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// The op-geth HTTP endpoint calls are forwarded to. Nothing is forwarded when omitted.
+    pub url: Option<String>,
+
+    /// The namespaces whose calls are forwarded, such as `eth` for `eth_blockNumber`.
+    pub namespaces: Vec<String>,
+
+    /// A file holding op-geth's hex JWT secret, for endpoints that require engine API
+    /// authentication.
+    pub jwt_secret_file: Option<PathBuf>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            namespaces: vec!["eth".to_string(), "engine".to_string()],
+            jwt_secret_file: None,
+        }
+    }
+}
+
+/// The listener for WebSocket RPC connections when they are served apart from HTTP.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WsConfig {
+    pub listen: SocketAddr,
+
+    /// The authentication of WebSocket callers, which is the same as for HTTP when unset.
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+}
+
+/// Bearer token authentication of RPC callers. When no token is set, every method but the `admin`
+/// namespace is open, and the `admin` namespace is only served over the unix socket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+
+    /// A file holding the token, which is preferred over `token` so it can be mounted as a
+    /// secret.
+    pub token_file: Option<PathBuf>,
+
+    /// Let unauthenticated callers use methods that only read the node's state.
+    pub open_read_only: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            token_file: None,
+            open_read_only: true,
+        }
+    }
+}
+
+impl AuthConfig {
+    /// The token callers must present, if authentication is on.
+    pub fn token(&self) -> Result<Option<String>, Box<dyn Error>> {
+        match &self.token_file {
+            Some(path) => {
+                let token = fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read token {}: {e}", path.display()))?;
+                Ok(Some(token.trim().to_string()))
+            }
+            None => Ok(self.token.clone()),
+        }
+    }
+}
+
+/// Cross-origin access to the RPC server, so that browser-based tools can call it directly.
+/// Cross-origin requests are refused when no origins are allowed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the server, or `"*"` to allow any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+            max_age_secs: 3600,
+        }
+    }
+}
+
+/// Serving the RPC over HTTPS and WSS. The server speaks plaintext unless a certificate and key
+/// are given or `self_signed` is set.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// A PEM file holding the certificate chain.
+    pub cert_path: Option<PathBuf>,
+
+    /// A PEM file holding the private key.
+    pub key_path: Option<PathBuf>,
+
+    /// Generate a self-signed certificate at startup, for development only.
+    pub self_signed: bool,
+}
+
+/// Limits on how often the RPC may be called. Calls are unlimited by default.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The limit applied to each caller's IP address across all methods.
+    pub per_ip: Option<RateConfig>,
+
+    /// Limits on individual methods, shared by all callers, keyed by method name.
+    pub methods: HashMap<String, RateConfig>,
+}
+
+/// Caps on the RPC server's resources, so that a flood of callers cannot starve the node of memory
+/// or file descriptors.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerLimitsConfig {
+    /// The most connections open at once over HTTP and WebSocket. Further connections are closed
+    /// as soon as they are accepted.
+    pub max_connections: u32,
+    pub max_subscriptions_per_connection: u32,
+
+    /// The most calls running at once across all connections. Further calls fail immediately.
+    pub max_concurrent_requests: usize,
+
+    /// The largest request body or WebSocket message accepted, in bytes. Larger requests are
+    /// rejected with an error rather than parsed.
+    pub max_request_body_size: u32,
+
+    /// The largest response sent, in bytes. Calls whose results are larger fail with an error
+    /// instead.
+    pub max_response_body_size: u32,
+}
+
+impl Default for ServerLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 100,
+            max_subscriptions_per_connection: 1024,
+            max_concurrent_requests: 256,
+            max_request_body_size: 10 * 1024 * 1024,
+            max_response_body_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// How long RPC calls may run before failing with a timeout error.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    pub default_secs: u64,
+
+    /// Timeouts for individual methods, in seconds, keyed by method name.
+    pub methods: HashMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            default_secs: 30,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateConfig {
+    /// The sustained number of calls allowed each second.
+    pub per_second: f64,
+
+    /// How many calls may be made at once before the sustained rate applies.
+    pub burst: u32,
+}
+
+/// Delivery of received messages to an HTTP endpoint, for services outside the network.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The endpoint that messages are posted to. No messages are posted when omitted.
+    pub url: Option<String>,
+
+    /// The topics whose messages are posted, or every topic when empty.
+    pub topics: Vec<String>,
+
+    /// A secret used to sign each post, so the endpoint can verify it came from this node.
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+
+    /// How many times a post is attempted before the message is dropped.
+    pub max_attempts: u32,
+
+    /// How long to wait before the first retry, doubling for each retry after.
+    pub retry_delay_millis: u64,
+
+    /// The longest wait between retries, however many there have been.
+    pub max_retry_delay_millis: u64,
+    pub timeout_millis: u64,
+
+    /// How many messages may await delivery before further messages are dropped.
+    pub queue_size: usize,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            topics: Vec::new(),
+            secret: None,
+            max_attempts: 5,
+            retry_delay_millis: 500,
+            max_retry_delay_millis: 60_000,
+            timeout_millis: 5000,
+            queue_size: 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GossipsubVersion {
+    #[serde(rename = "1.0")]
+    V1_0,
+    #[default]
+    #[serde(rename = "1.1")]
+    V1_1,
+}
+
+/// Peer scoring, which lets gossipsub prune lazy or malicious peers from the mesh. The defaults
+/// follow the gossipsub specification, except that mesh delivery penalties are disabled because
+/// they punish honest peers on low-traffic topics.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    pub enabled: bool,
+
+    // Thresholds below which peers lose gossip, publishing, and all interaction.
+    pub gossip_threshold: f64,
+    pub publish_threshold: f64,
+    pub graylist_threshold: f64,
+    pub accept_px_threshold: f64,
+    pub opportunistic_graft_threshold: f64,
+
+    // Peer-wide parameters.
+    pub topic_score_cap: f64,
+    pub app_specific_weight: f64,
+    pub ip_colocation_factor_weight: f64,
+    pub ip_colocation_factor_threshold: f64,
+    pub behaviour_penalty_weight: f64,
+    pub behaviour_penalty_threshold: f64,
+    pub behaviour_penalty_decay: f64,
+    pub decay_interval_secs: u64,
+    pub decay_to_zero: f64,
+    pub retain_score_secs: u64,
+
+    /// Parameters applied to every topic this node subscribes to, except those configured
+    /// separately such as the batch topic.
+    pub topic: TopicScoringConfig,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        let thresholds = PeerScoreThresholds::default();
+        let params = PeerScoreParams::default();
+        Self {
+            enabled: true,
+            gossip_threshold: thresholds.gossip_threshold,
+            publish_threshold: thresholds.publish_threshold,
+            graylist_threshold: thresholds.graylist_threshold,
+            accept_px_threshold: thresholds.accept_px_threshold,
+            opportunistic_graft_threshold: thresholds.opportunistic_graft_threshold,
+            topic_score_cap: params.topic_score_cap,
+            app_specific_weight: params.app_specific_weight,
+            ip_colocation_factor_weight: params.ip_colocation_factor_weight,
+            ip_colocation_factor_threshold: params.ip_colocation_factor_threshold,
+            behaviour_penalty_weight: params.behaviour_penalty_weight,
+            behaviour_penalty_threshold: params.behaviour_penalty_threshold,
+            behaviour_penalty_decay: params.behaviour_penalty_decay,
+            decay_interval_secs: params.decay_interval.as_secs(),
+            decay_to_zero: params.decay_to_zero,
+            retain_score_secs: params.retain_score.as_secs(),
+            topic: TopicScoringConfig::default(),
+        }
+    }
+}
+
+impl ScoringConfig {
+    pub fn thresholds(&self) -> PeerScoreThresholds {
+        PeerScoreThresholds {
+            gossip_threshold: self.gossip_threshold,
+            publish_threshold: self.publish_threshold,
+            graylist_threshold: self.graylist_threshold,
+            accept_px_threshold: self.accept_px_threshold,
+            opportunistic_graft_threshold: self.opportunistic_graft_threshold,
+        }
+    }
+
+    /// Build the score parameters, scoring each of `topics` with its own topic parameters.
+    pub fn params(&self, topics: &[(TopicHash, &TopicScoringConfig)]) -> PeerScoreParams {
+        PeerScoreParams {
+            topics: topics
+                .iter()
+                .map(|(topic, params)| (topic.clone(), params.params()))
+                .collect(),
+            topic_score_cap: self.topic_score_cap,
+            app_specific_weight: self.app_specific_weight,
+            ip_colocation_factor_weight: self.ip_colocation_factor_weight,
+            ip_colocation_factor_threshold: self.ip_colocation_factor_threshold,
+            behaviour_penalty_weight: self.behaviour_penalty_weight,
+            behaviour_penalty_threshold: self.behaviour_penalty_threshold,
+            behaviour_penalty_decay: self.behaviour_penalty_decay,
+            decay_interval: Duration::from_secs(self.decay_interval_secs),
+            decay_to_zero: self.decay_to_zero,
+            retain_score: Duration::from_secs(self.retain_score_secs),
+            ..PeerScoreParams::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopicScoringConfig {
+    pub topic_weight: f64,
+
+    // P1: time in mesh.
+    pub time_in_mesh_weight: f64,
+    pub time_in_mesh_quantum_millis: u64,
+    pub time_in_mesh_cap: f64,
+
+    // P2: first message deliveries.
+    pub first_message_deliveries_weight: f64,
+    pub first_message_deliveries_decay: f64,
+    pub first_message_deliveries_cap: f64,
+
+    // P3: mesh message delivery rate.
+    pub mesh_message_deliveries_weight: f64,
+    pub mesh_message_deliveries_decay: f64,
+    pub mesh_message_deliveries_cap: f64,
+    pub mesh_message_deliveries_threshold: f64,
+    pub mesh_message_deliveries_window_millis: u64,
+    pub mesh_message_deliveries_activation_secs: u64,
+
+    // P3b: sticky mesh delivery failures.
+    pub mesh_failure_penalty_weight: f64,
+    pub mesh_failure_penalty_decay: f64,
+
+    // P4: invalid messages.
+    pub invalid_message_deliveries_weight: f64,
+    pub invalid_message_deliveries_decay: f64,
+}
+
+impl Default for TopicScoringConfig {
+    fn default() -> Self {
+        let params = TopicScoreParams::default();
+        Self {
+            topic_weight: params.topic_weight,
+            time_in_mesh_weight: params.time_in_mesh_weight,
+            time_in_mesh_quantum_millis: params.time_in_mesh_quantum.as_millis() as u64,
+            time_in_mesh_cap: params.time_in_mesh_cap,
+            first_message_deliveries_weight: params.first_message_deliveries_weight,
+            first_message_deliveries_decay: params.first_message_deliveries_decay,
+            first_message_deliveries_cap: params.first_message_deliveries_cap,
+            mesh_message_deliveries_weight: 0.0,
+            mesh_message_deliveries_decay: params.mesh_message_deliveries_decay,
+            mesh_message_deliveries_cap: params.mesh_message_deliveries_cap,
+            mesh_message_deliveries_threshold: params.mesh_message_deliveries_threshold,
+            mesh_message_deliveries_window_millis: params.mesh_message_deliveries_window.as_millis()
+                as u64,
+            mesh_message_deliveries_activation_secs: params
+                .mesh_message_deliveries_activation
+                .as_secs(),
+            mesh_failure_penalty_weight: 0.0,
+            mesh_failure_penalty_decay: params.mesh_failure_penalty_decay,
+            invalid_message_deliveries_weight: params.invalid_message_deliveries_weight,
+            invalid_message_deliveries_decay: params.invalid_message_deliveries_decay,
+        }
+    }
+}
+
+impl TopicScoringConfig {
+    pub fn params(&self) -> TopicScoreParams {
+        TopicScoreParams {
+            topic_weight: self.topic_weight,
+            time_in_mesh_weight: self.time_in_mesh_weight,
+            time_in_mesh_quantum: Duration::from_millis(self.time_in_mesh_quantum_millis),
+            time_in_mesh_cap: self.time_in_mesh_cap,
+            first_message_deliveries_weight: self.first_message_deliveries_weight,
+            first_message_deliveries_decay: self.first_message_deliveries_decay,
+            first_message_deliveries_cap: self.first_message_deliveries_cap,
+            mesh_message_deliveries_weight: self.mesh_message_deliveries_weight,
+            mesh_message_deliveries_decay: self.mesh_message_deliveries_decay,
+            mesh_message_deliveries_cap: self.mesh_message_deliveries_cap,
+            mesh_message_deliveries_threshold: self.mesh_message_deliveries_threshold,
+            mesh_message_deliveries_window: Duration::from_millis(
+                self.mesh_message_deliveries_window_millis,
+            ),
+            mesh_message_deliveries_activation: Duration::from_secs(
+                self.mesh_message_deliveries_activation_secs,
+            ),
+            mesh_failure_penalty_weight: self.mesh_failure_penalty_weight,
+            mesh_failure_penalty_decay: self.mesh_failure_penalty_decay,
+            invalid_message_deliveries_weight: self.invalid_message_deliveries_weight,
+            invalid_message_deliveries_decay: self.invalid_message_deliveries_decay,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::block::BlockHash;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How many out-of-order messages are held per sender while waiting for a gap to fill. Once
+/// exceeded, the missing messages are presumed lost and delivery skips past them.
+const MAX_BUFFERED: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoteKind {
+    Prevote,
+    Precommit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsensusMessage {
+    /// A proposer's candidate block for a round.
+    Proposal {
+        height: u64,
+        round: u32,
+        block_hash: BlockHash,
+        payload: Vec<u8>,
+    },
+    Vote {
+        height: u64,
+        round: u32,
+        kind: VoteKind,
+        block_hash: BlockHash,
+    },
+    /// Notice that a block has been finalized at `height`.
+    Finality { height: u64, block_hash: BlockHash },
+}
+
+/// A consensus message numbered by its sender, so that receivers can deliver each sender's
+/// messages in the order they were sent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequencedMessage {
+    /// When the sender started, so that numbering restarts from zero if the sender restarts.
+    pub session: u64,
+    pub sequence: u64,
+    pub message: ConsensusMessage,
+}
+
+impl SequencedMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("consensus message serialization is infallible")
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+/// The application's consensus engine, which receives every sender's messages in order.
+pub trait ConsensusHandler: Send + Sync + 'static {
+    fn handle(&self, sender: PeerId, message: ConsensusMessage);
+}
+
+struct SenderState {
+    session: u64,
+    next: u64,
+    buffered: BTreeMap<u64, ConsensusMessage>,
+}
+
+/// Reorders each sender's messages by sequence number. The first message seen from a sender's
+/// session sets where its delivery starts, as a node may join after others began sending.
+#[derive(Default)]
+pub struct OrderedDelivery {
+    senders: HashMap<PeerId, SenderState>,
+}
+
+impl OrderedDelivery {
+    /// Accept a message from `sender`, returning whichever of its messages are now deliverable,
+    /// in order. Returns `None` if the message was already delivered or skipped.
+    pub fn receive(
+        &mut self,
+        sender: PeerId,
+        message: SequencedMessage,
+    ) -> Option<Vec<ConsensusMessage>> {
+        let state = self.senders.entry(sender).or_insert_with(|| SenderState {
+            session: message.session,
+            next: message.sequence,
+            buffered: BTreeMap::new(),
+        });
+        if message.session > state.session {
+            *state = SenderState {
+                session: message.session,
+                next: message.sequence,
+                buffered: BTreeMap::new(),
+            };
+        }
+        if message.session < state.session || message.sequence < state.next {
+            return None;
+        }
+        if state
+            .buffered
+            .insert(message.sequence, message.message)
+            .is_some()
+        {
+            return None;
+        }
+
+        // Give up on a gap that has not filled before the buffer overflows.
+        if state.buffered.len() > MAX_BUFFERED {
+            if let Some((&first, _)) = state.buffered.first_key_value() {
+                state.next = first;
+            }
+        }
+
+        let mut ready = Vec::new();
+        while let Some(message) = state.buffered.remove(&state.next) {
+            ready.push(message);
+            state.next += 1;
+        }
+        Some(ready)
+    }
+}
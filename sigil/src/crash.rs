@@ -0,0 +1,90 @@
+use crate::client::{ChannelDepths, SwarmClient};
+use crate::config::Config;
+use crate::data_dir::CRASH_REPORT_PREFIX;
+use crate::envelope::unix_millis;
+use crate::event::LoggedEvent;
+use crate::version::{BuildInfo, BUILD_INFO};
+use serde::{Deserialize, Serialize};
+use std::backtrace::Backtrace;
+use std::error::Error;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+
+/// How many of the latest events a crash report includes.
+pub const CRASH_REPORT_EVENTS: usize = 256;
+
+/// What the node was doing when it panicked, written to the data directory so that a crash inside
+/// the swarm task can be diagnosed from the volume it ran on.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// When the node panicked, in milliseconds since the Unix epoch.
+    pub at: u64,
+    pub message: String,
+
+    /// Where in the source the panic was raised.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    pub build: BuildInfo,
+
+    /// How many items were queued on each channel, which shows whether the swarm task had stalled.
+    pub channels: ChannelDepths,
+
+    /// The latest significant events, oldest first, or none if the event log was busy.
+    pub recent_events: Vec<LoggedEvent>,
+
+    /// The configuration the node ran with, without its secrets.
+    pub config: Config,
+}
+
+impl CrashReport {
+    /// Write the report into `dir` under a name unique to the moment of the crash, returning
+    /// where it was written.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let path = dir.join(format!("{CRASH_REPORT_PREFIX}{}.json", self.at));
+        fs::write(&path, serde_json::to_vec_pretty(self)?)
+            .map_err(|e| format!("failed to write crash report {}: {e}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Replace the panic hook with one that, after reporting the panic as usual, writes a
+/// `CrashReport` into `dir` and exits, so that a panic in any task takes the node down with a
+/// record of its state rather than leaving it running without that task.
+pub fn install(dir: PathBuf, config: Arc<Config>, client: SwarmClient) {
+    let report_panic = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        report_panic(info);
+        let report = report(info, &config, &client);
+        match report.write(&dir) {
+            Ok(path) => eprintln!("Wrote crash report to {}", path.display()),
+            Err(e) => eprintln!("Failed to write crash report: {e}"),
+        }
+        process::exit(101);
+    }));
+}
+
+fn report(info: &PanicHookInfo, config: &Config, client: &SwarmClient) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    CrashReport {
+        at: unix_millis(),
+        message,
+        location: info.location().map(ToString::to_string),
+        thread: std::thread::current().name().map(str::to_string),
+        backtrace: Backtrace::force_capture().to_string(),
+        build: BUILD_INFO,
+        channels: client.channel_depths(),
+        recent_events: client
+            .try_recent_events(CRASH_REPORT_EVENTS)
+            .unwrap_or_default(),
+        config: config.clone(),
+    }
+}
@@ -0,0 +1,61 @@
+use std::error::Error;
+use std::fs::{self, DirBuilder, File};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::Path;
+
+/// The file in the data directory that the node's keypair is kept in unless placed elsewhere.
+pub const KEYPAIR_FILE: &str = "node.key";
+
+/// The file in the data directory that the network snapshot is kept in unless placed elsewhere.
+pub const SNAPSHOT_FILE: &str = "network.json";
+
+/// The prefix of the crash reports written to the data directory when the node panics.
+pub const CRASH_REPORT_PREFIX: &str = "crash-";
+
+/// A file briefly created to check that the node can write to the data directory.
+const PROBE_FILE: &str = ".sigil-write-check";
+
+/// Create the data directory if it does not exist, accessible only by the node's user, and check
+/// that the node can write to it. A directory that other users can access or that belongs to
+/// another user is used, with a warning, as containers often mount volumes that way.
+pub fn prepare(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder
+        .create(path)
+        .map_err(|e| format!("failed to create data directory {}: {e}", path.display()))?;
+    let metadata = fs::metadata(path)
+        .map_err(|e| format!("failed to read data directory {}: {e}", path.display()))?;
+    if !metadata.is_dir() {
+        return Err(format!("data directory {} is not a directory", path.display()).into());
+    }
+
+    let probe = path.join(PROBE_FILE);
+    let probe_metadata = File::create(&probe)
+        .and_then(|file| file.metadata())
+        .map_err(|e| format!("data directory {} is not writable: {e}", path.display()))?;
+    let _ = fs::remove_file(&probe);
+    #[cfg(unix)]
+    {
+        if metadata.uid() != probe_metadata.uid() {
+            tracing::warn!(
+                path = %path.display(),
+                "Data directory belongs to another user"
+            );
+        }
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            tracing::warn!(
+                path = %path.display(),
+                mode = %format_args!("{:o}", mode & 0o777),
+                "Data directory is accessible by other users"
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = probe_metadata;
+    Ok(())
+}
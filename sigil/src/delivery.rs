@@ -0,0 +1,187 @@
+use crate::envelope::{AckRequest, Envelope};
+use bytes::Bytes;
+use libp2p::{gossipsub, PeerId};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// How long to wait for outstanding acknowledgements before republishing.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How many times a reliable message is published before reporting failure.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// How many received reliable messages we remember in order to surface each only once.
+const RECEIVED_CAPACITY: usize = 1024;
+
+/// The failure reported to a reliable publisher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    /// These recipients never acknowledged the message within the allowed attempts.
+    Unacknowledged(Vec<PeerId>),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryError::Unacknowledged(peers) => {
+                write!(f, "no acknowledgement from {} recipient(s)", peers.len())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+struct Pending {
+    topic: gossipsub::IdentTopic,
+    payload: Bytes,
+    recipients: Vec<PeerId>,
+    outstanding: HashSet<PeerId>,
+    attempts: u32,
+    last_attempt: Instant,
+    sender: oneshot::Sender<Result<(), DeliveryError>>,
+}
+
+/// Tracks reliable messages we have published and are still awaiting acknowledgements for.
+pub struct PendingDeliveries {
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+}
+
+impl Default for PendingDeliveries {
+    fn default() -> Self {
+        // Seed ids from the clock so that a restarted node does not reuse ids its peers remember.
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            next_id: seed,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl PendingDeliveries {
+    /// How many messages are still awaiting acknowledgements.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Begin tracking a new reliable message, returning the envelope for its first attempt. When
+    /// there are no recipients the publisher is answered immediately.
+    pub fn insert(
+        &mut self,
+        topic: gossipsub::IdentTopic,
+        payload: Bytes,
+        recipients: Vec<PeerId>,
+        sender: oneshot::Sender<Result<(), DeliveryError>>,
+        now: Instant,
+    ) -> Option<(gossipsub::IdentTopic, Envelope)> {
+        if recipients.is_empty() {
+            let _ = sender.send(Ok(()));
+            return None;
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let pending = Pending {
+            topic,
+            payload,
+            outstanding: recipients.iter().copied().collect(),
+            recipients,
+            attempts: 1,
+            last_attempt: now,
+            sender,
+        };
+        let attempt = Self::attempt(id, &pending);
+        self.pending.insert(id, pending);
+        Some(attempt)
+    }
+
+    /// Record an acknowledgement, answering the publisher once every recipient has replied.
+    pub fn acknowledge(&mut self, id: u64, from: PeerId) {
+        let Some(pending) = self.pending.get_mut(&id) else {
+            return;
+        };
+        pending.outstanding.remove(&from);
+        if pending.outstanding.is_empty() {
+            if let Some(pending) = self.pending.remove(&id) {
+                let _ = pending.sender.send(Ok(()));
+            }
+        }
+    }
+
+    /// Fail any message that has exhausted its attempts and return the envelopes of those that
+    /// are due to be republished.
+    pub fn poll_retries(&mut self, now: Instant) -> Vec<(gossipsub::IdentTopic, Envelope)> {
+        let mut retries = Vec::new();
+        let mut finished = Vec::new();
+        for (id, pending) in self.pending.iter_mut() {
+            if pending.sender.is_closed() {
+                finished.push(*id);
+                continue;
+            }
+            if now.duration_since(pending.last_attempt) < RETRY_INTERVAL {
+                continue;
+            }
+            if pending.attempts >= MAX_ATTEMPTS {
+                finished.push(*id);
+                continue;
+            }
+            pending.attempts += 1;
+            pending.last_attempt = now;
+            retries.push(Self::attempt(*id, pending));
+        }
+
+        for id in finished {
+            if let Some(pending) = self.pending.remove(&id) {
+                let missing = pending.outstanding.into_iter().collect();
+                let _ = pending
+                    .sender
+                    .send(Err(DeliveryError::Unacknowledged(missing)));
+            }
+        }
+        retries
+    }
+
+    fn attempt(id: u64, pending: &Pending) -> (gossipsub::IdentTopic, Envelope) {
+        let mut envelope = Envelope::new(pending.payload.clone());
+        envelope.ack = Some(AckRequest {
+            id,
+            recipients: pending.recipients.clone(),
+            attempt: pending.attempts,
+        });
+        (pending.topic.clone(), envelope)
+    }
+}
+
+/// Remembers recently received reliable messages so that retries are acknowledged again but only
+/// surfaced to the application once.
+#[derive(Default)]
+pub struct ReceivedDeliveries {
+    order: VecDeque<(PeerId, u64)>,
+    seen: HashSet<(PeerId, u64)>,
+}
+
+impl ReceivedDeliveries {
+    /// Returns whether this is the first time we have received this message.
+    pub fn first_receipt(&mut self, publisher: PeerId, id: u64) -> bool {
+        if !self.seen.insert((publisher, id)) {
+            return false;
+        }
+        self.order.push_back((publisher, id));
+        if self.order.len() > RECEIVED_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
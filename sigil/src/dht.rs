@@ -0,0 +1,215 @@
+#[cfg(feature = "kademlia")]
+use libp2p::kad::{self, store::MemoryStore};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "kademlia")]
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+#[cfg(feature = "kademlia")]
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhtError {
+    /// The local record store refused the operation, such as when it is full.
+    Store(String),
+    /// The query failed, such as for lack of peers to replicate to.
+    Query(String),
+    NotFound,
+    /// The node was built without the `kademlia` feature.
+    Disabled,
+}
+
+impl fmt::Display for DhtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DhtError::Store(e) => write!(f, "record store error: {e}"),
+            DhtError::Query(e) => write!(f, "DHT query failed: {e}"),
+            DhtError::NotFound => write!(f, "record not found"),
+            DhtError::Disabled => write!(f, "the DHT is not compiled into this node"),
+        }
+    }
+}
+
+impl std::error::Error for DhtError {}
+
+/// A peer in the Kademlia routing table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoutingTableEntry {
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+
+    /// The bucket holding the peer, which is the base-2 logarithm of its distance from us.
+    pub bucket: u32,
+}
+
+/// A query issued on behalf of a client, with whoever awaits its outcome.
+#[cfg(feature = "kademlia")]
+enum PendingQuery {
+    Put(oneshot::Sender<Result<(), DhtError>>),
+    Get(oneshot::Sender<Result<Vec<u8>, DhtError>>),
+    Provide(oneshot::Sender<Result<(), DhtError>>),
+    Providers {
+        providers: HashSet<PeerId>,
+        sender: oneshot::Sender<Result<Vec<PeerId>, DhtError>>,
+    },
+}
+
+/// Runs record and provider queries against the DHT for clients, answering each once it
+/// completes.
+#[cfg(feature = "kademlia")]
+#[derive(Default)]
+pub struct DhtQueries {
+    queries: HashMap<kad::QueryId, PendingQuery>,
+}
+
+#[cfg(feature = "kademlia")]
+impl DhtQueries {
+    /// How many queries are awaiting their outcome.
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    /// Store `value` under `key` locally and replicate it to the closest peers.
+    pub fn put(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        sender: oneshot::Sender<Result<(), DhtError>>,
+    ) {
+        let record = kad::Record::new(key, value);
+        match kad.put_record(record, kad::Quorum::One) {
+            Ok(id) => {
+                self.queries.insert(id, PendingQuery::Put(sender));
+            }
+            Err(e) => {
+                let _ = sender.send(Err(DhtError::Store(e.to_string())));
+            }
+        }
+    }
+
+    /// Find the value stored under `key`, answering with the first record found.
+    pub fn get(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<Vec<u8>, DhtError>>,
+    ) {
+        let id = kad.get_record(kad::RecordKey::new(&key));
+        self.queries.insert(id, PendingQuery::Get(sender));
+    }
+
+    /// Advertise this node as a provider of `key`.
+    pub fn provide(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<(), DhtError>>,
+    ) {
+        match kad.start_providing(kad::RecordKey::new(&key)) {
+            Ok(id) => {
+                self.queries.insert(id, PendingQuery::Provide(sender));
+            }
+            Err(e) => {
+                let _ = sender.send(Err(DhtError::Store(e.to_string())));
+            }
+        }
+    }
+
+    /// Find every provider of `key`.
+    pub fn providers(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        key: Vec<u8>,
+        sender: oneshot::Sender<Result<Vec<PeerId>, DhtError>>,
+    ) {
+        let id = kad.get_providers(kad::RecordKey::new(&key));
+        self.queries.insert(
+            id,
+            PendingQuery::Providers {
+                providers: HashSet::new(),
+                sender,
+            },
+        );
+    }
+
+    /// Handle the progress of a query issued by a client, returning any event for a query that
+    /// was not.
+    pub fn handle_event(
+        &mut self,
+        kad: &mut kad::Behaviour<MemoryStore>,
+        event: kad::Event,
+    ) -> Option<kad::Event> {
+        let kad::Event::OutboundQueryProgressed {
+            id, result, step, ..
+        } = &event
+        else {
+            return Some(event);
+        };
+        let Some(query) = self.queries.remove(id) else {
+            return Some(event);
+        };
+        match (query, result) {
+            (PendingQuery::Put(sender), kad::QueryResult::PutRecord(result)) => {
+                let result = result
+                    .as_ref()
+                    .map(|_| ())
+                    .map_err(|e| DhtError::Query(e.to_string()));
+                let _ = sender.send(result);
+            }
+            (PendingQuery::Get(sender), kad::QueryResult::GetRecord(result)) => match result {
+                Ok(kad::GetRecordOk::FoundRecord(found)) => {
+                    let _ = sender.send(Ok(found.record.value.clone()));
+                    // The first record answers the client, so the rest of the search is moot.
+                    if let Some(mut query) = kad.query_mut(id) {
+                        query.finish();
+                    }
+                }
+                Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => {
+                    let _ = sender.send(Err(DhtError::NotFound));
+                }
+                Err(kad::GetRecordError::NotFound { .. }) => {
+                    let _ = sender.send(Err(DhtError::NotFound));
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(DhtError::Query(e.to_string())));
+                }
+            },
+            (PendingQuery::Provide(sender), kad::QueryResult::StartProviding(result)) => {
+                let result = result
+                    .as_ref()
+                    .map(|_| ())
+                    .map_err(|e| DhtError::Query(e.to_string()));
+                let _ = sender.send(result);
+            }
+            (
+                PendingQuery::Providers {
+                    mut providers,
+                    sender,
+                },
+                kad::QueryResult::GetProviders(result),
+            ) => {
+                if let Ok(kad::GetProvidersOk::FoundProviders {
+                    providers: found, ..
+                }) = result
+                {
+                    providers.extend(found);
+                }
+                if step.last {
+                    let _ = sender.send(Ok(providers.into_iter().collect()));
+                } else {
+                    self.queries
+                        .insert(*id, PendingQuery::Providers { providers, sender });
+                }
+            }
+            (query, _) => {
+                self.queries.insert(*id, query);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,196 @@
+use crate::config::DialLimitsConfig;
+use crate::rpc::rate_limit::Bucket;
+use libp2p::core::transport::PortUse;
+use libp2p::core::Endpoint;
+use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
+use libp2p::swarm::{
+    dummy, ConnectionDenied, ConnectionId, DialError, FromSwarm, NetworkBehaviour, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// How often dials deferred by the limits are tried again.
+pub const DEFERRED_DIAL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many peers are tracked before those whose limits have refilled are forgotten.
+const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Why a dial was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DialLimitExceeded {
+    Global,
+    Peer(PeerId),
+}
+
+impl fmt::Display for DialLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialLimitExceeded::Global => write!(f, "the node is dialing too often"),
+            DialLimitExceeded::Peer(peer_id) => write!(f, "{peer_id} is being dialed too often"),
+        }
+    }
+}
+
+impl Error for DialLimitExceeded {}
+
+/// Whether `error` is a dial refused by the dial limits.
+pub fn is_rate_limited(error: &DialError) -> bool {
+    match error {
+        DialError::Denied { cause } => cause.downcast_ref::<DialLimitExceeded>().is_some(),
+        _ => false,
+    }
+}
+
+/// Token buckets limiting how often the node dials, in all and to each peer.
+pub struct DialLimiter {
+    config: DialLimitsConfig,
+    global: Option<Bucket>,
+    peers: HashMap<PeerId, Bucket>,
+}
+
+impl DialLimiter {
+    pub fn new(config: DialLimitsConfig) -> Self {
+        Self {
+            config,
+            global: None,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Take a dial to `peer_id`, or to an address of an unknown peer, at `now`. Nothing is taken
+    /// when either limit refuses it.
+    pub fn try_dial(
+        &mut self,
+        peer_id: Option<PeerId>,
+        now: Instant,
+    ) -> Result<(), DialLimitExceeded> {
+        let global = match &self.config.global {
+            Some(rate) => {
+                let bucket = self.global.get_or_insert_with(|| Bucket::new(rate, now));
+                if !bucket.ready(rate, now) {
+                    return Err(DialLimitExceeded::Global);
+                }
+                Some((bucket, rate))
+            }
+            None => None,
+        };
+        if let (Some(rate), Some(peer_id)) = (&self.config.per_peer, peer_id) {
+            if self.peers.len() >= MAX_TRACKED_PEERS && !self.peers.contains_key(&peer_id) {
+                self.peers.retain(|_, bucket| !bucket.is_full(rate, now));
+            }
+            let bucket = self
+                .peers
+                .entry(peer_id)
+                .or_insert_with(|| Bucket::new(rate, now));
+            if !bucket.try_take(rate, now) {
+                return Err(DialLimitExceeded::Peer(peer_id));
+            }
+        }
+        if let Some((bucket, rate)) = global {
+            bucket.try_take(rate, now);
+        }
+        Ok(())
+    }
+}
+
+/// Refuses dials over the limits, whichever behaviour or client asked for them. Refused dials fail
+/// with `DialError::Denied`, which `is_rate_limited` recognizes. It must come after every other
+/// behaviour that can deny a dial, so that dials they deny take nothing from the limits.
+pub struct Behaviour {
+    limiter: DialLimiter,
+}
+
+impl Behaviour {
+    pub fn new(config: DialLimitsConfig) -> Self {
+        Self {
+            limiter: DialLimiter::new(config),
+        }
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Infallible;
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        _addresses: &[Multiaddr],
+        _effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.limiter
+            .try_dial(maybe_peer, Instant::now())
+            .map_err(ConnectionDenied::new)?;
+        Ok(Vec::new())
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _addr: &Multiaddr,
+        _role_override: Endpoint,
+        _port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, _event: FromSwarm) {}
+
+    fn on_connection_handler_event(
+        &mut self,
+        _peer_id: PeerId,
+        _connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        Poll::Pending
+    }
+}
+
+/// A dial to a configured or remembered peer that the limits refused, to try again once they
+/// allow it rather than lose the peer.
+#[derive(Clone, Debug)]
+pub struct DeferredDial {
+    pub peer_id: Option<PeerId>,
+
+    /// The peer's addresses, of which there is exactly one when its id is unknown.
+    pub addresses: Vec<Multiaddr>,
+}
+
+impl DeferredDial {
+    pub fn opts(&self) -> DialOpts {
+        match self.peer_id {
+            Some(peer_id) => DialOpts::peer_id(peer_id)
+                .addresses(self.addresses.clone())
+                .condition(PeerCondition::DisconnectedAndNotDialing)
+                .build(),
+            None => DialOpts::unknown_peer_id()
+                .address(self.addresses[0].clone())
+                .build(),
+        }
+    }
+}
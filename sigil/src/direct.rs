@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// The request-response protocol used to message a single peer directly. Messages travel only
+/// over the already-encrypted connection to that peer and never touch the public gossip mesh.
+pub const DIRECT_PROTOCOL: &str = "/sigil/direct/1.0.0";
+
+/// A message sent to one peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DirectMessage {
+    /// The gossip topic this message belongs to, when it was published to a chosen subset of
+    /// peers rather than privately.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+/// The recipient's confirmation that a private message was received.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DirectAck;
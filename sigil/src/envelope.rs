@@ -0,0 +1,159 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the envelope wire format this node writes, carried in each envelope as `v`.
+/// Version 0, written by older nodes without `v`, carries payloads as JSON arrays of numbers.
+/// Version 1 carries them in base64, a third larger than the payload rather than several times.
+pub const WIRE_VERSION: u32 = 1;
+
+/// The wire format of every application message sigil publishes over gossipsub.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "WireEnvelope", into = "WireEnvelope")]
+pub struct Envelope {
+    /// Shared, rather than copied, by every message and event the payload is passed on in.
+    pub payload: Bytes,
+
+    /// A random value distinguishing deliberate re-sends of the same payload from replays.
+    pub nonce: u64,
+
+    /// When the publisher created this envelope, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// Present when the publisher wants specific recipients to acknowledge receipt.
+    pub ack: Option<AckRequest>,
+}
+
+/// An envelope as it is written on the wire, in any version this node can read.
+#[derive(Serialize, Deserialize)]
+struct WireEnvelope {
+    #[serde(default)]
+    v: u32,
+    payload: WirePayload,
+    nonce: u64,
+    timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ack: Option<AckRequest>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum WirePayload {
+    Base64(String),
+    Numbers(Bytes),
+}
+
+impl From<Envelope> for WireEnvelope {
+    fn from(envelope: Envelope) -> Self {
+        Self {
+            v: WIRE_VERSION,
+            payload: WirePayload::Base64(STANDARD.encode(&envelope.payload)),
+            nonce: envelope.nonce,
+            timestamp: envelope.timestamp,
+            ack: envelope.ack,
+        }
+    }
+}
+
+impl TryFrom<WireEnvelope> for Envelope {
+    type Error = String;
+
+    fn try_from(wire: WireEnvelope) -> Result<Self, Self::Error> {
+        let payload = match (wire.v, wire.payload) {
+            (0, WirePayload::Numbers(payload)) => payload,
+            (1, WirePayload::Base64(payload)) => STANDARD
+                .decode(payload)
+                .map_err(|e| format!("invalid payload: {e}"))?
+                .into(),
+            (v, _) if v > WIRE_VERSION => return Err(format!("unsupported envelope version {v}")),
+            (v, _) => return Err(format!("payload does not match envelope version {v}")),
+        };
+        Ok(Self {
+            payload,
+            nonce: wire.nonce,
+            timestamp: wire.timestamp,
+            ack: wire.ack,
+        })
+    }
+}
+
+/// Several envelopes published on one topic as a single gossip message, sparing small messages
+/// gossipsub's overhead for each. Receivers handle each envelope as if it had arrived alone.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Bundle {
+    pub envelopes: Vec<Envelope>,
+}
+
+/// A request that the listed recipients acknowledge a message on the ack topic.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AckRequest {
+    pub id: u64,
+    pub recipients: Vec<PeerId>,
+
+    /// Which publication of this message this is, starting from 1.
+    pub attempt: u32,
+}
+
+/// An acknowledgement published on the ack topic by a recipient of a reliable message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ack {
+    pub id: u64,
+    pub publisher: PeerId,
+}
+
+impl Envelope {
+    pub fn new(payload: impl Into<Bytes>) -> Self {
+        Self {
+            payload: payload.into(),
+            nonce: rand::random(),
+            timestamp: unix_millis(),
+            ack: None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("envelope serialization should not fail")
+    }
+
+    pub fn decode(bytes: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+impl Bundle {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("bundle serialization should not fail")
+    }
+
+    /// Decode a gossip message as an envelope or, failing that, a bundle of them. The envelope's
+    /// error is returned for messages that are neither.
+    pub fn decode_any(bytes: &[u8]) -> serde_json::Result<Vec<Envelope>> {
+        match Envelope::decode(bytes) {
+            Ok(envelope) => Ok(vec![envelope]),
+            Err(e) => serde_json::from_slice::<Bundle>(bytes)
+                .map(|bundle| bundle.envelopes)
+                .map_err(|_| e),
+        }
+    }
+}
+
+impl Ack {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("ack serialization should not fail")
+    }
+
+    pub fn decode(bytes: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+/// The current time in milliseconds since the Unix epoch.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,215 @@
+use crate::block::BlockHash;
+use crate::envelope::unix_millis;
+use crate::journal::ReceivedMessage;
+use crate::node::BootstrapStatus;
+use bytes::Bytes;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// How many events may be buffered for a slow subscriber before it starts missing them.
+pub const EVENT_CHANNEL_SIZE: usize = 1024;
+
+/// How many significant events the node remembers for `SwarmClient::recent_events`.
+pub const EVENT_LOG_SIZE: usize = 1000;
+
+/// Significant occurrences on a node, broadcast to every subscriber of its `SwarmClient`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// A message was received and accepted on a topic we subscribe to.
+    Message(ReceivedMessage),
+    PeerConnected {
+        peer: PeerId,
+    },
+    /// A peer sent us a message with `SwarmClient::send_private`, which no one else receives.
+    PrivateMessage {
+        peer: PeerId,
+        id: String,
+
+        /// When the message arrived, in milliseconds since the Unix epoch.
+        received_at: u64,
+        payload: Bytes,
+    },
+    /// Our last connection to a peer closed.
+    PeerDisconnected {
+        peer: PeerId,
+    },
+    /// A peer offered us a blob, which can be downloaded with `SwarmClient::fetch_blob`.
+    BlobOffered {
+        peer: PeerId,
+        id: u64,
+        name: String,
+        size: u64,
+    },
+    BlobProgress {
+        peer: PeerId,
+        id: u64,
+        received: u64,
+        size: u64,
+    },
+    BlobReceived {
+        peer: PeerId,
+        id: u64,
+        size: u64,
+    },
+    /// A peer finished downloading a blob we offered it.
+    BlobSent {
+        peer: PeerId,
+        id: u64,
+    },
+    /// A sealed batch was received and accepted for propagation.
+    BatchReceived {
+        peer: PeerId,
+        number: u64,
+        hash: BlockHash,
+        transactions: usize,
+    },
+    SyncProgress {
+        peer: PeerId,
+        received: u64,
+        total: u64,
+    },
+    /// A state sync from `peer` ended, successfully unless `error` is set.
+    SyncFinished {
+        peer: PeerId,
+        error: Option<String>,
+    },
+    /// A peer's clock was measured to be further from ours than the tolerated skew.
+    ClockSkewed {
+        peer: PeerId,
+
+        /// How far ahead of our clock the peer's is, in milliseconds.
+        skew_millis: i64,
+    },
+    /// A relayed connection to a peer was upgraded to a direct one by hole punching.
+    HolePunched {
+        peer: PeerId,
+    },
+    /// Hole punching to a peer failed, so it is only reached through a relay.
+    HolePunchFailed {
+        peer: PeerId,
+        error: String,
+    },
+    /// A bootstrap of the DHT's routing table finished with a different outcome than the last.
+    BootstrapChanged {
+        status: BootstrapStatus,
+    },
+    /// The event log fell behind the node and missed this many events, which are lost from it.
+    EventsMissed {
+        missed: u64,
+    },
+}
+
+impl NodeEvent {
+    /// Whether this event is worth remembering in the event log, unlike messages and progress
+    /// reports, which are frequent enough to crowd everything else out. Private messages are
+    /// never remembered, so that the log cannot leak them.
+    pub fn is_significant(&self) -> bool {
+        !matches!(
+            self,
+            NodeEvent::Message(_)
+                | NodeEvent::PrivateMessage { .. }
+                | NodeEvent::BlobProgress { .. }
+                | NodeEvent::SyncProgress { .. }
+        )
+    }
+
+    /// Whether this event concerns our connections to other peers.
+    pub fn is_peer_event(&self) -> bool {
+        matches!(
+            self,
+            NodeEvent::PeerConnected { .. } | NodeEvent::PeerDisconnected { .. }
+        )
+    }
+}
+
+/// An event remembered by the event log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    /// The event's position in the log, counting from the node's start.
+    pub sequence: u64,
+
+    /// When the event happened, in milliseconds since the Unix epoch.
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: NodeEvent,
+}
+
+/// The most recent significant events, so that tests and operators can see how a node reached
+/// its current state rather than only the state itself.
+#[derive(Debug)]
+pub struct EventLog {
+    capacity: usize,
+    next_sequence: u64,
+    events: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            next_sequence: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Remember `event` if it is significant, forgetting the oldest event once full.
+    pub fn record(&mut self, event: NodeEvent, at: u64) {
+        if !event.is_significant() {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(LoggedEvent {
+            sequence: self.next_sequence,
+            at,
+            event,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Up to `limit` events, oldest first. With `since`, these are the earliest events after that
+    /// sequence number, so a caller can read the log forwards; without, they are the latest.
+    pub fn events(&self, limit: usize, since: Option<u64>) -> Vec<LoggedEvent> {
+        match since {
+            Some(since) => self
+                .events
+                .iter()
+                .filter(|logged| logged.sequence > since)
+                .take(limit)
+                .cloned()
+                .collect(),
+            None => {
+                let skip = self.events.len().saturating_sub(limit);
+                self.events.iter().skip(skip).cloned().collect()
+            }
+        }
+    }
+
+    /// Record every event broadcast on `events` into a new log, until the channel closes.
+    pub fn spawn(events: &broadcast::Sender<NodeEvent>, capacity: usize) -> Arc<Mutex<Self>> {
+        let log = Arc::new(Mutex::new(Self::new(capacity)));
+        let mut receiver = events.subscribe();
+        let recorder = log.clone();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => recorder
+                        .lock()
+                        .expect("event log lock is never poisoned")
+                        .record(event, unix_millis()),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => recorder
+                        .lock()
+                        .expect("event log lock is never poisoned")
+                        .record(NodeEvent::EventsMissed { missed }, unix_millis()),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        log
+    }
+}
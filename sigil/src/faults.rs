@@ -0,0 +1,228 @@
+use crate::config::{FaultConfig, LinkFaults};
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::PeerId;
+use rand::Rng;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The faults injected into the node's connections, shared between the transport and the node's
+/// `SwarmClient` so that tests can change them while the node runs. Each change applies to the
+/// writes that follow it, on every connection.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    config: Arc<RwLock<FaultConfig>>,
+}
+
+impl Faults {
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    /// Replace every link's faults.
+    pub fn set(&self, config: FaultConfig) {
+        *self.config.write().expect("faults lock is never poisoned") = config;
+    }
+
+    /// The faults of connections to `peer_id`.
+    pub fn link(&self, peer_id: &PeerId) -> LinkFaults {
+        let config = self.config.read().expect("faults lock is never poisoned");
+        config.peers.get(peer_id).unwrap_or(&config.default).clone()
+    }
+
+    /// Set the faults of connections to peers without faults of their own.
+    pub fn set_default(&self, faults: LinkFaults) {
+        self.config
+            .write()
+            .expect("faults lock is never poisoned")
+            .default = faults;
+    }
+
+    pub fn set_peer(&self, peer_id: PeerId, faults: LinkFaults) {
+        self.config
+            .write()
+            .expect("faults lock is never poisoned")
+            .peers
+            .insert(peer_id, faults);
+    }
+
+    /// Return connections to `peer_id` to the default faults.
+    pub fn clear_peer(&self, peer_id: &PeerId) {
+        self.config
+            .write()
+            .expect("faults lock is never poisoned")
+            .peers
+            .remove(peer_id);
+    }
+
+    /// Whether no connection has any faults.
+    pub fn is_empty(&self) -> bool {
+        let config = self.config.read().expect("faults lock is never poisoned");
+        config.default.is_none() && config.peers.values().all(LinkFaults::is_none)
+    }
+
+    /// `muxer`, a connection to `peer_id`, with faults injected into the writes of its
+    /// substreams. Without the `fault-injection` feature, `muxer` itself.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject(&self, peer_id: PeerId, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        StreamMuxerBox::new(faulty::Faulty {
+            inner: muxer,
+            peer_id,
+            faults: self.clone(),
+        })
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    pub fn inject(&self, _peer_id: PeerId, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        muxer
+    }
+}
+
+impl LinkFaults {
+    pub fn is_none(&self) -> bool {
+        self.latency_millis == 0 && self.jitter_millis == 0 && self.drop_rate <= 0.0
+    }
+
+    /// How long a write waits before it is sent: the latency, a random jitter, and the
+    /// retransmission timeout if the write is dropped.
+    pub fn delay(&self, rng: &mut impl Rng) -> Duration {
+        let mut millis = self.latency_millis + rng.gen_range(0..=self.jitter_millis);
+        if rng.gen_bool(self.drop_rate.clamp(0.0, 1.0)) {
+            millis += self.retransmit_timeout_millis;
+        }
+        Duration::from_millis(millis)
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+mod faulty {
+    use super::Faults;
+    use futures::{ready, AsyncRead, AsyncWrite};
+    use libp2p::core::muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent};
+    use libp2p::PeerId;
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::time::Sleep;
+
+    pub struct Faulty {
+        pub inner: StreamMuxerBox,
+        pub peer_id: PeerId,
+        pub faults: Faults,
+    }
+
+    impl StreamMuxer for Faulty {
+        type Substream = FaultyStream<<StreamMuxerBox as StreamMuxer>::Substream>;
+        type Error = io::Error;
+
+        fn poll_inbound(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            let this = self.get_mut();
+            let stream = ready!(Pin::new(&mut this.inner).poll_inbound(cx))?;
+            Poll::Ready(Ok(FaultyStream::new(
+                stream,
+                this.peer_id,
+                this.faults.clone(),
+            )))
+        }
+
+        fn poll_outbound(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            let this = self.get_mut();
+            let stream = ready!(Pin::new(&mut this.inner).poll_outbound(cx))?;
+            Poll::Ready(Ok(FaultyStream::new(
+                stream,
+                this.peer_id,
+                this.faults.clone(),
+            )))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Pin::new(&mut self.get_mut().inner).poll_close(cx)
+        }
+
+        fn poll(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+            Pin::new(&mut self.get_mut().inner).poll(cx)
+        }
+    }
+
+    /// A substream whose writes are held up by the faults of its link. Protocols flush after each
+    /// message they write, so the delay is taken once for the writes between flushes, which
+    /// delays whole messages rather than every piece of them.
+    pub struct FaultyStream<S> {
+        inner: S,
+        peer_id: PeerId,
+        faults: Faults,
+        delay: Option<Pin<Box<Sleep>>>,
+
+        /// Whether the writes since the last flush have already waited.
+        delayed: bool,
+    }
+
+    impl<S> FaultyStream<S> {
+        fn new(inner: S, peer_id: PeerId, faults: Faults) -> Self {
+            Self {
+                inner,
+                peer_id,
+                faults,
+                delay: None,
+                delayed: false,
+            }
+        }
+    }
+
+    impl<S: AsyncRead + Unpin> AsyncRead for FaultyStream<S> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+        }
+    }
+
+    impl<S: AsyncWrite + Unpin> AsyncWrite for FaultyStream<S> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if !this.delayed {
+                this.delayed = true;
+                let delay = this
+                    .faults
+                    .link(&this.peer_id)
+                    .delay(&mut rand::thread_rng());
+                if !delay.is_zero() {
+                    this.delay = Some(Box::pin(tokio::time::sleep(delay)));
+                }
+            }
+            if let Some(sleep) = &mut this.delay {
+                ready!(sleep.as_mut().poll(cx));
+                this.delay = None;
+            }
+            Pin::new(&mut this.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+            this.delayed = false;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_close(cx)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use crate::config::IdentityConfig;
+use libp2p_identity::Keypair;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// The node's keypair as configured: read from its file, generated into it if missing, derived
+/// from the development seed, or generated afresh for this run when none of those are set.
+pub fn keypair(config: &IdentityConfig) -> Result<Keypair, Box<dyn Error>> {
+    if let Some(seed) = config.secret_key_seed {
+        tracing::warn!(
+            seed,
+            "Deriving the node's key from secret_key_seed. Anyone can derive the same key, so \
+             this must only be used for local testing."
+        );
+        let mut bytes = [0u8; 32];
+        bytes[0] = seed;
+        return Ok(Keypair::ed25519_from_bytes(bytes)?);
+    }
+    match &config.keypair_path {
+        Some(path) if path.exists() => read(path),
+        Some(path) if config.generate_if_missing => {
+            let key = Keypair::generate_ed25519();
+            write(path, &key)?;
+            Ok(key)
+        }
+        Some(path) => Err(format!("keypair {} does not exist", path.display()).into()),
+        None => Ok(Keypair::generate_ed25519()),
+    }
+}
+
+fn read(path: &Path) -> Result<Keypair, Box<dyn Error>> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("failed to read keypair {}: {e}", path.display()))?;
+    Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| format!("invalid keypair {}: {e}", path.display()).into())
+}
+
+/// Write a new keypair readable only by the node's user, refusing to replace an existing file.
+fn write(path: &Path, key: &Keypair) -> Result<(), Box<dyn Error>> {
+    let bytes = key.to_protobuf_encoding()?;
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(&bytes))
+        .map_err(|e| format!("failed to write keypair {}: {e}", path.display()).into())
+}
@@ -0,0 +1,64 @@
+use bytes::Bytes;
+use libp2p::{gossipsub::TopicHash, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// A gossip message as it was received by this node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub source: Option<PeerId>,
+
+    /// When we received the message, in milliseconds since the Unix epoch.
+    pub received_at: u64,
+
+    /// Shared with the journal, the webhook and every event subscriber rather than copied.
+    pub payload: Bytes,
+}
+
+/// A received message together with the topic it was received on.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReceivedMessage {
+    pub topic: String,
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+}
+
+/// A bounded log of the most recent messages received on each topic, kept for debugging
+/// propagation issues.
+pub struct MessageJournal {
+    capacity: usize,
+    topics: HashMap<TopicHash, VecDeque<JournalEntry>>,
+}
+
+impl MessageJournal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            topics: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, topic: TopicHash, entry: JournalEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let entries = self.topics.entry(topic).or_default();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// The last `limit` messages received on `topic`, oldest first.
+    pub fn recent(&self, topic: &TopicHash, limit: usize) -> Vec<JournalEntry> {
+        let Some(entries) = self.topics.get(topic) else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .skip(entries.len().saturating_sub(limit))
+            .cloned()
+            .collect()
+    }
+}
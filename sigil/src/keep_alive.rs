@@ -0,0 +1,192 @@
+use crate::config::KeepAliveConfig;
+use libp2p::core::transport::PortUse;
+use libp2p::core::upgrade::DeniedUpgrade;
+use libp2p::core::Endpoint;
+use libp2p::swarm::handler::ConnectionEvent;
+use libp2p::swarm::{
+    ConnectionDenied, ConnectionHandler, ConnectionHandlerEvent, ConnectionId, FromSwarm,
+    NetworkBehaviour, NotifyHandler, SubstreamProtocol, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use libp2p::{Multiaddr, PeerId};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// How often the node classifies its connections and extends how long each is kept open.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What a peer is to the node, which decides how long an unused connection to it stays open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionClass {
+    /// The peer is a relay the node holds a reservation on.
+    Relay,
+    /// The connection was established by hole punching.
+    Holepunched,
+    /// The peer is in one of the node's gossipsub meshes.
+    Mesh,
+    /// The peer is in the DHT's routing table.
+    Dht,
+    Other,
+}
+
+impl ConnectionClass {
+    pub const ALL: [ConnectionClass; 5] = [
+        ConnectionClass::Relay,
+        ConnectionClass::Holepunched,
+        ConnectionClass::Mesh,
+        ConnectionClass::Dht,
+        ConnectionClass::Other,
+    ];
+}
+
+/// Keeps each connection open past the swarm's idle timeout for as long as its class asks. The
+/// swarm closes connections that nothing has used for the shortest timeout of any class, so each
+/// connection is held open for the rest of its own class's timeout before that countdown starts.
+/// Connections are held as class `Other` when they are established, and for their class from
+/// each time the node calls `keep`.
+pub struct Behaviour {
+    config: KeepAliveConfig,
+    pending: VecDeque<ToSwarm<Infallible, Instant>>,
+}
+
+impl Behaviour {
+    pub fn new(config: KeepAliveConfig) -> Self {
+        Self {
+            config,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Hold the connection open for its class's timeout from now, unless it is already held
+    /// longer.
+    pub fn keep(&mut self, peer_id: PeerId, connection_id: ConnectionId, class: ConnectionClass) {
+        self.pending.push_back(ToSwarm::NotifyHandler {
+            peer_id,
+            handler: NotifyHandler::One(connection_id),
+            event: Instant::now() + self.hold(class),
+        });
+    }
+
+    /// How long a connection of `class` is held open before the swarm's idle timeout applies.
+    fn hold(&self, class: ConnectionClass) -> Duration {
+        self.config
+            .timeout(class)
+            .saturating_sub(self.config.shortest())
+    }
+
+    fn handler(&self) -> Handler {
+        Handler {
+            until: Instant::now() + self.hold(ConnectionClass::Other),
+            timer: None,
+        }
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = Handler;
+    type ToSwarm = Infallible;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(self.handler())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _peer: PeerId,
+        _addr: &Multiaddr,
+        _role_override: Endpoint,
+        _port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(self.handler())
+    }
+
+    fn on_swarm_event(&mut self, _event: FromSwarm) {}
+
+    fn on_connection_handler_event(
+        &mut self,
+        _peer_id: PeerId,
+        _connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.pending.pop_front().map_or(Poll::Pending, Poll::Ready)
+    }
+}
+
+/// Keeps its connection alive until a deadline, waking the connection when it passes so that the
+/// swarm's idle timeout starts on time.
+pub struct Handler {
+    until: Instant,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl ConnectionHandler for Handler {
+    type FromBehaviour = Instant;
+    type ToBehaviour = Infallible;
+    type InboundProtocol = DeniedUpgrade;
+    type OutboundProtocol = DeniedUpgrade;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = ();
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(DeniedUpgrade, ())
+    }
+
+    fn connection_keep_alive(&self) -> bool {
+        Instant::now() < self.until
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::ToBehaviour>,
+    > {
+        if Instant::now() < self.until {
+            let until = self.until;
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(sleep_until(until)));
+            if timer.as_mut().poll(cx).is_ready() {
+                self.timer = None;
+            }
+        }
+        Poll::Pending
+    }
+
+    fn on_behaviour_event(&mut self, until: Instant) {
+        if until > self.until {
+            self.until = until;
+            self.timer = None;
+        }
+    }
+
+    fn on_connection_event(
+        &mut self,
+        _event: ConnectionEvent<
+            Self::InboundProtocol,
+            Self::OutboundProtocol,
+            Self::InboundOpenInfo,
+            Self::OutboundOpenInfo,
+        >,
+    ) {
+    }
+}
@@ -0,0 +1,44 @@
+pub mod bandwidth;
+pub mod batch;
+pub mod blob;
+pub mod block;
+pub mod bundle;
+pub mod channels;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod consensus;
+pub mod crash;
+pub mod data_dir;
+pub mod delivery;
+pub mod dht;
+pub mod dial_limits;
+pub mod direct;
+pub mod envelope;
+pub mod event;
+pub mod faults;
+pub mod identity;
+pub mod journal;
+pub mod keep_alive;
+pub mod log_stream;
+pub mod logging;
+pub mod mempool;
+pub mod node;
+pub mod page;
+pub mod peers;
+pub mod relay;
+pub mod reload;
+pub mod replay;
+pub mod rpc;
+pub mod schedule;
+pub mod snapshot;
+pub mod stats;
+pub mod sync;
+pub mod telemetry;
+pub mod testing;
+pub mod transport;
+pub mod validation;
+pub mod version;
+pub mod webhook;
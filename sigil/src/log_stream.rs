@@ -0,0 +1,88 @@
+use crate::envelope::unix_millis;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// How many log lines may be buffered for a slow subscriber before it starts missing them.
+const LOG_CHANNEL_SIZE: usize = 1024;
+
+/// A log line, as streamed to subscribers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// When the line was logged, in milliseconds since the Unix epoch.
+    pub at: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A tracing layer that broadcasts every log line it sees, so that they can be streamed over RPC.
+/// Lines are only formatted while someone is subscribed.
+#[derive(Clone)]
+pub struct LogStream {
+    sender: broadcast::Sender<LogRecord>,
+}
+
+impl Default for LogStream {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(LOG_CHANNEL_SIZE).0,
+        }
+    }
+}
+
+impl LogStream {
+    /// Receive every log line from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<LogRecord> {
+        self.sender.subscribe()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogStream {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let _ = self.sender.send(LogRecord {
+            at: unix_millis(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::{LogConfig, LogFormat, LogRotation};
+use crate::log_stream::LogStream;
+use std::error::Error;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Replaces the log filter of the global subscriber.
+pub type LogHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Install the global subscriber, writing to stdout and to rotating files as configured, and to
+/// `stream` for RPC subscribers. The returned guard flushes the files when dropped. Only the first
+/// call in a process installs a subscriber, so tests may each call it.
+pub fn init(
+    config: &LogConfig,
+    stream: &LogStream,
+) -> Result<(LogHandle, Option<WorkerGuard>), Box<dyn Error>> {
+    let (filter, handle) = reload::Layer::new(config.env_filter()?);
+    let (file, guard) = match &config.directory {
+        Some(directory) => {
+            let rotation = match config.rotation {
+                LogRotation::Minutely => Rotation::MINUTELY,
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+                LogRotation::Never => Rotation::NEVER,
+            };
+            let mut appender = RollingFileAppender::builder()
+                .rotation(rotation)
+                .filename_prefix(&config.file_prefix);
+            if let Some(max_files) = config.max_files {
+                appender = appender.max_log_files(max_files);
+            }
+            let appender = appender
+                .build(directory)
+                .map_err(|e| format!("failed to log to {}: {e}", directory.display()))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(fmt_layer(config.format, writer, false)), Some(guard))
+        }
+        None => (None, None),
+    };
+    let stdout = config
+        .stdout
+        .then(|| fmt_layer(config.format, std::io::stdout, true));
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(stdout)
+        .with(file)
+        .with(stream.clone())
+        .try_init();
+    Ok((handle, guard))
+}
+
+/// A layer writing log lines to `writer` in `format`.
+fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match format {
+        LogFormat::Full => layer.boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
@@ -1,224 +1,221 @@
-use futures::stream::StreamExt;
-use jsonrpsee::core::async_trait;
-use jsonrpsee::proc_macros::rpc;
-use jsonrpsee::server::{RpcModule, ServerBuilder};
-use libp2p::{
-    core::Multiaddr,
-    dns, gossipsub, identify, mdns, noise, quic,
-    swarm::{NetworkBehaviour, SwarmEvent},
-    tcp, tls, yamux, SwarmBuilder,
-};
-use libp2p_identity::Keypair;
-use std::collections::hash_map::DefaultHasher;
+use clap::{Parser, Subcommand};
+use prometheus_client::registry::Registry;
+use sigil::config::Config;
+use sigil::crash;
+use sigil::data_dir;
+use sigil::identity;
+use sigil::log_stream::LogStream;
+use sigil::logging;
+use sigil::node::P2pNode;
+use sigil::reload::Reloader;
+use sigil::rpc;
+use sigil::telemetry;
+use sigil::version::BUILD_INFO;
 use std::error::Error;
-use std::hash::{Hash, Hasher};
-use std::time::Duration;
-use tokio::{io, io::AsyncBufReadExt, select};
-use tracing_subscriber::EnvFilter;
-
-#[rpc(server)]
-pub trait MyApi {
-    #[method(name = "say_hello")]
-    async fn say_hello(&self, name: String) -> jsonrpsee::core::RpcResult<String>;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use tokio::{io, io::AsyncBufReadExt, signal};
+
+/// The client of the Sigil rollup.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Path to a TOML, YAML or JSON configuration file; defaults are used when omitted. Any field
+    /// can be overridden by a `SIGIL_` environment variable, such as `SIGIL_RPC__LISTEN`.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-pub struct MyApiImpl;
-
-#[async_trait]
-impl MyApiServer for MyApiImpl {
-    async fn say_hello(&self, name: String) -> jsonrpsee::core::RpcResult<String> {
-        Ok(format!("Hello, {}!", name))
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
 }
 
-// We create a custom network behaviour that combines Gossipsub and Mdns.
-#[derive(NetworkBehaviour)]
-struct MyBehaviour {
-    gossipsub: gossipsub::Behaviour,
-    mdns: mdns::tokio::Behaviour,
-    identify: identify::Behaviour,
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the default configuration, with every setting commented.
+    PrintDefault,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .try_init();
+async fn main() {
+    let code = match run().await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            1
+        }
+    };
+    // Exit outright, as the runtime would otherwise wait on the blocking read of stdin.
+    process::exit(code);
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    if let Some(Command::Config(ConfigCommand::PrintDefault)) = args.command {
+        print!("{}", Config::default_toml());
+        return Ok(());
+    }
+    // Shared by everything that keeps the configuration, rather than copied into each.
+    let config = Arc::new(Config::load(args.config.as_deref())?);
+
+    let logs = LogStream::default();
+    let (log_handle, _log_guard) = logging::init(&config.log, &logs)?;
+    tracing::info!("{}", BUILD_INFO.banner());
+    let _pid_file = config
+        .pid_file
+        .as_deref()
+        .map(PidFile::create)
+        .transpose()?;
+
+    if let Some(data_dir) = &config.data_dir {
+        data_dir::prepare(data_dir)?;
+    }
+    let key = identity::keypair(&config.identity())?;
+    let peer_id = key.public().to_peer_id();
+    tracing::info!(%peer_id, "Starting node");
+
+    let mut registry = Registry::with_prefix("sigil");
+    let (node, client) = P2pNode::new(key, &config, &mut registry)?;
+    if let Some(data_dir) = &config.data_dir {
+        crash::install(data_dir.clone(), config.clone(), client.clone());
+    }
 
-    // TODO: clap/tracing/various env stuff
+    let mut reloader = Reloader::new(args.config, config.clone(), client.clone());
+    reloader.set_log_reloader(Box::new(move |filter| {
+        log_handle.reload(filter).map_err(|e| e.to_string())
+    }));
+    let reloader = Arc::new(reloader);
+    if config.reload.watch {
+        tokio::spawn(reloader.clone().watch(config.reload.watch_interval()));
+    }
 
     // Start an RPC server.
-    let server = ServerBuilder::default().build("0.0.0.0:3030").await?;
-    let mut module = RpcModule::new(());
-    module.merge(MyApiImpl.into_rpc())?;
-    let handle = server.start(module);
-
-    // Wait for server to finish or Ctrl-C
-    // tokio::signal::ctrl_c().await?;
-    // handle.stopped().await;
-
-    // Generate a private key for this node.
-    let key = Keypair::generate_ed25519();
-    println!("peer id {:?}", key.public().to_peer_id());
-
-    // TODO: defaults, pull from env.
-    // Prepare TCP connection management configuration.
-    let tcp_config = tcp::Config::new()
-        .ttl(64)
-        .nodelay(true)
-        .listen_backlog(1024)
-        .port_reuse(false);
-
-    // TODO: defaults, pull from env.
-    // Prepare QUIC connection management configuration.
-    let mut quic_config = quic::Config::new(&key);
-    quic_config.handshake_timeout = Duration::from_secs(5);
-    quic_config.max_idle_timeout = 10 * 1000;
-    quic_config.keep_alive_interval = Duration::from_secs(5);
-    quic_config.max_concurrent_stream_limit = 256;
-    quic_config.max_stream_data = 10_000_000;
-    quic_config.max_connection_data = 15_000_000;
-
-    // TODO: test DNS resolution when attempting to connect to peer.
-    // Prepare DNS configuration.
-    let dns_config = dns::ResolverConfig::new();
-    let dns_opts = dns::ResolverOpts::default();
-
-    let mut swarm = SwarmBuilder::with_existing_identity(key)
-        .with_tokio()
-        .with_tcp(
-            tcp_config,
-            (tls::Config::new, noise::Config::new),
-            yamux::Config::default,
-        )
-        .expect("swarm TCP configuration should have succeeded")
-        .with_quic_config(|_| quic_config)
-        .with_dns_config(dns_config, dns_opts)
-        // with relay_client
-        .with_behaviour(|key| {
-            // To content-address message, we can take the hash of message and use it as an ID.
-            let message_id_fn = |message: &gossipsub::Message| {
-                let mut s = DefaultHasher::new();
-                message.data.hash(&mut s);
-                gossipsub::MessageId::from(s.finish().to_string())
-            };
-
-            // Set a custom gossipsub configuration
-            let gossipsub_config = gossipsub::ConfigBuilder::default()
-                .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
-                .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
-                .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
-                .build()
-                .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?; // Temporary hack because `build` does not return a proper `std::error::Error`.
-
-            // build a gossipsub network behaviour
-            let gossipsub = gossipsub::Behaviour::new(
-                gossipsub::MessageAuthenticity::Signed(key.clone()),
-                gossipsub_config,
-            )?;
-
-            let agent_string = "sigil/1.0.0".to_string();
-            let mdns_string = agent_string.replace(['/', '.'], "_");
-            let mdns_config = mdns::Config::default().set_name(&mdns_string)?;
-            let mdns = mdns::tokio::Behaviour::new(mdns_config, key.public().to_peer_id())?;
-
-            // Prepare a means to identify this client.
-            // TODO: expose full config options.
-            let identify = identify::Behaviour::new(
-                identify::Config::new(agent_string.clone(), key.public())
-                    .with_agent_version(agent_string.clone()),
-            );
-
-            Ok(MyBehaviour {
-                gossipsub,
-                mdns,
-                identify,
-            })
-        })?
-        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
-        .build();
-
-    // Create a Gossipsub topic
-    let topic = gossipsub::IdentTopic::new("test-net");
-    // subscribes to our topic
-    swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
-
-    // Read full lines from stdin
-    let mut stdin = io::BufReader::new(io::stdin()).lines();
-
-    // Listen on all interfaces and whatever port the OS assigns
-    swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?)?;
-    swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
-
-    // Explicitly dial a remote peer.
-    // let remote_peer: Multiaddr = "/ip4/95.217.163.246/udp/3888/quic-v1".parse()?;
-    // let dial_result = swarm.dial(remote_peer);
-    // println!("dial result {:?}", dial_result);
-
-    println!("Sigil is alive.");
-
-    // TODO: add JSON-RPC server that runs in parallel such that we can issue method requests for
-    // peer discovery.
-
-    // Kick it off
-    loop {
-        select! {
-            Ok(Some(line)) = stdin.next_line() => {
-                if let Err(e) = swarm
-                    .behaviour_mut().gossipsub
-                    .publish(topic.clone(), line.as_bytes()) {
-                        println!("Publish error: {e:?}");
+    let handle = rpc::server::start(
+        &config.rpc,
+        client.clone(),
+        reloader.clone(),
+        registry.sub_registry_with_prefix("rpc"),
+        &config.telemetry.rpc_duration_buckets,
+        logs,
+    )
+    .await?;
+    let resource = vec![
+        ("service.name".to_string(), "sigil".to_string()),
+        (
+            "service.version".to_string(),
+            BUILD_INFO.version.to_string(),
+        ),
+        ("service.instance.id".to_string(), peer_id.to_string()),
+        ("sigil.network".to_string(), config.network.name.clone()),
+    ];
+    telemetry::start(&config.telemetry, registry, resource).await?;
+    let shutdown = client.clone();
+
+    // Read full lines from stdin and publish them to the first application topic.
+    if let Some(topic) = config.topics.application.first().cloned() {
+        tokio::spawn(async move {
+            let mut stdin = io::BufReader::new(io::stdin()).lines();
+            while let Ok(Some(line)) = stdin.next_line().await {
+                if let Err(e) = client.gossipsub_publish(&topic, line.into_bytes()).await {
+                    tracing::warn!(error = ?e, "Failed to publish line from stdin");
                 }
             }
-            event = swarm.select_next_some() => match event {
-                SwarmEvent::NewListenAddr { address, .. } => {
-                    println!("Local node is listening on {address}");
-                },
-                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                    println!("Successfully connected to {:?}", peer_id);
-                },
-                SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
-                    println!("Connection closed with {:?}, cause: {:?}", peer_id, cause);
-                },
-                SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
-                    println!("Failed to connect to {:?}: {:?}", peer_id, error);
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Identify(identify::Event::Received { connection_id, peer_id, info })) => {
-                    println!("Identified Peer: {}, AgentVersion: {}", peer_id, info.agent_version);
-                    // TODO: Add some rules about peer rejection based on semver plus environment
-                    // overrides.
-                    if !info.agent_version.contains("sigil/1.") {
-                        // If the AgentVersion indicates an IPFS client, ignore or disconnect
-                        println!("rejecting client: {}", peer_id);
-                        swarm.disconnect_peer_id(peer_id).unwrap_or_else(|err| {
-                            println!("Failed to disconnect: {:?}", err);
-                        });
-                    }
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
-                    for (peer_id, _multiaddr) in list {
-                        if peer_id != *swarm.local_peer_id() {
-                            println!("mDNS discovered a new peer: {peer_id}");
-                            swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
-                        }
-                    }
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
-                    for (peer_id, _multiaddr) in list {
-                        println!("mDNS discover peer has expired: {peer_id}");
-                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
-                    }
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                    propagation_source: peer_id,
-                    message_id: id,
-                    message,
-                })) => println!(
-                    "Got message: '{}' with id: {id} from peer: {peer_id}",
-                    String::from_utf8_lossy(&message.data),
-                ),
-                _ => {}
+        });
+    }
+
+    tracing::info!("Sigil is alive.");
+
+    // Run until we are signalled to stop or the node is shut down over RPC.
+    // Reload the configuration whenever we are hung up on.
+    let mut node = tokio::spawn(node.run());
+    let mut hangup = Hangup::new()?;
+    let shutdown_signal = shutdown_signal();
+    tokio::pin!(shutdown_signal);
+    let stopped = loop {
+        tokio::select! {
+            result = &mut shutdown_signal => {
+                result?;
+                break None;
+            }
+            result = &mut node => break Some(result),
+            _ = hangup.recv() => {
+                if let Err(e) = reloader.reload().await {
+                    tracing::error!(error = %e, "Failed to reload configuration");
+                }
             }
         }
+    };
+    handle.stop()?;
+    let result = match stopped {
+        Some(result) => result,
+        None => {
+            shutdown.shutdown().await?;
+            node.await
+        }
+    };
+    handle.stopped().await;
+    result.map_err(|e| format!("node failed: {e}"))?;
+    tracing::info!("Sigil has shut down.");
+    Ok(())
+}
+
+/// A file holding our process ID, removed when dropped.
+struct PidFile(PathBuf);
+
+impl PidFile {
+    fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::write(path, format!("{}\n", process::id()))
+            .map_err(|e| format!("failed to write pid file {}: {e}", path.display()))?;
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// SIGHUP, which asks for the configuration to be reloaded. It never arrives off Unix.
+struct Hangup {
+    #[cfg(unix)]
+    signal: signal::unix::Signal,
+}
+
+impl Hangup {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: signal::unix::signal(signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.signal.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Wait for ctrl-c or, on Unix, SIGTERM.
+async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
     }
+    #[cfg(not(unix))]
+    signal::ctrl_c().await
 }
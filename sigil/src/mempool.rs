@@ -0,0 +1,185 @@
+use libp2p::gossipsub;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The SHA-256 digest of a transaction's encoding, which identifies it in the pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TxHash(pub [u8; 32]);
+
+impl fmt::Display for TxHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl FromStr for TxHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(format!("transaction hash must be 64 hex characters: {s}"));
+        }
+        let mut hash = [0; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+                .map_err(|e| format!("invalid transaction hash {s}: {e}"))?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+impl Serialize for TxHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TxHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A transaction awaiting inclusion.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub nonce: u64,
+    pub data: Vec<u8>,
+}
+
+impl Transaction {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("transaction serialization is infallible")
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    pub fn hash(&self) -> TxHash {
+        TxHash(Sha256::digest(self.encode()).into())
+    }
+}
+
+/// Decides whether a transaction may enter the pool, such as by checking its signature or fee.
+pub trait TransactionValidator: Send + Sync + 'static {
+    fn validate(&self, transaction: &Transaction) -> bool;
+}
+
+#[derive(Debug)]
+pub enum MempoolError {
+    /// The transaction is already in the pool.
+    Duplicate(TxHash),
+    /// The pool's validator rejected the transaction.
+    Invalid(TxHash),
+    /// The transaction entered our pool but could not be gossiped to peers.
+    Publish(gossipsub::PublishError),
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolError::Duplicate(hash) => write!(f, "transaction {hash} is already pooled"),
+            MempoolError::Invalid(hash) => write!(f, "transaction {hash} is invalid"),
+            MempoolError::Publish(e) => write!(f, "failed to gossip transaction: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// A pooled transaction and its hash.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PooledTransaction {
+    pub hash: TxHash,
+    #[serde(flatten)]
+    pub transaction: Transaction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MempoolStatus {
+    pub pending: usize,
+    pub capacity: usize,
+}
+
+/// A bounded pool of pending transactions, deduplicated by hash. Once full, the oldest
+/// transaction is evicted to make room for each new one.
+pub struct Mempool {
+    capacity: usize,
+    transactions: HashMap<TxHash, Transaction>,
+    order: VecDeque<TxHash>,
+    validator: Option<Arc<dyn TransactionValidator>>,
+}
+
+impl Mempool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            transactions: HashMap::new(),
+            order: VecDeque::new(),
+            validator: None,
+        }
+    }
+
+    /// Replace the validator that transactions must pass to enter the pool. Without one, every
+    /// well-formed transaction is accepted.
+    pub fn set_validator(&mut self, validator: Arc<dyn TransactionValidator>) {
+        self.validator = Some(validator);
+    }
+
+    pub fn insert(&mut self, transaction: Transaction) -> Result<TxHash, MempoolError> {
+        let hash = transaction.hash();
+        if self.transactions.contains_key(&hash) {
+            return Err(MempoolError::Duplicate(hash));
+        }
+        if let Some(validator) = &self.validator {
+            if !validator.validate(&transaction) {
+                return Err(MempoolError::Invalid(hash));
+            }
+        }
+        while self.transactions.len() >= self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.transactions.remove(&oldest);
+        }
+        self.transactions.insert(hash, transaction);
+        self.order.push_back(hash);
+        Ok(hash)
+    }
+
+    /// Remove transactions, such as once they have been included in a block.
+    pub fn remove(&mut self, hashes: &[TxHash]) {
+        for hash in hashes {
+            self.transactions.remove(hash);
+        }
+        self.order
+            .retain(|hash| self.transactions.contains_key(hash));
+    }
+
+    /// Up to `limit` pooled transactions, oldest first.
+    pub fn pending(&self, limit: usize) -> Vec<PooledTransaction> {
+        self.order
+            .iter()
+            .take(limit)
+            .map(|hash| PooledTransaction {
+                hash: *hash,
+                transaction: self.transactions[hash].clone(),
+            })
+            .collect()
+    }
+
+    pub fn status(&self) -> MempoolStatus {
+        MempoolStatus {
+            pending: self.transactions.len(),
+            capacity: self.capacity,
+        }
+    }
+}
@@ -0,0 +1,2222 @@
+use crate::batch::{Batch, BatchLimits};
+use crate::blob::{self, BlobBehaviour, BlobTransfers, BLOB_PROTOCOL};
+#[cfg(not(feature = "kademlia"))]
+use crate::block::BlockError;
+#[cfg(feature = "kademlia")]
+use crate::block::{BlockBehaviour, BlockExchange, BLOCK_PROTOCOL, KAD_PROTOCOL};
+use crate::bundle::{Bundler, Publisher, ReadyBundle};
+use crate::channels::{Channel, ChannelMetrics, Source, Turn};
+#[cfg(feature = "chaos")]
+use crate::chaos::Restart;
+use crate::client::{ClientError, SwarmClient, SwarmCommand};
+use crate::clock::{ClockSkew, SkewChange, TimeBehaviour, TIME_PROTOCOL};
+#[cfg(feature = "kademlia")]
+use crate::config::KadMode;
+use crate::config::{ChaosConfig, Config, GossipsubVersion};
+use crate::consensus::{ConsensusHandler, ConsensusMessage, OrderedDelivery, SequencedMessage};
+use crate::delivery::{self, PendingDeliveries, ReceivedDeliveries};
+#[cfg(not(feature = "kademlia"))]
+use crate::dht::DhtError;
+#[cfg(feature = "kademlia")]
+use crate::dht::{DhtQueries, RoutingTableEntry};
+use crate::dial_limits::{self, DeferredDial, DEFERRED_DIAL_INTERVAL};
+use crate::direct::{DirectAck, DirectMessage, DIRECT_PROTOCOL};
+use crate::envelope::{self, Ack, Bundle, Envelope};
+use crate::event::{NodeEvent, EVENT_CHANNEL_SIZE};
+use crate::faults::Faults;
+use crate::journal::{JournalEntry, MessageJournal, ReceivedMessage};
+use crate::keep_alive::{self, ConnectionClass, KEEP_ALIVE_INTERVAL};
+use crate::mempool::{
+    Mempool, MempoolError, MempoolStatus, Transaction, TransactionValidator, TxHash,
+};
+use crate::peers::PeerInfoCache;
+use crate::relay::{RelayInfo, Relays, ReservationStatus};
+use crate::replay::{ReplayCache, ReplayError};
+use crate::schedule::{Job, Scheduler};
+use crate::snapshot::{NetworkSnapshot, SnapshotPeer};
+use crate::stats::{GossipStats, SessionStats};
+use crate::sync::{MemoryState, StateSync, SyncBehaviour, SyncState, SYNC_PROTOCOL};
+use crate::transport;
+use crate::validation::{
+    EnvelopeValidator, MessageValidator, Validated, ValidationPipeline, MAX_MESSAGE_SIZE,
+};
+use crate::version::{self, BuildInfo, BUILD_INFO};
+use crate::webhook::Webhook;
+use futures::future;
+use futures::stream::StreamExt;
+use libp2p::core::{muxing::StreamMuxerBox, transport::Boxed};
+#[cfg(feature = "dcutr")]
+use libp2p::dcutr;
+#[cfg(feature = "kademlia")]
+use libp2p::kad::{self, store::MemoryStore};
+#[cfg(feature = "mdns")]
+use libp2p::mdns;
+#[cfg(not(all(
+    feature = "dcutr",
+    feature = "kademlia",
+    feature = "mdns",
+    feature = "relay-server"
+)))]
+use libp2p::swarm::dummy;
+use libp2p::{
+    allow_block_list::{self, BlockedPeers},
+    connection_limits, dns, gossipsub, identify,
+    multiaddr::Protocol,
+    noise, relay,
+    request_response::{self, ProtocolSupport},
+    swarm::{
+        behaviour::toggle::Toggle, dial_opts::DialOpts, ConnectionId, DialError, NetworkBehaviour,
+        SwarmEvent,
+    },
+    tls, yamux, Multiaddr, PeerId, StreamProtocol, Swarm, SwarmBuilder,
+};
+use libp2p_identity::Keypair;
+use prometheus_client::registry::Registry;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{
+    io, select,
+    sync::{broadcast, mpsc, oneshot},
+};
+
+/// The bytes a gossipsub message adds around its data, such as its signature and source.
+const MESSAGE_OVERHEAD: usize = 1024;
+
+/// How long shutting down waits for connections to close before giving up on them.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many client commands of each priority may be queued before senders wait on the event loop.
+const COMMAND_CHANNEL_SIZE: usize = 256;
+
+// Behaviours compiled out by their cargo feature are stood in for by a behaviour that does nothing,
+// so that the set of fields is the same in every build.
+#[cfg(feature = "mdns")]
+type Mdns = mdns::tokio::Behaviour;
+#[cfg(not(feature = "mdns"))]
+type Mdns = dummy::Behaviour;
+
+#[cfg(feature = "kademlia")]
+type Kademlia = kad::Behaviour<MemoryStore>;
+#[cfg(not(feature = "kademlia"))]
+type Kademlia = dummy::Behaviour;
+
+#[cfg(feature = "kademlia")]
+type Blocks = BlockBehaviour;
+#[cfg(not(feature = "kademlia"))]
+type Blocks = dummy::Behaviour;
+
+#[cfg(feature = "relay-server")]
+type RelayServer = relay::Behaviour;
+#[cfg(not(feature = "relay-server"))]
+type RelayServer = dummy::Behaviour;
+
+#[cfg(feature = "dcutr")]
+type Dcutr = dcutr::Behaviour;
+#[cfg(not(feature = "dcutr"))]
+type Dcutr = dummy::Behaviour;
+
+// We create a custom network behaviour that combines Gossipsub and Mdns.
+#[derive(NetworkBehaviour)]
+pub struct MyBehaviour {
+    gossipsub: gossipsub::Behaviour,
+    mdns: Toggle<Mdns>,
+    identify: identify::Behaviour,
+    direct: request_response::cbor::Behaviour<DirectMessage, DirectAck>,
+    blob: BlobBehaviour,
+    kad: Kademlia,
+    block: Blocks,
+    sync: SyncBehaviour,
+    time: TimeBehaviour,
+    blocked: allow_block_list::Behaviour<BlockedPeers>,
+    relay_client: relay::client::Behaviour,
+    relay: Toggle<RelayServer>,
+    dcutr: Dcutr,
+    limits: connection_limits::Behaviour,
+    keep_alive: keep_alive::Behaviour,
+    dial_limits: dial_limits::Behaviour,
+}
+
+impl MyBehaviour {
+    /// Record an address of `peer_id` in the DHT's routing table, if the node has a DHT.
+    fn add_address(&mut self, peer_id: &PeerId, address: Multiaddr) {
+        #[cfg(feature = "kademlia")]
+        self.kad.add_address(peer_id, address);
+        #[cfg(not(feature = "kademlia"))]
+        let _ = (peer_id, address);
+    }
+
+    /// Drop `peer_id` from the DHT's routing table, returning whether it was there.
+    fn remove_peer(&mut self, peer_id: &PeerId) -> bool {
+        #[cfg(feature = "kademlia")]
+        {
+            self.kad.remove_peer(peer_id).is_some()
+        }
+        #[cfg(not(feature = "kademlia"))]
+        {
+            let _ = peer_id;
+            false
+        }
+    }
+
+    /// The peers in the DHT's routing table, if the node has a DHT.
+    fn routing_table_peers(&mut self) -> HashSet<PeerId> {
+        #[cfg(feature = "kademlia")]
+        {
+            self.kad
+                .kbuckets()
+                .flat_map(|bucket| {
+                    bucket
+                        .iter()
+                        .map(|entry| *entry.node.key.preimage())
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        }
+        #[cfg(not(feature = "kademlia"))]
+        {
+            HashSet::new()
+        }
+    }
+}
+
+/// What a node is and where it can be reached.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub peer_id: PeerId,
+    pub node_name: Option<String>,
+    pub agent_version: String,
+    pub protocol_version: String,
+    pub listen_addresses: Vec<Multiaddr>,
+    pub external_addresses: Vec<Multiaddr>,
+
+    /// How long the node has been running.
+    pub uptime_secs: u64,
+    pub build: BuildInfo,
+}
+
+/// An open connection to a peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+
+    /// Whether we dialed the peer, rather than it dialing us.
+    pub outbound: bool,
+}
+
+/// The node's internals, for diagnosing a misbehaving node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeState {
+    pub info: NodeInfo,
+    pub connections: Vec<ConnectionInfo>,
+    pub relays: Vec<RelayInfo>,
+
+    /// The peers in our mesh for each subscribed topic.
+    pub mesh: BTreeMap<String, Vec<PeerId>>,
+    pub pending_dials: usize,
+    pub pending_dht_queries: usize,
+    pub pending_deliveries: usize,
+    pub mempool: MempoolStatus,
+}
+
+/// A summary of the node's health, gathered in one pass of its event loop so that it is
+/// consistent and takes a single command to fetch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeStatus {
+    pub connected_peers: usize,
+
+    /// The distinct peers in any of the node's gossipsub meshes.
+    pub mesh_peers: usize,
+
+    /// The relays that have accepted a reservation.
+    pub relays: usize,
+    pub external_addresses: Vec<Multiaddr>,
+    pub bootstrap: BootstrapStatus,
+    pub nat: NatStatus,
+}
+
+/// How the last bootstrap of the DHT's routing table went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootstrapStatus {
+    /// No bootstrap has finished yet.
+    Pending,
+    Done,
+    /// The last bootstrap timed out.
+    Failed,
+    /// The node has no DHT.
+    Disabled,
+}
+
+/// Whether peers can reach the node, as far as it can tell without probing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatStatus {
+    /// Peers have confirmed an external address that is not relayed.
+    Public,
+    /// Peers can only reach the node through the relays that accepted its reservations.
+    Private,
+    Unknown,
+}
+
+/// A sigil peer-to-peer node, driven by `run` and commanded through its `SwarmClient`.
+pub struct P2pNode {
+    swarm: Swarm<MyBehaviour>,
+    control_receiver: mpsc::Receiver<SwarmCommand>,
+    consensus_receiver: mpsc::Receiver<SwarmCommand>,
+    bulk_receiver: mpsc::Receiver<SwarmCommand>,
+    ack_topic: gossipsub::IdentTopic,
+    mempool_topic: gossipsub::IdentTopic,
+    batch_topic: gossipsub::IdentTopic,
+    batch_limits: BatchLimits,
+    consensus_topic: gossipsub::IdentTopic,
+    consensus: OrderedDelivery,
+    consensus_handler: Option<Arc<dyn ConsensusHandler>>,
+    consensus_session: u64,
+    consensus_sequence: u64,
+    deliveries: PendingDeliveries,
+    received: ReceivedDeliveries,
+    bundler: Bundler,
+    replay: ReplayCache,
+    journal: MessageJournal,
+    stats: GossipStats,
+    session: SessionStats,
+    validation: ValidationPipeline,
+    validated_receiver: mpsc::Receiver<Validated>,
+    pending_direct: HashMap<
+        request_response::OutboundRequestId,
+        oneshot::Sender<Result<(), request_response::OutboundFailure>>,
+    >,
+    pending_dials: HashMap<ConnectionId, oneshot::Sender<Result<PeerId, DialError>>>,
+
+    /// Dials to configured and remembered peers that wait for the dial limits, oldest first.
+    deferred_dials: VecDeque<DeferredDial>,
+    connections: HashMap<ConnectionId, ConnectionInfo>,
+
+    /// The connections that hole punching established.
+    holepunched: HashSet<ConnectionId>,
+    relays: Relays,
+    blobs: BlobTransfers,
+    #[cfg(feature = "kademlia")]
+    blocks: BlockExchange,
+    #[cfg(feature = "kademlia")]
+    dht: DhtQueries,
+    bootstrap: BootstrapStatus,
+    sync: StateSync,
+    sync_on_join: bool,
+    mempool: Mempool,
+    events: broadcast::Sender<NodeEvent>,
+    webhook: Option<Webhook>,
+    channels: ChannelMetrics,
+    peers: PeerInfoCache,
+    snapshot_path: Option<PathBuf>,
+    snapshot_max_peers: usize,
+    clock: ClockSkew,
+    clock_penalty: f64,
+    node_name: Option<String>,
+    protocol_version: String,
+    scheduler: Scheduler,
+    chaos: ChaosConfig,
+
+    /// How long the event loop stalls before it next polls, as chaos decided.
+    stall: Option<Duration>,
+    turn: Turn,
+    started: Instant,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl P2pNode {
+    /// Build the swarm for this node, returning the node and a client for commanding it.
+    pub fn new(
+        key: Keypair,
+        config: &Config,
+        registry: &mut Registry,
+    ) -> Result<(Self, SwarmClient), Box<dyn Error>> {
+        Self::with_transport(
+            key,
+            config,
+            registry,
+            transport::build,
+            config.network.listen_addresses(),
+        )
+    }
+
+    /// Build the node over the transport that `build_transport` makes in place of TCP and QUIC,
+    /// listening on `listen_addresses` rather than the configured ports, such as to run it on a
+    /// network simulated in memory.
+    pub fn with_transport(
+        key: Keypair,
+        config: &Config,
+        registry: &mut Registry,
+        build_transport: impl FnOnce(
+            &Keypair,
+            &Config,
+            &Faults,
+        ) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn Error>>,
+        listen_addresses: impl IntoIterator<Item = Multiaddr>,
+    ) -> Result<(Self, SwarmClient), Box<dyn Error>> {
+        let faults = Faults::new(config.faults.clone());
+        let transport = build_transport(&key, config, &faults)?;
+
+        // TODO: test DNS resolution when attempting to connect to peer.
+        // Prepare DNS configuration.
+        let dns_config = dns::ResolverConfig::new();
+        let dns_opts = dns::ResolverOpts::default();
+
+        let protocol_version = version::protocol_version(&config.network.name);
+        let mut swarm = SwarmBuilder::with_existing_identity(key)
+            .with_tokio()
+            .with_other_transport(|_| transport)?
+            .with_dns_config(dns_config, dns_opts)
+            .with_relay_client(
+                (tls::Config::new, noise::Config::new),
+                yamux::Config::default,
+            )?
+            .with_behaviour(|key, relay_client| {
+                // Identify a message by its topic, publisher, and sequence number as well as its
+                // data, so that identical payloads from different publications are not
+                // deduplicated. Content-addressed topics hash only the data, so no two messages of
+                // the same content will be propagated. Batches are always content-addressed, so a
+                // batch republished by another peer is not propagated twice.
+                let content_addressed: HashSet<gossipsub::TopicHash> = config
+                    .gossipsub
+                    .content_addressed_topics
+                    .iter()
+                    .map(String::as_str)
+                    .chain([config.topics.batch.as_str()])
+                    .map(|topic| gossipsub::IdentTopic::new(topic).hash())
+                    .collect();
+                let message_id_fn = move |message: &gossipsub::Message| {
+                    message_id(message, content_addressed.contains(&message.topic))
+                };
+
+                // Set a custom gossipsub configuration
+                let mut gossipsub_builder = gossipsub::ConfigBuilder::default();
+                gossipsub_builder
+                    .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
+                    .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
+                    .message_id_fn(message_id_fn)
+                    .validate_messages() // Messages are only propagated once our validation pipeline accepts them.
+                    .flood_publish(config.gossipsub.flood_publish)
+                    // Leave room for batches; other topics are held to their own limit during validation.
+                    .max_transmit_size(
+                        (config.batch.max_size + MESSAGE_OVERHEAD).max(MAX_MESSAGE_SIZE),
+                    );
+                match config.gossipsub.protocol_version {
+                    GossipsubVersion::V1_1 => {
+                        gossipsub_builder
+                            .protocol_id_prefix(config.gossipsub.protocol_id_prefix.clone());
+                    }
+                    GossipsubVersion::V1_0 => {
+                        gossipsub_builder.protocol_id(
+                            format!("{}/1.0.0", config.gossipsub.protocol_id_prefix),
+                            gossipsub::Version::V1_0,
+                        );
+                    }
+                }
+                if config.gossipsub.support_floodsub {
+                    gossipsub_builder.support_floodsub();
+                }
+                let gossipsub_config = gossipsub_builder.build().map_err(io::Error::other)?; // Temporary hack because `build` does not return a proper `std::error::Error`.
+
+                // build a gossipsub network behaviour
+                let authenticity = gossipsub::MessageAuthenticity::Signed(key.clone());
+                let mut gossipsub = if config.telemetry.gossipsub_metrics {
+                    gossipsub::Behaviour::new_with_metrics(
+                        authenticity,
+                        gossipsub_config,
+                        registry.sub_registry_with_prefix("gossipsub"),
+                        gossipsub::MetricsConfig::default(),
+                    )?
+                } else {
+                    gossipsub::Behaviour::new(authenticity, gossipsub_config)?
+                };
+
+                // Score peers so that the mesh can shed lazy or malicious participants.
+                let scoring = &config.gossipsub.scoring;
+                if scoring.enabled {
+                    let topics: Vec<_> = config
+                        .scored_topics()
+                        .into_iter()
+                        .map(|(topic, params)| (gossipsub::IdentTopic::new(topic).hash(), params))
+                        .collect();
+                    gossipsub
+                        .with_peer_score(scoring.params(&topics), scoring.thresholds())
+                        .map_err(io::Error::other)?;
+                }
+
+                // Only discover peers on the same network and release.
+                #[cfg(feature = "mdns")]
+                let mdns = {
+                    let mdns_string = protocol_version
+                        .trim_start_matches('/')
+                        .replace(['/', '.'], "_");
+                    let mdns_config = mdns::Config::default().set_name(&mdns_string)?;
+                    config
+                        .mdns()
+                        .then(|| {
+                            mdns::tokio::Behaviour::new(mdns_config, key.public().to_peer_id())
+                        })
+                        .transpose()?
+                };
+                #[cfg(not(feature = "mdns"))]
+                let mdns: Option<Mdns> = match config.mdns() {
+                    true => return Err(compiled_out("mDNS discovery", "mdns").into()),
+                    false => None,
+                };
+
+                // Prepare a means to identify this client, by name if it has one.
+                let agent_version = BUILD_INFO.agent_version(config.node_name.as_deref());
+                let identify = identify::Behaviour::new(
+                    identify::Config::new(protocol_version.clone(), key.public())
+                        .with_agent_version(agent_version),
+                );
+
+                // Prepare a protocol for messaging a single peer off the gossip mesh.
+                let direct = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(DIRECT_PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+
+                // Prepare a protocol for transferring blobs too large to gossip.
+                let blob = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(BLOB_PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+
+                // Prepare a DHT for advertising the providers of content-addressed blocks. Nodes
+                // serve the DHT unless configured otherwise, as peers found over mDNS never
+                // confirm external addresses.
+                #[cfg(any(feature = "dcutr", feature = "kademlia", feature = "relay-server"))]
+                let peer_id = key.public().to_peer_id();
+                #[cfg(feature = "kademlia")]
+                let kad = {
+                    // The routing table is refreshed by the node's scheduler instead.
+                    let mut kad_config = kad::Config::new(StreamProtocol::new(KAD_PROTOCOL));
+                    kad_config.set_periodic_bootstrap_interval(None);
+                    let mut kad =
+                        kad::Behaviour::with_config(peer_id, MemoryStore::new(peer_id), kad_config);
+                    kad.set_mode(match config.kad_mode() {
+                        KadMode::Client => Some(kad::Mode::Client),
+                        KadMode::Server => Some(kad::Mode::Server),
+                        KadMode::Auto => None,
+                    });
+                    kad
+                };
+                #[cfg(not(feature = "kademlia"))]
+                let kad = dummy::Behaviour;
+
+                // Relay connections for peers behind NATs, if this node is a relay.
+                #[cfg(feature = "relay-server")]
+                let relay = config
+                    .relay()
+                    .then(|| relay::Behaviour::new(peer_id, relay::Config::default()));
+                #[cfg(not(feature = "relay-server"))]
+                let relay: Option<RelayServer> = match config.relay() {
+                    true => return Err(compiled_out("relaying", "relay-server").into()),
+                    false => None,
+                };
+
+                // Upgrade relayed connections to direct ones by hole punching through NATs.
+                #[cfg(feature = "dcutr")]
+                let dcutr = dcutr::Behaviour::new(peer_id);
+                #[cfg(not(feature = "dcutr"))]
+                let dcutr = dummy::Behaviour;
+
+                // Prepare a protocol for fetching blocks from their providers.
+                #[cfg(feature = "kademlia")]
+                let block = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(BLOCK_PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+                #[cfg(not(feature = "kademlia"))]
+                let block = dummy::Behaviour;
+
+                // Prepare a protocol for serving and downloading state snapshots.
+                let sync = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(SYNC_PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+
+                // Prepare a protocol for asking peers the time, to measure their clocks' skew.
+                let time = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(TIME_PROTOCOL), ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+
+                Ok(MyBehaviour {
+                    gossipsub,
+                    mdns: mdns.into(),
+                    identify,
+                    direct,
+                    blob,
+                    kad,
+                    block,
+                    sync,
+                    time,
+                    blocked: allow_block_list::Behaviour::default(),
+                    relay_client,
+                    relay: relay.into(),
+                    dcutr,
+                    limits: connection_limits::Behaviour::new(config.peer_limits()),
+                    keep_alive: keep_alive::Behaviour::new(config.keep_alive.clone()),
+                    dial_limits: dial_limits::Behaviour::new(config.dial_limits.clone()),
+                })
+            })?
+            // Connections of classes with longer timeouts are held open by `keep_alive`.
+            .with_swarm_config(|c| c.with_idle_connection_timeout(config.keep_alive.shortest()))
+            .build();
+
+        // Subscribe to the application topics.
+        for topic in &config.topics.application {
+            swarm
+                .behaviour_mut()
+                .gossipsub
+                .subscribe(&gossipsub::IdentTopic::new(topic))?;
+        }
+
+        // Every node listens for acknowledgements of the reliable messages it publishes.
+        let ack_topic = gossipsub::IdentTopic::new(&config.topics.ack);
+        swarm.behaviour_mut().gossipsub.subscribe(&ack_topic)?;
+
+        // Every node pools the transactions gossiped to it.
+        let mempool_topic = gossipsub::IdentTopic::new(&config.topics.mempool);
+        swarm.behaviour_mut().gossipsub.subscribe(&mempool_topic)?;
+        let batch_topic = gossipsub::IdentTopic::new(&config.topics.batch);
+        swarm.behaviour_mut().gossipsub.subscribe(&batch_topic)?;
+        let consensus_topic = gossipsub::IdentTopic::new(&config.topics.consensus);
+        swarm
+            .behaviour_mut()
+            .gossipsub
+            .subscribe(&consensus_topic)?;
+
+        // Listen on all interfaces, on the configured ports or whatever ports the OS assigns.
+        for address in listen_addresses {
+            swarm.listen_on(address)?;
+        }
+
+        // Join the network through the configured peers. Those over the dial limits are dialed
+        // as the limits allow.
+        let mut deferred_dials = VecDeque::new();
+        for peer in &config.network.peers {
+            let peer_id = peer.peer_id();
+            match peer_id {
+                Some(peer_id) => {
+                    for address in &peer.addresses {
+                        swarm.behaviour_mut().add_address(&peer_id, address.clone());
+                    }
+                }
+                None if peer.addresses.len() != 1 => {
+                    return Err("a peer without an id must be listed by exactly one address".into())
+                }
+                None => {}
+            }
+            let dial = DeferredDial {
+                peer_id,
+                addresses: peer.addresses.clone(),
+            };
+            if let Err(e) = dial_or_defer(&mut swarm, dial, &mut deferred_dials) {
+                tracing::warn!(?peer_id, error = %e, "Failed to dial configured peer");
+            }
+        }
+
+        // Pick up where the last run left off: rejoin its topics and its peers, and take the
+        // external addresses its peers confirmed as still ours.
+        let snapshot_path = config.snapshot_path();
+        let snapshot = snapshot_path
+            .as_deref()
+            .map(NetworkSnapshot::load)
+            .transpose()
+            .unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "Ignoring network snapshot");
+                None
+            })
+            .flatten()
+            .unwrap_or_default();
+        for topic in &snapshot.topics {
+            let topic = gossipsub::IdentTopic::new(topic);
+            if let Err(e) = swarm.behaviour_mut().gossipsub.subscribe(&topic) {
+                tracing::error!(%topic, error = ?e, "Failed to resubscribe to topic");
+            }
+        }
+        for address in &snapshot.external_addresses {
+            swarm.add_external_address(address.clone());
+        }
+        for peer in &snapshot.peers {
+            for address in &peer.addresses {
+                swarm
+                    .behaviour_mut()
+                    .add_address(&peer.peer_id, address.clone());
+            }
+            let dial = DeferredDial {
+                peer_id: Some(peer.peer_id),
+                addresses: peer.addresses.clone(),
+            };
+            if let Err(e) = dial_or_defer(&mut swarm, dial, &mut deferred_dials) {
+                tracing::warn!(
+                    peer_id = %peer.peer_id,
+                    error = %e,
+                    "Failed to dial remembered peer"
+                );
+            }
+        }
+
+        // Reserve a slot on each configured or remembered relay by listening through it.
+        let mut relays = Relays::default();
+        let remembered_relays = snapshot
+            .relays
+            .iter()
+            .filter(|address| !config.relay.reservations.contains(address));
+        for address in config.relay.reservations.iter().chain(remembered_relays) {
+            let Some(Protocol::P2p(peer_id)) = address.iter().last() else {
+                return Err(format!("relay address {address} does not end in a peer id").into());
+            };
+            let listener = swarm.listen_on(address.clone().with(Protocol::P2pCircuit))?;
+            relays.request(peer_id, address.clone(), listener);
+        }
+
+        // Explicitly dial a remote peer.
+        // let remote_peer: Multiaddr = "/ip4/95.217.163.246/udp/3888/quic-v1".parse()?;
+        // let dial_result = swarm.dial(remote_peer);
+        // println!("dial result {:?}", dial_result);
+
+        // Schedule the node's recurring jobs. The first snapshot is only written once the node has
+        // had time to reconnect.
+        let mut scheduler = Scheduler::default();
+        scheduler.every(Job::RetryDeliveries, delivery::RETRY_INTERVAL);
+        if snapshot_path.is_some() {
+            scheduler.every(Job::SaveSnapshot, config.snapshot.interval());
+        }
+        #[cfg(feature = "kademlia")]
+        if let Some(interval) = config.schedule.kad_bootstrap_interval() {
+            scheduler.every(Job::KadBootstrap, interval);
+        }
+        if let Some(interval) = config.schedule.relay_check_interval() {
+            scheduler.every(Job::CheckRelays, interval);
+        }
+        if let Some(interval) = config.gossipsub.bundle.flush_interval() {
+            scheduler.every(Job::FlushBundles, interval);
+        }
+        scheduler.every(Job::DialDeferred, DEFERRED_DIAL_INTERVAL);
+        scheduler.every(Job::ExpireBlobs, blob::EXPIRY_INTERVAL);
+        scheduler.every(Job::KeepAlive, KEEP_ALIVE_INTERVAL);
+        if let Some(interval) = config.chaos.interval() {
+            if cfg!(feature = "chaos") {
+                tracing::warn!(?interval, "Chaos is enabled");
+                scheduler.every(Job::Chaos, interval);
+            } else {
+                tracing::warn!("Chaos is configured, but this build cannot cause it");
+            }
+        }
+
+        let validator = Arc::new(EnvelopeValidator::new(
+            ack_topic.hash(),
+            batch_topic.hash(),
+            config.batch.limits(),
+        ));
+        let (validation, validated_receiver) = ValidationPipeline::new(
+            validator,
+            config.gossipsub.validation_concurrency,
+            config.gossipsub.validation_queue_size,
+            Duration::from_millis(config.gossipsub.validation_timeout_millis),
+        );
+
+        let (control_sender, control_receiver) = mpsc::channel(COMMAND_CHANNEL_SIZE);
+        let (consensus_sender, consensus_receiver) = mpsc::channel(COMMAND_CHANNEL_SIZE);
+        let (bulk_sender, bulk_receiver) = mpsc::channel(COMMAND_CHANNEL_SIZE);
+        let (events, _) = broadcast::channel::<NodeEvent>(EVENT_CHANNEL_SIZE);
+        let channels = ChannelMetrics::new(registry);
+        let node = P2pNode {
+            swarm,
+            control_receiver,
+            consensus_receiver,
+            bulk_receiver,
+            ack_topic,
+            mempool_topic,
+            batch_topic,
+            batch_limits: config.batch.limits(),
+            consensus_topic,
+            consensus: OrderedDelivery::default(),
+            consensus_handler: None,
+            consensus_session: envelope::unix_millis(),
+            consensus_sequence: 0,
+            deliveries: PendingDeliveries::default(),
+            received: ReceivedDeliveries::default(),
+            bundler: Bundler::new(&config.gossipsub.bundle),
+            stats: GossipStats::new(registry),
+            session: SessionStats::default(),
+            validation,
+            validated_receiver,
+            journal: MessageJournal::new(config.gossipsub.journal_capacity),
+            replay: ReplayCache::new(Duration::from_secs(config.gossipsub.replay_window_secs)),
+            pending_direct: HashMap::new(),
+            pending_dials: HashMap::new(),
+            deferred_dials,
+            connections: HashMap::new(),
+            holepunched: HashSet::new(),
+            relays,
+            blobs: BlobTransfers::new(&config.blob, events.clone()),
+            #[cfg(feature = "kademlia")]
+            blocks: BlockExchange::default(),
+            #[cfg(feature = "kademlia")]
+            dht: DhtQueries::default(),
+            bootstrap: if cfg!(feature = "kademlia") {
+                BootstrapStatus::Pending
+            } else {
+                BootstrapStatus::Disabled
+            },
+            sync: StateSync::new(
+                Arc::new(MemoryState::default()),
+                config.sync.chunk_entries,
+                events.clone(),
+            ),
+            sync_on_join: config.sync.on_join,
+            mempool: Mempool::new(config.mempool.capacity),
+            events: events.clone(),
+            webhook: Webhook::spawn(&config.webhook, channels.clone()),
+            channels,
+            peers: PeerInfoCache::default(),
+            snapshot_path,
+            snapshot_max_peers: config.snapshot.max_peers,
+            clock: ClockSkew::new(&config.clock, events.clone()),
+            clock_penalty: config.clock.penalty,
+            node_name: config.node_name.clone(),
+            protocol_version,
+            scheduler,
+            chaos: config.chaos.clone(),
+            stall: None,
+            turn: Turn::default(),
+            started: Instant::now(),
+            shutdown: None,
+        };
+        let client = SwarmClient::new(
+            control_sender,
+            consensus_sender,
+            bulk_sender,
+            events,
+            faults,
+            node.relays.snapshot(),
+            node.channels.clone(),
+        );
+        Ok((node, client))
+    }
+
+    /// Replace the validator that received messages must pass before they are accepted.
+    pub fn set_validator(&mut self, validator: Arc<dyn MessageValidator>) {
+        self.validation.set_validator(validator);
+    }
+
+    /// Replace the validator that transactions must pass to enter the mempool.
+    pub fn set_transaction_validator(&mut self, validator: Arc<dyn TransactionValidator>) {
+        self.mempool.set_validator(validator);
+    }
+
+    /// Set the consensus engine that receives consensus messages from other nodes.
+    pub fn set_consensus_handler(&mut self, handler: Arc<dyn ConsensusHandler>) {
+        self.consensus_handler = Some(handler);
+    }
+
+    /// Replace the application state that is served to, and synchronized from, other nodes.
+    pub fn set_state(&mut self, state: Arc<dyn SyncState>) {
+        self.sync.set_state(state);
+    }
+
+    /// Drive the swarm, servicing client commands until a client shuts the node down.
+    pub async fn run(mut self) {
+        while self.shutdown.is_none() {
+            // Branches are polled in order, so queued commands are serviced strictly by priority,
+            // and each takes only its budget in a turn, so that busy gossip cannot starve them.
+            let [control, validation, consensus, swarm, bulk, jobs] = [
+                Source::Control,
+                Source::Validation,
+                Source::Consensus,
+                Source::Swarm,
+                Source::Bulk,
+                Source::Jobs,
+            ]
+            .map(|source| self.turn.allows(source));
+            select! {
+                biased;
+                Some(command) = self.control_receiver.recv(), if control => {
+                    self.turn.take(Source::Control);
+                    self.handle_command(command);
+                }
+                Some(validated) = self.validated_receiver.recv(), if validation => {
+                    self.turn.take(Source::Validation);
+                    self.handle_validated(validated);
+                }
+                Some(command) = self.consensus_receiver.recv(), if consensus => {
+                    self.turn.take(Source::Consensus);
+                    self.handle_command(command);
+                }
+                event = self.swarm.select_next_some(), if swarm => {
+                    self.turn.take(Source::Swarm);
+                    self.handle_event(event);
+                }
+                Some(command) = self.bulk_receiver.recv(), if bulk => {
+                    self.turn.take(Source::Bulk);
+                    self.handle_command(command);
+                }
+                job = self.scheduler.next(), if jobs => {
+                    self.turn.take(Source::Jobs);
+                    self.run_job(job);
+                }
+                // Only reached when no source with budget left has anything ready.
+                _ = future::ready(()), if self.turn.is_limited() => self.turn.end(),
+            }
+            self.record_channel_depths();
+            if let Some(stall) = self.stall.take() {
+                tokio::time::sleep(stall).await;
+            }
+        }
+        self.shut_down().await;
+    }
+
+    /// Record how much is queued on each channel the event loop drains, now that it has taken
+    /// its next item.
+    fn record_channel_depths(&self) {
+        let depths = [
+            (Channel::Control, self.control_receiver.len()),
+            (Channel::Consensus, self.consensus_receiver.len()),
+            (Channel::Bulk, self.bulk_receiver.len()),
+            (Channel::Validation, self.validation.in_flight()),
+            (Channel::Events, self.events.len()),
+            (
+                Channel::Webhook,
+                self.webhook.as_ref().map_or(0, Webhook::queued),
+            ),
+        ];
+        for (channel, depth) in depths {
+            self.channels.set_depth(channel, depth);
+        }
+    }
+
+    /// Disconnect from every peer, giving connections a moment to close cleanly, and then answer
+    /// whoever asked for the shutdown.
+    async fn shut_down(&mut self) {
+        tracing::info!("Shutting down node");
+        // Messages still waiting for their bundles go out before the connections close.
+        let bundles = self.bundler.flush();
+        self.publish_bundles(bundles);
+        self.save_snapshot();
+        let peers: Vec<_> = self.swarm.connected_peers().copied().collect();
+        for peer_id in peers {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        let closed = tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+            while self.swarm.network_info().num_peers() > 0 {
+                let event = self.swarm.select_next_some().await;
+                self.handle_event(event);
+            }
+        });
+        if closed.await.is_err() {
+            tracing::warn!("Timed out waiting for connections to close");
+        }
+        if let Some(sender) = self.shutdown.take() {
+            let _ = sender.send(());
+        }
+    }
+
+    fn handle_command(&mut self, command: SwarmCommand) {
+        match command {
+            SwarmCommand::GossipsubPublish {
+                topic,
+                data,
+                sender,
+            } => {
+                let topic = gossipsub::IdentTopic::new(topic);
+                if self.bundler.bundles(&topic.hash()) {
+                    let publisher = Publisher::Message(sender);
+                    let bundles = self
+                        .bundler
+                        .push(topic, Envelope::new(data), Some(publisher));
+                    self.publish_bundles(bundles);
+                } else {
+                    let result = self.publish(topic, &Envelope::new(data));
+                    let _ = sender.send(result);
+                }
+            }
+            SwarmCommand::PublishReliable {
+                topic,
+                data,
+                recipients,
+                sender,
+            } => {
+                let topic = gossipsub::IdentTopic::new(topic);
+                if let Some((topic, envelope)) =
+                    self.deliveries
+                        .insert(topic, data, recipients, sender, Instant::now())
+                {
+                    // A failed first attempt, such as before the mesh forms, is simply retried.
+                    if let Err(e) = self.publish(topic, &envelope) {
+                        tracing::warn!(error = ?e, "Reliable publish failed");
+                    }
+                }
+            }
+            SwarmCommand::RecentMessages {
+                topic,
+                limit,
+                sender,
+            } => {
+                let topic = gossipsub::IdentTopic::new(topic).hash();
+                let _ = sender.send(self.journal.recent(&topic, limit));
+            }
+            SwarmCommand::TopicStats { sender } => {
+                let _ = sender.send(self.stats.snapshot(Instant::now()));
+            }
+            SwarmCommand::ConnectedPeers { sender } => {
+                let _ = sender.send(self.swarm.connected_peers().copied().collect());
+            }
+            SwarmCommand::Peers { sender } => {
+                let mut peers = self.peers.list(self.swarm.connected_peers().copied());
+                for peer in &mut peers {
+                    peer.clock_skew_millis = self.clock.skew(&peer.peer_id);
+                }
+                let _ = sender.send(peers);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::RoutingTablePeers { sender } => {
+                let peers = self
+                    .swarm
+                    .behaviour_mut()
+                    .kad
+                    .kbuckets()
+                    .flat_map(|bucket| {
+                        bucket
+                            .iter()
+                            .map(|entry| {
+                                let addresses = entry.node.value.iter().cloned().collect();
+                                (*entry.node.key.preimage(), addresses)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                let _ = sender.send(peers);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::RoutingTable { sender } => {
+                let mut entries = Vec::new();
+                for bucket in self.swarm.behaviour_mut().kad.kbuckets() {
+                    let index = bucket.range().0.ilog2().unwrap_or(0);
+                    entries.extend(bucket.iter().map(|entry| RoutingTableEntry {
+                        peer_id: *entry.node.key.preimage(),
+                        addresses: entry.node.value.iter().cloned().collect(),
+                        bucket: index,
+                    }));
+                }
+                let _ = sender.send(entries);
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::RoutingTablePeers { sender } => {
+                let _ = sender.send(Vec::new());
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::RoutingTable { sender } => {
+                let _ = sender.send(Vec::new());
+            }
+            SwarmCommand::SendDirect {
+                peer_id,
+                message,
+                sender,
+            } => {
+                let request_id = self
+                    .swarm
+                    .behaviour_mut()
+                    .direct
+                    .send_request(&peer_id, message);
+                self.pending_direct.insert(request_id, sender);
+            }
+            SwarmCommand::OfferBlob {
+                peer_id,
+                name,
+                data,
+                sender,
+            } => {
+                let behaviour = &mut self.swarm.behaviour_mut().blob;
+                self.blobs.offer(behaviour, peer_id, name, data, sender);
+            }
+            SwarmCommand::FetchBlob {
+                peer_id,
+                id,
+                sender,
+            } => {
+                let behaviour = &mut self.swarm.behaviour_mut().blob;
+                self.blobs.fetch(behaviour, peer_id, id, sender);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::PutBlock { data, sender } => {
+                let result = self.blocks.put(&mut self.swarm.behaviour_mut().kad, data);
+                let _ = sender.send(result);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::GetBlock { hash, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().kad;
+                self.blocks.get(behaviour, hash, sender);
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::PutBlock { sender, .. } => {
+                let _ = sender.send(Err(BlockError::Disabled));
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::GetBlock { sender, .. } => {
+                let _ = sender.send(Err(BlockError::Disabled));
+            }
+            SwarmCommand::PublishBatch { batch, sender } => {
+                let _ = sender.send(self.publish_batch(&batch));
+            }
+            SwarmCommand::PublishConsensus { message, sender } => {
+                let _ = sender.send(self.publish_consensus(message));
+            }
+            SwarmCommand::SubmitTransaction {
+                transaction,
+                sender,
+            } => self.submit_transaction(transaction, sender),
+            SwarmCommand::MempoolContent { limit, sender } => {
+                let _ = sender.send(self.mempool.pending(limit));
+            }
+            SwarmCommand::MempoolStatus { sender } => {
+                let _ = sender.send(self.mempool.status());
+            }
+            SwarmCommand::SyncState { peer_id, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().sync;
+                self.sync.start(behaviour, peer_id, Some(sender));
+            }
+            SwarmCommand::AddPeer { address, sender } => {
+                if let Some(Protocol::P2p(peer_id)) = address.iter().last() {
+                    self.swarm
+                        .behaviour_mut()
+                        .add_address(&peer_id, address.clone());
+                }
+                let _ = sender.send(self.swarm.dial(address));
+            }
+            SwarmCommand::RemovePeer { peer_id, sender } => {
+                let known = self.swarm.behaviour_mut().remove_peer(&peer_id);
+                let connected = self.swarm.disconnect_peer_id(peer_id).is_ok();
+                let _ = sender.send(known || connected);
+            }
+            SwarmCommand::BanPeer { peer_id, sender } => {
+                // Blocking a peer also closes its connections.
+                let behaviour = self.swarm.behaviour_mut();
+                behaviour.blocked.block_peer(peer_id);
+                behaviour.remove_peer(&peer_id);
+                let _ = sender.send(());
+            }
+            SwarmCommand::DisconnectPeer { peer_id, sender } => {
+                let _ = sender.send(self.swarm.disconnect_peer_id(peer_id).is_ok());
+            }
+            SwarmCommand::SetPeerLimits { limits, sender } => {
+                *self.swarm.behaviour_mut().limits.limits_mut() = limits;
+                let _ = sender.send(());
+            }
+            SwarmCommand::SetTopicScoring { topics, sender } => {
+                let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+                for (topic, params) in topics {
+                    if let Err(e) =
+                        gossipsub.set_topic_params(gossipsub::IdentTopic::new(&topic), params)
+                    {
+                        tracing::warn!(
+                            %topic,
+                            error = %e,
+                            "Failed to set score parameters of topic"
+                        );
+                    }
+                }
+                let _ = sender.send(());
+            }
+            SwarmCommand::Shutdown { sender } => {
+                self.shutdown = Some(sender);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::KadPut { key, value, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().kad;
+                self.dht.put(behaviour, key, value, sender);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::KadGet { key, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().kad;
+                self.dht.get(behaviour, key, sender);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::KadProvide { key, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().kad;
+                self.dht.provide(behaviour, key, sender);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmCommand::KadProviders { key, sender } => {
+                let behaviour = &mut self.swarm.behaviour_mut().kad;
+                self.dht.providers(behaviour, key, sender);
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::KadPut { sender, .. } | SwarmCommand::KadProvide { sender, .. } => {
+                let _ = sender.send(Err(DhtError::Disabled));
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::KadGet { sender, .. } => {
+                let _ = sender.send(Err(DhtError::Disabled));
+            }
+            #[cfg(not(feature = "kademlia"))]
+            SwarmCommand::KadProviders { sender, .. } => {
+                let _ = sender.send(Err(DhtError::Disabled));
+            }
+            SwarmCommand::Dial { address, sender } => {
+                self.dial(DialOpts::unknown_peer_id().address(address).build(), sender);
+            }
+            SwarmCommand::DialPeer { peer_id, sender } => {
+                self.dial(DialOpts::peer_id(peer_id).build(), sender);
+            }
+            SwarmCommand::SubscribeTopic { topic, sender } => {
+                let topic = gossipsub::IdentTopic::new(topic);
+                let result = self
+                    .swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .subscribe(&topic)
+                    .map_err(ClientError::Subscription);
+                let _ = sender.send(result);
+            }
+            SwarmCommand::UnsubscribeTopic { topic, sender } => {
+                let result = if self.is_reserved_topic(&topic) {
+                    Err(ClientError::ReservedTopic(topic))
+                } else {
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .unsubscribe(&gossipsub::IdentTopic::new(topic))
+                        .map_err(ClientError::Publish)
+                };
+                let _ = sender.send(result);
+            }
+            SwarmCommand::ListTopics { sender } => {
+                let topics = self.swarm.behaviour().gossipsub.topics();
+                let _ = sender.send(topics.map(ToString::to_string).collect());
+            }
+            SwarmCommand::NodeInfo { sender } => {
+                let _ = sender.send(self.info());
+            }
+            SwarmCommand::SessionStats { sender } => {
+                let _ = sender.send(SessionStats {
+                    uptime_secs: self.started.elapsed().as_secs(),
+                    ..self.session.clone()
+                });
+            }
+            SwarmCommand::NodeStatus { sender } => {
+                let _ = sender.send(self.status());
+            }
+            SwarmCommand::NodeState { sender } => {
+                let gossipsub = &self.swarm.behaviour().gossipsub;
+                let mesh = gossipsub
+                    .topics()
+                    .map(|topic| {
+                        (
+                            topic.to_string(),
+                            gossipsub.mesh_peers(topic).copied().collect(),
+                        )
+                    })
+                    .collect();
+                let _ = sender.send(NodeState {
+                    info: self.info(),
+                    connections: self.connections.values().cloned().collect(),
+                    relays: self.relays.list().to_vec(),
+                    mesh,
+                    pending_dials: self.pending_dials.len(),
+                    pending_dht_queries: self.pending_dht_queries(),
+                    pending_deliveries: self.deliveries.len(),
+                    mempool: self.mempool.status(),
+                });
+            }
+        }
+    }
+
+    /// How many DHT queries issued by clients are awaiting their outcome.
+    fn pending_dht_queries(&self) -> usize {
+        #[cfg(feature = "kademlia")]
+        {
+            self.dht.len()
+        }
+        #[cfg(not(feature = "kademlia"))]
+        {
+            0
+        }
+    }
+
+    fn status(&self) -> NodeStatus {
+        let relayed = |address: &Multiaddr| address.iter().any(|p| p == Protocol::P2pCircuit);
+        let relays = self.relays.reservations();
+        let nat = if self.swarm.external_addresses().any(|a| !relayed(a)) {
+            NatStatus::Public
+        } else if relays > 0 {
+            NatStatus::Private
+        } else {
+            NatStatus::Unknown
+        };
+        NodeStatus {
+            connected_peers: self.swarm.connected_peers().count(),
+            mesh_peers: self.swarm.behaviour().gossipsub.all_mesh_peers().count(),
+            relays,
+            external_addresses: self.swarm.external_addresses().cloned().collect(),
+            bootstrap: self.bootstrap,
+            nat,
+        }
+    }
+
+    fn info(&self) -> NodeInfo {
+        NodeInfo {
+            peer_id: *self.swarm.local_peer_id(),
+            node_name: self.node_name.clone(),
+            agent_version: BUILD_INFO.agent_version(self.node_name.as_deref()),
+            protocol_version: self.protocol_version.clone(),
+            listen_addresses: self.swarm.listeners().cloned().collect(),
+            external_addresses: self.swarm.external_addresses().cloned().collect(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            build: BUILD_INFO,
+        }
+    }
+
+    /// Dial a peer, answering `sender` once the connection is established or fails.
+    fn dial(&mut self, opts: DialOpts, sender: oneshot::Sender<Result<PeerId, DialError>>) {
+        let connection_id = opts.connection_id();
+        match self.swarm.dial(opts) {
+            Ok(()) => {
+                self.pending_dials.insert(connection_id, sender);
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+            }
+        }
+    }
+
+    /// Write the node's view of the network to its snapshot, if it keeps one. A node without
+    /// peers keeps the last snapshot it wrote, which remembers where to find some.
+    fn save_snapshot(&self) {
+        let Some(path) = &self.snapshot_path else {
+            return;
+        };
+        if self.swarm.network_info().num_peers() == 0 {
+            return;
+        }
+        if let Err(e) = self.snapshot().save(path) {
+            tracing::error!(error = %e, "Failed to save network snapshot");
+        }
+    }
+
+    /// The node's view of the network, with its connected peers ordered by their gossipsub
+    /// score and dialable at the addresses they listen on or that we reached them at.
+    fn snapshot(&self) -> NetworkSnapshot {
+        let gossipsub = &self.swarm.behaviour().gossipsub;
+        let mut peers: Vec<SnapshotPeer> = self
+            .swarm
+            .connected_peers()
+            .filter_map(|peer_id| {
+                let mut addresses = self.peers.get(peer_id)?.listen_addresses.clone();
+                for connection in self.connections.values() {
+                    if connection.peer_id == *peer_id
+                        && connection.outbound
+                        && !addresses.contains(&connection.address)
+                    {
+                        addresses.push(connection.address.clone());
+                    }
+                }
+                Some(SnapshotPeer {
+                    peer_id: *peer_id,
+                    addresses,
+                })
+            })
+            .collect();
+        let score = |peer: &SnapshotPeer| gossipsub.peer_score(&peer.peer_id).unwrap_or(0.0);
+        peers.sort_by(|a, b| score(b).total_cmp(&score(a)));
+        peers.truncate(self.snapshot_max_peers);
+        NetworkSnapshot {
+            peers,
+            relays: self
+                .relays
+                .list()
+                .into_iter()
+                .filter(|relay| relay.status == ReservationStatus::Accepted)
+                .map(|relay| relay.address)
+                .collect(),
+            external_addresses: self.swarm.external_addresses().cloned().collect(),
+            topics: gossipsub.topics().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Whether `topic` carries the node's own traffic, such as acknowledgements, and so must stay
+    /// subscribed.
+    fn is_reserved_topic(&self, topic: &str) -> bool {
+        let hash = gossipsub::IdentTopic::new(topic).hash();
+        [
+            &self.ack_topic,
+            &self.mempool_topic,
+            &self.batch_topic,
+            &self.consensus_topic,
+        ]
+        .iter()
+        .any(|reserved| reserved.hash() == hash)
+    }
+
+    fn publish(
+        &mut self,
+        topic: gossipsub::IdentTopic,
+        envelope: &Envelope,
+    ) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
+        let result = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(topic, envelope.encode());
+        if result.is_ok() {
+            self.session.messages_published += 1;
+        }
+        result
+    }
+
+    fn publish_consensus(
+        &mut self,
+        message: ConsensusMessage,
+    ) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
+        let sequenced = SequencedMessage {
+            session: self.consensus_session,
+            sequence: self.consensus_sequence,
+            message,
+        };
+        let result = self.publish(
+            self.consensus_topic.clone(),
+            &Envelope::new(sequenced.encode()),
+        );
+        // Only numbers that were actually published are consumed, so receivers see no gaps.
+        if result.is_ok() {
+            self.consensus_sequence += 1;
+        }
+        result
+    }
+
+    fn publish_batch(
+        &mut self,
+        batch: &Batch,
+    ) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
+        let data = batch.encode();
+        if data.len() > self.batch_limits.max_size {
+            return Err(gossipsub::PublishError::MessageTooLarge);
+        }
+        let result = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(self.batch_topic.clone(), data);
+        if result.is_ok() {
+            self.session.messages_published += 1;
+        }
+        result
+    }
+
+    /// Pool a transaction submitted locally and gossip it. It stays pooled even if it cannot be
+    /// gossiped yet. On a bundled mempool topic, the submitter is answered once its bundle is
+    /// published.
+    fn submit_transaction(
+        &mut self,
+        transaction: Transaction,
+        sender: oneshot::Sender<Result<TxHash, MempoolError>>,
+    ) {
+        let data = transaction.encode();
+        let hash = match self.mempool.insert(transaction) {
+            Ok(hash) => hash,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        if self.bundler.bundles(&self.mempool_topic.hash()) {
+            let publisher = Publisher::Transaction(hash, sender);
+            let bundles = self.bundler.push(
+                self.mempool_topic.clone(),
+                Envelope::new(data),
+                Some(publisher),
+            );
+            self.publish_bundles(bundles);
+            return;
+        }
+        let result = self
+            .publish(self.mempool_topic.clone(), &Envelope::new(data))
+            .map(|_| hash)
+            .map_err(MempoolError::Publish);
+        let _ = sender.send(result);
+    }
+
+    /// Publish bundles of small messages, answering each of their publishers with the id of the
+    /// gossip message that carried theirs.
+    fn publish_bundles(&mut self, bundles: Vec<ReadyBundle>) {
+        for ReadyBundle {
+            topic,
+            bundle,
+            publishers,
+        } in bundles
+        {
+            let result = self
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(topic.clone(), bundle.encode());
+            match &result {
+                Ok(_) => self.session.messages_published += bundle.envelopes.len() as u64,
+                Err(e) => tracing::warn!(
+                    %topic,
+                    messages = bundle.envelopes.len(),
+                    error = ?e,
+                    "Failed to publish a bundle"
+                ),
+            }
+            for publisher in publishers {
+                publisher.answer(&result);
+            }
+        }
+    }
+
+    fn run_job(&mut self, job: Job) {
+        match job {
+            Job::RetryDeliveries => self.retry_deliveries(),
+            Job::SaveSnapshot => self.save_snapshot(),
+            Job::KadBootstrap => self.bootstrap_kad(),
+            Job::CheckRelays => self.check_relays(),
+            Job::FlushBundles => {
+                let bundles = self.bundler.flush();
+                self.publish_bundles(bundles);
+            }
+            Job::DialDeferred => self.dial_deferred(),
+            Job::ExpireBlobs => self.blobs.expire(Instant::now()),
+            Job::KeepAlive => self.keep_connections_alive(),
+            Job::Chaos => self.strike(),
+        }
+    }
+
+    /// Disrupt the node as chaos decides, logging each disruption so that soak runs can line it
+    /// up with how the node recovered.
+    #[cfg(feature = "chaos")]
+    fn strike(&mut self) {
+        use rand::seq::SliceRandom;
+
+        let mut rng = rand::thread_rng();
+        let strike = self.chaos.strike(&mut rng);
+        if strike.disconnect {
+            let peers: Vec<_> = self.swarm.connected_peers().copied().collect();
+            if let Some(peer_id) = peers.choose(&mut rng) {
+                tracing::warn!(%peer_id, "Chaos: disconnecting from peer");
+                let _ = self.swarm.disconnect_peer_id(*peer_id);
+            }
+        }
+        if let Some(stall) = strike.stall {
+            tracing::warn!(?stall, "Chaos: stalling the event loop");
+            self.stall = Some(stall);
+        }
+        match strike.restart {
+            Some(Restart::Gossipsub) => {
+                tracing::warn!("Chaos: leaving and rejoining every topic");
+                let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+                let topics: Vec<_> = gossipsub
+                    .topics()
+                    .map(|hash| gossipsub::IdentTopic::new(hash.as_str()))
+                    .collect();
+                for topic in &topics {
+                    let _ = gossipsub.unsubscribe(topic);
+                    let _ = gossipsub.subscribe(topic);
+                }
+            }
+            #[cfg(feature = "kademlia")]
+            Some(Restart::Kademlia) => {
+                tracing::warn!("Chaos: emptying the DHT's routing table");
+                let behaviour = self.swarm.behaviour_mut();
+                for peer_id in &behaviour.routing_table_peers() {
+                    behaviour.remove_peer(peer_id);
+                }
+            }
+            #[cfg(not(feature = "kademlia"))]
+            Some(Restart::Kademlia) => {}
+            Some(Restart::Relays) => {
+                tracing::warn!("Chaos: closing every relay reservation");
+                for listener in self.relays.listeners() {
+                    self.swarm.remove_listener(listener);
+                }
+            }
+            None => {}
+        }
+    }
+
+    #[cfg(not(feature = "chaos"))]
+    fn strike(&mut self) {}
+
+    /// Refresh the DHT's routing table, which fails harmlessly while the node knows no peers.
+    fn bootstrap_kad(&mut self) {
+        #[cfg(feature = "kademlia")]
+        let _ = self.swarm.behaviour_mut().kad.bootstrap();
+    }
+
+    /// Request again each reservation that a relay has closed, such as when it restarted.
+    fn check_relays(&mut self) {
+        for (peer_id, address) in self.relays.closed_relays() {
+            let circuit = address.clone().with(Protocol::P2pCircuit);
+            match self.swarm.listen_on(circuit) {
+                Ok(listener) => {
+                    tracing::info!(relay = %peer_id, "Requesting a reservation on relay again");
+                    self.relays.request(peer_id, address, listener);
+                }
+                Err(e) => self.relays.failed(peer_id, e.to_string()),
+            }
+        }
+    }
+
+    /// Dial the deferred peers in turn, until the dial limits refuse one again.
+    fn dial_deferred(&mut self) {
+        while let Some(dial) = self.deferred_dials.pop_front() {
+            match self.swarm.dial(dial.opts()) {
+                Ok(()) | Err(DialError::DialPeerConditionFalse(_)) => {}
+                Err(e) if dial_limits::is_rate_limited(&e) => {
+                    self.deferred_dials.push_front(dial);
+                    break;
+                }
+                Err(e) => tracing::warn!(
+                    peer_id = ?dial.peer_id,
+                    error = %e,
+                    "Failed to dial deferred peer"
+                ),
+            }
+        }
+    }
+
+    /// Classify each connection by what its peer is to the node, and hold those of a class open
+    /// for its timeout from now. Connections of no class are left to lapse.
+    fn keep_connections_alive(&mut self) {
+        let behaviour = self.swarm.behaviour_mut();
+        let mesh: HashSet<PeerId> = behaviour.gossipsub.all_mesh_peers().copied().collect();
+        let dht = behaviour.routing_table_peers();
+        for (connection_id, connection) in &self.connections {
+            let peer_id = connection.peer_id;
+            let class = if self.relays.is_reserved(&peer_id) {
+                ConnectionClass::Relay
+            } else if self.holepunched.contains(connection_id) {
+                ConnectionClass::Holepunched
+            } else if mesh.contains(&peer_id) {
+                ConnectionClass::Mesh
+            } else if dht.contains(&peer_id) {
+                ConnectionClass::Dht
+            } else {
+                continue;
+            };
+            behaviour.keep_alive.keep(peer_id, *connection_id, class);
+        }
+    }
+
+    fn retry_deliveries(&mut self) {
+        for (topic, envelope) in self.deliveries.poll_retries(Instant::now()) {
+            if let Err(e) = self.publish(topic, &envelope) {
+                tracing::warn!(error = ?e, "Reliable publish retry failed");
+            }
+        }
+    }
+
+    fn handle_direct(&mut self, event: request_response::Event<DirectMessage, DirectAck>) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request_id,
+                        request,
+                        channel,
+                    },
+            } => {
+                match request.topic {
+                    Some(topic) => {
+                        tracing::debug!(
+                            %peer,
+                            %topic,
+                            payload = %String::from_utf8_lossy(&request.payload),
+                            "Got direct message"
+                        );
+                        self.record_message(
+                            gossipsub::IdentTopic::new(topic).hash(),
+                            JournalEntry {
+                                id: format!("direct-{request_id}"),
+                                source: Some(peer),
+                                received_at: envelope::unix_millis(),
+                                payload: request.payload.into(),
+                            },
+                        );
+                    }
+                    None => {
+                        // Only its size is logged, as the payload is for the application alone.
+                        tracing::debug!(
+                            %peer,
+                            bytes = request.payload.len(),
+                            "Got private message"
+                        );
+                        let _ = self.events.send(NodeEvent::PrivateMessage {
+                            peer,
+                            id: format!("direct-{request_id}"),
+                            received_at: envelope::unix_millis(),
+                            payload: request.payload.into(),
+                        });
+                    }
+                }
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .direct
+                    .send_response(channel, DirectAck)
+                    .is_err()
+                {
+                    tracing::warn!(%peer, "Failed to acknowledge private message");
+                }
+            }
+            request_response::Event::Message {
+                message: request_response::Message::Response { request_id, .. },
+                ..
+            } => {
+                if let Some(sender) = self.pending_direct.remove(&request_id) {
+                    let _ = sender.send(Ok(()));
+                }
+            }
+            request_response::Event::OutboundFailure {
+                request_id, error, ..
+            } => {
+                if let Some(sender) = self.pending_direct.remove(&request_id) {
+                    let _ = sender.send(Err(error));
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::warn!(%peer, %error, "Failed to receive private message");
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    fn handle_validated(&mut self, validated: Validated) {
+        let Validated {
+            id,
+            propagation_source,
+            message,
+            acceptance,
+        } = validated;
+        let acceptance = match acceptance {
+            gossipsub::MessageAcceptance::Accept => {
+                self.handle_message(propagation_source, id.clone(), message)
+            }
+            rejected => rejected,
+        };
+        self.report_validation(&id, &propagation_source, acceptance);
+    }
+
+    fn report_validation(
+        &mut self,
+        id: &gossipsub::MessageId,
+        propagation_source: &PeerId,
+        acceptance: gossipsub::MessageAcceptance,
+    ) {
+        if let Err(e) = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(id, propagation_source, acceptance)
+        {
+            tracing::warn!(%id, error = ?e, "Failed to report validation of message");
+        }
+    }
+
+    /// Process a message that passed validation, returning whether it should be propagated.
+    fn handle_message(
+        &mut self,
+        peer_id: PeerId,
+        id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) -> gossipsub::MessageAcceptance {
+        // Acknowledgements are only of interest to the publisher they are addressed to.
+        if message.topic == self.ack_topic.hash() {
+            match (Ack::decode(&message.data), message.source) {
+                (Ok(ack), Some(source)) if ack.publisher == *self.swarm.local_peer_id() => {
+                    self.deliveries.acknowledge(ack.id, source);
+                }
+                (Err(e), _) => {
+                    tracing::warn!(%peer_id, error = %e, "Malformed ack");
+                    return gossipsub::MessageAcceptance::Reject;
+                }
+                _ => {}
+            }
+            return gossipsub::MessageAcceptance::Accept;
+        }
+
+        // Batches are carried bare rather than in an envelope, as they are deduplicated by content.
+        if message.topic == self.batch_topic.hash() {
+            let batch = match Batch::decode(&message.data, self.batch_limits) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    tracing::warn!(%peer_id, error = %e, "Malformed batch");
+                    return gossipsub::MessageAcceptance::Reject;
+                }
+            };
+            tracing::debug!(
+                %peer_id,
+                number = batch.number,
+                transactions = batch.transactions.len(),
+                "Got batch"
+            );
+            let included: Vec<_> = batch.transactions.iter().map(Transaction::hash).collect();
+            self.mempool.remove(&included);
+            let _ = self.events.send(NodeEvent::BatchReceived {
+                peer: peer_id,
+                number: batch.number,
+                hash: batch.hash(),
+                transactions: batch.transactions.len(),
+            });
+            return gossipsub::MessageAcceptance::Accept;
+        }
+
+        let envelopes = match Bundle::decode_any(&message.data) {
+            Ok(envelopes) => envelopes,
+            Err(e) => {
+                tracing::warn!(%peer_id, error = %e, "Malformed message");
+                return gossipsub::MessageAcceptance::Reject;
+            }
+        };
+
+        // Each envelope of a bundle is handled as if it had arrived alone. The bundle propagates if
+        // any of them is accepted, unless one is invalid.
+        let bundled = envelopes.len() > 1;
+        let mut acceptance = gossipsub::MessageAcceptance::Ignore;
+        for (index, envelope) in envelopes.into_iter().enumerate() {
+            let id = match bundled {
+                true => format!("{id}-{index}"),
+                false => id.to_string(),
+            };
+            match self.handle_envelope(peer_id, &message.topic, message.source, id, envelope) {
+                gossipsub::MessageAcceptance::Reject => {
+                    return gossipsub::MessageAcceptance::Reject
+                }
+                gossipsub::MessageAcceptance::Accept => {
+                    acceptance = gossipsub::MessageAcceptance::Accept
+                }
+                gossipsub::MessageAcceptance::Ignore => {}
+            }
+        }
+        acceptance
+    }
+
+    /// Process one envelope of a message that passed validation, returning whether the message
+    /// should be propagated for its sake.
+    fn handle_envelope(
+        &mut self,
+        peer_id: PeerId,
+        topic: &gossipsub::TopicHash,
+        source: Option<PeerId>,
+        id: String,
+        envelope: Envelope,
+    ) -> gossipsub::MessageAcceptance {
+        // Reject stale messages and replays of ones we have already accepted.
+        if let Some(source) = source {
+            if let Err(e) = self.replay.check(
+                source,
+                envelope.nonce,
+                envelope.timestamp,
+                envelope::unix_millis(),
+            ) {
+                if e == ReplayError::Duplicate {
+                    self.stats.record_duplicate(topic);
+                }
+                tracing::warn!(%peer_id, error = %e, "Rejected message");
+                return gossipsub::MessageAcceptance::Ignore;
+            }
+        }
+
+        // Acknowledge reliable messages addressed to us, surfacing retries only once.
+        if let (Some(request), Some(source)) = (&envelope.ack, source) {
+            let local_peer_id = *self.swarm.local_peer_id();
+            if request.recipients.contains(&local_peer_id) {
+                let ack = Ack {
+                    id: request.id,
+                    publisher: source,
+                };
+                if let Err(e) = self
+                    .swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .publish(self.ack_topic.clone(), ack.encode())
+                {
+                    tracing::warn!(error = ?e, "Ack publish failed");
+                }
+            }
+            // Retries must still propagate to recipients that have not yet acknowledged.
+            if !self.received.first_receipt(source, request.id) {
+                self.stats.record_duplicate(topic);
+                return gossipsub::MessageAcceptance::Accept;
+            }
+        }
+
+        // Consensus messages are handed to the consensus engine in each sender's order.
+        if *topic == self.consensus_topic.hash() {
+            let (Some(source), Ok(sequenced)) =
+                (source, SequencedMessage::decode(&envelope.payload))
+            else {
+                tracing::warn!(%peer_id, "Malformed consensus message");
+                return gossipsub::MessageAcceptance::Reject;
+            };
+            let Some(ready) = self.consensus.receive(source, sequenced) else {
+                self.stats.record_duplicate(topic);
+                return gossipsub::MessageAcceptance::Ignore;
+            };
+            for message in ready {
+                match &self.consensus_handler {
+                    Some(handler) => handler.handle(source, message),
+                    None => tracing::debug!(peer_id = %source, ?message, "Got consensus message"),
+                }
+            }
+            return gossipsub::MessageAcceptance::Accept;
+        }
+
+        // Transactions are pooled rather than surfaced, and duplicates are not propagated again.
+        if *topic == self.mempool_topic.hash() {
+            let transaction = match Transaction::decode(&envelope.payload) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    tracing::warn!(%peer_id, error = %e, "Malformed transaction");
+                    return gossipsub::MessageAcceptance::Reject;
+                }
+            };
+            return match self.mempool.insert(transaction) {
+                Ok(_) => gossipsub::MessageAcceptance::Accept,
+                Err(MempoolError::Duplicate(_)) => {
+                    self.stats.record_duplicate(topic);
+                    gossipsub::MessageAcceptance::Ignore
+                }
+                Err(e) => {
+                    tracing::warn!(%peer_id, error = %e, "Rejected transaction");
+                    gossipsub::MessageAcceptance::Reject
+                }
+            };
+        }
+
+        tracing::debug!(
+            %peer_id,
+            %id,
+            payload = %String::from_utf8_lossy(&envelope.payload),
+            "Got message"
+        );
+        self.record_message(
+            topic.clone(),
+            JournalEntry {
+                id,
+                source,
+                received_at: envelope::unix_millis(),
+                payload: envelope.payload,
+            },
+        );
+        gossipsub::MessageAcceptance::Accept
+    }
+
+    /// Keep a received message for inspection, and forward it to the webhook if one is set.
+    fn record_message(&mut self, topic: gossipsub::TopicHash, entry: JournalEntry) {
+        if let Some(webhook) = &self.webhook {
+            webhook.send(&topic, &entry);
+        }
+        let _ = self.events.send(NodeEvent::Message(ReceivedMessage {
+            topic: topic.to_string(),
+            entry: entry.clone(),
+        }));
+        self.journal.record(topic, entry);
+    }
+
+    fn handle_event(&mut self, event: SwarmEvent<MyBehaviourEvent>) {
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => {
+                tracing::info!(%address, "Local node is listening");
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                tracing::info!(%peer_id, "Connected to peer");
+                self.session.connections += 1;
+                self.connections.insert(
+                    connection_id,
+                    ConnectionInfo {
+                        peer_id,
+                        address: endpoint.get_remote_address().clone(),
+                        outbound: endpoint.is_dialer(),
+                    },
+                );
+                if let Some(sender) = self.pending_dials.remove(&connection_id) {
+                    let _ = sender.send(Ok(peer_id));
+                }
+                if num_established.get() == 1 {
+                    let _ = self.events.send(NodeEvent::PeerConnected { peer: peer_id });
+                }
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                connection_id,
+                cause,
+                num_established,
+                ..
+            } => {
+                self.connections.remove(&connection_id);
+                self.holepunched.remove(&connection_id);
+                tracing::info!(%peer_id, ?cause, "Connection closed");
+                if num_established == 0 {
+                    self.peers.disconnected(&peer_id);
+                    self.clock.disconnected(&peer_id);
+                    self.stats.forget_peer(&peer_id);
+                    self.blobs.forget_peer(&peer_id);
+                    self.sync.forget_peer(&peer_id);
+                    let _ = self
+                        .events
+                        .send(NodeEvent::PeerDisconnected { peer: peer_id });
+                }
+            }
+            SwarmEvent::OutgoingConnectionError {
+                peer_id,
+                connection_id,
+                error,
+            } => {
+                tracing::warn!(?peer_id, %error, "Failed to connect");
+                if let Some(peer_id) = peer_id {
+                    self.relays.failed(peer_id, error.to_string());
+                }
+                if let Some(sender) = self.pending_dials.remove(&connection_id) {
+                    let _ = sender.send(Err(error));
+                }
+            }
+            SwarmEvent::ListenerError { listener_id, error } => {
+                if let Some(relay) = self.relays.relay_of(listener_id) {
+                    self.relays.failed(relay, error.to_string());
+                }
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                let error = reason.err().map(|e| e.to_string());
+                if let Some(relay) = self.relays.closed(listener_id, error) {
+                    tracing::info!(%relay, "Reservation on relay closed");
+                }
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::RelayClient(
+                relay::client::Event::ReservationReqAccepted { relay_peer_id, .. },
+            )) => {
+                tracing::info!(relay = %relay_peer_id, "Reservation accepted by relay");
+                self.relays.accepted(relay_peer_id);
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Identify(identify::Event::Received {
+                peer_id,
+                info,
+                ..
+            })) => {
+                tracing::debug!(%peer_id, agent_version = %info.agent_version, "Identified peer");
+                // Peers on another network or an incompatible release, including clients other
+                // than sigil, present a different protocol version.
+                if info.protocol_version != self.protocol_version {
+                    tracing::warn!(
+                        %peer_id,
+                        protocol_version = %info.protocol_version,
+                        "Rejecting incompatible peer"
+                    );
+                    self.swarm
+                        .disconnect_peer_id(peer_id)
+                        .unwrap_or_else(|err| {
+                            tracing::warn!(%peer_id, error = ?err, "Failed to disconnect");
+                        });
+                } else {
+                    self.peers.identified(peer_id, &info);
+                    let behaviour = &mut self.swarm.behaviour_mut().time;
+                    self.clock.measure(behaviour, peer_id);
+                    if self.sync_on_join && !self.sync.synced() {
+                        // A sync already in progress makes this a no-op, and a failed one is
+                        // retried with the next peer identified.
+                        let behaviour = &mut self.swarm.behaviour_mut().sync;
+                        self.sync.start(behaviour, peer_id, None);
+                    }
+                }
+            }
+            #[cfg(feature = "mdns")]
+            SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
+                for (peer_id, multiaddr) in list {
+                    if peer_id != *self.swarm.local_peer_id() {
+                        tracing::debug!(%peer_id, "mDNS discovered a new peer");
+                        self.swarm
+                            .behaviour_mut()
+                            .gossipsub
+                            .add_explicit_peer(&peer_id);
+                        self.swarm.behaviour_mut().add_address(&peer_id, multiaddr);
+                    }
+                }
+            }
+            #[cfg(feature = "mdns")]
+            SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
+                for (peer_id, _multiaddr) in list {
+                    tracing::debug!(%peer_id, "mDNS peer expired");
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .remove_explicit_peer(&peer_id);
+                }
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source: peer_id,
+                message_id: id,
+                message,
+            })) => {
+                self.session.messages_received += 1;
+                self.stats.record_message(
+                    &message.topic,
+                    peer_id,
+                    message.data.len(),
+                    Instant::now(),
+                );
+                if let Err(id) = self.validation.submit(id, peer_id, message) {
+                    self.channels.blocked(Channel::Validation);
+                    tracing::warn!(%peer_id, %id, "Validation queue full, ignoring message");
+                    self.report_validation(&id, &peer_id, gossipsub::MessageAcceptance::Ignore);
+                }
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Direct(event)) => self.handle_direct(event),
+            SwarmEvent::Behaviour(MyBehaviourEvent::Blob(event)) => {
+                let behaviour = &mut self.swarm.behaviour_mut().blob;
+                self.blobs.handle_event(behaviour, event);
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Sync(event)) => {
+                let behaviour = &mut self.swarm.behaviour_mut().sync;
+                self.sync.handle_event(behaviour, event);
+            }
+            #[cfg(feature = "dcutr")]
+            SwarmEvent::Behaviour(MyBehaviourEvent::Dcutr(dcutr::Event {
+                remote_peer_id,
+                result,
+            })) => match result {
+                Ok(connection_id) => {
+                    tracing::info!(peer_id = %remote_peer_id, "Hole punched a direct connection");
+                    self.session.holepunches_succeeded += 1;
+                    let _ = self.events.send(NodeEvent::HolePunched {
+                        peer: remote_peer_id,
+                    });
+                    self.holepunched.insert(connection_id);
+                }
+                Err(e) => {
+                    tracing::warn!(peer_id = %remote_peer_id, error = %e, "Failed to hole punch");
+                    self.session.holepunches_failed += 1;
+                    let _ = self.events.send(NodeEvent::HolePunchFailed {
+                        peer: remote_peer_id,
+                        error: e.to_string(),
+                    });
+                }
+            },
+            SwarmEvent::Behaviour(MyBehaviourEvent::Time(event)) => {
+                let behaviour = &mut self.swarm.behaviour_mut().time;
+                let Some((peer_id, change)) = self.clock.handle_event(behaviour, event) else {
+                    return;
+                };
+                // Skewed peers keep their penalty until their clock is measured back in line.
+                if self.clock_penalty != 0.0 {
+                    let score = match change {
+                        SkewChange::Exceeded => -self.clock_penalty,
+                        SkewChange::Recovered => 0.0,
+                    };
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .set_application_score(&peer_id, score);
+                }
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmEvent::Behaviour(MyBehaviourEvent::Kad(event)) => {
+                if let kad::Event::OutboundQueryProgressed {
+                    result: kad::QueryResult::Bootstrap(result),
+                    step,
+                    ..
+                } = &event
+                {
+                    let status = match result {
+                        Ok(_) if step.last => Some(BootstrapStatus::Done),
+                        Ok(_) => None,
+                        Err(_) => Some(BootstrapStatus::Failed),
+                    };
+                    if let Some(status) = status.filter(|status| *status != self.bootstrap) {
+                        self.bootstrap = status;
+                        let _ = self.events.send(NodeEvent::BootstrapChanged { status });
+                    }
+                }
+                let Some(event) = self
+                    .dht
+                    .handle_event(&mut self.swarm.behaviour_mut().kad, event)
+                else {
+                    return;
+                };
+                let local_peer_id = *self.swarm.local_peer_id();
+                let behaviour = &mut self.swarm.behaviour_mut().block;
+                self.blocks
+                    .handle_kad_event(behaviour, local_peer_id, event);
+            }
+            #[cfg(feature = "kademlia")]
+            SwarmEvent::Behaviour(MyBehaviourEvent::Block(event)) => {
+                let behaviour = self.swarm.behaviour_mut();
+                self.blocks
+                    .handle_block_event(&mut behaviour.block, &mut behaviour.kad, event);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The id gossipsub knows `message` by, a SHA-256 digest of its data and, unless its topic is
+/// content-addressed, of its topic, source and sequence number. Nodes must agree on it whatever
+/// they were built with, and peers must not be able to publish another message with the same id.
+pub fn message_id(message: &gossipsub::Message, content_addressed: bool) -> gossipsub::MessageId {
+    let mut hasher = Sha256::new();
+    if !content_addressed {
+        let topic = message.topic.as_str().as_bytes();
+        hasher.update((topic.len() as u64).to_be_bytes());
+        hasher.update(topic);
+        match message.source {
+            Some(source) => {
+                let source = source.to_bytes();
+                hasher.update([1]);
+                hasher.update((source.len() as u64).to_be_bytes());
+                hasher.update(source);
+            }
+            None => hasher.update([0]),
+        }
+        match message.sequence_number {
+            Some(sequence_number) => {
+                hasher.update([1]);
+                hasher.update(sequence_number.to_be_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hasher.update(&message.data);
+    gossipsub::MessageId::from(hex::encode(hasher.finalize()))
+}
+
+/// Dial a configured or remembered peer, deferring the dial to `deferred` if the dial limits
+/// refuse it for now.
+fn dial_or_defer(
+    swarm: &mut Swarm<MyBehaviour>,
+    dial: DeferredDial,
+    deferred: &mut VecDeque<DeferredDial>,
+) -> Result<(), DialError> {
+    match swarm.dial(dial.opts()) {
+        Err(e) if dial_limits::is_rate_limited(&e) => {
+            deferred.push_back(dial);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// The error for a configuration asking for a behaviour that this build left out.
+#[cfg(not(all(feature = "mdns", feature = "relay-server")))]
+fn compiled_out(behaviour: &str, feature: &str) -> String {
+    format!("{behaviour} is configured, but the node was built without the `{feature}` feature")
+}
@@ -0,0 +1,72 @@
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+/// How many items a page holds when the caller does not say.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// The most items a page may hold, however many the caller asks for.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Which peers to list, and where to resume a listing from.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerQuery {
+    /// The `next_cursor` of the previous page, to list the peers after it.
+    pub cursor: Option<PeerId>,
+    pub limit: Option<usize>,
+
+    /// Only peers whose base58 id starts with this.
+    pub prefix: Option<String>,
+
+    /// Only peers in this Kademlia bucket, where bucket `i` holds peers at a distance of at least
+    /// `2^i` and less than `2^(i+1)` from us. Ignored for listings not from the routing table.
+    pub bucket: Option<u32>,
+}
+
+/// One page of a listing, ordered by peer id.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+
+    /// The cursor for the next page, or `None` if this is the last.
+    pub next_cursor: Option<PeerId>,
+}
+
+impl PeerQuery {
+    /// The page of `items` this query selects, with `peer` giving each item's peer and `bucket` its
+    /// bucket, if it has one.
+    pub fn page<T>(
+        &self,
+        items: Vec<T>,
+        peer: impl Fn(&T) -> PeerId,
+        bucket: impl Fn(&T) -> Option<u32>,
+    ) -> Page<T> {
+        let limit = self
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let cursor = self.cursor.map(|cursor| cursor.to_string());
+        let mut items: Vec<(String, T)> = items
+            .into_iter()
+            .map(|item| (peer(&item).to_string(), item))
+            .filter(|(id, item)| {
+                cursor.as_ref().is_none_or(|cursor| id > cursor)
+                    && self
+                        .prefix
+                        .as_ref()
+                        .is_none_or(|prefix| id.starts_with(prefix))
+                    && self
+                        .bucket
+                        .is_none_or(|wanted| bucket(item) == Some(wanted))
+            })
+            .collect();
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let more = items.len() > limit;
+        items.truncate(limit);
+        let items: Vec<T> = items.into_iter().map(|(_, item)| item).collect();
+        Page {
+            next_cursor: more.then(|| items.last().map(&peer)).flatten(),
+            items,
+        }
+    }
+}
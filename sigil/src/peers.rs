@@ -0,0 +1,69 @@
+use crate::version;
+use libp2p::{identify, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A connected peer, with what it told us about itself over identify.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+
+    /// The name the peer's operator gave it, if any.
+    pub node_name: Option<String>,
+
+    /// The peer's agent version, or `None` until it has been identified.
+    pub agent_version: Option<String>,
+    pub protocol_version: Option<String>,
+    pub listen_addresses: Vec<Multiaddr>,
+
+    /// How far ahead of our clock the peer's is, in milliseconds, once it has been measured.
+    pub clock_skew_millis: Option<i64>,
+}
+
+/// What connected peers have told us about themselves, kept until they disconnect.
+#[derive(Default)]
+pub struct PeerInfoCache {
+    peers: HashMap<PeerId, PeerInfo>,
+}
+
+impl PeerInfoCache {
+    /// Record what `peer_id` identified itself as.
+    pub fn identified(&mut self, peer_id: PeerId, info: &identify::Info) {
+        self.peers.insert(
+            peer_id,
+            PeerInfo {
+                peer_id,
+                node_name: version::node_name(&info.agent_version).map(str::to_string),
+                agent_version: Some(info.agent_version.clone()),
+                protocol_version: Some(info.protocol_version.clone()),
+                listen_addresses: info.listen_addrs.clone(),
+                clock_skew_millis: None,
+            },
+        );
+    }
+
+    pub fn disconnected(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerInfo> {
+        self.peers.get(peer_id)
+    }
+
+    /// What is known of each of `peers`, which are listed bare if they have not been identified.
+    pub fn list(&self, peers: impl IntoIterator<Item = PeerId>) -> Vec<PeerInfo> {
+        peers
+            .into_iter()
+            .map(|peer_id| {
+                self.peers.get(&peer_id).cloned().unwrap_or(PeerInfo {
+                    peer_id,
+                    node_name: None,
+                    agent_version: None,
+                    protocol_version: None,
+                    listen_addresses: Vec::new(),
+                    clock_skew_millis: None,
+                })
+            })
+            .collect()
+    }
+}
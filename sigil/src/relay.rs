@@ -0,0 +1,154 @@
+use libp2p::core::transport::ListenerId;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationStatus {
+    /// The reservation has been requested but the relay has not yet accepted it.
+    Pending,
+    Accepted,
+    /// The reservation was refused or has lapsed.
+    Closed,
+}
+
+/// A relay this node listens through, so that peers can reach it from behind a NAT.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelayInfo {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+    pub status: ReservationStatus,
+
+    /// The last error listening through the relay, such as why the reservation closed.
+    pub error: Option<String>,
+}
+
+/// Tracks the reservations this node has requested from relays.
+#[derive(Default)]
+pub struct Relays {
+    relays: HashMap<PeerId, RelayInfo>,
+    listeners: HashMap<ListenerId, PeerId>,
+    snapshot: RelaySnapshot,
+}
+
+/// The node's relays as of their last change, shared between the node and its `SwarmClient` so
+/// that they can be read without waiting on the node's event loop.
+#[derive(Clone, Debug, Default)]
+pub struct RelaySnapshot {
+    relays: Arc<RwLock<Arc<[RelayInfo]>>>,
+}
+
+impl RelaySnapshot {
+    /// The relays, ordered by peer id. The list is shared rather than copied, and later changes
+    /// replace it rather than change it.
+    pub fn get(&self) -> Arc<[RelayInfo]> {
+        self.relays
+            .read()
+            .expect("relays lock is never poisoned")
+            .clone()
+    }
+}
+
+impl Relays {
+    /// Record a reservation requested by listening on `address` through `peer_id`.
+    pub fn request(&mut self, peer_id: PeerId, address: Multiaddr, listener: ListenerId) {
+        self.listeners.insert(listener, peer_id);
+        self.relays.insert(
+            peer_id,
+            RelayInfo {
+                peer_id,
+                address,
+                status: ReservationStatus::Pending,
+                error: None,
+            },
+        );
+        self.publish();
+    }
+
+    pub fn accepted(&mut self, peer_id: PeerId) {
+        if let Some(relay) = self.relays.get_mut(&peer_id) {
+            relay.status = ReservationStatus::Accepted;
+            relay.error = None;
+            self.publish();
+        }
+    }
+
+    /// The relay that `listener` listens through, if any.
+    pub fn relay_of(&self, listener: ListenerId) -> Option<PeerId> {
+        self.listeners.get(&listener).copied()
+    }
+
+    /// Record an error reaching or listening through a relay, which may or may not close its
+    /// listener. Errors for peers that are not relays are ignored.
+    pub fn failed(&mut self, peer_id: PeerId, error: String) {
+        if let Some(relay) = self.relays.get_mut(&peer_id) {
+            relay.error = Some(error);
+            self.publish();
+        }
+    }
+
+    /// Record that the listener through a relay closed, returning the relay's peer id if it was
+    /// one of ours.
+    pub fn closed(&mut self, listener: ListenerId, error: Option<String>) -> Option<PeerId> {
+        let peer_id = self.listeners.remove(&listener)?;
+        if let Some(relay) = self.relays.get_mut(&peer_id) {
+            relay.status = ReservationStatus::Closed;
+            relay.error = error.or(relay.error.take());
+            self.publish();
+        }
+        Some(peer_id)
+    }
+
+    /// The relays whose reservations have closed, to be requested again.
+    pub fn closed_relays(&self) -> Vec<(PeerId, Multiaddr)> {
+        self.relays
+            .values()
+            .filter(|relay| relay.status == ReservationStatus::Closed)
+            .map(|relay| (relay.peer_id, relay.address.clone()))
+            .collect()
+    }
+
+    /// The listeners through relays that are still open.
+    pub fn listeners(&self) -> Vec<ListenerId> {
+        self.listeners.keys().copied().collect()
+    }
+
+    /// Whether `peer_id` has accepted a reservation.
+    pub fn is_reserved(&self, peer_id: &PeerId) -> bool {
+        self.relays
+            .get(peer_id)
+            .is_some_and(|relay| relay.status == ReservationStatus::Accepted)
+    }
+
+    /// How many relays have accepted a reservation.
+    pub fn reservations(&self) -> usize {
+        self.relays
+            .values()
+            .filter(|relay| relay.status == ReservationStatus::Accepted)
+            .count()
+    }
+
+    /// The relays, ordered by peer id, as `RelaySnapshot::get` returns them.
+    pub fn list(&self) -> Arc<[RelayInfo]> {
+        self.snapshot.get()
+    }
+
+    /// A handle on the relays that follows their changes.
+    pub fn snapshot(&self) -> RelaySnapshot {
+        self.snapshot.clone()
+    }
+
+    /// Replace the shared list after a change. Relays change rarely, so readers are spared the
+    /// copy instead.
+    fn publish(&mut self) {
+        let mut relays: Vec<RelayInfo> = self.relays.values().cloned().collect();
+        relays.sort_by_key(|relay| relay.peer_id);
+        *self
+            .snapshot
+            .relays
+            .write()
+            .expect("relays lock is never poisoned") = relays.into();
+    }
+}
@@ -0,0 +1,294 @@
+use crate::client::{ClientError, SwarmClient};
+use crate::config::Config;
+use crate::rpc::allowlist::Allowlist;
+use crate::rpc::rate_limit::RateLimiter;
+use serde_json::Value;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing_subscriber::EnvFilter;
+
+/// Replaces the filter deciding which log lines are emitted.
+pub type LogReloader = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// The settings that can change while the node runs, by their path in the configuration. Every
+/// other setting only takes effect on restart.
+pub const TUNABLE_SETTINGS: &[&str] = &[
+    "log.level",
+    "log.modules",
+    "log.filter",
+    "peer_limits",
+    "rpc.rate_limit",
+    "rpc.allowed_ips",
+    "gossipsub.scoring.topic",
+    "batch.scoring",
+    "faults",
+];
+
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The node was started without a configuration file.
+    NoConfigFile,
+    Config(String),
+    /// The setting at this path only takes effect on restart.
+    NotTunable(String),
+    Log(String),
+    Node(ClientError),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::NoConfigFile => write!(f, "no configuration file to reload"),
+            ReloadError::Config(e) => write!(f, "{e}"),
+            ReloadError::NotTunable(path) => {
+                write!(f, "{path} cannot be changed while the node runs")
+            }
+            ReloadError::Log(e) => write!(f, "failed to replace log filter: {e}"),
+            ReloadError::Node(e) => write!(f, "failed to apply configuration: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+/// Applies the settings that can change while the node runs, listed in [`TUNABLE_SETTINGS`], from
+/// the re-read configuration file or from updates over RPC.
+pub struct Reloader {
+    path: Option<PathBuf>,
+    /// Replaced, never changed in place, so that snapshots handed out stay as they were.
+    config: RwLock<Arc<Config>>,
+    client: SwarmClient,
+    rate_limiter: Arc<RateLimiter>,
+    allowlist: Arc<Allowlist>,
+    log: Option<LogReloader>,
+
+    /// Held while settings are applied, so that concurrent reloads and updates apply in turn.
+    applying: tokio::sync::Mutex<()>,
+}
+
+impl Reloader {
+    /// Reload from the file at `path`, which `config` was read from when the node started.
+    pub fn new(path: Option<PathBuf>, config: Arc<Config>, client: SwarmClient) -> Self {
+        Self {
+            path,
+            rate_limiter: Arc::new(RateLimiter::new(config.rpc.rate_limit.clone())),
+            allowlist: Arc::new(Allowlist::new(config.rpc.allowed_ips.clone())),
+            config: RwLock::new(config),
+            client,
+            log: None,
+            applying: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Let reloads replace the log filter.
+    pub fn set_log_reloader(&mut self, log: LogReloader) {
+        self.log = Some(log);
+    }
+
+    /// The rate limiter that reloads update, to be enforced by the RPC server.
+    pub fn rate_limiter(&self) -> Arc<RateLimiter> {
+        self.rate_limiter.clone()
+    }
+
+    /// The allowlist that reloads update, to be enforced by the RPC server.
+    pub fn allowlist(&self) -> Arc<Allowlist> {
+        self.allowlist.clone()
+    }
+
+    /// The configuration in effect, which is the one the node started with updated by reloads.
+    /// It is a snapshot shared with every other reader, which later reloads leave untouched.
+    pub fn config(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .expect("config lock is never poisoned")
+            .clone()
+    }
+
+    /// Re-read the configuration file and apply its tunable settings, warning about any other
+    /// setting that changed.
+    pub async fn reload(&self) -> Result<(), ReloadError> {
+        let path = self.path.as_ref().ok_or(ReloadError::NoConfigFile)?;
+        let config = Config::load(Some(path)).map_err(|e| ReloadError::Config(e.to_string()))?;
+        let _applying = self.applying.lock().await;
+        let restart_only: Vec<String> =
+            changed_settings(&to_value(&self.config()), &to_value(&config))
+                .into_iter()
+                .filter(|path| !is_tunable(path))
+                .collect();
+        self.apply(config).await?;
+        if !restart_only.is_empty() {
+            tracing::warn!(
+                settings = %restart_only.join(", "),
+                "Changes to these settings only take effect on restart"
+            );
+        }
+        tracing::info!(path = %path.display(), "Reloaded configuration");
+        Ok(())
+    }
+
+    /// Apply `settings`, a partial configuration holding only tunable settings, over the
+    /// configuration in effect, returning the configuration now in effect. The settings last until
+    /// the file is next reloaded.
+    pub async fn update(&self, settings: Value) -> Result<Arc<Config>, ReloadError> {
+        let mut paths = Vec::new();
+        leaf_paths("", &settings, &mut paths);
+        if let Some(path) = paths.into_iter().find(|path| !is_tunable(path)) {
+            return Err(ReloadError::NotTunable(path));
+        }
+        let _applying = self.applying.lock().await;
+        let mut merged = to_value(&self.config());
+        merge(&mut merged, settings);
+        let config: Config =
+            serde_json::from_value(merged).map_err(|e| ReloadError::Config(e.to_string()))?;
+        self.apply(config).await?;
+        tracing::info!("Updated configuration over RPC");
+        Ok(self.config())
+    }
+
+    /// Reload the file whenever it is modified, checking every `interval`.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        let modified = |path: &PathBuf| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        let mut last: Option<SystemTime> = modified(&path);
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            let current = modified(&path);
+            if current.is_none() || current == last {
+                continue;
+            }
+            last = current;
+            if let Err(e) = self.reload().await {
+                tracing::error!(error = %e, "Failed to reload configuration");
+            }
+        }
+    }
+
+    async fn apply(&self, config: Config) -> Result<(), ReloadError> {
+        if let Some(log) = &self.log {
+            let filter = config
+                .log
+                .env_filter()
+                .map_err(|e| ReloadError::Log(e.to_string()))?;
+            log(filter).map_err(ReloadError::Log)?;
+        }
+        self.rate_limiter.set_config(config.rpc.rate_limit.clone());
+        self.allowlist.set_networks(config.rpc.allowed_ips.clone());
+        self.client.faults().set(config.faults.clone());
+        self.client
+            .set_peer_limits(config.peer_limits())
+            .await
+            .map_err(ReloadError::Node)?;
+        // Scoring can only be switched on or off at startup, and topics only change on restart.
+        let mut scoring = Config::clone(&self.config());
+        if scoring.gossipsub.scoring.enabled {
+            scoring.gossipsub.scoring.topic = config.gossipsub.scoring.topic.clone();
+            scoring.batch.scoring = config.batch.scoring.clone();
+            let topics = scoring
+                .scored_topics()
+                .into_iter()
+                .map(|(topic, params)| (topic.to_string(), params.params()))
+                .collect();
+            self.client
+                .set_topic_scoring(topics)
+                .await
+                .map_err(ReloadError::Node)?;
+        }
+
+        let mut current = self.config.write().expect("config lock is never poisoned");
+        // Snapshots still held are left as they were, by copying the configuration for this change.
+        // Only tunable settings are taken from `config`, so the secrets that are not serialized,
+        // and so are missing from configurations merged by `update`, keep their running values.
+        let effective = Arc::make_mut(&mut *current);
+        if self.log.is_some() {
+            effective.log.level = config.log.level;
+            effective.log.modules = config.log.modules;
+            effective.log.filter = config.log.filter;
+        }
+        effective.rpc.rate_limit = config.rpc.rate_limit;
+        effective.rpc.allowed_ips = config.rpc.allowed_ips;
+        effective.peer_limits = config.peer_limits;
+        effective.gossipsub.scoring.topic = config.gossipsub.scoring.topic;
+        effective.batch.scoring = config.batch.scoring;
+        effective.faults = config.faults;
+        Ok(())
+    }
+}
+
+fn to_value(config: &Config) -> Value {
+    serde_json::to_value(config).expect("configs always serialize")
+}
+
+fn is_tunable(path: &str) -> bool {
+    TUNABLE_SETTINGS.iter().any(|tunable| {
+        path == *tunable
+            || path
+                .strip_prefix(tunable)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// The paths of the settings that differ between `old` and `new`.
+fn changed_settings(old: &Value, new: &Value) -> Vec<String> {
+    let mut changed = Vec::new();
+    diff("", old, new, &mut changed);
+    changed
+}
+
+fn diff(path: &str, old: &Value, new: &Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for key in old
+                .keys()
+                .chain(new.keys().filter(|key| !old.contains_key(*key)))
+            {
+                let null = Value::Null;
+                let old = old.get(key).unwrap_or(&null);
+                let new = new.get(key).unwrap_or(&null);
+                diff(&join(path, key), old, new, changed);
+            }
+        }
+        (old, new) if old != new => changed.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// The paths of the settings in a partial configuration, counting an empty table as a setting.
+fn leaf_paths(path: &str, settings: &Value, paths: &mut Vec<String>) {
+    match settings {
+        Value::Object(table) if !table.is_empty() => {
+            for (key, value) in table {
+                leaf_paths(&join(path, key), value, paths);
+            }
+        }
+        _ => paths.push(path.to_string()),
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Merge `patch` into `target`, replacing everything but tables, which are merged key by key.
+fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
@@ -0,0 +1,73 @@
+use libp2p::PeerId;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+/// Why a message was rejected as a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    /// We have already accepted a message from this sender with this nonce.
+    Duplicate,
+    /// The message's timestamp lies outside the acceptance window.
+    Stale,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Duplicate => write!(f, "duplicate nonce"),
+            ReplayError::Stale => write!(f, "timestamp outside the acceptance window"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// A sliding-window cache of the (sender, nonce) pairs we have accepted. Messages timestamped more
+/// than `window` away from our clock are rejected outright, so a nonce only needs to be remembered
+/// until its message could no longer pass that check.
+pub struct ReplayCache {
+    window_millis: u64,
+    seen: HashSet<(PeerId, u64)>,
+    order: VecDeque<(u64, PeerId, u64)>,
+}
+
+impl ReplayCache {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window_millis: window.as_millis() as u64,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Accept a message from `sender` unless it is stale or a replay. Times are in milliseconds
+    /// since the Unix epoch.
+    pub fn check(
+        &mut self,
+        sender: PeerId,
+        nonce: u64,
+        timestamp: u64,
+        now: u64,
+    ) -> Result<(), ReplayError> {
+        self.prune(now);
+        if timestamp.abs_diff(now) > self.window_millis {
+            return Err(ReplayError::Stale);
+        }
+        if !self.seen.insert((sender, nonce)) {
+            return Err(ReplayError::Duplicate);
+        }
+        self.order.push_back((timestamp, sender, nonce));
+        Ok(())
+    }
+
+    fn prune(&mut self, now: u64) {
+        while let Some(&(timestamp, sender, nonce)) = self.order.front() {
+            if timestamp.saturating_add(self.window_millis) >= now {
+                break;
+            }
+            self.order.pop_front();
+            self.seen.remove(&(sender, nonce));
+        }
+    }
+}
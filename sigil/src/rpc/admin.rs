@@ -0,0 +1,119 @@
+use super::{client_error, Versioned};
+use crate::client::SwarmClient;
+use crate::config::Config;
+use crate::node::NodeInfo;
+use crate::reload::{ReloadError, Reloader};
+use jsonrpsee::core::{async_trait, RpcResult};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE};
+use jsonrpsee::types::ErrorObjectOwned;
+use libp2p::{Multiaddr, PeerId};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Peer management in the manner of geth's `admin` namespace. None of these methods are
+/// read-only, so all of them require a token, and without one they are only served over the unix
+/// socket.
+#[rpc(server, client, namespace = "admin")]
+pub trait AdminApi {
+    /// Dial `address`, adding it to the routing table if it names its peer.
+    #[method(name = "addPeer")]
+    async fn add_peer(&self, address: Multiaddr) -> RpcResult<bool>;
+
+    /// Forget a peer's addresses and disconnect from it, returning whether it was known.
+    #[method(name = "removePeer")]
+    async fn remove_peer(&self, peer_id: PeerId) -> RpcResult<bool>;
+
+    /// Disconnect from a peer and refuse any further connections with it.
+    #[method(name = "banPeer")]
+    async fn ban_peer(&self, peer_id: PeerId) -> RpcResult<bool>;
+
+    /// Close every connection to a peer, returning whether there were any.
+    #[method(name = "disconnectPeer")]
+    async fn disconnect_peer(&self, peer_id: PeerId) -> RpcResult<bool>;
+
+    #[method(name = "nodeInfo")]
+    async fn node_info(&self) -> RpcResult<Versioned<NodeInfo>>;
+
+    /// Re-read the configuration file, applying the settings that can change while running.
+    #[method(name = "reloadConfig")]
+    async fn reload_config(&self) -> RpcResult<bool>;
+
+    /// Apply `settings`, a partial configuration such as `{"peer_limits": {"max_established":
+    /// 50}}`, to the running node, returning the configuration now in effect without its secrets.
+    /// Only the settings that can change while running are accepted, and they last until the file
+    /// is next reloaded.
+    #[method(name = "updateConfig")]
+    async fn update_config(&self, settings: Value) -> RpcResult<Arc<Config>>;
+
+    /// Stop the node, after which the process exits.
+    #[method(name = "shutdown")]
+    async fn shutdown(&self) -> RpcResult<bool>;
+}
+
+/// Serves a node's peer management over JSON-RPC.
+pub struct AdminRpc {
+    client: SwarmClient,
+    reloader: Arc<Reloader>,
+}
+
+impl AdminRpc {
+    pub fn new(client: SwarmClient, reloader: Arc<Reloader>) -> Self {
+        Self { client, reloader }
+    }
+}
+
+#[async_trait]
+impl AdminApiServer for AdminRpc {
+    async fn add_peer(&self, address: Multiaddr) -> RpcResult<bool> {
+        self.client.add_peer(address).await.map_err(client_error)?;
+        Ok(true)
+    }
+
+    async fn remove_peer(&self, peer_id: PeerId) -> RpcResult<bool> {
+        self.client.remove_peer(peer_id).await.map_err(client_error)
+    }
+
+    async fn ban_peer(&self, peer_id: PeerId) -> RpcResult<bool> {
+        self.client.ban_peer(peer_id).await.map_err(client_error)?;
+        Ok(true)
+    }
+
+    async fn disconnect_peer(&self, peer_id: PeerId) -> RpcResult<bool> {
+        self.client
+            .disconnect_peer(peer_id)
+            .await
+            .map_err(client_error)
+    }
+
+    async fn node_info(&self) -> RpcResult<Versioned<NodeInfo>> {
+        self.client
+            .node_info()
+            .await
+            .map(Versioned::new)
+            .map_err(client_error)
+    }
+
+    async fn reload_config(&self) -> RpcResult<bool> {
+        self.reloader.reload().await.map_err(|e| match e {
+            ReloadError::Node(e) => client_error(e),
+            e => ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>),
+        })?;
+        Ok(true)
+    }
+
+    async fn update_config(&self, settings: Value) -> RpcResult<Arc<Config>> {
+        self.reloader.update(settings).await.map_err(|e| match e {
+            ReloadError::Node(e) => client_error(e),
+            ReloadError::NotTunable(_) | ReloadError::Config(_) => {
+                ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e.to_string(), None::<()>)
+            }
+            e => ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>),
+        })
+    }
+
+    async fn shutdown(&self) -> RpcResult<bool> {
+        self.client.shutdown().await.map_err(client_error)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,98 @@
+use super::server::RemoteAddr;
+use futures::future::{self, Either, Ready};
+use ipnet::IpNet;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, Request};
+use jsonrpsee::MethodResponse;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use tower::Layer;
+
+/// The error code returned for calls from addresses outside the allowlist.
+pub const FORBIDDEN_CODE: i32 = -32008;
+
+/// The networks that may call the server. Every address may when none are listed.
+pub struct Allowlist {
+    networks: RwLock<Vec<IpNet>>,
+}
+
+impl Allowlist {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self {
+            networks: RwLock::new(networks),
+        }
+    }
+
+    pub fn set_networks(&self, networks: Vec<IpNet>) {
+        *self
+            .networks
+            .write()
+            .expect("allowlist lock is never poisoned") = networks;
+    }
+
+    /// Whether `caller` may call the server. Callers of unknown address are only allowed when
+    /// every address is.
+    pub fn allows(&self, caller: Option<IpAddr>) -> bool {
+        let networks = self
+            .networks
+            .read()
+            .expect("allowlist lock is never poisoned");
+        if networks.is_empty() {
+            return true;
+        }
+        // Match IPv4 callers that reached a dual-stack listener as IPv4-mapped IPv6 addresses.
+        caller.is_some_and(|caller| {
+            let caller = caller.to_canonical();
+            networks.iter().any(|network| network.contains(&caller))
+        })
+    }
+}
+
+/// JSON-RPC middleware that refuses calls from addresses outside the allowlist.
+#[derive(Clone)]
+pub struct AllowlistLayer {
+    allowlist: Arc<Allowlist>,
+}
+
+impl AllowlistLayer {
+    pub fn new(allowlist: Arc<Allowlist>) -> Self {
+        Self { allowlist }
+    }
+}
+
+impl<S> Layer<S> for AllowlistLayer {
+    type Service = Allowed<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Allowed {
+            inner,
+            allowlist: self.allowlist.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Allowed<S> {
+    inner: S,
+    allowlist: Arc<Allowlist>,
+}
+
+impl<'a, S> RpcServiceT<'a> for Allowed<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let caller = request
+            .extensions()
+            .get::<RemoteAddr>()
+            .map(|addr| addr.0.ip());
+        if self.allowlist.allows(caller) {
+            Either::Left(self.inner.call(request))
+        } else {
+            let error = ErrorObject::owned(FORBIDDEN_CODE, "address not allowed", None::<()>);
+            Either::Right(future::ready(MethodResponse::error(request.id, error)))
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::READ_ONLY_METHODS;
+use futures::future::{self, Either, Ready};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::HttpRequest;
+use jsonrpsee::types::{ErrorObject, Request};
+use jsonrpsee::MethodResponse;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// The error code returned for calls that require a token the caller did not present.
+pub const UNAUTHORIZED_CODE: i32 = -32003;
+
+/// Marks a request whose `Authorization` header carried the configured bearer token.
+#[derive(Clone, Copy, Debug)]
+struct Authenticated;
+
+/// HTTP middleware that checks each request's bearer token. It marks authenticated requests
+/// rather than rejecting others, as which methods need a token is only known once the JSON-RPC
+/// call is parsed. WebSocket connections are authenticated by their upgrade request.
+#[derive(Clone)]
+pub struct TokenLayer {
+    token: Arc<String>,
+}
+
+impl TokenLayer {
+    pub fn new(token: String) -> Self {
+        Self {
+            token: Arc::new(token),
+        }
+    }
+}
+
+impl<S> Layer<S> for TokenLayer {
+    type Service = Token<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Token {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Token<S> {
+    inner: S,
+    token: Arc<String>,
+}
+
+impl<S, B> Service<HttpRequest<B>> for Token<S>
+where
+    S: Service<HttpRequest<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: HttpRequest<B>) -> Self::Future {
+        let authenticated = request
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes()));
+        if authenticated {
+            request.extensions_mut().insert(Authenticated);
+        }
+        self.inner.call(request)
+    }
+}
+
+/// JSON-RPC middleware that rejects calls from unauthenticated callers, except to read-only
+/// methods when those are left open.
+#[derive(Clone, Copy)]
+pub struct AuthLayer {
+    open_read_only: bool,
+}
+
+impl AuthLayer {
+    pub fn new(open_read_only: bool) -> Self {
+        Self { open_read_only }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth {
+            inner,
+            open_read_only: self.open_read_only,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Auth<S> {
+    inner: S,
+    open_read_only: bool,
+}
+
+impl<'a, S> RpcServiceT<'a> for Auth<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let allowed = request.extensions().get::<Authenticated>().is_some()
+            || (self.open_read_only && READ_ONLY_METHODS.contains(&request.method_name()));
+        if allowed {
+            Either::Left(self.inner.call(request))
+        } else {
+            let error = ErrorObject::owned(UNAUTHORIZED_CODE, "unauthorized", None::<()>);
+            Either::Right(future::ready(MethodResponse::error(request.id, error)))
+        }
+    }
+}
+
+/// Compare secrets in time independent of where they first differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,41 @@
+//! Typed clients for a node's RPC, generated from the same traits the server implements so that
+//! callers cannot drift from it. Import the traits to call their methods on either client.
+
+pub use super::{
+    AdminApiClient, DebugApiClient, KadApiClient, MempoolApiClient, MetaApiClient, MyApiClient,
+    NetApiClient,
+};
+pub use jsonrpsee::core::client::{Error, Subscription, SubscriptionClientT};
+pub use jsonrpsee::http_client::HttpClient;
+pub use jsonrpsee::ws_client::WsClient;
+
+use http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+use jsonrpsee::http_client::HttpClientBuilder;
+use jsonrpsee::ws_client::WsClientBuilder;
+
+/// A client for the node at `url`, such as `http://localhost:3030`, presenting `token` if the
+/// node requires one. It cannot subscribe; use `ws` for that.
+pub fn http(url: &str, token: Option<&str>) -> Result<HttpClient, Error> {
+    HttpClientBuilder::default()
+        .set_headers(headers(token)?)
+        .build(url)
+}
+
+/// A client for the node at `url`, such as `ws://localhost:3030`, presenting `token` if the node
+/// requires one.
+pub async fn ws(url: &str, token: Option<&str>) -> Result<WsClient, Error> {
+    WsClientBuilder::default()
+        .set_headers(headers(token)?)
+        .build(url)
+        .await
+}
+
+fn headers(token: Option<&str>) -> Result<HeaderMap, Error> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(|e| Error::Custom(format!("invalid token: {e}")))?;
+        headers.insert(AUTHORIZATION, value);
+    }
+    Ok(headers)
+}
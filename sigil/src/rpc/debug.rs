@@ -0,0 +1,53 @@
+use super::{client_error, Versioned};
+use crate::client::{ChannelDepths, SwarmClient};
+use crate::config::Config;
+use crate::node::NodeState;
+use crate::reload::Reloader;
+use jsonrpsee::core::{async_trait, RpcResult};
+use jsonrpsee::proc_macros::rpc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Everything `debug_dumpState` reports.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateDump {
+    #[serde(flatten)]
+    pub node: NodeState,
+    pub channels: ChannelDepths,
+
+    /// The configuration in effect, without its secrets.
+    pub config: Arc<Config>,
+}
+
+/// Introspection for diagnosing a misbehaving node.
+#[rpc(server, client, namespace = "debug")]
+pub trait DebugApi {
+    /// A snapshot of the node's connections, relays, meshes, queues, and configuration.
+    #[method(name = "dumpState")]
+    async fn dump_state(&self) -> RpcResult<Versioned<StateDump>>;
+}
+
+pub struct DebugRpc {
+    client: SwarmClient,
+    reloader: Arc<Reloader>,
+}
+
+impl DebugRpc {
+    pub fn new(client: SwarmClient, reloader: Arc<Reloader>) -> Self {
+        Self { client, reloader }
+    }
+}
+
+#[async_trait]
+impl DebugApiServer for DebugRpc {
+    async fn dump_state(&self) -> RpcResult<Versioned<StateDump>> {
+        // Measure the channels before our own command joins them.
+        let channels = self.client.channel_depths();
+        let node = self.client.node_state().await.map_err(client_error)?;
+        Ok(Versioned::new(StateDump {
+            node,
+            channels,
+            config: self.reloader.config(),
+        }))
+    }
+}
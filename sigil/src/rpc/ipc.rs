@@ -0,0 +1,92 @@
+use super::limits::ConcurrencyLayer;
+use super::metrics::MetricsLayer;
+use super::proxy::ProxyLayer;
+use super::timeout::TimeoutLayer;
+use crate::config::RpcConfig;
+use jsonrpsee::server::{
+    serve_with_graceful_shutdown, Methods, RpcServiceBuilder, ServerBuilder, StopHandle,
+};
+use std::error::Error;
+use std::fs::{self, DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process;
+use tokio::net::UnixListener;
+
+/// Serve `methods` over HTTP on a unix socket at `path` until `stop_handle` stops. Callers are
+/// neither authenticated nor rate limited, as only the node's own user can open the socket.
+pub fn spawn(
+    path: &Path,
+    config: &RpcConfig,
+    methods: Methods,
+    metrics: MetricsLayer,
+    concurrency: ConcurrencyLayer,
+    proxy: Option<ProxyLayer>,
+    stop_handle: StopHandle,
+) -> Result<(), Box<dyn Error>> {
+    // Replace a socket left behind by an earlier run, but nothing else.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()).into());
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{} is in use by another running node", path.display()).into());
+        }
+    }
+    let listener = bind(path).map_err(|e| format!("failed to bind {}: {e}", path.display()))?;
+
+    let builder = ServerBuilder::default()
+        .max_request_body_size(config.limits.max_request_body_size)
+        .max_response_body_size(config.limits.max_response_body_size)
+        .set_rpc_middleware(
+            RpcServiceBuilder::new()
+                .layer(metrics)
+                .layer(concurrency)
+                .layer(TimeoutLayer::new(config.timeouts.clone()))
+                .option_layer(proxy),
+        )
+        .to_service_builder();
+    let path = path.to_path_buf();
+    tokio::spawn(async move {
+        loop {
+            let socket = tokio::select! {
+                result = listener.accept() => match result {
+                    Ok((socket, _)) => socket,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Failed to accept IPC connection");
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+            let service = builder.clone().build(methods.clone(), stop_handle.clone());
+            let stopped = stop_handle.clone().shutdown();
+            tokio::spawn(async move {
+                if let Err(e) = serve_with_graceful_shutdown(socket, service, stopped).await {
+                    tracing::warn!(error = %e, "IPC connection failed");
+                }
+            });
+        }
+        let _ = fs::remove_file(&path);
+    });
+    Ok(())
+}
+
+/// Bind a socket at `path` that only the node's user can open. The socket is bound in a directory
+/// only that user can enter, and moved into place once its permissions are restricted, so other
+/// users never get a moment in which they could connect.
+fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let private = path.with_file_name(format!(".{name}.{}", process::id()));
+    DirBuilder::new().mode(0o700).create(&private)?;
+    let staged = private.join("socket");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, Permissions::from_mode(0o600))?;
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&staged);
+    let _ = fs::remove_dir(&private);
+    bound
+}
@@ -0,0 +1,74 @@
+use super::client_error;
+use crate::client::SwarmClient;
+use jsonrpsee::core::{async_trait, RpcResult};
+use jsonrpsee::proc_macros::rpc;
+use libp2p::PeerId;
+
+/// The error code returned when a DHT operation fails or finds nothing.
+pub const DHT_ERROR_CODE: i32 = -32004;
+
+/// Records and providers on the Kademlia DHT, keyed and valued by UTF-8 strings.
+#[rpc(server, client, namespace = "kad")]
+pub trait KadApi {
+    /// Store `value` under `key`, returning once it has been replicated to a peer.
+    #[method(name = "put")]
+    async fn put(&self, key: String, value: String) -> RpcResult<bool>;
+
+    #[method(name = "get")]
+    async fn get(&self, key: String) -> RpcResult<String>;
+
+    /// Advertise this node as a provider of `key`.
+    #[method(name = "provide")]
+    async fn provide(&self, key: String) -> RpcResult<bool>;
+
+    #[method(name = "providers")]
+    async fn providers(&self, key: String) -> RpcResult<Vec<PeerId>>;
+}
+
+/// Serves the DHT over JSON-RPC.
+pub struct KadRpc {
+    client: SwarmClient,
+}
+
+impl KadRpc {
+    pub fn new(client: SwarmClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl KadApiServer for KadRpc {
+    async fn put(&self, key: String, value: String) -> RpcResult<bool> {
+        self.client
+            .kad_put(key.into_bytes(), value.into_bytes())
+            .await
+            .map_err(client_error)?;
+        Ok(true)
+    }
+
+    async fn get(&self, key: String) -> RpcResult<String> {
+        let value = self
+            .client
+            .kad_get(key.into_bytes())
+            .await
+            .map_err(client_error)?;
+        // Valid UTF-8, the usual case, becomes the string without being copied.
+        Ok(String::from_utf8(value)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+    }
+
+    async fn provide(&self, key: String) -> RpcResult<bool> {
+        self.client
+            .kad_provide(key.into_bytes())
+            .await
+            .map_err(client_error)?;
+        Ok(true)
+    }
+
+    async fn providers(&self, key: String) -> RpcResult<Vec<PeerId>> {
+        self.client
+            .kad_providers(key.into_bytes())
+            .await
+            .map_err(client_error)
+    }
+}
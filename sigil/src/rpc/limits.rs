@@ -0,0 +1,66 @@
+use futures::future::{self, BoxFuture, Either, Ready};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, Request};
+use jsonrpsee::MethodResponse;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tower::Layer;
+
+/// The error code returned for calls refused because too many are already running.
+pub const SERVER_BUSY_CODE: i32 = -32009;
+
+/// JSON-RPC middleware that refuses calls beyond a number running at once, across all
+/// connections, so that a flood of calls cannot exhaust the node's memory.
+#[derive(Clone)]
+pub struct ConcurrencyLayer {
+    permits: Arc<Semaphore>,
+}
+
+impl ConcurrencyLayer {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLayer {
+    type Service = Concurrency<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Concurrency {
+            inner,
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Concurrency<S> {
+    inner: S,
+    permits: Arc<Semaphore>,
+}
+
+impl<'a, S> RpcServiceT<'a> for Concurrency<S>
+where
+    S: RpcServiceT<'a> + 'a,
+{
+    type Future = Either<BoxFuture<'a, MethodResponse>, Ready<MethodResponse>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => {
+                let response = self.inner.call(request);
+                Either::Left(Box::pin(async move {
+                    let response = response.await;
+                    drop(permit);
+                    response
+                }))
+            }
+            Err(_) => {
+                let error = ErrorObject::owned(SERVER_BUSY_CODE, "server is busy", None::<()>);
+                Either::Right(future::ready(MethodResponse::error(request.id, error)))
+            }
+        }
+    }
+}
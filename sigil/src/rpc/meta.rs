@@ -0,0 +1,55 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use jsonrpsee::proc_macros::rpc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The version of the RPC API. The minor version rises when methods or fields are added and the
+/// major version when any are changed or removed, so clients can tell what they may rely on.
+pub const API_VERSION: &str = "1.2";
+
+/// The namespaces the server offers, with `sigil` standing for the methods without one.
+const MODULES: &[&str] = &["admin", "debug", "kad", "mempool", "net", "rpc", "sigil"];
+
+/// A structured response, stamped with the API version that produced it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub api_version: String,
+    #[serde(flatten)]
+    pub result: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(result: T) -> Self {
+        Self {
+            api_version: API_VERSION.to_string(),
+            result,
+        }
+    }
+}
+
+/// What the server offers, so that clients can negotiate capabilities.
+#[rpc(server, client)]
+pub trait MetaApi {
+    /// Every namespace the server offers, mapped to the API version it is served at.
+    #[method(name = "rpc_modules")]
+    async fn rpc_modules(&self) -> RpcResult<BTreeMap<String, String>>;
+
+    #[method(name = "api_version")]
+    async fn api_version(&self) -> RpcResult<String>;
+}
+
+pub struct MetaRpc;
+
+#[async_trait]
+impl MetaApiServer for MetaRpc {
+    async fn rpc_modules(&self) -> RpcResult<BTreeMap<String, String>> {
+        Ok(MODULES
+            .iter()
+            .map(|module| (module.to_string(), API_VERSION.to_string()))
+            .collect())
+    }
+
+    async fn api_version(&self) -> RpcResult<String> {
+        Ok(API_VERSION.to_string())
+    }
+}
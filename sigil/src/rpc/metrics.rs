@@ -0,0 +1,110 @@
+use super::server::RemoteAddr;
+use futures::future::BoxFuture;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::Request;
+use jsonrpsee::MethodResponse;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::family::MetricConstructor;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+use tower::Layer;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct CallLabels {
+    method: String,
+    outcome: &'static str,
+}
+
+/// Logs every RPC call and records how long each method takes, so that slow or failing methods
+/// are visible. Calls to methods other than `methods` are recorded as `unknown`, so that callers
+/// cannot grow the metrics without bound.
+#[derive(Clone)]
+pub struct MetricsLayer {
+    methods: Arc<HashSet<String>>,
+    durations: Family<CallLabels, Histogram, Buckets>,
+}
+
+/// Builds the histogram of each method and outcome with the configured buckets.
+#[derive(Clone)]
+struct Buckets(Arc<[f64]>);
+
+impl MetricConstructor<Histogram> for Buckets {
+    fn new_metric(&self) -> Histogram {
+        Histogram::new(self.0.iter().copied())
+    }
+}
+
+impl MetricsLayer {
+    pub fn new<'a>(
+        registry: &mut Registry,
+        methods: impl IntoIterator<Item = &'a str>,
+        buckets: &[f64],
+    ) -> Self {
+        let durations = Family::new_with_constructor(Buckets(buckets.into()));
+        registry.register(
+            "call_duration_seconds",
+            "How long RPC calls took, by method and outcome",
+            durations.clone(),
+        );
+        Self {
+            methods: Arc::new(methods.into_iter().map(str::to_string).collect()),
+            durations,
+        }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            methods: self.methods.clone(),
+            durations: self.durations.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Metrics<S> {
+    inner: S,
+    methods: Arc<HashSet<String>>,
+    durations: Family<CallLabels, Histogram, Buckets>,
+}
+
+impl<'a, S> RpcServiceT<'a> for Metrics<S>
+where
+    S: RpcServiceT<'a> + 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = request.method_name().to_string();
+        let caller = request.extensions().get::<RemoteAddr>().map(|addr| addr.0);
+        let known = self.methods.contains(&method);
+        let durations = self.durations.clone();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let started = Instant::now();
+            let response = response.await;
+            let duration = started.elapsed();
+            let outcome = if response.is_success() { "ok" } else { "error" };
+            tracing::info!(
+                method,
+                caller = caller.map(|addr| addr.to_string()),
+                duration_ms = duration.as_secs_f64() * 1000.0,
+                outcome,
+                "RPC call"
+            );
+            let method = if known { method } else { "unknown".to_string() };
+            durations
+                .get_or_create(&CallLabels { method, outcome })
+                .observe(duration.as_secs_f64());
+            response
+        })
+    }
+}
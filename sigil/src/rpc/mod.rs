@@ -0,0 +1,460 @@
+mod admin;
+pub mod allowlist;
+mod auth;
+pub mod client;
+mod debug;
+#[cfg(unix)]
+mod ipc;
+mod kad;
+pub mod limits;
+mod meta;
+mod metrics;
+mod net;
+mod proxy;
+pub mod rate_limit;
+pub mod server;
+mod timeout;
+mod tls;
+
+pub use admin::{AdminApiClient, AdminApiServer, AdminRpc};
+pub use auth::UNAUTHORIZED_CODE;
+pub use debug::{DebugApiClient, DebugApiServer, DebugRpc, StateDump};
+pub use kad::{KadApiClient, KadApiServer, KadRpc, DHT_ERROR_CODE};
+pub use meta::{MetaApiClient, MetaApiServer, MetaRpc, Versioned, API_VERSION};
+pub use net::{NetApiClient, NetApiServer, NetRpc};
+pub use proxy::UPSTREAM_ERROR_CODE;
+pub use timeout::TIMEOUT_CODE;
+
+use crate::client::{ClientError, SwarmClient};
+use crate::dht::RoutingTableEntry;
+use crate::event::{LoggedEvent, NodeEvent};
+use crate::log_stream::{LogRecord, LogStream};
+use crate::mempool::{MempoolError, MempoolStatus, PooledTransaction};
+use crate::node::{NodeInfo, NodeStatus};
+use crate::page::{Page, PeerQuery};
+use crate::peers::PeerInfo;
+use crate::relay::RelayInfo;
+use crate::stats::SessionStats;
+use jsonrpsee::core::{async_trait, RpcResult, SubscriptionResult};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE};
+use jsonrpsee::types::ErrorObjectOwned;
+use jsonrpsee::{PendingSubscriptionSink, SubscriptionMessage};
+use libp2p::gossipsub::PublishError;
+use libp2p::swarm::DialError;
+use libp2p::{Multiaddr, PeerId};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+use tracing_subscriber::filter::Targets;
+
+/// The error code returned when a message could not be published, such as for lack of peers.
+pub const PUBLISH_ERROR_CODE: i32 = -32001;
+
+/// The error code returned when a peer could not be dialed.
+pub const DIAL_ERROR_CODE: i32 = -32002;
+
+/// The error code returned when the node has stopped and can no longer serve calls.
+pub const NODE_UNAVAILABLE_CODE: i32 = -32006;
+
+/// Methods that only read the node's state, which may be left open when authentication is on.
+pub const READ_ONLY_METHODS: &[&str] = &[
+    "say_hello",
+    "connected_peers",
+    "kademlia_routing_table_peers",
+    "kademlia_routing_table",
+    "connected_peers_page",
+    "peers",
+    "subscribe_messages",
+    "unsubscribe_messages",
+    "subscribe_peer_events",
+    "unsubscribe_peer_events",
+    "kad_get",
+    "kad_providers",
+    "list_topics",
+    "rpc_modules",
+    "api_version",
+    "events",
+    "node_info",
+    "node_status",
+    "stats",
+    "relays",
+    "mempool_content",
+    "mempool_status",
+    "net_peerCount",
+    "net_listening",
+    "net_version",
+];
+
+/// How many transactions `mempool_content` returns when no limit is given.
+const DEFAULT_MEMPOOL_LIMIT: usize = 100;
+
+/// How many events `events` returns when no limit is given.
+const DEFAULT_EVENT_LIMIT: usize = 100;
+
+#[rpc(server, client)]
+pub trait MyApi {
+    #[method(name = "say_hello")]
+    async fn say_hello(&self, name: String) -> RpcResult<String>;
+
+    /// Publish `data` to `topic`, returning the id of the published message.
+    #[method(name = "gossipsub_publish")]
+    async fn gossipsub_publish(&self, topic: String, data: String) -> RpcResult<String>;
+
+    #[method(name = "connected_peers")]
+    async fn connected_peers(&self) -> RpcResult<Vec<PeerId>>;
+
+    /// Every peer in the Kademlia routing table, mapped to the addresses known for it.
+    /// `kademlia_routing_table` lists it a page at a time, for large networks.
+    #[method(name = "kademlia_routing_table_peers")]
+    async fn kademlia_routing_table_peers(&self) -> RpcResult<HashMap<PeerId, Vec<Multiaddr>>>;
+
+    /// A page of the Kademlia routing table, optionally filtered by bucket or peer id prefix.
+    #[method(name = "kademlia_routing_table")]
+    async fn kademlia_routing_table(
+        &self,
+        query: Option<PeerQuery>,
+    ) -> RpcResult<Versioned<Page<RoutingTableEntry>>>;
+
+    /// A page of the connected peers, optionally filtered by peer id prefix.
+    #[method(name = "connected_peers_page")]
+    async fn connected_peers_page(
+        &self,
+        query: Option<PeerQuery>,
+    ) -> RpcResult<Versioned<Page<PeerId>>>;
+
+    /// A page of the connected peers with their names and versions, optionally filtered by peer
+    /// id prefix.
+    #[method(name = "peers")]
+    async fn peers(&self, query: Option<PeerQuery>) -> RpcResult<Versioned<Page<PeerInfo>>>;
+
+    /// The node's identity, addresses, versions, and uptime.
+    #[method(name = "node_info")]
+    async fn node_info(&self) -> RpcResult<Versioned<NodeInfo>>;
+
+    /// Peer, mesh and relay counts, external addresses, and bootstrap and NAT status.
+    #[method(name = "node_status")]
+    async fn node_status(&self) -> RpcResult<Versioned<NodeStatus>>;
+
+    /// Uptime, connections, messages and hole punches since the node started.
+    #[method(name = "stats")]
+    async fn stats(&self) -> RpcResult<Versioned<SessionStats>>;
+
+    /// The relays this node has requested reservations from, and whether each was accepted.
+    #[method(name = "relays")]
+    async fn relays(&self) -> RpcResult<Vec<RelayInfo>>;
+
+    /// Dial `address`, returning the id of the peer once connected.
+    #[method(name = "dial")]
+    async fn dial(&self, address: Multiaddr) -> RpcResult<PeerId>;
+
+    /// Dial a peer at whichever addresses the node knows for it, such as from the routing table.
+    #[method(name = "dial_peer")]
+    async fn dial_peer(&self, peer_id: PeerId) -> RpcResult<PeerId>;
+
+    /// Subscribe to the topic `name`, returning false if already subscribed.
+    #[method(name = "subscribe_topic")]
+    async fn subscribe_topic(&self, name: String) -> RpcResult<bool>;
+
+    /// Unsubscribe from the topic `name`, returning false if not subscribed. The topics the node
+    /// relies on cannot be unsubscribed from.
+    #[method(name = "unsubscribe_topic")]
+    async fn unsubscribe_topic(&self, name: String) -> RpcResult<bool>;
+
+    #[method(name = "list_topics")]
+    async fn list_topics(&self) -> RpcResult<Vec<String>>;
+
+    /// Recent connections, disconnections, and other significant events, oldest first. With
+    /// `since`, the earliest events after that sequence number; otherwise the latest.
+    #[method(name = "events")]
+    async fn events(&self, limit: Option<usize>, since: Option<u64>)
+        -> RpcResult<Vec<LoggedEvent>>;
+
+    /// Stream messages as they are received, optionally only those on `topic`.
+    #[subscription(name = "subscribe_messages" => "message", unsubscribe = "unsubscribe_messages", item = NodeEvent)]
+    async fn subscribe_messages(&self, topic: Option<String>) -> SubscriptionResult;
+
+    /// Stream peer connections and disconnections as they happen.
+    #[subscription(name = "subscribe_peer_events" => "peer_event", unsubscribe = "unsubscribe_peer_events", item = NodeEvent)]
+    async fn subscribe_peer_events(&self) -> SubscriptionResult;
+
+    /// Stream the node's log lines, optionally only those matching `filter`, given as `RUST_LOG`
+    /// style target directives such as `sigil=debug,libp2p_kad=info`. Lines the node's own log
+    /// filter drops are never streamed.
+    #[subscription(name = "subscribe_logs" => "log", unsubscribe = "unsubscribe_logs", item = LogRecord)]
+    async fn subscribe_logs(&self, filter: Option<String>) -> SubscriptionResult;
+}
+
+pub struct MyApiImpl {
+    client: SwarmClient,
+    logs: LogStream,
+}
+
+impl MyApiImpl {
+    pub fn new(client: SwarmClient, logs: LogStream) -> Self {
+        Self { client, logs }
+    }
+}
+
+#[async_trait]
+impl MyApiServer for MyApiImpl {
+    async fn say_hello(&self, name: String) -> RpcResult<String> {
+        Ok(format!("Hello, {}!", name))
+    }
+
+    async fn gossipsub_publish(&self, topic: String, data: String) -> RpcResult<String> {
+        match self
+            .client
+            .gossipsub_publish(topic, data.into_bytes())
+            .await
+        {
+            Ok(id) => Ok(id.to_string()),
+            Err(e) => Err(client_error(e)),
+        }
+    }
+
+    async fn connected_peers(&self) -> RpcResult<Vec<PeerId>> {
+        self.client.connected_peers().await.map_err(client_error)
+    }
+
+    async fn kademlia_routing_table_peers(&self) -> RpcResult<HashMap<PeerId, Vec<Multiaddr>>> {
+        self.client
+            .routing_table_peers()
+            .await
+            .map_err(client_error)
+    }
+
+    async fn kademlia_routing_table(
+        &self,
+        query: Option<PeerQuery>,
+    ) -> RpcResult<Versioned<Page<RoutingTableEntry>>> {
+        let entries = self.client.routing_table().await.map_err(client_error)?;
+        Ok(Versioned::new(query.unwrap_or_default().page(
+            entries,
+            |entry| entry.peer_id,
+            |entry| Some(entry.bucket),
+        )))
+    }
+
+    async fn connected_peers_page(
+        &self,
+        query: Option<PeerQuery>,
+    ) -> RpcResult<Versioned<Page<PeerId>>> {
+        let peers = self.client.connected_peers().await.map_err(client_error)?;
+        Ok(Versioned::new(query.unwrap_or_default().page(
+            peers,
+            |peer| *peer,
+            |_| None,
+        )))
+    }
+
+    async fn peers(&self, query: Option<PeerQuery>) -> RpcResult<Versioned<Page<PeerInfo>>> {
+        let peers = self.client.peers().await.map_err(client_error)?;
+        Ok(Versioned::new(query.unwrap_or_default().page(
+            peers,
+            |peer| peer.peer_id,
+            |_| None,
+        )))
+    }
+
+    async fn node_info(&self) -> RpcResult<Versioned<NodeInfo>> {
+        self.client
+            .node_info()
+            .await
+            .map(Versioned::new)
+            .map_err(client_error)
+    }
+
+    async fn node_status(&self) -> RpcResult<Versioned<NodeStatus>> {
+        self.client
+            .node_status()
+            .await
+            .map(Versioned::new)
+            .map_err(client_error)
+    }
+
+    async fn stats(&self) -> RpcResult<Versioned<SessionStats>> {
+        self.client
+            .session_stats()
+            .await
+            .map(Versioned::new)
+            .map_err(client_error)
+    }
+
+    async fn relays(&self) -> RpcResult<Vec<RelayInfo>> {
+        Ok(self.client.my_relays().to_vec())
+    }
+
+    async fn dial(&self, address: Multiaddr) -> RpcResult<PeerId> {
+        self.client.dial(address).await.map_err(client_error)
+    }
+
+    async fn dial_peer(&self, peer_id: PeerId) -> RpcResult<PeerId> {
+        self.client.dial_peer(peer_id).await.map_err(client_error)
+    }
+
+    async fn subscribe_topic(&self, name: String) -> RpcResult<bool> {
+        self.client
+            .subscribe_topic(name)
+            .await
+            .map_err(client_error)
+    }
+
+    async fn unsubscribe_topic(&self, name: String) -> RpcResult<bool> {
+        self.client
+            .unsubscribe_topic(name)
+            .await
+            .map_err(client_error)
+    }
+
+    async fn list_topics(&self) -> RpcResult<Vec<String>> {
+        self.client.list_topics().await.map_err(client_error)
+    }
+
+    async fn events(
+        &self,
+        limit: Option<usize>,
+        since: Option<u64>,
+    ) -> RpcResult<Vec<LoggedEvent>> {
+        Ok(self
+            .client
+            .recent_events(limit.unwrap_or(DEFAULT_EVENT_LIMIT), since))
+    }
+
+    async fn subscribe_messages(
+        &self,
+        pending: PendingSubscriptionSink,
+        topic: Option<String>,
+    ) -> SubscriptionResult {
+        forward_events(pending, self.client.subscribe_events(), move |event| {
+            matches!(event, NodeEvent::Message(message)
+                if topic.as_ref().is_none_or(|topic| *topic == message.topic))
+        })
+        .await
+    }
+
+    async fn subscribe_peer_events(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        forward_events(
+            pending,
+            self.client.subscribe_events(),
+            NodeEvent::is_peer_event,
+        )
+        .await
+    }
+
+    async fn subscribe_logs(
+        &self,
+        pending: PendingSubscriptionSink,
+        filter: Option<String>,
+    ) -> SubscriptionResult {
+        let filter = match filter.map(|filter| filter.parse::<Targets>()).transpose() {
+            Ok(filter) => filter,
+            Err(e) => {
+                let error = ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e.to_string(), None::<()>);
+                pending.reject(error).await;
+                return Ok(());
+            }
+        };
+        forward_events(pending, self.logs.subscribe(), move |record: &LogRecord| {
+            filter.as_ref().is_none_or(|filter| {
+                record
+                    .level
+                    .parse()
+                    .is_ok_and(|level| filter.would_enable(&record.target, &level))
+            })
+        })
+        .await
+    }
+}
+
+/// Forward every event accepted by `filter` to a subscriber until it unsubscribes. A subscriber
+/// too slow to keep up misses events rather than holding up the node.
+async fn forward_events<T: Clone + Serialize>(
+    pending: PendingSubscriptionSink,
+    mut events: broadcast::Receiver<T>,
+    filter: impl Fn(&T) -> bool,
+) -> SubscriptionResult {
+    let sink = pending.accept().await?;
+    loop {
+        let event = tokio::select! {
+            _ = sink.closed() => return Ok(()),
+            event = events.recv() => match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        };
+        if filter(&event) {
+            sink.send(SubscriptionMessage::from_json(&event)?).await?;
+        }
+    }
+}
+
+#[rpc(server, client, namespace = "mempool")]
+pub trait MempoolApi {
+    /// Pending transactions, oldest first.
+    #[method(name = "content")]
+    async fn content(&self, limit: Option<usize>) -> RpcResult<Vec<PooledTransaction>>;
+
+    #[method(name = "status")]
+    async fn status(&self) -> RpcResult<Versioned<MempoolStatus>>;
+}
+
+/// Serves a node's mempool over JSON-RPC.
+pub struct MempoolRpc {
+    client: SwarmClient,
+}
+
+impl MempoolRpc {
+    pub fn new(client: SwarmClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl MempoolApiServer for MempoolRpc {
+    async fn content(&self, limit: Option<usize>) -> RpcResult<Vec<PooledTransaction>> {
+        self.client
+            .mempool_content(limit.unwrap_or(DEFAULT_MEMPOOL_LIMIT))
+            .await
+            .map_err(client_error)
+    }
+
+    async fn status(&self) -> RpcResult<Versioned<MempoolStatus>> {
+        self.client
+            .mempool_status()
+            .await
+            .map(Versioned::new)
+            .map_err(client_error)
+    }
+}
+
+fn publish_error(e: PublishError) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(
+        PUBLISH_ERROR_CODE,
+        format!("publish failed: {e}"),
+        None::<()>,
+    )
+}
+
+fn dial_error(e: DialError) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(DIAL_ERROR_CODE, format!("dial failed: {e}"), None::<()>)
+}
+
+/// The JSON-RPC error for a failed node command, with a code telling callers whether the fault
+/// was theirs, the network's, or the node's.
+fn client_error(e: ClientError) -> ErrorObjectOwned {
+    match e {
+        ClientError::NodeUnavailable => {
+            ErrorObjectOwned::owned(NODE_UNAVAILABLE_CODE, e.to_string(), None::<()>)
+        }
+        ClientError::Publish(e) => publish_error(e),
+        ClientError::Dial(e) => dial_error(e),
+        ClientError::Dht(e) => ErrorObjectOwned::owned(DHT_ERROR_CODE, e.to_string(), None::<()>),
+        ClientError::ReservedTopic(_)
+        | ClientError::Mempool(MempoolError::Duplicate(_) | MempoolError::Invalid(_)) => {
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e.to_string(), None::<()>)
+        }
+        e => ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>),
+    }
+}
@@ -0,0 +1,50 @@
+use super::client_error;
+use crate::client::SwarmClient;
+use jsonrpsee::core::{async_trait, RpcResult};
+use jsonrpsee::proc_macros::rpc;
+
+/// The subset of Ethereum's `net` namespace that makes sense for a sigil node, so that tooling
+/// pointed at it alongside op-geth gets sensible answers.
+#[rpc(server, client, namespace = "net")]
+pub trait NetApi {
+    /// The number of connected peers, as a hex quantity.
+    #[method(name = "peerCount")]
+    async fn peer_count(&self) -> RpcResult<String>;
+
+    /// Whether the node is listening for connections.
+    #[method(name = "listening")]
+    async fn listening(&self) -> RpcResult<bool>;
+
+    /// The configured network id, in decimal.
+    #[method(name = "version")]
+    async fn version(&self) -> RpcResult<String>;
+}
+
+/// Serves the `net` namespace over JSON-RPC.
+pub struct NetRpc {
+    client: SwarmClient,
+    network_id: u64,
+}
+
+impl NetRpc {
+    pub fn new(client: SwarmClient, network_id: u64) -> Self {
+        Self { client, network_id }
+    }
+}
+
+#[async_trait]
+impl NetApiServer for NetRpc {
+    async fn peer_count(&self) -> RpcResult<String> {
+        let peers = self.client.connected_peers().await.map_err(client_error)?;
+        Ok(format!("{:#x}", peers.len()))
+    }
+
+    async fn listening(&self) -> RpcResult<bool> {
+        let info = self.client.node_info().await.map_err(client_error)?;
+        Ok(!info.listen_addresses.is_empty())
+    }
+
+    async fn version(&self) -> RpcResult<String> {
+        Ok(self.network_id.to_string())
+    }
+}
@@ -0,0 +1,168 @@
+use crate::config::ProxyConfig;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::future::{BoxFuture, Either};
+use hmac::{Hmac, Mac};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, ErrorObjectOwned, Request};
+use jsonrpsee::{MethodResponse, ResponsePayload};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use sha2::Sha256;
+use std::error::Error;
+use std::fs;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::Layer;
+
+/// The error code returned when a forwarded call could not reach op-geth.
+pub const UPSTREAM_ERROR_CODE: i32 = -32010;
+
+/// Forwards calls in the configured namespaces to op-geth, passing its results and errors back
+/// unchanged, and lets every other call through to sigil's own methods.
+#[derive(Clone)]
+pub struct ProxyLayer {
+    upstream: Arc<Upstream>,
+}
+
+struct Upstream {
+    url: String,
+    prefixes: Vec<String>,
+    jwt_secret: Option<Vec<u8>>,
+    max_response_size: usize,
+    client: reqwest::Client,
+}
+
+impl ProxyLayer {
+    /// The proxy for the configured endpoint, or `None` if none is configured.
+    pub fn new(
+        config: &ProxyConfig,
+        max_response_size: u32,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(url) = config.url.clone() else {
+            return Ok(None);
+        };
+        let jwt_secret = match &config.jwt_secret_file {
+            Some(path) => {
+                let secret = fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read JWT secret {}: {e}", path.display()))?;
+                let secret = secret.trim();
+                let secret = hex::decode(secret.strip_prefix("0x").unwrap_or(secret))
+                    .map_err(|e| format!("invalid JWT secret {}: {e}", path.display()))?;
+                Some(secret)
+            }
+            None => None,
+        };
+        Ok(Some(Self {
+            upstream: Arc::new(Upstream {
+                url,
+                prefixes: config
+                    .namespaces
+                    .iter()
+                    .map(|namespace| format!("{namespace}_"))
+                    .collect(),
+                jwt_secret,
+                max_response_size: max_response_size as usize,
+                client: reqwest::Client::new(),
+            }),
+        }))
+    }
+}
+
+impl<S> Layer<S> for ProxyLayer {
+    type Service = Proxy<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Proxy {
+            inner,
+            upstream: self.upstream.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Proxy<S> {
+    inner: S,
+    upstream: Arc<Upstream>,
+}
+
+impl<'a, S> RpcServiceT<'a> for Proxy<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, BoxFuture<'a, MethodResponse>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = request.method_name();
+        if !self
+            .upstream
+            .prefixes
+            .iter()
+            .any(|prefix| method.starts_with(prefix.as_str()))
+        {
+            return Either::Left(self.inner.call(request));
+        }
+        let upstream = self.upstream.clone();
+        Either::Right(Box::pin(async move {
+            let id = request.id.clone();
+            match upstream.forward(&request).await {
+                Ok(UpstreamResponse {
+                    result: Some(result),
+                    ..
+                }) => MethodResponse::response(
+                    id,
+                    ResponsePayload::success(result),
+                    upstream.max_response_size,
+                ),
+                Ok(UpstreamResponse {
+                    error: Some(error), ..
+                }) => MethodResponse::error(id, error),
+                Ok(_) => MethodResponse::error(
+                    id,
+                    ErrorObject::owned(
+                        UPSTREAM_ERROR_CODE,
+                        "empty response from op-geth",
+                        None::<()>,
+                    ),
+                ),
+                Err(e) => {
+                    let message = format!("op-geth unavailable: {e}");
+                    MethodResponse::error(
+                        id,
+                        ErrorObject::owned(UPSTREAM_ERROR_CODE, message, None::<()>),
+                    )
+                }
+            }
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct UpstreamResponse {
+    result: Option<Box<RawValue>>,
+    error: Option<ErrorObjectOwned>,
+}
+
+impl Upstream {
+    async fn forward(&self, request: &Request<'_>) -> Result<UpstreamResponse, reqwest::Error> {
+        let mut post = self.client.post(&self.url).json(request);
+        if let Some(secret) = &self.jwt_secret {
+            post = post.bearer_auth(jwt(secret));
+        }
+        post.send().await?.error_for_status()?.json().await
+    }
+}
+
+/// An engine API token, which op-geth accepts for a minute either side of its issue time.
+fn jwt(secret: &[u8]) -> String {
+    let issued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims = URL_SAFE_NO_PAD.encode(format!(r#"{{"iat":{issued_at}}}"#));
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(format!("{header}.{claims}").as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("{header}.{claims}.{signature}")
+}
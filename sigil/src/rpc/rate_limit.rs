@@ -0,0 +1,183 @@
+use super::server::RemoteAddr;
+use crate::config::{RateConfig, RateLimitConfig};
+use futures::future::{self, Either, Ready};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, Request};
+use jsonrpsee::MethodResponse;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use tower::Layer;
+
+/// The error code returned for calls refused by a rate limit.
+pub const RATE_LIMITED_CODE: i32 = -32005;
+
+/// How many callers are tracked before idle ones are forgotten.
+const MAX_TRACKED_CALLERS: usize = 4096;
+
+/// A token bucket, which allows bursts of up to `burst` calls and refills at `per_second`.
+#[derive(Clone, Debug)]
+pub struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    pub fn new(rate: &RateConfig, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: &RateConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst as f64);
+        self.updated = now;
+    }
+
+    /// Whether a call may be made at `now`, without making it.
+    pub fn ready(&mut self, rate: &RateConfig, now: Instant) -> bool {
+        self.refill(rate, now);
+        self.tokens >= 1.0
+    }
+
+    pub fn try_take(&mut self, rate: &RateConfig, now: Instant) -> bool {
+        if !self.ready(rate, now) {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// Whether the bucket has refilled, so that it is indistinguishable from a new one.
+    pub fn is_full(&mut self, rate: &RateConfig, now: Instant) -> bool {
+        self.refill(rate, now);
+        self.tokens >= rate.burst as f64
+    }
+}
+
+/// Limits how often each caller may call the server, and how often each listed method may be
+/// called across all callers so that expensive queries cannot stall the node.
+pub struct RateLimiter {
+    config: RwLock<RateLimitConfig>,
+    callers: Mutex<HashMap<IpAddr, Bucket>>,
+    methods: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            callers: Mutex::new(HashMap::new()),
+            methods: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replace the limits, forgetting how much of the old ones callers had used.
+    pub fn set_config(&self, config: RateLimitConfig) {
+        *self
+            .config
+            .write()
+            .expect("rate limiter lock is never poisoned") = config;
+        self.callers
+            .lock()
+            .expect("rate limiter lock is never poisoned")
+            .clear();
+        self.methods
+            .lock()
+            .expect("rate limiter lock is never poisoned")
+            .clear();
+    }
+
+    /// Take a call to `method` from `caller` at `now`, returning false if a limit refuses it.
+    /// Calls from an unknown address are only subject to method limits.
+    pub fn check(&self, caller: Option<IpAddr>, method: &str, now: Instant) -> bool {
+        let config = self
+            .config
+            .read()
+            .expect("rate limiter lock is never poisoned");
+        if let (Some(rate), Some(caller)) = (&config.per_ip, caller) {
+            let mut callers = self
+                .callers
+                .lock()
+                .expect("rate limiter lock is never poisoned");
+            if callers.len() >= MAX_TRACKED_CALLERS && !callers.contains_key(&caller) {
+                // A caller whose bucket has refilled is indistinguishable from a new one.
+                callers.retain(|_, bucket| !bucket.is_full(rate, now));
+            }
+            let bucket = callers
+                .entry(caller)
+                .or_insert_with(|| Bucket::new(rate, now));
+            if !bucket.try_take(rate, now) {
+                return false;
+            }
+        }
+        if let Some(rate) = config.methods.get(method) {
+            let mut methods = self
+                .methods
+                .lock()
+                .expect("rate limiter lock is never poisoned");
+            let bucket = methods
+                .entry(method.to_string())
+                .or_insert_with(|| Bucket::new(rate, now));
+            if !bucket.try_take(rate, now) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// JSON-RPC middleware that refuses calls over the configured rate limits.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<'a, S> RpcServiceT<'a> for RateLimit<S>
+where
+    S: RpcServiceT<'a>,
+{
+    type Future = Either<S::Future, Ready<MethodResponse>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let caller = request
+            .extensions()
+            .get::<RemoteAddr>()
+            .map(|addr| addr.0.ip());
+        if self
+            .limiter
+            .check(caller, request.method_name(), Instant::now())
+        {
+            Either::Left(self.inner.call(request))
+        } else {
+            let error = ErrorObject::owned(RATE_LIMITED_CODE, "rate limit exceeded", None::<()>);
+            Either::Right(future::ready(MethodResponse::error(request.id, error)))
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use super::allowlist::AllowlistLayer;
+use super::auth::{AuthLayer, TokenLayer};
+use super::limits::ConcurrencyLayer;
+use super::metrics::MetricsLayer;
+use super::proxy::ProxyLayer;
+use super::rate_limit::RateLimitLayer;
+use super::timeout::TimeoutLayer;
+use super::tls;
+use super::{
+    AdminApiServer, AdminRpc, DebugApiServer, DebugRpc, KadApiServer, KadRpc, MempoolApiServer,
+    MempoolRpc, MetaApiServer, MetaRpc, MyApiImpl, MyApiServer, NetApiServer, NetRpc,
+};
+use crate::client::SwarmClient;
+use crate::config::{AuthConfig, CorsConfig, RpcConfig};
+use crate::log_stream::LogStream;
+use crate::reload::Reloader;
+use http::{HeaderName, HeaderValue, Method};
+use jsonrpsee::server::{
+    serve_with_graceful_shutdown, stop_channel, HttpRequest, Methods, RpcModule, RpcServiceBuilder,
+    ServerBuilder, ServerHandle,
+};
+use prometheus_client::registry::Registry;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tower::Service;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Start serving JSON-RPC over HTTP and WebSocket, or HTTPS and WSS if TLS is configured, on one
+/// listener or on a listener each, and on a unix socket if one is configured, returning a handle
+/// that stops the server when dropped.
+pub async fn start(
+    config: &RpcConfig,
+    client: SwarmClient,
+    reloader: Arc<Reloader>,
+    registry: &mut Registry,
+    buckets: &[f64],
+    logs: LogStream,
+) -> Result<ServerHandle, Box<dyn Error>> {
+    let mut module = RpcModule::new(());
+    module.merge(MyApiImpl::new(client.clone(), logs).into_rpc())?;
+    module.merge(MempoolRpc::new(client.clone()).into_rpc())?;
+    module.merge(DebugRpc::new(client.clone(), reloader.clone()).into_rpc())?;
+    module.merge(MetaRpc.into_rpc())?;
+    module.merge(NetRpc::new(client.clone(), config.network_id).into_rpc())?;
+    module.merge(KadRpc::new(client.clone()).into_rpc())?;
+    let methods = Methods::from(module.clone());
+    // Administration is only served where callers must present a token, or over the unix socket,
+    // which only the host's users can reach.
+    module.merge(AdminRpc::new(client, reloader.clone()).into_rpc())?;
+    let admin_methods = Methods::from(module);
+
+    // Rate limiting is always in place, so that reloading can impose limits.
+    let metrics = MetricsLayer::new(registry, admin_methods.method_names(), buckets);
+    let timeout = TimeoutLayer::new(config.timeouts.clone());
+    let concurrency = ConcurrencyLayer::new(config.limits.max_concurrent_requests);
+    let cors = cors_layer(&config.cors)?;
+    let proxy = ProxyLayer::new(&config.proxy, config.limits.max_response_body_size)?;
+    let service_builder = |auth: &AuthConfig| -> Result<_, Box<dyn Error>> {
+        let token = auth.token()?;
+        let methods = match token {
+            Some(_) => admin_methods.clone(),
+            None => methods.clone(),
+        };
+        let http_middleware = tower::ServiceBuilder::new()
+            .option_layer(cors.clone())
+            .option_layer(token.clone().map(TokenLayer::new));
+        let rpc_middleware = RpcServiceBuilder::new()
+            .layer(metrics.clone())
+            .layer(AllowlistLayer::new(reloader.allowlist()))
+            .layer(concurrency.clone())
+            .layer(RateLimitLayer::new(reloader.rate_limiter()))
+            .option_layer(token.is_some().then(|| AuthLayer::new(auth.open_read_only)))
+            .layer(timeout.clone())
+            .option_layer(proxy.clone());
+        let builder = ServerBuilder::default()
+            .max_connections(config.limits.max_connections)
+            .max_subscriptions_per_connection(config.limits.max_subscriptions_per_connection)
+            .max_request_body_size(config.limits.max_request_body_size)
+            .max_response_body_size(config.limits.max_response_body_size)
+            .set_http_middleware(http_middleware)
+            .set_rpc_middleware(rpc_middleware);
+        Ok((builder, methods))
+    };
+    let tls = tls::acceptor(&config.tls, config.listen)?;
+    let mut listeners = Vec::new();
+    match &config.ws {
+        Some(ws) => {
+            let (http, http_methods) = service_builder(&config.auth)?;
+            let (ws_builder, ws_methods) =
+                service_builder(ws.auth.as_ref().unwrap_or(&config.auth))?;
+            listeners.push((
+                TcpListener::bind(config.listen).await?,
+                http.http_only(),
+                http_methods,
+            ));
+            listeners.push((
+                TcpListener::bind(ws.listen).await?,
+                ws_builder.ws_only(),
+                ws_methods,
+            ));
+        }
+        None => {
+            let (builder, methods) = service_builder(&config.auth)?;
+            listeners.push((TcpListener::bind(config.listen).await?, builder, methods));
+        }
+    }
+
+    let (stop_handle, server_handle) = stop_channel();
+    if let Some(path) = &config.ipc_path {
+        #[cfg(unix)]
+        super::ipc::spawn(
+            path,
+            config,
+            admin_methods,
+            metrics,
+            concurrency,
+            proxy,
+            stop_handle.clone(),
+        )?;
+        #[cfg(not(unix))]
+        return Err(format!(
+            "cannot serve RPC on {}: unix sockets are unsupported",
+            path.display()
+        )
+        .into());
+    }
+    // jsonrpsee only counts connections while they have a request in flight, so idle ones are
+    // capped here, across all listeners, to bound the file descriptors they hold.
+    let open = Arc::new(Semaphore::new(config.limits.max_connections as usize));
+    // Accept connections ourselves rather than through `Server`, so that they can be wrapped in
+    // TLS before being handed to the service.
+    for (listener, builder, methods) in listeners {
+        let builder = builder.to_service_builder();
+        let stop_handle = stop_handle.clone();
+        let tls = tls.clone();
+        let open = open.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, addr) = tokio::select! {
+                    result = listener.accept() => match result {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Failed to accept RPC connection");
+                            continue;
+                        }
+                    },
+                    _ = stop_handle.clone().shutdown() => break,
+                };
+                let Ok(permit) = open.clone().try_acquire_owned() else {
+                    tracing::warn!(%addr, "Refusing RPC connection: too many open");
+                    continue;
+                };
+                let service = WithRemoteAddr {
+                    inner: builder.clone().build(methods.clone(), stop_handle.clone()),
+                    addr,
+                };
+                let stopped = stop_handle.clone().shutdown();
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    let result = match tls {
+                        Some(acceptor) => match acceptor.accept(socket).await {
+                            Ok(stream) => {
+                                serve_with_graceful_shutdown(stream, service, stopped).await
+                            }
+                            Err(e) => Err(e.into()),
+                        },
+                        None => serve_with_graceful_shutdown(socket, service, stopped).await,
+                    };
+                    if let Err(e) = result {
+                        tracing::warn!(error = %e, "RPC connection failed");
+                    }
+                    drop(permit);
+                });
+            }
+        });
+    }
+    Ok(server_handle)
+}
+
+/// The address of the connection a request arrived on, carried in the request's extensions.
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteAddr(pub SocketAddr);
+
+#[derive(Clone)]
+struct WithRemoteAddr<S> {
+    inner: S,
+    addr: SocketAddr,
+}
+
+impl<S, B> Service<HttpRequest<B>> for WithRemoteAddr<S>
+where
+    S: Service<HttpRequest<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: HttpRequest<B>) -> Self::Future {
+        request.extensions_mut().insert(RemoteAddr(self.addr));
+        self.inner.call(request)
+    }
+}
+
+/// The CORS policy for the configured origins, or `None` if no origins are allowed.
+fn cors_layer(config: &CorsConfig) -> Result<Option<CorsLayer>, Box<dyn Error>> {
+    if config.allowed_origins.is_empty() {
+        return Ok(None);
+    }
+    let origins = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .allowed_origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    };
+    let methods = config
+        .allowed_methods
+        .iter()
+        .map(|method| method.parse::<Method>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let headers = config
+        .allowed_headers
+        .iter()
+        .map(|header| header.parse::<HeaderName>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .max_age(Duration::from_secs(config.max_age_secs)),
+    ))
+}
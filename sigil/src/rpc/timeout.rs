@@ -0,0 +1,70 @@
+use crate::config::TimeoutConfig;
+use futures::future::BoxFuture;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::types::{ErrorObject, Request};
+use jsonrpsee::MethodResponse;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::Layer;
+
+/// The error code returned for calls that did not finish within their method's timeout.
+pub const TIMEOUT_CODE: i32 = -32007;
+
+/// Fails calls that run longer than their method's timeout, so that a stalled query cannot hold
+/// a caller forever.
+#[derive(Clone)]
+pub struct TimeoutLayer {
+    config: Arc<TimeoutConfig>,
+}
+
+impl TimeoutLayer {
+    pub fn new(config: TimeoutConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    config: Arc<TimeoutConfig>,
+}
+
+impl<'a, S> RpcServiceT<'a> for Timeout<S>
+where
+    S: RpcServiceT<'a> + 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let secs = self
+            .config
+            .methods
+            .get(request.method_name())
+            .copied()
+            .unwrap_or(self.config.default_secs);
+        let id = request.id.clone();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            match tokio::time::timeout(Duration::from_secs(secs), response).await {
+                Ok(response) => response,
+                Err(_) => {
+                    let message = format!("call did not finish within {secs} seconds");
+                    MethodResponse::error(id, ErrorObject::owned(TIMEOUT_CODE, message, None::<()>))
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use crate::config::TlsConfig;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+/// Build the acceptor that terminates TLS on RPC connections, or `None` to serve plaintext.
+pub fn acceptor(
+    config: &TlsConfig,
+    listen: SocketAddr,
+) -> Result<Option<TlsAcceptor>, Box<dyn Error>> {
+    let (certs, key) = match (&config.cert_path, &config.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = CertificateDer::pem_file_iter(cert_path)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| format!("failed to read certificate {}: {e}", cert_path.display()))?;
+            let key = PrivateKeyDer::from_pem_file(key_path)
+                .map_err(|e| format!("failed to read key {}: {e}", key_path.display()))?;
+            (certs, key)
+        }
+        (None, None) if config.self_signed => self_signed(listen)?,
+        (None, None) => return Ok(None),
+        _ => return Err("TLS needs both a certificate and a key".into()),
+    };
+
+    let mut server_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+}
+
+/// Generate a throwaway certificate for local development, which clients will not trust unless
+/// told to.
+fn self_signed(
+    listen: SocketAddr,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Box<dyn Error>> {
+    let names = vec!["localhost".to_string(), listen.ip().to_string()];
+    let certified = rcgen::generate_simple_self_signed(names)?;
+    tracing::warn!("Serving RPC with a self-signed certificate");
+    let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+    Ok((vec![certified.cert.der().clone()], key.into()))
+}
@@ -0,0 +1,63 @@
+use std::future::poll_fn;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+/// A recurring job the node runs between handling events and commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Job {
+    /// Republish reliable deliveries whose recipients have not acknowledged them in time.
+    RetryDeliveries,
+    /// Write the node's view of the network to its snapshot.
+    SaveSnapshot,
+    /// Refresh the DHT's routing table by looking up the node's own id.
+    KadBootstrap,
+    /// Request again the reservations that relays have closed.
+    CheckRelays,
+    /// Publish the bundles of small messages that have not filled up.
+    FlushBundles,
+    /// Dial the configured and remembered peers whose dials the dial limits deferred.
+    DialDeferred,
+    /// Forget the blob transfers that have gone idle.
+    ExpireBlobs,
+    /// Hold each connection open for as long as what its peer is to the node asks.
+    KeepAlive,
+    /// Disrupt the node at random, in builds with the `chaos` feature.
+    Chaos,
+}
+
+/// The node's recurring jobs, each due on its own interval, so that features schedule their work
+/// here rather than each adding a timer to the event loop.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<(Job, Interval)>,
+}
+
+impl Scheduler {
+    /// Run `job` every `period`, the first time once a period has passed. Runs that fall behind,
+    /// such as while the event loop is busy, are delayed rather than caught up in a burst.
+    pub fn every(&mut self, job: Job, period: Duration) {
+        let mut interval = time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        self.jobs.push((job, interval));
+    }
+
+    /// The jobs that have been scheduled, in the order they were.
+    pub fn jobs(&self) -> impl Iterator<Item = Job> + '_ {
+        self.jobs.iter().map(|(job, _)| *job)
+    }
+
+    /// Wait until a job is due and return it. Of jobs due together, the one scheduled first is
+    /// returned first. Without jobs, this never completes.
+    pub async fn next(&mut self) -> Job {
+        poll_fn(|cx| {
+            for (job, interval) in &mut self.jobs {
+                if interval.poll_tick(cx).is_ready() {
+                    return Poll::Ready(*job);
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
@@ -0,0 +1,52 @@
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// The node's view of the network when it was last written, restored at startup so that a
+/// restarted node need not rediscover its peers from the configured ones alone.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSnapshot {
+    /// Peers the node was connected to, best first, with the addresses they can be dialed at.
+    pub peers: Vec<SnapshotPeer>,
+
+    /// The addresses of the relays the node held reservations on.
+    pub relays: Vec<Multiaddr>,
+
+    /// The addresses peers confirmed they could reach the node at.
+    pub external_addresses: Vec<Multiaddr>,
+
+    /// The gossipsub topics the node was subscribed to.
+    pub topics: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotPeer {
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+}
+
+impl NetworkSnapshot {
+    /// Read the snapshot at `path`, or `None` if none has been written yet.
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("failed to read snapshot {}: {e}", path.display()).into()),
+        };
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| format!("invalid snapshot {}: {e}", path.display()).into())
+    }
+
+    /// Write the snapshot to `path`, replacing the previous one only once it is complete so that
+    /// a crash mid-write leaves the old snapshot intact.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let staged = path.with_extension("json.tmp");
+        fs::write(&staged, serde_json::to_vec_pretty(self)?)
+            .and_then(|()| fs::rename(&staged, path))
+            .map_err(|e| format!("failed to write snapshot {}: {e}", path.display()).into())
+    }
+}
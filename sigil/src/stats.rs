@@ -0,0 +1,202 @@
+use libp2p::{gossipsub::TopicHash, PeerId};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::{counter::Counter, family::Family};
+use prometheus_client::registry::Registry;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// The span, in seconds, over which message and byte rates are averaged.
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// How many peers' contributions are counted on each topic. Past it, a newly seen peer replaces
+/// the one that forwarded the fewest messages.
+pub const MAX_TOPIC_PEERS: usize = 64;
+
+/// A snapshot of the traffic this node has received on one topic.
+#[derive(Clone, Debug, Serialize)]
+pub struct TopicStats {
+    pub topic: String,
+    pub messages: u64,
+    pub bytes: u64,
+    pub duplicates: u64,
+    pub messages_per_sec: f64,
+    pub bytes_per_sec: f64,
+
+    /// The fraction of received messages that were duplicates of ones already accepted.
+    pub duplicate_rate: f64,
+
+    /// How many messages each connected peer forwarded to us, for at most `MAX_TOPIC_PEERS` of
+    /// those that forwarded the most.
+    pub peers: HashMap<PeerId, u64>,
+}
+
+/// Totals since the node started, for a quick check that it is doing what it should.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub uptime_secs: u64,
+
+    /// Connections established, counting every connection to a peer rather than the peer once.
+    pub connections: u64,
+
+    /// Gossipsub messages this node published, including retried deliveries.
+    pub messages_published: u64,
+
+    /// Gossipsub messages peers forwarded to us, before validation.
+    pub messages_received: u64,
+
+    /// Direct connections upgraded from relayed ones by hole punching, and attempts that failed.
+    pub holepunches_succeeded: u64,
+    pub holepunches_failed: u64,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TopicLabels {
+    topic: String,
+}
+
+#[derive(Default)]
+struct TopicCounters {
+    messages: u64,
+    bytes: u64,
+    duplicates: u64,
+    peers: HashMap<PeerId, u64>,
+
+    /// Messages and bytes received in each recent second, oldest first.
+    window: VecDeque<(u64, u64, u64)>,
+}
+
+impl TopicCounters {
+    fn record_peer(&mut self, peer: PeerId) {
+        if self.peers.len() >= MAX_TOPIC_PEERS && !self.peers.contains_key(&peer) {
+            let fewest = self
+                .peers
+                .iter()
+                .min_by_key(|(_, messages)| **messages)
+                .map(|(peer, _)| *peer);
+            if let Some(fewest) = fewest {
+                self.peers.remove(&fewest);
+            }
+        }
+        *self.peers.entry(peer).or_default() += 1;
+    }
+
+    fn prune(&mut self, second: u64) {
+        while let Some(&(oldest, _, _)) = self.window.front() {
+            if oldest + RATE_WINDOW_SECS > second {
+                break;
+            }
+            self.window.pop_front();
+        }
+    }
+}
+
+/// Per-topic gossip statistics, mirrored into the metrics registry so operators can spot hot or
+/// abusive topics.
+pub struct GossipStats {
+    started: Instant,
+    topics: HashMap<TopicHash, TopicCounters>,
+    messages: Family<TopicLabels, Counter>,
+    bytes: Family<TopicLabels, Counter>,
+    duplicates: Family<TopicLabels, Counter>,
+}
+
+impl GossipStats {
+    pub fn new(registry: &mut Registry) -> Self {
+        let stats = Self {
+            started: Instant::now(),
+            topics: HashMap::new(),
+            messages: Family::default(),
+            bytes: Family::default(),
+            duplicates: Family::default(),
+        };
+        registry.register(
+            "topic_messages",
+            "Messages received per topic",
+            stats.messages.clone(),
+        );
+        registry.register(
+            "topic_bytes",
+            "Payload bytes received per topic",
+            stats.bytes.clone(),
+        );
+        registry.register(
+            "topic_duplicates",
+            "Duplicate messages received per topic",
+            stats.duplicates.clone(),
+        );
+        stats
+    }
+
+    /// Record a message on `topic` forwarded to us by `peer`.
+    pub fn record_message(&mut self, topic: &TopicHash, peer: PeerId, bytes: usize, now: Instant) {
+        let second = now.duration_since(self.started).as_secs();
+        let counters = self.topics.entry(topic.clone()).or_default();
+        counters.messages += 1;
+        counters.bytes += bytes as u64;
+        counters.record_peer(peer);
+        counters.prune(second);
+        match counters.window.back_mut() {
+            Some((last, messages, window_bytes)) if *last == second => {
+                *messages += 1;
+                *window_bytes += bytes as u64;
+            }
+            _ => counters.window.push_back((second, 1, bytes as u64)),
+        }
+
+        let labels = Self::labels(topic);
+        self.messages.get_or_create(&labels).inc();
+        self.bytes.get_or_create(&labels).inc_by(bytes as u64);
+    }
+
+    /// Forget what `peer` forwarded to us, now that we have no connection to it.
+    pub fn forget_peer(&mut self, peer: &PeerId) {
+        for counters in self.topics.values_mut() {
+            counters.peers.remove(peer);
+        }
+    }
+
+    /// Record that a message received on `topic` duplicated one we had already accepted.
+    pub fn record_duplicate(&mut self, topic: &TopicHash) {
+        self.topics.entry(topic.clone()).or_default().duplicates += 1;
+        self.duplicates.get_or_create(&Self::labels(topic)).inc();
+    }
+
+    pub fn snapshot(&mut self, now: Instant) -> Vec<TopicStats> {
+        let elapsed = now.duration_since(self.started);
+        let second = elapsed.as_secs();
+        let span = elapsed.as_secs_f64().clamp(1.0, RATE_WINDOW_SECS as f64);
+        self.topics
+            .iter_mut()
+            .map(|(topic, counters)| {
+                counters.prune(second);
+                let (messages, bytes) = counters
+                    .window
+                    .iter()
+                    .fold((0, 0), |(m, b), (_, messages, bytes)| {
+                        (m + messages, b + bytes)
+                    });
+                TopicStats {
+                    topic: topic.to_string(),
+                    messages: counters.messages,
+                    bytes: counters.bytes,
+                    duplicates: counters.duplicates,
+                    messages_per_sec: messages as f64 / span,
+                    bytes_per_sec: bytes as f64 / span,
+                    duplicate_rate: if counters.messages == 0 {
+                        0.0
+                    } else {
+                        counters.duplicates as f64 / counters.messages as f64
+                    },
+                    peers: counters.peers.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn labels(topic: &TopicHash) -> TopicLabels {
+        TopicLabels {
+            topic: topic.to_string(),
+        }
+    }
+}
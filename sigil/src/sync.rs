@@ -0,0 +1,345 @@
+use crate::event::NodeEvent;
+use libp2p::{request_response, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot};
+
+/// The request-response protocol a joining node uses to download a snapshot of the application
+/// state from a peer, so it does not start from an empty state.
+pub const SYNC_PROTOCOL: &str = "/sigil/sync/1.0.0";
+
+/// How long a fresh snapshot is handed to further peers that start a sync, rather than taking
+/// another, so that peers asking again and again cannot have the whole state copied each time.
+const SNAPSHOT_REUSE: Duration = Duration::from_secs(10);
+
+pub type SyncBehaviour = request_response::cbor::Behaviour<SyncRequest, SyncResponse>;
+
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+/// The application-defined key-value state that nodes synchronize when joining.
+pub trait SyncState: Send + Sync + 'static {
+    /// Every entry of the current state, in a stable order.
+    fn snapshot(&self) -> Vec<Entry>;
+
+    /// Check a snapshot downloaded from a peer before it replaces the local state.
+    fn verify(&self, _entries: &[Entry]) -> bool {
+        true
+    }
+
+    /// Replace the local state with a verified snapshot.
+    fn restore(&self, entries: Vec<Entry>);
+}
+
+/// The default state, an in-memory map.
+#[derive(Default)]
+pub struct MemoryState {
+    entries: Mutex<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryState {
+    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries
+            .lock()
+            .expect("state lock is never poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    pub fn insert(&self, key: Vec<u8>, value: Vec<u8>) {
+        self.entries
+            .lock()
+            .expect("state lock is never poisoned")
+            .insert(key, value);
+    }
+}
+
+impl SyncState for MemoryState {
+    fn snapshot(&self) -> Vec<Entry> {
+        self.entries
+            .lock()
+            .expect("state lock is never poisoned")
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn restore(&self, entries: Vec<Entry>) {
+        *self.entries.lock().expect("state lock is never poisoned") = entries.into_iter().collect();
+    }
+}
+
+/// A request for the chunk of entries starting at `offset`. The first request omits the snapshot
+/// id, asking the peer to take a fresh snapshot; the rest name it so every chunk is consistent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyncRequest {
+    pub snapshot: Option<u64>,
+    pub offset: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Chunk {
+        snapshot: u64,
+        total: u64,
+        entries: Vec<Entry>,
+    },
+    /// The requested snapshot is no longer held.
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    /// A sync with another peer is already in progress.
+    InProgress,
+    /// The downloaded snapshot failed verification and was discarded.
+    Rejected,
+    /// The peer discarded the snapshot before we finished downloading it.
+    Expired,
+    Interrupted(String),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::InProgress => write!(f, "state sync already in progress"),
+            SyncError::Rejected => write!(f, "state snapshot failed verification"),
+            SyncError::Expired => write!(f, "state snapshot expired during sync"),
+            SyncError::Interrupted(e) => write!(f, "state sync interrupted: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+type SyncSender = oneshot::Sender<Result<u64, SyncError>>;
+
+struct ActiveSync {
+    peer: PeerId,
+    snapshot: Option<u64>,
+    entries: Vec<Entry>,
+    sender: Option<SyncSender>,
+}
+
+/// Serves snapshots of the local state to joining peers, and downloads one when we join.
+pub struct StateSync {
+    state: Arc<dyn SyncState>,
+    chunk_entries: usize,
+    next_snapshot: u64,
+    /// The newest snapshot taken, and when.
+    latest: Option<(u64, Instant, Arc<Vec<Entry>>)>,
+    /// The snapshot each peer is downloading. A peer starting another sync replaces only its own.
+    served: HashMap<PeerId, (u64, Arc<Vec<Entry>>)>,
+    active: Option<ActiveSync>,
+    request: Option<request_response::OutboundRequestId>,
+    synced: bool,
+    events: broadcast::Sender<NodeEvent>,
+}
+
+impl StateSync {
+    pub fn new(
+        state: Arc<dyn SyncState>,
+        chunk_entries: usize,
+        events: broadcast::Sender<NodeEvent>,
+    ) -> Self {
+        Self {
+            state,
+            chunk_entries: chunk_entries.max(1),
+            next_snapshot: 0,
+            latest: None,
+            served: HashMap::new(),
+            active: None,
+            request: None,
+            synced: false,
+            events,
+        }
+    }
+
+    pub fn set_state(&mut self, state: Arc<dyn SyncState>) {
+        self.state = state;
+        self.latest = None;
+        self.served.clear();
+    }
+
+    /// Forget the snapshot `peer` was downloading, as it has disconnected.
+    pub fn forget_peer(&mut self, peer: &PeerId) {
+        self.served.remove(peer);
+    }
+
+    /// Whether a snapshot has been downloaded and restored since this node started.
+    pub fn synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Download the state from `peer`, answering `sender` with the number of entries restored.
+    pub fn start(
+        &mut self,
+        behaviour: &mut SyncBehaviour,
+        peer: PeerId,
+        sender: Option<SyncSender>,
+    ) {
+        if self.active.is_some() {
+            if let Some(sender) = sender {
+                let _ = sender.send(Err(SyncError::InProgress));
+            }
+            return;
+        }
+        self.active = Some(ActiveSync {
+            peer,
+            snapshot: None,
+            entries: Vec::new(),
+            sender,
+        });
+        self.request_chunk(behaviour);
+    }
+
+    pub fn handle_event(
+        &mut self,
+        behaviour: &mut SyncBehaviour,
+        event: request_response::Event<SyncRequest, SyncResponse>,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => {
+                let response = self.serve(peer, request, Instant::now());
+                if behaviour.send_response(channel, response).is_err() {
+                    tracing::warn!(%peer, "Failed to serve state snapshot");
+                }
+            }
+            request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } if self.request == Some(request_id) => {
+                self.request = None;
+                match response {
+                    SyncResponse::Chunk {
+                        snapshot,
+                        total,
+                        entries,
+                    } => self.handle_chunk(behaviour, snapshot, total, entries),
+                    SyncResponse::Expired => self.finish(Err(SyncError::Expired)),
+                }
+            }
+            request_response::Event::OutboundFailure {
+                request_id, error, ..
+            } if self.request == Some(request_id) => {
+                self.request = None;
+                self.finish(Err(SyncError::Interrupted(error.to_string())));
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::warn!(%peer, %error, "Failed to serve state snapshot");
+            }
+            _ => {}
+        }
+    }
+
+    fn serve(&mut self, peer: PeerId, request: SyncRequest, now: Instant) -> SyncResponse {
+        let (snapshot, entries) = match request.snapshot {
+            Some(id) => match self.served.get(&peer).filter(|(served, _)| *served == id) {
+                Some((id, entries)) => (*id, entries.clone()),
+                None => return SyncResponse::Expired,
+            },
+            None => {
+                let (id, entries) = match &self.latest {
+                    Some((id, taken_at, entries))
+                        if now.duration_since(*taken_at) < SNAPSHOT_REUSE =>
+                    {
+                        (*id, entries.clone())
+                    }
+                    _ => {
+                        let id = self.next_snapshot;
+                        self.next_snapshot += 1;
+                        let entries = Arc::new(self.state.snapshot());
+                        self.latest = Some((id, now, entries.clone()));
+                        (id, entries)
+                    }
+                };
+                self.served.insert(peer, (id, entries.clone()));
+                (id, entries)
+            }
+        };
+        let start = (request.offset as usize).min(entries.len());
+        let end = (start + self.chunk_entries).min(entries.len());
+        SyncResponse::Chunk {
+            snapshot,
+            total: entries.len() as u64,
+            entries: entries[start..end].to_vec(),
+        }
+    }
+
+    fn handle_chunk(
+        &mut self,
+        behaviour: &mut SyncBehaviour,
+        snapshot: u64,
+        total: u64,
+        entries: Vec<Entry>,
+    ) {
+        let Some(active) = &mut self.active else {
+            return;
+        };
+        if active.snapshot.is_some_and(|id| id != snapshot)
+            || (entries.is_empty() && (active.entries.len() as u64) < total)
+        {
+            self.finish(Err(SyncError::Interrupted(
+                "peer returned an inconsistent snapshot".to_string(),
+            )));
+            return;
+        }
+        active.snapshot = Some(snapshot);
+        active.entries.extend(entries);
+        let received = active.entries.len() as u64;
+        let _ = self.events.send(NodeEvent::SyncProgress {
+            peer: active.peer,
+            received,
+            total,
+        });
+        if received < total {
+            self.request_chunk(behaviour);
+            return;
+        }
+
+        let entries = std::mem::take(&mut active.entries);
+        if !self.state.verify(&entries) {
+            self.finish(Err(SyncError::Rejected));
+            return;
+        }
+        self.state.restore(entries);
+        self.synced = true;
+        self.finish(Ok(received));
+    }
+
+    fn request_chunk(&mut self, behaviour: &mut SyncBehaviour) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        let request = SyncRequest {
+            snapshot: active.snapshot,
+            offset: active.entries.len() as u64,
+        };
+        self.request = Some(behaviour.send_request(&active.peer, request));
+    }
+
+    fn finish(&mut self, result: Result<u64, SyncError>) {
+        let Some(active) = self.active.take() else {
+            return;
+        };
+        let _ = self.events.send(NodeEvent::SyncFinished {
+            peer: active.peer,
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+        if let Some(sender) = active.sender {
+            let _ = sender.send(result);
+        }
+    }
+}
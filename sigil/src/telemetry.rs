@@ -0,0 +1,360 @@
+use crate::config::{OtlpConfig, TelemetryConfig};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The largest scrape request we read, which is far more than Prometheus sends.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Export the registry's metrics as configured: serve them to Prometheus scrapes and push them to
+/// an OpenTelemetry collector. `resource` names this node to the collector, as OpenTelemetry
+/// resource attributes such as `service.instance.id`.
+pub async fn start(
+    config: &TelemetryConfig,
+    registry: Registry,
+    resource: Vec<(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let registry = Arc::new(registry);
+    if let Some(listen) = config.metrics_listen {
+        let listener = TcpListener::bind(listen)
+            .await
+            .map_err(|e| format!("failed to serve metrics on {listen}: {e}"))?;
+        tracing::info!(url = %format_args!("http://{listen}/metrics"), "Serving metrics");
+        tokio::spawn(serve(listener, registry.clone()));
+    }
+    if let Some(endpoint) = config.otlp.endpoint.clone() {
+        tokio::spawn(push(endpoint, config.otlp.clone(), registry, resource));
+    }
+    Ok(())
+}
+
+async fn serve(listener: TcpListener, registry: Arc<Registry>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to accept metrics connection");
+                continue;
+            }
+        };
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = scrape(stream, &registry).await {
+                tracing::warn!(error = %e, "Metrics connection failed");
+            }
+        });
+    }
+}
+
+/// Answer one HTTP request, with the metrics if it asks for `/metrics`.
+async fn scrape(mut stream: TcpStream, registry: &Registry) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = line.split(|&b| b == b' ');
+    let (method, path) = (parts.next(), parts.next());
+    let path = path.map(|path| path.split(|&b| b == b'?').next().unwrap_or_default());
+    let response = match (method, path) {
+        (Some(b"GET"), Some(b"/metrics")) => {
+            let mut body = String::new();
+            encode(&mut body, registry).map_err(std::io::Error::other)?;
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn push(
+    endpoint: String,
+    config: OtlpConfig,
+    registry: Arc<Registry>,
+    resource: Vec<(String, String)>,
+) {
+    let client = reqwest::Client::new();
+    let started = unix_nanos();
+    let mut interval = tokio::time::interval(config.interval());
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let mut text = String::new();
+        if let Err(e) = encode(&mut text, &registry) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+            continue;
+        }
+        let body = otlp_metrics(&text, &resource, started, unix_nanos());
+        let mut post = client.post(&endpoint).json(&body);
+        for (name, value) in &config.headers {
+            post = post.header(name, value);
+        }
+        let result = match post.send().await {
+            Ok(response) => response.error_for_status().map(drop),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!(%endpoint, error = %e, "Failed to push metrics");
+        }
+    }
+}
+
+fn unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Convert metrics in the OpenMetrics text format into an OTLP/HTTP JSON export request, with
+/// counters as cumulative sums, histograms as cumulative explicit-bucket histograms, and every
+/// other metric as a gauge. Timestamps are in nanoseconds since the Unix epoch.
+pub fn otlp_metrics(text: &str, resource: &[(String, String)], start: u128, now: u128) -> Value {
+    let mut families: Vec<Family> = Vec::new();
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = rest.split_once(' ').unwrap_or((rest, "unknown"));
+            Family::named(&mut families, name).kind = kind.to_string();
+        } else if let Some(rest) = line.strip_prefix("# HELP ") {
+            let (name, help) = rest.split_once(' ').unwrap_or((rest, ""));
+            Family::named(&mut families, name).help = help.to_string();
+        } else if !line.starts_with('#') {
+            if let (Some(family), Some(sample)) = (families.last_mut(), Sample::parse(line)) {
+                family.samples.push(sample);
+            }
+        }
+    }
+
+    let times = (start.to_string(), now.to_string());
+    let metrics: Vec<Value> = families
+        .iter()
+        .filter(|family| !family.samples.is_empty())
+        .map(|family| family.to_otlp(&times))
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": attributes(resource) },
+            "scopeMetrics": [{
+                "scope": { "name": "sigil" },
+                "metrics": metrics,
+            }],
+        }],
+    })
+}
+
+struct Family {
+    name: String,
+    kind: String,
+    help: String,
+    samples: Vec<Sample>,
+}
+
+struct Sample {
+    name: String,
+    labels: Vec<(String, String)>,
+    value: f64,
+}
+
+impl Family {
+    /// The family whose metadata is being read, or a new one if `name` starts another.
+    fn named<'a>(families: &'a mut Vec<Family>, name: &str) -> &'a mut Family {
+        if families.last().is_none_or(|family| family.name != name) {
+            families.push(Family {
+                name: name.to_string(),
+                kind: "unknown".to_string(),
+                help: String::new(),
+                samples: Vec::new(),
+            });
+        }
+        families.last_mut().expect("a family was found or pushed")
+    }
+
+    fn to_otlp(&self, (start, now): &(String, String)) -> Value {
+        let point = |labels: &[(String, String)]| {
+            json!({
+                "attributes": attributes(labels),
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+            })
+        };
+        let mut metric = json!({ "name": self.name, "description": self.help });
+        match self.kind.as_str() {
+            "counter" => {
+                let points: Vec<Value> = self
+                    .samples
+                    .iter()
+                    .filter(|sample| sample.name.ends_with("_total"))
+                    .map(|sample| {
+                        let mut point = point(&sample.labels);
+                        point["asDouble"] = json!(sample.value);
+                        point
+                    })
+                    .collect();
+                metric["sum"] = json!({
+                    "dataPoints": points,
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                });
+            }
+            "histogram" => {
+                // Gather each series' samples, which are spread over _bucket, _sum and _count.
+                let mut series: BTreeMap<Vec<(String, String)>, Histogram> = BTreeMap::new();
+                for sample in &self.samples {
+                    let mut labels = sample.labels.clone();
+                    let le = labels
+                        .iter()
+                        .position(|(name, _)| name == "le")
+                        .map(|i| labels.remove(i).1);
+                    let histogram = series.entry(labels).or_default();
+                    match sample.name.strip_prefix(self.name.as_str()) {
+                        Some("_sum") => histogram.sum = sample.value,
+                        Some("_count") => histogram.count = sample.value as u64,
+                        Some("_bucket") => {
+                            let bound = le.and_then(|le| le.parse().ok()).unwrap_or(f64::INFINITY);
+                            histogram.buckets.push((bound, sample.value as u64));
+                        }
+                        _ => {}
+                    }
+                }
+                let points: Vec<Value> = series
+                    .into_iter()
+                    .map(|(labels, histogram)| {
+                        let mut point = point(&labels);
+                        histogram.fill(&mut point);
+                        point
+                    })
+                    .collect();
+                metric["histogram"] = json!({
+                    "dataPoints": points,
+                    "aggregationTemporality": 2,
+                });
+            }
+            _ => {
+                let points: Vec<Value> = self
+                    .samples
+                    .iter()
+                    .map(|sample| {
+                        let mut point = point(&sample.labels);
+                        point["asDouble"] = json!(sample.value);
+                        point
+                    })
+                    .collect();
+                metric["gauge"] = json!({ "dataPoints": points });
+            }
+        }
+        metric
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Each bucket's upper bound and the cumulative count of observations up to it.
+    buckets: Vec<(f64, u64)>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn fill(mut self, point: &mut Value) {
+        self.buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let bounds: Vec<f64> = self
+            .buckets
+            .iter()
+            .map(|(bound, _)| *bound)
+            .filter(|bound| bound.is_finite())
+            .collect();
+        // OTLP counts each bucket's observations alone, with a last bucket above every bound.
+        let mut counts: Vec<String> = Vec::new();
+        let mut previous = 0;
+        for (bound, cumulative) in &self.buckets {
+            if bound.is_finite() {
+                counts.push(cumulative.saturating_sub(previous).to_string());
+                previous = *cumulative;
+            }
+        }
+        counts.push(self.count.saturating_sub(previous).to_string());
+        point["count"] = json!(self.count.to_string());
+        point["sum"] = json!(self.sum);
+        point["bucketCounts"] = json!(counts);
+        point["explicitBounds"] = json!(bounds);
+    }
+}
+
+impl Sample {
+    /// Parse a sample line such as `name{label="value"} 1.0`, ignoring any timestamp.
+    fn parse(line: &str) -> Option<Self> {
+        let name_end = line.find(['{', ' '])?;
+        let name = line[..name_end].to_string();
+        let mut rest = &line[name_end..];
+        let mut labels = Vec::new();
+        if let Some(body) = rest.strip_prefix('{') {
+            let mut chars = body.char_indices();
+            let mut label = String::new();
+            loop {
+                let (i, c) = chars.next()?;
+                match c {
+                    '}' => {
+                        rest = &body[i + 1..];
+                        break;
+                    }
+                    ',' | ' ' => {}
+                    '=' => {
+                        if chars.next()?.1 != '"' {
+                            return None;
+                        }
+                        let mut value = String::new();
+                        loop {
+                            match chars.next()?.1 {
+                                '"' => break,
+                                '\\' => match chars.next()?.1 {
+                                    'n' => value.push('\n'),
+                                    c => value.push(c),
+                                },
+                                c => value.push(c),
+                            }
+                        }
+                        labels.push((std::mem::take(&mut label), value));
+                    }
+                    c => label.push(c),
+                }
+            }
+        }
+        let value = match rest.split_whitespace().next()? {
+            "+Inf" => f64::INFINITY,
+            "-Inf" => f64::NEG_INFINITY,
+            value => value.parse().ok()?,
+        };
+        Some(Self {
+            name,
+            labels,
+            value,
+        })
+    }
+}
+
+fn attributes(pairs: &[(String, String)]) -> Vec<Value> {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
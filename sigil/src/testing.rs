@@ -0,0 +1,221 @@
+use crate::client::SwarmClient;
+use crate::config::{Config, ConfigFormat, Peer};
+use crate::node::P2pNode;
+use crate::transport;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use libp2p_identity::Keypair;
+use prometheus_client::registry::Registry;
+use std::error::Error;
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+
+/// How often `TestNetwork::wait_for_connections` checks the nodes' connections.
+const CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A network of nodes running inside the current tokio runtime, for testing protocols without
+/// building images or opening RPC servers. Every node uses the test profile, listens on
+/// localhost, and is configured to dial every node started before it.
+///
+/// A simulated network connects its nodes in memory instead, with peer ids fixed by their index.
+/// Nothing then waits on the operating system, so in a runtime with paused time, such as that of
+/// `#[tokio::test(start_paused = true)]`, time only passes once every node is idle. The node's
+/// own timers, such as those of its scheduled jobs, then fire at once, though libp2p keeps some
+/// of its protocols' timers on the wall clock. The order in which nodes handle what happens
+/// depends far less on how threads are scheduled than it does over sockets.
+pub struct TestNetwork {
+    nodes: Vec<TestNode>,
+    simulated: bool,
+
+    /// How many nodes have ever been started, which is the index of the next.
+    started: usize,
+}
+
+/// One node of a `TestNetwork`. Its task is aborted when it is dropped.
+pub struct TestNode {
+    pub peer_id: PeerId,
+
+    /// The TCP or memory address the node listens on, including its peer id.
+    pub address: Multiaddr,
+    pub config: Config,
+    pub client: SwarmClient,
+    task: JoinHandle<()>,
+}
+
+impl TestNetwork {
+    /// Start `size` nodes with the test profile's configuration.
+    pub fn start(size: usize) -> Result<Self, Box<dyn Error>> {
+        Self::start_with(size, |_, _| {})
+    }
+
+    /// Start `size` nodes, letting `configure` change each node's configuration, given its index,
+    /// before it starts. Changes to its TCP port or configured peers break the wiring.
+    pub fn start_with(
+        size: usize,
+        configure: impl FnMut(usize, &mut Config),
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::launch(size, false, configure)
+    }
+
+    /// Start `size` nodes on a network simulated in memory.
+    pub fn simulate(size: usize) -> Result<Self, Box<dyn Error>> {
+        Self::simulate_with(size, |_, _| {})
+    }
+
+    /// Start `size` nodes on a network simulated in memory, letting `configure` change each node's
+    /// configuration as `start_with` does. Its ports are ignored.
+    pub fn simulate_with(
+        size: usize,
+        configure: impl FnMut(usize, &mut Config),
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::launch(size, true, configure)
+    }
+
+    fn launch(
+        size: usize,
+        simulated: bool,
+        mut configure: impl FnMut(usize, &mut Config),
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut network = Self {
+            nodes: Vec::with_capacity(size),
+            simulated,
+            started: 0,
+        };
+        for _ in 0..size {
+            network.join_with(&mut configure)?;
+        }
+        Ok(network)
+    }
+
+    /// Start another node, configured to dial every node running.
+    pub fn join(&mut self) -> Result<&TestNode, Box<dyn Error>> {
+        self.join_with(|_, _| {})
+    }
+
+    /// Start another node, letting `configure` change its configuration as `start_with` does.
+    /// Indexes count every node ever started, so a node joining after others left never shares
+    /// an index, a name or, on a simulated network, a peer id with them.
+    pub fn join_with(
+        &mut self,
+        configure: impl FnOnce(usize, &mut Config),
+    ) -> Result<&TestNode, Box<dyn Error>> {
+        let index = self.started;
+        let (key, listen_address) = if self.simulated {
+            let mut seed = [0u8; 32];
+            seed[..8].copy_from_slice(&(index as u64 + 1).to_le_bytes());
+            let port = rand::random::<u64>().max(1);
+            (
+                Keypair::ed25519_from_bytes(seed)?,
+                Multiaddr::empty().with(Protocol::Memory(port)),
+            )
+        } else {
+            (
+                Keypair::generate_ed25519(),
+                Multiaddr::from(Ipv4Addr::LOCALHOST).with(Protocol::Tcp(free_port()?)),
+            )
+        };
+        let peer_id = key.public().to_peer_id();
+        let mut config = Config::parse_with_env("profile = \"test\"", ConfigFormat::Toml, [])?;
+        config.node_name = Some(format!("node-{index}"));
+        if let Some(Protocol::Tcp(port)) = listen_address.iter().last() {
+            config.network.tcp_port = Some(port);
+        }
+        config.network.peers = self
+            .nodes
+            .iter()
+            .map(|node| Peer {
+                peer_id: Some(node.peer_id),
+                addresses: vec![node.address.clone()],
+            })
+            .collect();
+        configure(index, &mut config);
+
+        let (node, client) = if self.simulated {
+            P2pNode::with_transport(
+                key,
+                &config,
+                &mut Registry::default(),
+                transport::memory,
+                [listen_address.clone()],
+            )?
+        } else {
+            P2pNode::new(key, &config, &mut Registry::default())?
+        };
+        self.started += 1;
+        self.nodes.push(TestNode {
+            peer_id,
+            address: listen_address.with(Protocol::P2p(peer_id)),
+            config,
+            client,
+            task: tokio::spawn(node.run()),
+        });
+        Ok(self.nodes.last().expect("a node was just pushed"))
+    }
+
+    /// Shut down the node at `index` of `nodes`, waiting for it to stop, and return its peer id.
+    /// The nodes after it move down by one.
+    pub async fn leave(&mut self, index: usize) -> PeerId {
+        let mut node = self.nodes.remove(index);
+        let _ = node.client.shutdown().await;
+        let _ = (&mut node.task).await;
+        node.peer_id
+    }
+
+    pub fn nodes(&self) -> &[TestNode] {
+        &self.nodes
+    }
+
+    pub fn clients(&self) -> Vec<SwarmClient> {
+        self.nodes.iter().map(|node| node.client.clone()).collect()
+    }
+
+    /// Wait until every node is connected to every other, failing after `timeout`.
+    pub async fn wait_for_connections(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut connected = true;
+            for node in &self.nodes {
+                let peers = node.client.connected_peers().await?;
+                connected &= self
+                    .nodes
+                    .iter()
+                    .all(|other| other.peer_id == node.peer_id || peers.contains(&other.peer_id));
+            }
+            if connected {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "the {} nodes were not all connected after {timeout:?}",
+                    self.nodes.len()
+                )
+                .into());
+            }
+            sleep(CONNECTION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Shut every node down and wait for it to stop.
+    pub async fn shutdown(self) {
+        for mut node in self.nodes {
+            let _ = node.client.shutdown().await;
+            let _ = (&mut node.task).await;
+        }
+    }
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A TCP port on localhost that is free now. Another process may take it before the node binds
+/// it, which is rare enough for tests.
+fn free_port() -> Result<u16, Box<dyn Error>> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port())
+}
@@ -0,0 +1,171 @@
+use crate::bandwidth::Bandwidth;
+use crate::config::Config;
+use crate::faults::Faults;
+use futures::future::{self, BoxFuture};
+use futures::{AsyncRead, AsyncWrite, FutureExt, TryFutureExt};
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::{Boxed, MemoryTransport, Transport};
+use libp2p::core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo};
+use libp2p::{noise, quic, tcp, tls, yamux, PeerId};
+use libp2p_identity::Keypair;
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+/// The node's TCP and QUIC transports, whose connections' substreams are capped and throttled as
+/// the bandwidth settings say, and held up by `faults` in builds that inject them.
+pub fn build(
+    key: &Keypair,
+    config: &Config,
+    faults: &Faults,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn Error>> {
+    let limits = &config.bandwidth;
+    let bandwidth = Bandwidth::new(limits);
+    if !cfg!(feature = "fault-injection") && !faults.is_empty() {
+        tracing::warn!("Faults are configured, but this build cannot inject them");
+    }
+
+    // TODO: defaults, pull from env.
+    // Prepare TCP connection management configuration.
+    let tcp_config = tcp::Config::new()
+        .ttl(64)
+        .nodelay(true)
+        .listen_backlog(1024);
+    let mut yamux_config = yamux::Config::default();
+    yamux_config.set_max_num_streams(limits.max_substreams_per_connection as usize);
+    let tcp_bandwidth = bandwidth.clone();
+    let tcp_faults = faults.clone();
+    let tcp = tcp::tokio::Transport::new(tcp_config)
+        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+        .authenticate(Security::new(key)?)
+        .multiplex(yamux_config)
+        .map(move |(peer_id, muxer), _| {
+            let muxer = tcp_bandwidth.throttle(StreamMuxerBox::new(muxer));
+            (peer_id, tcp_faults.inject(peer_id, muxer))
+        });
+
+    // TODO: defaults, pull from env.
+    // Prepare QUIC connection management configuration.
+    let mut quic_config = quic::Config::new(key);
+    quic_config.handshake_timeout = Duration::from_secs(5);
+    quic_config.max_idle_timeout = 10 * 1000;
+    quic_config.keep_alive_interval = Duration::from_secs(5);
+    quic_config.max_concurrent_stream_limit = limits.max_substreams_per_connection;
+    quic_config.max_stream_data = 10_000_000;
+    quic_config.max_connection_data = 15_000_000;
+    let quic_faults = faults.clone();
+    let quic = quic::tokio::Transport::new(quic_config).map(move |(peer_id, muxer), _| {
+        let muxer = bandwidth.throttle(StreamMuxerBox::new(muxer));
+        (peer_id, quic_faults.inject(peer_id, muxer))
+    });
+
+    Ok(tcp
+        .or_transport(quic)
+        .map(|either, _| either.into_inner())
+        .boxed())
+}
+
+/// A transport between nodes of this process on `/memory/` addresses, for simulating a network
+/// in tests. Its connections are secured with noise and held up by `faults` like any other, but
+/// not throttled.
+pub fn memory(
+    key: &Keypair,
+    _config: &Config,
+    faults: &Faults,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn Error>> {
+    let faults = faults.clone();
+    Ok(MemoryTransport::default()
+        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+        .authenticate(noise::Config::new(key)?)
+        .multiplex(yamux::Config::default())
+        .map(move |(peer_id, muxer), _| {
+            (peer_id, faults.inject(peer_id, StreamMuxerBox::new(muxer)))
+        })
+        .boxed())
+}
+
+/// Secures TCP connections with TLS or noise, whichever the peer supports, preferring TLS.
+#[derive(Clone)]
+struct Security {
+    tls: tls::Config,
+    noise: noise::Config,
+}
+
+impl Security {
+    fn new(key: &Keypair) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            tls: tls::Config::new(key)?,
+            noise: noise::Config::new(key)?,
+        })
+    }
+}
+
+type SecureStream<C> = future::Either<tls::TlsStream<C>, noise::Output<C>>;
+
+impl UpgradeInfo for Security {
+    type Info = &'static str;
+    type InfoIter = Vec<&'static str>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.tls
+            .protocol_info()
+            .chain(self.noise.protocol_info())
+            .collect()
+    }
+}
+
+impl Security {
+    fn is_tls(&self, info: &str) -> bool {
+        self.tls.protocol_info().any(|tls| tls == info)
+    }
+}
+
+impl<C> InboundConnectionUpgrade<C> for Security
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, SecureStream<C>);
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        if self.is_tls(info) {
+            self.tls
+                .upgrade_inbound(socket, info)
+                .map_ok(|(peer_id, stream)| (peer_id, future::Either::Left(stream)))
+                .map_err(io::Error::other)
+                .boxed()
+        } else {
+            self.noise
+                .upgrade_inbound(socket, info)
+                .map_ok(|(peer_id, stream)| (peer_id, future::Either::Right(stream)))
+                .map_err(io::Error::other)
+                .boxed()
+        }
+    }
+}
+
+impl<C> OutboundConnectionUpgrade<C> for Security
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, SecureStream<C>);
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        if self.is_tls(info) {
+            self.tls
+                .upgrade_outbound(socket, info)
+                .map_ok(|(peer_id, stream)| (peer_id, future::Either::Left(stream)))
+                .map_err(io::Error::other)
+                .boxed()
+        } else {
+            self.noise
+                .upgrade_outbound(socket, info)
+                .map_ok(|(peer_id, stream)| (peer_id, future::Either::Right(stream)))
+                .map_err(io::Error::other)
+                .boxed()
+        }
+    }
+}
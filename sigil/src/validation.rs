@@ -0,0 +1,151 @@
+use crate::batch::{Batch, BatchLimits};
+use crate::envelope::{Ack, Bundle};
+use futures::future::BoxFuture;
+use libp2p::{gossipsub, PeerId};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+
+/// Decides whether a received gossip message should be accepted and propagated. Validation runs
+/// off the swarm's event loop, so implementations are free to perform expensive checks such as
+/// verifying signatures on application payloads.
+pub trait MessageValidator: Send + Sync + 'static {
+    fn validate(
+        &self,
+        message: gossipsub::Message,
+    ) -> BoxFuture<'static, gossipsub::MessageAcceptance>;
+}
+
+/// The largest message accepted on topics other than the batch topic, which is gossipsub's
+/// default transmit limit.
+pub const MAX_MESSAGE_SIZE: usize = 65536;
+
+/// The default validator, which accepts any well-formed envelope, acknowledgement, or batch.
+pub struct EnvelopeValidator {
+    ack_topic: gossipsub::TopicHash,
+    batch_topic: gossipsub::TopicHash,
+    batch_limits: BatchLimits,
+}
+
+impl EnvelopeValidator {
+    pub fn new(
+        ack_topic: gossipsub::TopicHash,
+        batch_topic: gossipsub::TopicHash,
+        batch_limits: BatchLimits,
+    ) -> Self {
+        Self {
+            ack_topic,
+            batch_topic,
+            batch_limits,
+        }
+    }
+}
+
+impl MessageValidator for EnvelopeValidator {
+    fn validate(
+        &self,
+        message: gossipsub::Message,
+    ) -> BoxFuture<'static, gossipsub::MessageAcceptance> {
+        let well_formed = if message.topic == self.batch_topic {
+            Batch::decode(&message.data, self.batch_limits).is_ok()
+        } else if message.data.len() > MAX_MESSAGE_SIZE {
+            false
+        } else if message.topic == self.ack_topic {
+            Ack::decode(&message.data).is_ok()
+        } else {
+            Bundle::decode_any(&message.data).is_ok_and(|envelopes| !envelopes.is_empty())
+        };
+        Box::pin(async move {
+            if well_formed {
+                gossipsub::MessageAcceptance::Accept
+            } else {
+                gossipsub::MessageAcceptance::Reject
+            }
+        })
+    }
+}
+
+/// The outcome of validating one message.
+pub struct Validated {
+    pub id: gossipsub::MessageId,
+    pub propagation_source: PeerId,
+    pub message: gossipsub::Message,
+    pub acceptance: gossipsub::MessageAcceptance,
+}
+
+/// Runs a `MessageValidator` over received messages with bounded concurrency and a per-message
+/// timeout, delivering each result back to the event loop.
+pub struct ValidationPipeline {
+    validator: Arc<dyn MessageValidator>,
+    queue: Arc<Semaphore>,
+    queue_size: usize,
+    workers: Arc<Semaphore>,
+    timeout: Duration,
+    sender: mpsc::Sender<Validated>,
+}
+
+impl ValidationPipeline {
+    /// Create a pipeline validating at most `concurrency` messages at once, with at most
+    /// `queue_size` messages in flight, returning it with the receiver of its results.
+    pub fn new(
+        validator: Arc<dyn MessageValidator>,
+        concurrency: usize,
+        queue_size: usize,
+        timeout: Duration,
+    ) -> (Self, mpsc::Receiver<Validated>) {
+        let (sender, receiver) = mpsc::channel(queue_size.max(1));
+        let pipeline = Self {
+            validator,
+            queue: Arc::new(Semaphore::new(queue_size)),
+            queue_size,
+            workers: Arc::new(Semaphore::new(concurrency)),
+            timeout,
+            sender,
+        };
+        (pipeline, receiver)
+    }
+
+    pub fn set_validator(&mut self, validator: Arc<dyn MessageValidator>) {
+        self.validator = validator;
+    }
+
+    /// How many messages are being validated or waiting for the event loop to take their result.
+    pub fn in_flight(&self) -> usize {
+        self.queue_size - self.queue.available_permits()
+    }
+
+    /// Begin validating a message. When the queue is full the message is handed back so that it
+    /// can be ignored immediately.
+    pub fn submit(
+        &self,
+        id: gossipsub::MessageId,
+        propagation_source: PeerId,
+        message: gossipsub::Message,
+    ) -> Result<(), gossipsub::MessageId> {
+        let Ok(queued) = self.queue.clone().try_acquire_owned() else {
+            return Err(id);
+        };
+        let validator = self.validator.clone();
+        let workers = self.workers.clone();
+        let timeout = self.timeout;
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let _queued = queued;
+            let acceptance = match workers.acquire_owned().await {
+                Ok(_worker) => tokio::time::timeout(timeout, validator.validate(message.clone()))
+                    .await
+                    .unwrap_or(gossipsub::MessageAcceptance::Ignore),
+                Err(_) => gossipsub::MessageAcceptance::Ignore,
+            };
+            let _ = sender
+                .send(Validated {
+                    id,
+                    propagation_source,
+                    message,
+                    acceptance,
+                })
+                .await;
+        });
+        Ok(())
+    }
+}
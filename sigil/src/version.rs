@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// What build of sigil is running.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: Cow<'static, str>,
+
+    /// The commit the binary was built from, or `unknown` if it was built outside a checkout.
+    pub git_hash: Cow<'static, str>,
+
+    /// When the binary was built, as an RFC 3339 UTC timestamp.
+    pub build_timestamp: Cow<'static, str>,
+
+    /// The compiler the binary was built with.
+    pub rustc_version: Cow<'static, str>,
+
+    /// The locked versions of the dependencies that shape how the node talks to its peers and
+    /// clients.
+    pub dependencies: Cow<'static, [Dependency]>,
+}
+
+/// A crate the binary was built against.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: Cow<'static, str>,
+    pub version: Cow<'static, str>,
+}
+
+impl BuildInfo {
+    /// The agent version this build identifies itself to peers by, which names the libp2p it
+    /// speaks through and ends with the node's name in parentheses, if it has one.
+    pub fn agent_version(&self, node_name: Option<&str>) -> String {
+        let mut agent = format!("sigil/{}+{}", self.version, self.git_hash);
+        if let Some(libp2p) = self.dependency("libp2p") {
+            agent.push_str(&format!(" libp2p/{libp2p}"));
+        }
+        if let Some(name) = node_name {
+            agent.push_str(&format!(" ({name})"));
+        }
+        agent
+    }
+
+    /// The version of the dependency `name`, if it is reported.
+    pub fn dependency(&self, name: &str) -> Option<&str> {
+        self.dependencies
+            .iter()
+            .find(|dependency| dependency.name == name)
+            .map(|dependency| dependency.version.as_ref())
+    }
+
+    /// A line describing the build, for logging at startup.
+    pub fn banner(&self) -> String {
+        let dependencies: Vec<_> = self
+            .dependencies
+            .iter()
+            .map(|dependency| format!("{} {}", dependency.name, dependency.version))
+            .collect();
+        format!(
+            "sigil {} ({} built {} with {}; {})",
+            self.version,
+            self.git_hash,
+            self.build_timestamp,
+            self.rustc_version,
+            dependencies.join(", ")
+        )
+    }
+}
+
+/// The build of this binary.
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    version: Cow::Borrowed(env!("CARGO_PKG_VERSION")),
+    git_hash: Cow::Borrowed(env!("SIGIL_GIT_HASH")),
+    build_timestamp: Cow::Borrowed(env!("SIGIL_BUILD_TIMESTAMP")),
+    rustc_version: Cow::Borrowed(env!("SIGIL_RUSTC_VERSION")),
+    dependencies: Cow::Borrowed(&[
+        dependency("libp2p", env!("SIGIL_DEPENDENCY_LIBP2P")),
+        dependency(
+            "libp2p-gossipsub",
+            env!("SIGIL_DEPENDENCY_LIBP2P_GOSSIPSUB"),
+        ),
+        dependency("jsonrpsee", env!("SIGIL_DEPENDENCY_JSONRPSEE")),
+        dependency("tokio", env!("SIGIL_DEPENDENCY_TOKIO")),
+    ]),
+};
+
+const fn dependency(name: &'static str, version: &'static str) -> Dependency {
+    Dependency {
+        name: Cow::Borrowed(name),
+        version: Cow::Borrowed(version),
+    }
+}
+
+/// The node name at the end of a peer's agent version, if it gave one.
+pub fn node_name(agent_version: &str) -> Option<&str> {
+    let (_, name) = agent_version.strip_suffix(')')?.rsplit_once(" (")?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// The identify protocol version of this release on the named network. Peers presenting another
+/// are on a different network or an incompatible release.
+pub fn protocol_version(network: &str) -> String {
+    format!(
+        "/{network}/{}.{}",
+        env!("CARGO_PKG_VERSION_MAJOR"),
+        env!("CARGO_PKG_VERSION_MINOR")
+    )
+}
@@ -0,0 +1,105 @@
+use crate::channels::{Channel, ChannelMetrics};
+use crate::config::WebhookConfig;
+use crate::journal::{JournalEntry, ReceivedMessage};
+use hmac::{Hmac, Mac};
+use libp2p::gossipsub::{IdentTopic, TopicHash};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// The header carrying the hex HMAC-SHA256 of the request body, keyed by the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-Sigil-Signature";
+
+/// Forwards received messages to an HTTP endpoint so that services outside the network can
+/// consume them. Messages are posted in order by a background task, which retries failed posts
+/// with exponential backoff.
+pub struct Webhook {
+    topics: HashSet<TopicHash>,
+    sender: mpsc::Sender<ReceivedMessage>,
+    channels: ChannelMetrics,
+}
+
+impl Webhook {
+    /// Start delivering to the configured endpoint, or return `None` if none is configured.
+    pub fn spawn(config: &WebhookConfig, channels: ChannelMetrics) -> Option<Self> {
+        let url = config.url.clone()?;
+        let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+        tokio::spawn(deliver(url, config.clone(), receiver));
+        Some(Self {
+            topics: config
+                .topics
+                .iter()
+                .map(|topic| IdentTopic::new(topic).hash())
+                .collect(),
+            sender,
+            channels,
+        })
+    }
+
+    /// Queue a message received on `topic` for delivery, if the webhook wants that topic.
+    pub fn send(&self, topic: &TopicHash, entry: &JournalEntry) {
+        if !self.topics.is_empty() && !self.topics.contains(topic) {
+            return;
+        }
+        let message = ReceivedMessage {
+            topic: topic.to_string(),
+            entry: entry.clone(),
+        };
+        if self.sender.try_send(message).is_err() {
+            self.channels.blocked(Channel::Webhook);
+            tracing::warn!(id = entry.id, "Webhook queue full, dropping message");
+        }
+    }
+
+    /// How many messages are waiting to be posted.
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+}
+
+async fn deliver(
+    url: String,
+    config: WebhookConfig,
+    mut receiver: mpsc::Receiver<ReceivedMessage>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(config.timeout_millis))
+        .build()
+        .expect("webhook HTTP client configuration should have succeeded");
+    while let Some(message) = receiver.recv().await {
+        let body = serde_json::to_vec(&message).expect("webhook serialization is infallible");
+        let max_delay = Duration::from_millis(config.max_retry_delay_millis);
+        let mut delay = Duration::from_millis(config.retry_delay_millis).min(max_delay);
+        for attempt in 1..=config.max_attempts.max(1) {
+            let mut request = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(secret) = &config.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret.as_bytes(), &body));
+            }
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => break,
+                Err(e) if attempt == config.max_attempts.max(1) => {
+                    tracing::warn!(id = message.entry.id, error = %e, "Webhook gave up on message");
+                }
+                Err(_) => {
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2).min(max_delay);
+                }
+            }
+        }
+    }
+}
+
+/// The hex HMAC-SHA256 of `body` keyed by `secret`, as sent in the signature header.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
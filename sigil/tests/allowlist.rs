@@ -0,0 +1,14 @@
+use sigil::rpc::allowlist::Allowlist;
+
+#[test]
+fn test_allowlist_admits_only_listed_networks() {
+    let allowlist = Allowlist::new(Vec::new());
+    assert!(allowlist.allows(Some("203.0.113.7".parse().unwrap())));
+    assert!(allowlist.allows(None));
+
+    allowlist.set_networks(vec!["10.0.0.0/8".parse().unwrap()]);
+    assert!(allowlist.allows(Some("10.1.2.3".parse().unwrap())));
+    assert!(allowlist.allows(Some("::ffff:10.1.2.3".parse().unwrap())));
+    assert!(!allowlist.allows(Some("203.0.113.7".parse().unwrap())));
+    assert!(!allowlist.allows(None));
+}
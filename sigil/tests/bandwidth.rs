@@ -0,0 +1,24 @@
+use sigil::bandwidth::ByteBucket;
+use sigil::config::ByteRateConfig;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_bucket_allows_bursts_then_the_sustained_rate() {
+    let bucket = ByteBucket::new(ByteRateConfig {
+        bytes_per_second: 1000,
+        burst_bytes: 500,
+    });
+    let start = Instant::now();
+    assert_eq!(bucket.available(start), Ok(500));
+    bucket.consume(500);
+
+    // Overdrawn buckets wait until they are back in credit.
+    bucket.consume(100);
+    let wait = bucket.available(start).unwrap_err();
+    assert!(wait > Duration::from_millis(100) && wait <= Duration::from_millis(101));
+    assert_eq!(
+        bucket.available(start + Duration::from_millis(350)),
+        Ok(250)
+    );
+    assert_eq!(bucket.available(start + Duration::from_secs(10)), Ok(500));
+}
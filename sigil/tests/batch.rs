@@ -0,0 +1,49 @@
+use sigil::batch::{Batch, BatchError, BatchLimits};
+use sigil::block::BlockHash;
+use sigil::mempool::Transaction;
+
+fn batch(transactions: usize) -> Batch {
+    Batch {
+        number: 7,
+        parent_hash: BlockHash::of(b"parent"),
+        l1_origin: 100,
+        timestamp: 1_700_000_000,
+        transactions: (0..transactions as u64)
+            .map(|nonce| Transaction {
+                nonce,
+                data: vec![0; 256],
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_batch_round_trips_and_enforces_limits() {
+    let limits = BatchLimits {
+        max_size: 1024 * 1024,
+        max_decompressed_size: 1024 * 1024,
+    };
+    let batch = batch(64);
+    let encoded = batch.encode();
+    assert_eq!(Batch::decode(&encoded, limits).unwrap(), batch);
+
+    let tight = BatchLimits {
+        max_size: encoded.len() - 1,
+        ..limits
+    };
+    assert!(matches!(
+        Batch::decode(&encoded, tight),
+        Err(BatchError::TooLarge { .. })
+    ));
+
+    // Highly compressible batches are refused before they are decompressed.
+    let bomb = BatchLimits {
+        max_decompressed_size: encoded.len(),
+        ..limits
+    };
+    assert!(matches!(
+        Batch::decode(&encoded, bomb),
+        Err(BatchError::TooLarge { .. })
+    ));
+    assert!(Batch::decode(b"not a batch", limits).is_err());
+}
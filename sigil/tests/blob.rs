@@ -0,0 +1,63 @@
+use sigil::blob::BlobError;
+use sigil::client::ClientError;
+use sigil::testing::TestNetwork;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::test(start_paused = true)]
+async fn test_blob_offers_are_capped_per_peer() {
+    let network = TestNetwork::simulate_with(2, |_, config| {
+        config.blob.max_offers_per_peer = 1;
+    })
+    .expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+    let [sender, recipient] = network.nodes() else {
+        unreachable!()
+    };
+
+    let first = sender
+        .client
+        .offer_blob(recipient.peer_id, "first", vec![1; 16])
+        .await;
+    assert!(first.is_ok());
+    let second = sender
+        .client
+        .offer_blob(recipient.peer_id, "second", vec![2; 16])
+        .await;
+    assert!(matches!(
+        second,
+        Err(ClientError::Blob(BlobError::Rejected))
+    ));
+    network.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_idle_blob_offers_expire() {
+    let network = TestNetwork::simulate_with(2, |_, config| {
+        config.blob.idle_secs = 60;
+    })
+    .expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+    let [sender, recipient] = network.nodes() else {
+        unreachable!()
+    };
+
+    let id = sender
+        .client
+        .offer_blob(recipient.peer_id, "snapshot", vec![1; 16])
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(120)).await;
+    let fetched = recipient.client.fetch_blob(sender.peer_id, id).await;
+    assert!(matches!(
+        fetched,
+        Err(ClientError::Blob(BlobError::Unknown))
+    ));
+    network.shutdown().await;
+}
@@ -0,0 +1,14 @@
+use sigil::block::BlockHash;
+
+#[test]
+fn test_block_hash_round_trips_through_hex() {
+    let hash = BlockHash::of(b"sigil");
+    let hex = hash.to_string();
+
+    assert_eq!(hex.len(), 64);
+    assert_eq!(hex.parse::<BlockHash>(), Ok(hash));
+    assert_eq!(format!("0x{hex}").parse::<BlockHash>(), Ok(hash));
+    assert_ne!(BlockHash::of(b"sigil!"), hash);
+    assert!("abcd".parse::<BlockHash>().is_err());
+    assert!("zz".repeat(32).parse::<BlockHash>().is_err());
+}
@@ -0,0 +1,110 @@
+use libp2p::gossipsub::{IdentTopic, MessageId, PublishError};
+use sigil::bundle::{Bundler, Publisher};
+use sigil::config::BundleConfig;
+use sigil::envelope::{Bundle, Envelope};
+use sigil::mempool::{MempoolError, TxHash};
+use tokio::sync::oneshot;
+
+#[test]
+fn test_bundles_fill_up_to_their_limits() {
+    let mut bundler = Bundler::new(&BundleConfig {
+        topics: vec!["mempool".into()],
+        max_messages: 3,
+        ..BundleConfig::default()
+    });
+    let (mempool, blocks) = (IdentTopic::new("mempool"), IdentTopic::new("blocks"));
+    assert!(bundler.bundles(&mempool.hash()));
+    assert!(!bundler.bundles(&blocks.hash()));
+
+    assert!(bundler
+        .push(mempool.clone(), Envelope::new(vec![1]), None)
+        .is_empty());
+    assert!(bundler
+        .push(mempool.clone(), Envelope::new(vec![2]), None)
+        .is_empty());
+    let ready = bundler.push(mempool.clone(), Envelope::new(vec![3]), None);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].bundle.envelopes.len(), 3);
+    assert!(bundler.is_empty());
+
+    bundler.push(mempool.clone(), Envelope::new(vec![4]), None);
+    let flushed = bundler.flush();
+    assert_eq!(flushed.len(), 1);
+    assert_eq!(flushed[0].topic.hash(), mempool.hash());
+    assert!(bundler.flush().is_empty());
+}
+
+#[test]
+fn test_a_message_too_large_for_a_bundle_starts_the_next() {
+    let mut bundler = Bundler::new(&BundleConfig {
+        topics: vec!["mempool".into()],
+        max_bytes: 200,
+        ..BundleConfig::default()
+    });
+    let topic = IdentTopic::new("mempool");
+
+    assert!(bundler
+        .push(topic.clone(), Envelope::new(vec![1]), None)
+        .is_empty());
+    let ready = bundler.push(topic.clone(), Envelope::new(vec![0; 100]), None);
+    assert_eq!(ready.len(), 2);
+    assert_eq!(ready[0].bundle.envelopes[0].payload.as_ref(), &[1]);
+    assert_eq!(ready[1].bundle.envelopes[0].payload.len(), 100);
+}
+
+#[test]
+fn test_messages_decode_alone_or_bundled() {
+    let envelope = Envelope::new(vec![1, 2]);
+    let alone = Bundle::decode_any(&envelope.encode()).unwrap();
+    assert_eq!(alone.len(), 1);
+    assert_eq!(alone[0].nonce, envelope.nonce);
+
+    let bundle = Bundle {
+        envelopes: vec![envelope.clone(), Envelope::new(vec![3])],
+    };
+    let bundled = Bundle::decode_any(&bundle.encode()).unwrap();
+    assert_eq!(bundled.len(), 2);
+    assert_eq!(bundled[0].nonce, envelope.nonce);
+
+    assert!(Bundle::decode_any(b"not json").is_err());
+}
+
+#[test]
+fn test_publishers_hear_how_their_bundle_was_published() {
+    let topic = IdentTopic::new("mempool");
+    let mut bundler = Bundler::new(&BundleConfig {
+        topics: vec!["mempool".into()],
+        ..BundleConfig::default()
+    });
+    let (message, mut message_id) = oneshot::channel();
+    let (transaction, mut transaction_hash) = oneshot::channel();
+    let hash = TxHash([7; 32]);
+    bundler.push(
+        topic.clone(),
+        Envelope::new(vec![1]),
+        Some(Publisher::Message(message)),
+    );
+    bundler.push(
+        topic,
+        Envelope::new(vec![2]),
+        Some(Publisher::Transaction(hash, transaction)),
+    );
+    let published = Ok(MessageId::from("bundle"));
+    for ready in bundler.flush() {
+        for publisher in ready.publishers {
+            publisher.answer(&published);
+        }
+    }
+    assert_eq!(
+        message_id.try_recv().unwrap().unwrap(),
+        MessageId::from("bundle")
+    );
+    assert_eq!(transaction_hash.try_recv().unwrap().unwrap(), hash);
+
+    let (transaction, mut failure) = oneshot::channel();
+    Publisher::Transaction(hash, transaction).answer(&Err(PublishError::InsufficientPeers));
+    assert!(matches!(
+        failure.try_recv().unwrap(),
+        Err(MempoolError::Publish(PublishError::InsufficientPeers))
+    ));
+}
@@ -0,0 +1,39 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use sigil::channels::{Channel, ChannelMetrics, Source, Turn, TURN_BUDGET};
+use sigil::client::Priority;
+
+#[test]
+fn test_channel_metrics_are_shared_and_exported() {
+    let mut registry = Registry::with_prefix("sigil");
+    let metrics = ChannelMetrics::new(&mut registry);
+    let sender = metrics.clone();
+    sender.set_depth(Channel::from(Priority::Bulk), 3);
+    sender.blocked(Channel::Webhook);
+    sender.blocked(Channel::Webhook);
+    assert_eq!(metrics.depth(Channel::Bulk), 3);
+    assert_eq!(metrics.blocked_sends(Channel::Webhook), 2);
+    assert_eq!(metrics.blocked_sends(Channel::Control), 0);
+
+    let mut text = String::new();
+    encode(&mut text, &registry).unwrap();
+    assert!(text.contains("sigil_channel_depth{channel=\"bulk\"} 3"));
+    assert!(text.contains("sigil_channel_blocked_sends_total{channel=\"webhook\"} 2"));
+}
+
+#[test]
+fn test_a_source_that_used_its_budget_waits_for_the_next_turn() {
+    let mut turn = Turn::default();
+    assert!(!turn.is_limited());
+    for _ in 0..TURN_BUDGET {
+        assert!(turn.allows(Source::Swarm));
+        turn.take(Source::Swarm);
+    }
+    assert!(!turn.allows(Source::Swarm));
+    assert!(turn.allows(Source::Jobs));
+    assert!(turn.is_limited());
+
+    turn.end();
+    assert!(turn.allows(Source::Swarm));
+    assert!(!turn.is_limited());
+}
@@ -0,0 +1,53 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sigil::chaos::{Restart, Strike};
+use sigil::config::{ChaosConfig, Config, ConfigFormat};
+use std::time::Duration;
+
+#[test]
+fn test_chaos_strikes_by_chance() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let calm = ChaosConfig {
+        interval_secs: 1,
+        disconnect_rate: 0.0,
+        stall_rate: 0.0,
+        restart_rate: 0.0,
+        ..ChaosConfig::default()
+    };
+    assert!(calm.strike(&mut rng).is_empty());
+
+    let wild = ChaosConfig {
+        disconnect_rate: 1.0,
+        stall_rate: 1.0,
+        restart_rate: 1.0,
+        max_stall_millis: 100,
+        ..calm
+    };
+    let mut restarted = Vec::new();
+    for _ in 0..100 {
+        let strike = wild.strike(&mut rng);
+        assert!(strike.disconnect);
+        assert!(strike
+            .stall
+            .is_some_and(|stall| stall <= Duration::from_millis(100)));
+        restarted.extend(strike.restart);
+    }
+    for restart in Restart::ALL {
+        assert!(restarted.contains(&restart), "{restart:?} never restarted");
+    }
+    assert!(!Strike {
+        disconnect: true,
+        ..Strike::default()
+    }
+    .is_empty());
+}
+
+#[test]
+fn test_chaos_is_off_by_default() {
+    let config = Config::parse_with_env("", ConfigFormat::Toml, []).unwrap();
+    assert_eq!(config.chaos.interval(), None);
+
+    let config =
+        Config::parse_with_env("[chaos]\ninterval_secs = 30", ConfigFormat::Toml, []).unwrap();
+    assert_eq!(config.chaos.interval(), Some(Duration::from_secs(30)));
+}
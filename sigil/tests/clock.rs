@@ -0,0 +1,11 @@
+use sigil::clock::estimate_skew;
+
+#[test]
+fn test_skew_is_measured_against_the_middle_of_the_round_trip() {
+    // A peer answering 1000ms into a 200ms round trip is 900ms ahead of us.
+    assert_eq!(estimate_skew(10_000, 11_000, 10_200), 900);
+    assert_eq!(estimate_skew(10_000, 9_000, 10_200), -1_100);
+
+    // A clock stepped backwards mid-request is treated as an instant round trip.
+    assert_eq!(estimate_skew(10_000, 10_500, 9_000), 500);
+}
@@ -0,0 +1,198 @@
+use sigil::config::{Config, ConfigFormat, KadMode, Profile, Role};
+use sigil::keep_alive::ConnectionClass;
+use sigil::version::{self, BUILD_INFO};
+use std::time::Duration;
+
+#[test]
+fn test_env_overrides_layer_on_top_of_toml() {
+    let contents = "[rpc]\nlisten = \"127.0.0.1:3030\"\nnetwork_id = 10\n";
+    let vars = [
+        ("SIGIL_RPC__LISTEN", "0.0.0.0:4040"),
+        ("SIGIL_RPC__ALLOWED_IPS", "[\"10.0.0.0/8\"]"),
+        ("SIGIL_RPC__RATE_LIMIT__PER_IP__BURST", "20"),
+        ("SIGIL_RPC__RATE_LIMIT__PER_IP__PER_SECOND", "2.5"),
+        ("SIGIL_LOG__STDOUT", "false"),
+        ("SIGIL_RPC__AUTH__TOKEN", "\"12345\""),
+        ("HOME", "/root"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, vars).unwrap();
+    assert_eq!(config.rpc.listen.to_string(), "0.0.0.0:4040");
+    assert_eq!(config.rpc.network_id, 10);
+    assert_eq!(config.rpc.allowed_ips, vec!["10.0.0.0/8".parse().unwrap()]);
+    let per_ip = config.rpc.rate_limit.per_ip.unwrap();
+    assert_eq!((per_ip.burst, per_ip.per_second), (20, 2.5));
+    assert!(!config.log.stdout);
+    assert_eq!(config.rpc.auth.token.as_deref(), Some("12345"));
+
+    let typo = [("SIGIL_RPC__LISTN".to_string(), "0.0.0.0:4040".to_string())];
+    assert!(Config::parse_with_env("", ConfigFormat::Toml, typo).is_err());
+}
+
+#[test]
+fn test_yaml_and_json_configs_match_toml() {
+    let toml =
+        "pid_file = \"/run/sigil.pid\"\n[rpc]\nnetwork_id = 10\nallowed_ips = [\"10.0.0.0/8\"]\n";
+    let yaml = "pid_file: /run/sigil.pid\nrpc:\n  network_id: 10\n  allowed_ips: [10.0.0.0/8]\n";
+    let json = r#"{"pid_file": "/run/sigil.pid", "rpc": {"network_id": 10, "allowed_ips": ["10.0.0.0/8"]}}"#;
+    for (contents, format) in [
+        (toml, ConfigFormat::Toml),
+        (yaml, ConfigFormat::Yaml),
+        (json, ConfigFormat::Json),
+    ] {
+        let config = Config::parse_with_env(contents, format, []).unwrap();
+        assert_eq!(config.pid_file.unwrap().to_str(), Some("/run/sigil.pid"));
+        assert_eq!(config.rpc.network_id, 10);
+        assert_eq!(config.rpc.allowed_ips, vec!["10.0.0.0/8".parse().unwrap()]);
+    }
+    assert_eq!(ConfigFormat::of("sigil.yml".as_ref()), ConfigFormat::Yaml);
+    assert_eq!(ConfigFormat::of("sigil.conf".as_ref()), ConfigFormat::Toml);
+}
+
+#[test]
+fn test_default_toml_matches_the_defaults() {
+    let parsed = Config::parse_with_env(Config::default_toml(), ConfigFormat::Toml, []).unwrap();
+    assert_eq!(
+        toml::Value::try_from(parsed).unwrap(),
+        toml::Value::try_from(Config::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_peers_are_listed_by_id_addresses_or_both() {
+    let contents = r#"
+        [[network.peers]]
+        peer_id = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
+
+        [[network.peers]]
+        addresses = [
+            "/dns4/boot.example.com/tcp/9000/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN",
+            "/ip4/203.0.113.7/udp/9001/quic-v1",
+        ]
+
+        [[network.peers]]
+        addresses = ["/ip4/203.0.113.8/tcp/9000"]
+    "#;
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    let ids: Vec<_> = config
+        .network
+        .peers
+        .iter()
+        .map(|peer| peer.peer_id().map(|id| id.to_string()))
+        .collect();
+    let id = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN".to_string();
+    assert_eq!(ids, vec![Some(id.clone()), Some(id), None]);
+    assert_eq!(config.network.peers[1].addresses.len(), 2);
+}
+
+#[test]
+fn test_role_presets_yield_to_explicit_settings() {
+    let contents =
+        "role = \"relay\"\n[network]\nmdns = true\n[peer_limits]\nmax_established = 10\n";
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    assert!(config.relay());
+    assert!(config.mdns());
+    assert_eq!(config.kad_mode(), KadMode::Server);
+    let limits = config.peer_limits.or(&Role::Relay.peer_limits());
+    assert_eq!(limits.max_established, Some(10));
+    assert_eq!(limits.max_established_per_peer, Some(2));
+
+    let client = Config::parse_with_env("role = \"client\"", ConfigFormat::Toml, []).unwrap();
+    assert_eq!(client.kad_mode(), KadMode::Client);
+    assert!(!client.relay());
+    assert!(!Config::default().relay());
+}
+
+#[test]
+fn test_profiles_yield_to_explicit_settings() {
+    let contents = "role = \"bootnode\"\nprofile = \"dev\"\n[gossipsub]\nflood_publish = false\n";
+    let dev = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    assert_eq!(dev.profile, Some(Profile::Dev));
+    assert!(dev.mdns());
+    assert!(!dev.gossipsub.flood_publish);
+    assert_eq!(dev.identity().keypair_path, None);
+
+    let vars = [("SIGIL_PROFILE".to_string(), "test".to_string())];
+    let test = Config::parse_with_env("", ConfigFormat::Toml, vars).unwrap();
+    assert!(!test.mdns());
+    assert!(!test.gossipsub.scoring.enabled);
+
+    // Production nodes must keep their keypair in a file.
+    assert!(Config::parse_with_env("profile = \"prod\"", ConfigFormat::Toml, []).is_err());
+    let prod = "profile = \"prod\"\ndata_dir = \"/var/lib/sigil\"\n";
+    let prod = Config::parse_with_env(prod, ConfigFormat::Toml, []).unwrap();
+    assert!(!prod.mdns());
+    assert!(prod.gossipsub.scoring.enabled);
+    assert!(prod.identity().keypair_path.is_some());
+}
+
+#[test]
+fn test_log_level_and_module_overrides_compose_a_filter() {
+    let contents = "[log]\nlevel = \"warn\"\nformat = \"json\"\n[log.modules]\nsigil = \"debug\"\n";
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    let filter = config.log.env_filter().unwrap().to_string();
+    assert!(filter.contains("warn"));
+    assert!(filter.contains("sigil=debug"));
+
+    let mut invalid = config.log;
+    invalid.modules.insert("sigil".into(), "loud".into());
+    assert!(invalid.env_filter().is_err());
+}
+
+#[test]
+fn test_keypair_is_kept_in_the_data_directory() {
+    let config =
+        Config::parse_with_env("data_dir = \"/var/lib/sigil\"", ConfigFormat::Toml, []).unwrap();
+    let keypair_path = config.identity().keypair_path.unwrap();
+    assert_eq!(keypair_path.to_str(), Some("/var/lib/sigil/node.key"));
+
+    let contents = "data_dir = \"/var/lib/sigil\"\n[identity]\nkeypair_path = \"keys/a.key\"\n";
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    let keypair_path = config.identity().keypair_path.unwrap();
+    assert_eq!(keypair_path.to_str(), Some("/var/lib/sigil/keys/a.key"));
+
+    let contents = "data_dir = \"/var/lib/sigil\"\n[identity]\nkeypair_path = \"/etc/a.key\"\n";
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    let keypair_path = config.identity().keypair_path.unwrap();
+    assert_eq!(keypair_path.to_str(), Some("/etc/a.key"));
+
+    assert!(Config::default().identity().keypair_path.is_none());
+}
+
+#[test]
+fn test_node_name_is_carried_in_the_agent_version() {
+    let config = Config::parse_with_env("node_name = \"relay (eu-1)\"", ConfigFormat::Toml, []);
+    assert!(config.is_err());
+    let config = Config::parse_with_env("node_name = \"relay-eu-1\"", ConfigFormat::Toml, []);
+    let name = config.unwrap().node_name;
+
+    let agent_version = BUILD_INFO.agent_version(name.as_deref());
+    assert_eq!(version::node_name(&agent_version), Some("relay-eu-1"));
+    assert_eq!(version::node_name(&BUILD_INFO.agent_version(None)), None);
+}
+
+#[test]
+fn test_keep_alive_timeouts_follow_the_connection_class() {
+    let contents = "[keep_alive]\nmesh_secs = 90\ndht_secs = 0\n";
+    let keep_alive = Config::parse_with_env(contents, ConfigFormat::Toml, [])
+        .unwrap()
+        .keep_alive;
+    assert_eq!(
+        keep_alive.timeout(ConnectionClass::Mesh),
+        Duration::from_secs(90)
+    );
+    assert_eq!(
+        keep_alive.timeout(ConnectionClass::Relay),
+        Duration::from_secs(600)
+    );
+    assert_eq!(
+        keep_alive.timeout(ConnectionClass::Other),
+        Duration::from_secs(60)
+    );
+    assert_eq!(keep_alive.shortest(), Duration::ZERO);
+
+    assert_eq!(
+        Config::default().keep_alive.shortest(),
+        Duration::from_secs(30)
+    );
+}
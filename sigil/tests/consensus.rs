@@ -0,0 +1,57 @@
+use libp2p::PeerId;
+use sigil::block::BlockHash;
+use sigil::consensus::{ConsensusMessage, OrderedDelivery, SequencedMessage};
+
+fn finality(session: u64, sequence: u64) -> SequencedMessage {
+    SequencedMessage {
+        session,
+        sequence,
+        message: ConsensusMessage::Finality {
+            height: sequence,
+            block_hash: BlockHash::of(&sequence.to_be_bytes()),
+        },
+    }
+}
+
+fn heights(messages: Vec<ConsensusMessage>) -> Vec<u64> {
+    messages
+        .into_iter()
+        .map(|message| match message {
+            ConsensusMessage::Finality { height, .. } => height,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_consensus_messages_are_delivered_in_sender_order() {
+    let mut delivery = OrderedDelivery::default();
+    let sender = PeerId::random();
+
+    // The first message seen sets where delivery starts.
+    assert_eq!(
+        heights(delivery.receive(sender, finality(1, 5)).unwrap()),
+        [5]
+    );
+    assert!(delivery.receive(sender, finality(1, 7)).unwrap().is_empty());
+    assert!(delivery.receive(sender, finality(1, 8)).unwrap().is_empty());
+    assert_eq!(
+        heights(delivery.receive(sender, finality(1, 6)).unwrap()),
+        [6, 7, 8]
+    );
+    assert!(delivery.receive(sender, finality(1, 6)).is_none());
+
+    // Other senders are ordered independently.
+    let other = PeerId::random();
+    assert_eq!(
+        heights(delivery.receive(other, finality(1, 0)).unwrap()),
+        [0]
+    );
+
+    // A restarted sender numbers its messages from zero again.
+    assert_eq!(
+        heights(delivery.receive(sender, finality(2, 0)).unwrap()),
+        [0]
+    );
+    assert!(delivery.receive(sender, finality(1, 9)).is_none());
+}
@@ -0,0 +1,43 @@
+use sigil::client::ChannelDepths;
+use sigil::config::Config;
+use sigil::crash::CrashReport;
+use sigil::version::BUILD_INFO;
+use std::fs;
+
+#[test]
+fn test_crash_report_is_written_without_secrets() {
+    let dir = std::env::temp_dir().join(format!("sigil-crash-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.rpc.auth.token = Some("hunter2".to_string());
+    config.telemetry.otlp.headers.insert(
+        "authorization".to_string(),
+        "Bearer correct-horse".to_string(),
+    );
+    let report = CrashReport {
+        at: 1_700_000_000_000,
+        message: "swarm task panicked".to_string(),
+        location: Some("src/node.rs:1:1".to_string()),
+        thread: Some("tokio-runtime-worker".to_string()),
+        backtrace: String::new(),
+        build: BUILD_INFO,
+        channels: ChannelDepths {
+            control: 3,
+            consensus: 0,
+            bulk: 0,
+            events: 0,
+        },
+        recent_events: Vec::new(),
+        config,
+    };
+
+    let path = report.write(&dir).unwrap();
+    assert_eq!(path, dir.join("crash-1700000000000.json"));
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains("hunter2"));
+    assert!(!contents.contains("correct-horse"));
+    let written: CrashReport = serde_json::from_str(&contents).unwrap();
+    assert_eq!(written.message, report.message);
+    assert_eq!(written.channels.control, 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
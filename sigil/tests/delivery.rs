@@ -0,0 +1,65 @@
+use libp2p::{gossipsub, PeerId};
+use sigil::delivery::{
+    DeliveryError, PendingDeliveries, ReceivedDeliveries, MAX_ATTEMPTS, RETRY_INTERVAL,
+};
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+#[test]
+fn test_reliable_delivery_completes_once_all_recipients_ack() {
+    let mut deliveries = PendingDeliveries::default();
+    let (alice, bob) = (PeerId::random(), PeerId::random());
+    let (sender, mut receiver) = oneshot::channel();
+    let (_, envelope) = deliveries
+        .insert(
+            gossipsub::IdentTopic::new("test"),
+            b"hello".to_vec(),
+            vec![alice, bob],
+            sender,
+            Instant::now(),
+        )
+        .expect("a message with recipients should be published");
+    let id = envelope.ack.expect("reliable messages request acks").id;
+
+    deliveries.acknowledge(id, alice);
+    assert!(receiver.try_recv().is_err());
+    deliveries.acknowledge(id, bob);
+    assert_eq!(receiver.try_recv(), Ok(Ok(())));
+}
+
+#[test]
+fn test_reliable_delivery_reports_unacknowledged_recipients() {
+    let mut deliveries = PendingDeliveries::default();
+    let alice = PeerId::random();
+    let (sender, mut receiver) = oneshot::channel();
+    let mut now = Instant::now();
+    deliveries.insert(
+        gossipsub::IdentTopic::new("test"),
+        b"hello".to_vec(),
+        vec![alice],
+        sender,
+        now,
+    );
+
+    for attempt in 2..=MAX_ATTEMPTS {
+        now += RETRY_INTERVAL;
+        let retries = deliveries.poll_retries(now);
+        assert_eq!(retries.len(), 1);
+        assert_eq!(retries[0].1.ack.as_ref().map(|a| a.attempt), Some(attempt));
+    }
+    now += RETRY_INTERVAL;
+    assert!(deliveries.poll_retries(now).is_empty());
+    assert_eq!(
+        receiver.try_recv(),
+        Ok(Err(DeliveryError::Unacknowledged(vec![alice])))
+    );
+}
+
+#[test]
+fn test_retries_are_surfaced_once() {
+    let mut received = ReceivedDeliveries::default();
+    let publisher = PeerId::random();
+    assert!(received.first_receipt(publisher, 7));
+    assert!(!received.first_receipt(publisher, 7));
+    assert!(received.first_receipt(publisher, 8));
+}
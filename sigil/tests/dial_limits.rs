@@ -0,0 +1,45 @@
+use libp2p::PeerId;
+use sigil::config::{DialLimitsConfig, RateConfig};
+use sigil::dial_limits::{DialLimitExceeded, DialLimiter};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_dials_are_limited_globally_and_per_peer() {
+    let mut limiter = DialLimiter::new(DialLimitsConfig {
+        global: Some(RateConfig {
+            per_second: 1.0,
+            burst: 3,
+        }),
+        per_peer: Some(RateConfig {
+            per_second: 1.0,
+            burst: 2,
+        }),
+    });
+    let (alpha, bravo) = (PeerId::random(), PeerId::random());
+    let now = Instant::now();
+
+    assert!(limiter.try_dial(Some(alpha), now).is_ok());
+    assert!(limiter.try_dial(Some(alpha), now).is_ok());
+    assert_eq!(
+        limiter.try_dial(Some(alpha), now),
+        Err(DialLimitExceeded::Peer(alpha))
+    );
+
+    // The refused dial took nothing from the global limit.
+    assert!(limiter.try_dial(Some(bravo), now).is_ok());
+    assert_eq!(limiter.try_dial(None, now), Err(DialLimitExceeded::Global));
+
+    let later = now + Duration::from_secs(1);
+    assert!(limiter.try_dial(Some(alpha), later).is_ok());
+}
+
+#[test]
+fn test_dials_are_unlimited_without_limits() {
+    let mut limiter = DialLimiter::new(DialLimitsConfig {
+        global: None,
+        per_peer: None,
+    });
+    let peer_id = PeerId::random();
+    let now = Instant::now();
+    assert!((0..1000).all(|_| limiter.try_dial(Some(peer_id), now).is_ok()));
+}
@@ -0,0 +1,33 @@
+use bytes::Bytes;
+use sigil::envelope::Envelope;
+
+#[test]
+fn test_envelope_payloads_are_encoded_in_base64() {
+    let encoded = br#"{"v":1,"payload":"aGk=","nonce":7,"timestamp":1000}"#;
+    let envelope = Envelope::decode(encoded).unwrap();
+    assert_eq!(envelope.payload, Bytes::from_static(b"hi"));
+    assert_eq!(envelope.encode(), encoded);
+
+    let shared = Envelope::new(envelope.payload.clone());
+    assert_eq!(shared.payload.as_ptr(), envelope.payload.as_ptr());
+}
+
+#[test]
+fn test_envelopes_from_older_nodes_are_still_decoded() {
+    let legacy = br#"{"payload":[104,105],"nonce":7,"timestamp":1000}"#;
+    let envelope = Envelope::decode(legacy).unwrap();
+    assert_eq!(envelope.payload, Bytes::from_static(b"hi"));
+    assert_eq!(
+        envelope.encode(),
+        br#"{"v":1,"payload":"aGk=","nonce":7,"timestamp":1000}"#
+    );
+}
+
+#[test]
+fn test_envelopes_of_unknown_versions_are_rejected() {
+    assert!(Envelope::decode(br#"{"v":2,"payload":"aGk=","nonce":7,"timestamp":1000}"#).is_err());
+    assert!(
+        Envelope::decode(br#"{"v":1,"payload":[104,105],"nonce":7,"timestamp":1000}"#).is_err()
+    );
+    assert!(Envelope::decode(br#"{"payload":"aGk=","nonce":7,"timestamp":1000}"#).is_err());
+}
@@ -0,0 +1,89 @@
+use bytes::Bytes;
+use libp2p::PeerId;
+use sigil::event::{EventLog, LoggedEvent, NodeEvent};
+use sigil::journal::{JournalEntry, ReceivedMessage};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+#[test]
+fn test_message_events_serialize_flat() {
+    let source = PeerId::random();
+    let event = NodeEvent::Message(ReceivedMessage {
+        topic: "test-net".to_string(),
+        entry: JournalEntry {
+            id: "1".to_string(),
+            source: Some(source),
+            received_at: 1_000,
+            payload: Bytes::from_static(b"hi"),
+        },
+    });
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        serde_json::json!({
+            "type": "message",
+            "topic": "test-net",
+            "id": "1",
+            "source": source.to_string(),
+            "received_at": 1_000,
+            "payload": [104, 105],
+        })
+    );
+    assert!(!event.is_peer_event());
+    assert!(NodeEvent::PeerConnected { peer: source }.is_peer_event());
+}
+
+#[test]
+fn test_event_log_keeps_recent_significant_events() {
+    let mut log = EventLog::new(3);
+    for _ in 0..4 {
+        log.record(
+            NodeEvent::PeerConnected {
+                peer: PeerId::random(),
+            },
+            1_000,
+        );
+    }
+    log.record(
+        NodeEvent::SyncProgress {
+            peer: PeerId::random(),
+            received: 1,
+            total: 2,
+        },
+        2_000,
+    );
+
+    let sequences = |events: Vec<LoggedEvent>| -> Vec<u64> {
+        events.iter().map(|logged| logged.sequence).collect()
+    };
+    assert_eq!(sequences(log.events(10, None)), vec![1, 2, 3]);
+    assert_eq!(sequences(log.events(2, None)), vec![2, 3]);
+    assert_eq!(sequences(log.events(1, Some(1))), vec![2]);
+    assert_eq!(sequences(log.events(10, Some(3))), Vec::<u64>::new());
+}
+
+#[tokio::test]
+async fn test_event_log_marks_the_events_it_missed() {
+    let (events, _receiver) = broadcast::channel(2);
+    let log = EventLog::spawn(&events, 10);
+    // The recorder cannot run before the test yields, so it falls behind.
+    for _ in 0..5 {
+        events
+            .send(NodeEvent::PeerConnected {
+                peer: PeerId::random(),
+            })
+            .unwrap();
+    }
+    while log.lock().unwrap().events(10, None).len() < 3 {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    let logged = log.lock().unwrap().events(10, None);
+    assert!(matches!(
+        logged[0].event,
+        NodeEvent::EventsMissed { missed: 3 }
+    ));
+    assert!(logged[1..]
+        .iter()
+        .all(|logged| matches!(logged.event, NodeEvent::PeerConnected { .. })));
+}
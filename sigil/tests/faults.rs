@@ -0,0 +1,67 @@
+use libp2p::PeerId;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sigil::config::{Config, ConfigFormat, LinkFaults};
+use sigil::faults::Faults;
+use std::time::Duration;
+
+#[test]
+fn test_link_faults_delay_writes() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let steady = LinkFaults {
+        latency_millis: 50,
+        ..LinkFaults::default()
+    };
+    assert_eq!(steady.delay(&mut rng), Duration::from_millis(50));
+
+    let jittery = LinkFaults {
+        jitter_millis: 20,
+        ..steady.clone()
+    };
+    for _ in 0..100 {
+        let delay = jittery.delay(&mut rng);
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(70));
+    }
+
+    // Dropped writes are resent after the retransmission timeout.
+    let lossy = LinkFaults {
+        drop_rate: 1.0,
+        ..steady
+    };
+    assert_eq!(lossy.delay(&mut rng), Duration::from_millis(250));
+    assert!(LinkFaults::default().is_none());
+}
+
+#[test]
+fn test_faults_apply_per_peer_over_the_default() {
+    let contents = r#"
+        [faults.default]
+        latency_millis = 10
+
+        [faults.peers."12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"]
+        latency_millis = 100
+        drop_rate = 0.05
+    "#;
+    let config = Config::parse_with_env(contents, ConfigFormat::Toml, []).unwrap();
+    let faults = Faults::new(config.faults);
+    let slow: PeerId = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"
+        .parse()
+        .unwrap();
+    let other = PeerId::random();
+    assert_eq!(faults.link(&slow).latency_millis, 100);
+    assert_eq!(faults.link(&slow).retransmit_timeout_millis, 200);
+    assert_eq!(faults.link(&other).latency_millis, 10);
+
+    faults.clear_peer(&slow);
+    assert_eq!(faults.link(&slow).latency_millis, 10);
+    faults.set_default(LinkFaults::default());
+    assert!(faults.is_empty());
+    faults.set_peer(
+        other,
+        LinkFaults {
+            jitter_millis: 5,
+            ..LinkFaults::default()
+        },
+    );
+    assert!(!faults.is_empty());
+}
@@ -0,0 +1,29 @@
+use sigil::config::IdentityConfig;
+use sigil::identity;
+use std::fs;
+
+#[test]
+fn test_keypair_is_generated_once_and_reused() {
+    let path = std::env::temp_dir().join(format!("sigil-identity-{}.key", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut config = IdentityConfig {
+        keypair_path: Some(path.clone()),
+        ..IdentityConfig::default()
+    };
+    let generated = identity::keypair(&config).unwrap();
+    let reloaded = identity::keypair(&config).unwrap();
+    assert_eq!(generated.public(), reloaded.public());
+
+    fs::remove_file(&path).unwrap();
+    config.generate_if_missing = false;
+    assert!(identity::keypair(&config).is_err());
+
+    let seeded = IdentityConfig {
+        secret_key_seed: Some(7),
+        ..IdentityConfig::default()
+    };
+    assert_eq!(
+        identity::keypair(&seeded).unwrap().public(),
+        identity::keypair(&seeded).unwrap().public()
+    );
+}
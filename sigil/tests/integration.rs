@@ -1,40 +1,34 @@
+mod test_helper;
+
 use anyhow::{Context, Result};
+use libp2p::multiaddr::Protocol;
 use reqwest::Client;
 use serde_json::json;
+use sigil::config::{Config, ConfigFormat, Profile};
+use sigil::relay::ReservationStatus;
+use sigil::rpc::client::MyApiClient;
 use std::panic::AssertUnwindSafe;
-use std::string::String;
-use testcontainers::{
-    core::{ContainerAsync, IntoContainerPort, WaitFor},
-    runners::AsyncRunner,
-    GenericImage,
-};
-
-async fn get_container_logs(container: &ContainerAsync<GenericImage>) -> String {
-    match container.stdout_to_vec().await {
-        Ok(log_bytes) => String::from_utf8_lossy(&log_bytes).into_owned(),
-        Err(e) => format!("Failed to retrieve container logs: {}", e),
-    }
-}
+use std::time::Duration;
+use test_helper::{log_failures, DockerNetwork, Nat, Partition, SigilTestInstance, RPC_PORT};
 
 #[tokio::test]
 async fn test_sigil() {
-    let container = GenericImage::new("sigil", "dev")
-        .with_exposed_port(3030.tcp())
-        .with_wait_for(WaitFor::message_on_stdout("Sigil is alive."))
-        .start()
+    let instance = SigilTestInstance::builder()
+        .build()
         .await
         .expect("Failed to start sigil container");
 
     if let Err(e) = async {
-        let host_port = container
-            .get_host_port_ipv4(3030)
+        let host_port = instance
+            .container
+            .get_host_port_ipv4(RPC_PORT)
             .await
             .context("Failed to get host port")?;
 
-        let client = reqwest::Client::new();
+        let client = Client::new();
         let response = client
             .post(&format!("http://localhost:{}", host_port))
-            .json(&serde_json::json!({
+            .json(&json!({
                 "jsonrpc": "2.0",
                 "method": "say_hello",
                 "params": ["Sigil"],
@@ -57,7 +51,284 @@ async fn test_sigil() {
     }
     .await
     {
-        let logs = get_container_logs(&container).await;
+        let logs = instance.logs().await;
         panic!("Test failed: {}. Container logs:\n{}", e, logs);
     }
 }
+
+#[tokio::test]
+async fn test_typed_client_greets() {
+    let instance = SigilTestInstance::builder()
+        .build()
+        .await
+        .expect("Failed to start sigil container");
+
+    let result: Result<()> = async {
+        let greeting = instance
+            .client
+            .say_hello("Sigil".to_string())
+            .await
+            .context("Failed to send request")?;
+        if greeting != "Hello, Sigil!" {
+            anyhow::bail!("Unexpected greeting: {greeting}");
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        panic!(
+            "Test failed: {e:#}. Container logs:\n{}",
+            instance.logs().await
+        );
+    }
+}
+
+#[test]
+fn test_instance_config_renders_settings() {
+    let peer = "/ip4/172.18.0.2/tcp/9000/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+    let builder = SigilTestInstance::builder()
+        .port(9100)
+        .peers([peer.parse().unwrap()])
+        .is_relay(true)
+        .setting("mempool.capacity", 10);
+    let config = Config::parse_with_env(&builder.config(), ConfigFormat::Toml, []).unwrap();
+    assert_eq!(config.profile, Some(Profile::Test));
+    assert_eq!(config.network.tcp_port, Some(9100));
+    assert_eq!(config.network.peers[0].addresses[0].to_string(), peer);
+    assert!(config.relay());
+    assert_eq!(config.mempool.capacity, 10);
+}
+
+#[test]
+fn test_log_failures_finds_panics_errors_and_overflows() {
+    let logs = "\
+2024-01-01T00:00:00.000000Z  INFO sigil::node: Sigil is alive.
+2024-01-01T00:00:01.000000Z \x1b[31mERROR\x1b[0m sigil::node: Failed to save snapshot
+thread 'tokio-runtime-worker' panicked at src/node.rs:10:5:
+Validation queue full, ignoring message 1 from peer 12D3KooW
+{\"level\":\"ERROR\",\"fields\":{\"message\":\"Bootstrap failed\"}}
+2024-01-01T00:00:02.000000Z  WARN sigil::node: Relay refused the reservation: ERRORS ahead";
+    assert_eq!(
+        log_failures(logs, &[]),
+        [
+            "2024-01-01T00:00:01.000000Z ERROR sigil::node: Failed to save snapshot",
+            "thread 'tokio-runtime-worker' panicked at src/node.rs:10:5:",
+            "Validation queue full, ignoring message 1 from peer 12D3KooW",
+            "{\"level\":\"ERROR\",\"fields\":{\"message\":\"Bootstrap failed\"}}",
+        ]
+    );
+    assert_eq!(
+        log_failures(logs, &["snapshot".to_string(), "queue full".to_string()]).len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_nodes_connect_to_configured_peers() {
+    let shared = DockerNetwork::create()
+        .await
+        .expect("Failed to create network");
+    let alpha = SigilTestInstance::builder()
+        .network(&shared)
+        .build()
+        .await
+        .expect("Failed to start alpha");
+    let address = alpha
+        .address(&shared)
+        .await
+        .expect("Failed to find alpha's address");
+    let bravo = SigilTestInstance::builder()
+        .network(&shared)
+        .peers([address])
+        .build()
+        .await
+        .expect("Failed to start bravo");
+
+    let timeout = Duration::from_secs(30);
+    if let Err(e) = bravo.wait_for_peer(alpha.peer_id, timeout).await {
+        panic!("Test failed: {e:#}. Bravo's logs:\n{}", bravo.logs().await);
+    }
+    alpha
+        .wait_for_connected(1, timeout)
+        .await
+        .expect("Alpha did not see bravo connect");
+}
+
+#[tokio::test]
+async fn test_gossip_reaches_connected_peers() {
+    let shared = DockerNetwork::create()
+        .await
+        .expect("Failed to create network");
+    let alpha = SigilTestInstance::builder()
+        .network(&shared)
+        .build()
+        .await
+        .expect("Failed to start alpha");
+    let address = alpha
+        .address(&shared)
+        .await
+        .expect("Failed to find alpha's address");
+    let bravo = SigilTestInstance::builder()
+        .network(&shared)
+        .peers([address])
+        .build()
+        .await
+        .expect("Failed to start bravo");
+
+    if let Err(e) = async {
+        let timeout = Duration::from_secs(30);
+        alpha
+            .wait_for_mesh("test-net", bravo.peer_id, timeout)
+            .await?;
+        bravo
+            .wait_for_mesh("test-net", alpha.peer_id, timeout)
+            .await?;
+
+        alpha.publish("test-net", "from alpha").await?;
+        let message = bravo
+            .expect_message("test-net", "from alpha", timeout)
+            .await?;
+        if message.entry.source != Some(alpha.peer_id) {
+            anyhow::bail!("Message came from {:?}", message.entry.source);
+        }
+        bravo.publish("test-net", "from bravo").await?;
+        alpha
+            .expect_message("test-net", "from bravo", timeout)
+            .await?;
+        Ok::<(), anyhow::Error>(())
+    }
+    .await
+    {
+        panic!(
+            "Test failed: {e:#}. Alpha's logs:\n{}\nBravo's logs:\n{}",
+            alpha.logs().await,
+            bravo.logs().await
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_partitioned_nodes_reach_each_other_once_healed() {
+    let shared = DockerNetwork::create()
+        .await
+        .expect("Failed to create network");
+    let alpha = SigilTestInstance::builder()
+        .network(&shared)
+        .build()
+        .await
+        .expect("Failed to start alpha");
+    let bravo = SigilTestInstance::builder()
+        .network(&shared)
+        .build()
+        .await
+        .expect("Failed to start bravo");
+
+    if let Err(e) = async {
+        let partition = Partition::split(&shared, &[&bravo]).await?;
+        let address = alpha.address(&shared).await?;
+        let dialed = tokio::time::timeout(Duration::from_secs(15), bravo.client.dial(address));
+        if let Ok(Ok(peer_id)) = dialed.await {
+            anyhow::bail!("Dialed {peer_id} across the partition");
+        }
+
+        partition.heal().await?;
+        let address = alpha.address(&shared).await?;
+        let peer_id = bravo
+            .client
+            .dial(address)
+            .await
+            .context("Failed to dial once healed")?;
+        if peer_id != alpha.peer_id {
+            anyhow::bail!("Dialed {peer_id} instead of {}", alpha.peer_id);
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await
+    {
+        panic!(
+            "Test failed: {e}. Alpha's logs:\n{}\nBravo's logs:\n{}",
+            alpha.logs().await,
+            bravo.logs().await
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_nodes_behind_nats_hole_punch_through_a_relay() {
+    let shared = DockerNetwork::create()
+        .await
+        .expect("Failed to create network");
+    let relay = SigilTestInstance::builder()
+        .is_relay(true)
+        .network(&shared)
+        .build()
+        .await
+        .expect("Failed to start the relay");
+    let relay_address = relay
+        .address(&shared)
+        .await
+        .expect("Failed to find the relay");
+
+    // Each node sits behind a NAT of its own, so it is only reachable through its reservation on
+    // the relay until a hole is punched. Reservations made before the NAT routes the node's
+    // traffic fail, and are retried every second.
+    let mut instances = Vec::new();
+    let mut nats = Vec::new();
+    for name in ["alpha", "bravo"] {
+        let nat = Nat::create(&shared).await.expect("Failed to create NAT");
+        let instance = SigilTestInstance::builder()
+            .setting("relay.reservations", vec![relay_address.to_string()])
+            .setting("schedule.relay_check_interval_secs", 1)
+            .build()
+            .await
+            .unwrap_or_else(|e| panic!("Failed to start {name}: {e}"));
+        nat.attach(&instance)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to put {name} behind its NAT: {e}"));
+        instances.push(instance);
+        nats.push(nat);
+    }
+    let [alpha, bravo] = instances.as_slice() else {
+        unreachable!()
+    };
+
+    if let Err(e) =
+        async {
+            alpha
+                .wait_for(
+                    "a reservation on the relay",
+                    Duration::from_secs(60),
+                    |client| async move {
+                        Ok(client
+                            .relays()
+                            .await?
+                            .iter()
+                            .any(|relay| relay.status == ReservationStatus::Accepted))
+                    },
+                )
+                .await?;
+            let circuit = relay_address
+                .clone()
+                .with(Protocol::P2pCircuit)
+                .with(Protocol::P2p(alpha.peer_id));
+            bravo
+                .client
+                .dial(circuit)
+                .await
+                .context("Failed to dial alpha through the relay")?;
+            bravo
+            .wait_for("a hole punched to alpha", Duration::from_secs(60), |client| async move {
+                Ok(client.stats().await?.result.holepunches_succeeded > 0)
+            })
+            .await
+        }
+        .await
+    {
+        panic!(
+            "Test failed: {e}. Relay's logs:\n{}\nAlpha's logs:\n{}\nBravo's logs:\n{}",
+            relay.logs().await,
+            alpha.logs().await,
+            bravo.logs().await
+        );
+    }
+}
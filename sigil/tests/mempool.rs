@@ -0,0 +1,47 @@
+use sigil::mempool::{Mempool, MempoolError, Transaction, TransactionValidator};
+use std::sync::Arc;
+
+fn transaction(nonce: u64) -> Transaction {
+    Transaction {
+        nonce,
+        data: b"transfer".to_vec(),
+    }
+}
+
+struct EvenNonces;
+
+impl TransactionValidator for EvenNonces {
+    fn validate(&self, transaction: &Transaction) -> bool {
+        transaction.nonce.is_multiple_of(2)
+    }
+}
+
+#[test]
+fn test_mempool_deduplicates_and_evicts_oldest() {
+    let mut mempool = Mempool::new(2);
+    let first = mempool.insert(transaction(1)).unwrap();
+    assert!(matches!(
+        mempool.insert(transaction(1)),
+        Err(MempoolError::Duplicate(hash)) if hash == first
+    ));
+
+    let second = mempool.insert(transaction(2)).unwrap();
+    let third = mempool.insert(transaction(3)).unwrap();
+    let pending: Vec<_> = mempool.pending(10).iter().map(|tx| tx.hash).collect();
+    assert_eq!(pending, vec![second, third]);
+
+    mempool.remove(&[second]);
+    assert_eq!(mempool.status().pending, 1);
+    assert_eq!(mempool.pending(10)[0].hash, third);
+}
+
+#[test]
+fn test_mempool_applies_validator() {
+    let mut mempool = Mempool::new(10);
+    mempool.set_validator(Arc::new(EvenNonces));
+    assert!(matches!(
+        mempool.insert(transaction(1)),
+        Err(MempoolError::Invalid(_))
+    ));
+    assert!(mempool.insert(transaction(2)).is_ok());
+}
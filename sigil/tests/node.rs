@@ -0,0 +1,33 @@
+use libp2p::gossipsub::{IdentTopic, Message, MessageId};
+use libp2p::PeerId;
+use sigil::node::message_id;
+
+fn message(source: PeerId, sequence_number: u64) -> Message {
+    Message {
+        source: Some(source),
+        data: b"hello".to_vec(),
+        sequence_number: Some(sequence_number),
+        topic: IdentTopic::new("test-net").hash(),
+    }
+}
+
+#[test]
+fn test_message_ids_are_sha256_digests() {
+    let (alpha, bravo) = (PeerId::random(), PeerId::random());
+
+    // Content-addressed ids are the digest of the data alone, whoever published it.
+    let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    assert_eq!(
+        message_id(&message(alpha, 1), true),
+        MessageId::from(digest)
+    );
+    assert_eq!(
+        message_id(&message(bravo, 2), true),
+        MessageId::from(digest)
+    );
+
+    let id = message_id(&message(alpha, 1), false);
+    assert_eq!(id, message_id(&message(alpha, 1), false));
+    assert_ne!(id, message_id(&message(alpha, 2), false));
+    assert_ne!(id, message_id(&message(bravo, 1), false));
+}
@@ -0,0 +1,50 @@
+use libp2p::PeerId;
+use sigil::page::PeerQuery;
+
+#[test]
+fn test_peer_pages_resume_from_their_cursor() {
+    let peers: Vec<(PeerId, u32)> = (0..5).map(|i| (PeerId::random(), i % 2)).collect();
+    let mut sorted: Vec<PeerId> = peers.iter().map(|(peer, _)| *peer).collect();
+    sorted.sort_by_key(|peer| peer.to_string());
+
+    let mut query = PeerQuery {
+        limit: Some(2),
+        ..PeerQuery::default()
+    };
+    let mut listed = Vec::new();
+    loop {
+        let page = query.page(
+            peers.clone(),
+            |(peer, _)| *peer,
+            |(_, bucket)| Some(*bucket),
+        );
+        assert!(page.items.len() <= 2);
+        listed.extend(page.items.iter().map(|(peer, _)| *peer));
+        match page.next_cursor {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    assert_eq!(listed, sorted);
+
+    // Filters narrow the listing before it is paged.
+    let odd = PeerQuery {
+        bucket: Some(1),
+        ..PeerQuery::default()
+    }
+    .page(
+        peers.clone(),
+        |(peer, _)| *peer,
+        |(_, bucket)| Some(*bucket),
+    );
+    assert_eq!(odd.items.len(), 2);
+    assert_eq!(odd.next_cursor, None);
+
+    let prefix = sorted[3].to_string()[..20].to_string();
+    let matching = PeerQuery {
+        prefix: Some(prefix),
+        ..PeerQuery::default()
+    }
+    .page(peers, |(peer, _)| *peer, |_| None);
+    assert_eq!(matching.items.len(), 1);
+}
@@ -0,0 +1,41 @@
+use sigil::config::{RateConfig, RateLimitConfig};
+use sigil::rpc::rate_limit::RateLimiter;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_rate_limiter_allows_bursts_then_refills() {
+    let limiter = RateLimiter::new(RateLimitConfig {
+        per_ip: Some(RateConfig {
+            per_second: 2.0,
+            burst: 3,
+        }),
+        methods: HashMap::from([(
+            "kademlia_routing_table_peers".to_string(),
+            RateConfig {
+                per_second: 1.0,
+                burst: 1,
+            },
+        )]),
+    });
+    let alice = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    let bob = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    let now = Instant::now();
+
+    for _ in 0..3 {
+        assert!(limiter.check(alice, "say_hello", now));
+    }
+    assert!(!limiter.check(alice, "say_hello", now));
+    assert!(limiter.check(bob, "say_hello", now));
+
+    // Half a second refills one call at two per second.
+    let later = now + Duration::from_millis(500);
+    assert!(limiter.check(alice, "say_hello", later));
+    assert!(!limiter.check(alice, "say_hello", later));
+
+    // Method limits are shared by all callers.
+    assert!(limiter.check(bob, "kademlia_routing_table_peers", now));
+    assert!(!limiter.check(None, "kademlia_routing_table_peers", now));
+    assert!(limiter.check(None, "say_hello", now));
+}
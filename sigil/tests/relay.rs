@@ -0,0 +1,37 @@
+use libp2p::core::transport::ListenerId;
+use libp2p::{Multiaddr, PeerId};
+use sigil::relay::{Relays, ReservationStatus};
+
+#[test]
+fn test_relay_reservations_track_their_listener() {
+    let mut relays = Relays::default();
+    let relay = PeerId::random();
+    let address: Multiaddr = format!("/ip4/10.0.0.1/tcp/4001/p2p/{relay}")
+        .parse()
+        .unwrap();
+    let listener = ListenerId::next();
+    let snapshot = relays.snapshot();
+    relays.request(relay, address.clone(), listener);
+    let pending = snapshot.get();
+    assert_eq!(pending[0].status, ReservationStatus::Pending);
+
+    // Lists already taken stay as they were.
+    relays.accepted(relay);
+    assert_eq!(snapshot.get()[0].status, ReservationStatus::Accepted);
+    assert_eq!(pending[0].status, ReservationStatus::Pending);
+
+    // Listeners that are not through a relay are ignored, and errors outlive the listener.
+    assert_eq!(relays.closed(ListenerId::next(), None), None);
+    assert_eq!(relays.relay_of(listener), Some(relay));
+    relays.failed(relay, "reservation expired".to_string());
+    assert_eq!(relays.closed(listener, None), Some(relay));
+    let closed = &relays.list()[0];
+    assert_eq!(closed.status, ReservationStatus::Closed);
+    assert_eq!(closed.address, address);
+    assert_eq!(closed.error.as_deref(), Some("reservation expired"));
+    assert_eq!(relays.closed_relays(), [(relay, address.clone())]);
+
+    // Requesting the reservation again makes it pending until the relay accepts it.
+    relays.request(relay, address, ListenerId::next());
+    assert!(relays.closed_relays().is_empty());
+}
@@ -0,0 +1,53 @@
+use serde_json::json;
+use sigil::reload::Reloader;
+use sigil::testing::TestNetwork;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_fault_updates_build_on_each_other() {
+    let network = TestNetwork::simulate(1).expect("Failed to start the network");
+    let node = &network.nodes()[0];
+    let reloader = Reloader::new(None, Arc::new(node.config.clone()), node.client.clone());
+
+    reloader
+        .update(json!({"faults": {"default": {"latency_millis": 20}}}))
+        .await
+        .unwrap();
+    let config = reloader
+        .update(json!({"faults": {"default": {"drop_rate": 0.5}}}))
+        .await
+        .unwrap();
+    assert_eq!(config.faults.default.latency_millis, 20);
+    assert_eq!(config.faults.default.drop_rate, 0.5);
+    assert_eq!(
+        node.client.faults().link(&node.peer_id),
+        config.faults.default
+    );
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_updates_keep_the_running_secrets() {
+    let network = TestNetwork::simulate(1).expect("Failed to start the network");
+    let node = &network.nodes()[0];
+    let mut config = node.config.clone();
+    config
+        .telemetry
+        .otlp
+        .headers
+        .insert("authorization".to_string(), "Bearer secret".to_string());
+    let reloader = Reloader::new(None, Arc::new(config), node.client.clone());
+
+    let config = reloader
+        .update(json!({"rpc": {"allowed_ips": ["10.0.0.0/8"]}}))
+        .await
+        .unwrap();
+    assert_eq!(
+        config.telemetry.otlp.headers["authorization"],
+        "Bearer secret"
+    );
+    assert!(!serde_json::to_string(&*config)
+        .unwrap()
+        .contains("Bearer secret"));
+    network.shutdown().await;
+}
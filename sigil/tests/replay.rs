@@ -0,0 +1,31 @@
+use libp2p::PeerId;
+use sigil::replay::{ReplayCache, ReplayError};
+use std::time::Duration;
+
+#[test]
+fn test_replay_cache_rejects_duplicates_and_stale_messages() {
+    let mut cache = ReplayCache::new(Duration::from_secs(60));
+    let sender = PeerId::random();
+    let now = 1_000_000;
+
+    assert_eq!(cache.check(sender, 1, now, now), Ok(()));
+    assert_eq!(
+        cache.check(sender, 1, now, now),
+        Err(ReplayError::Duplicate)
+    );
+    assert_eq!(cache.check(PeerId::random(), 1, now, now), Ok(()));
+    assert_eq!(
+        cache.check(sender, 2, now - 61_000, now),
+        Err(ReplayError::Stale)
+    );
+    assert_eq!(
+        cache.check(sender, 3, now + 61_000, now),
+        Err(ReplayError::Stale)
+    );
+
+    // Once a nonce has aged out of the window, its replay is still rejected as stale.
+    assert_eq!(
+        cache.check(sender, 1, now, now + 120_000),
+        Err(ReplayError::Stale)
+    );
+}
@@ -0,0 +1,318 @@
+use bytes::Bytes;
+use libp2p::{Multiaddr, PeerId};
+use serde::Serialize;
+use serde_json::Value;
+use sigil::block::BlockHash;
+use sigil::client::ChannelDepths;
+use sigil::config::Config;
+use sigil::dht::RoutingTableEntry;
+use sigil::event::{LoggedEvent, NodeEvent};
+use sigil::journal::{JournalEntry, ReceivedMessage};
+use sigil::log_stream::LogRecord;
+use sigil::mempool::{MempoolStatus, PooledTransaction, Transaction, TxHash};
+use sigil::node::{BootstrapStatus, ConnectionInfo, NatStatus, NodeInfo, NodeState, NodeStatus};
+use sigil::page::Page;
+use sigil::peers::PeerInfo;
+use sigil::relay::{RelayInfo, ReservationStatus};
+use sigil::rpc::{MetaApiServer, MetaRpc, StateDump, Versioned};
+use sigil::stats::SessionStats;
+use sigil::version::{BuildInfo, Dependency};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::{env, fs};
+
+const PEER: &str = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+
+fn peer() -> PeerId {
+    PEER.parse().unwrap()
+}
+
+fn address() -> Multiaddr {
+    "/ip4/203.0.113.7/tcp/9000".parse().unwrap()
+}
+
+/// Compare `value`'s JSON with the golden file `name` under `tests/golden/rpc`, so that the shape
+/// of the RPC API's responses and subscription items only changes on purpose. After an intended
+/// change, rewrite the files by running these tests with `UPDATE_GOLDEN=1`, and raise
+/// `API_VERSION`. Methods answering with a plain string, number, boolean, or list of peer ids or
+/// topics are left out.
+fn check(name: &str, value: impl Serialize) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/rpc")
+        .join(format!("{name}.json"));
+    let actual = serde_json::to_value(value).unwrap();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+    let golden: Value = serde_json::from_str(&golden).unwrap();
+    assert_eq!(
+        actual,
+        golden,
+        "The JSON of {name} no longer matches {}",
+        path.display()
+    );
+}
+
+fn node_info() -> NodeInfo {
+    NodeInfo {
+        peer_id: peer(),
+        node_name: Some("sequencer-eu-1".to_string()),
+        agent_version: "sigil/0.1.0 (sequencer-eu-1)".to_string(),
+        protocol_version: "/sigil/0.1".to_string(),
+        listen_addresses: vec!["/ip4/0.0.0.0/tcp/9000".parse().unwrap()],
+        external_addresses: vec![address()],
+        uptime_secs: 3600,
+        build: BuildInfo {
+            version: Cow::Borrowed("0.1.0"),
+            git_hash: Cow::Borrowed("0123abc"),
+            build_timestamp: Cow::Borrowed("2024-01-01T00:00:00Z"),
+            rustc_version: Cow::Borrowed("rustc 1.80.0"),
+            dependencies: Cow::Owned(vec![Dependency {
+                name: Cow::Borrowed("libp2p"),
+                version: Cow::Borrowed("0.54.1"),
+            }]),
+        },
+    }
+}
+
+fn relay_info() -> RelayInfo {
+    RelayInfo {
+        peer_id: peer(),
+        address: address(),
+        status: ReservationStatus::Closed,
+        error: Some("connection refused".to_string()),
+    }
+}
+
+fn received_message() -> ReceivedMessage {
+    ReceivedMessage {
+        topic: "test-net".to_string(),
+        entry: JournalEntry {
+            id: "3f2a".to_string(),
+            source: Some(peer()),
+            received_at: 1_700_000_000_000,
+            payload: Bytes::from_static(b"hi"),
+        },
+    }
+}
+
+#[test]
+fn test_node_responses_match_golden_json() {
+    check("node_info", Versioned::new(node_info()));
+    check(
+        "node_status",
+        Versioned::new(NodeStatus {
+            connected_peers: 12,
+            mesh_peers: 6,
+            relays: 1,
+            external_addresses: vec![address()],
+            bootstrap: BootstrapStatus::Done,
+            nat: NatStatus::Public,
+        }),
+    );
+    check(
+        "stats",
+        Versioned::new(SessionStats {
+            uptime_secs: 3600,
+            connections: 12,
+            messages_published: 40,
+            messages_received: 95,
+            holepunches_succeeded: 3,
+            holepunches_failed: 1,
+        }),
+    );
+    check("relays", vec![relay_info()]);
+    check(
+        "peers",
+        Versioned::new(Page {
+            items: vec![PeerInfo {
+                peer_id: peer(),
+                node_name: Some("sequencer-eu-1".to_string()),
+                agent_version: Some("sigil/0.1.0 (sequencer-eu-1)".to_string()),
+                protocol_version: Some("/sigil/0.1".to_string()),
+                listen_addresses: vec![address()],
+                clock_skew_millis: Some(-12),
+            }],
+            next_cursor: Some(peer()),
+        }),
+    );
+    check(
+        "connected_peers_page",
+        Versioned::new(Page {
+            items: vec![peer()],
+            next_cursor: None::<PeerId>,
+        }),
+    );
+    check(
+        "events",
+        vec![LoggedEvent {
+            sequence: 7,
+            at: 1_700_000_000_000,
+            event: NodeEvent::PeerConnected { peer: peer() },
+        }],
+    );
+
+    // The configuration in a dump is checked against the default configuration file instead.
+    let mut dump = serde_json::to_value(Versioned::new(StateDump {
+        node: NodeState {
+            info: node_info(),
+            connections: vec![ConnectionInfo {
+                peer_id: peer(),
+                address: address(),
+                outbound: true,
+            }],
+            relays: vec![relay_info()],
+            mesh: BTreeMap::from([("test-net".to_string(), vec![peer()])]),
+            pending_dials: 1,
+            pending_dht_queries: 2,
+            pending_deliveries: 3,
+            mempool: MempoolStatus {
+                pending: 4,
+                capacity: 10000,
+            },
+        },
+        channels: ChannelDepths {
+            control: 0,
+            consensus: 1,
+            bulk: 2,
+            events: 3,
+        },
+        config: Arc::new(Config::default()),
+    }))
+    .unwrap();
+    dump.as_object_mut().unwrap().remove("config");
+    check("debug_dumpState", dump);
+}
+
+#[tokio::test]
+async fn test_rpc_modules_match_golden_json() {
+    check("rpc_modules", MetaRpc.rpc_modules().await.unwrap());
+}
+
+#[test]
+fn test_dht_and_mempool_responses_match_golden_json() {
+    check(
+        "kademlia_routing_table_peers",
+        HashMap::from([(peer(), vec![address()])]),
+    );
+    check(
+        "kademlia_routing_table",
+        Versioned::new(Page {
+            items: vec![RoutingTableEntry {
+                peer_id: peer(),
+                addresses: vec![address()],
+                bucket: 255,
+            }],
+            next_cursor: None,
+        }),
+    );
+    check(
+        "mempool_content",
+        vec![PooledTransaction {
+            hash: TxHash([0xab; 32]),
+            transaction: Transaction {
+                nonce: 1,
+                data: vec![1, 2, 3],
+            },
+        }],
+    );
+    check(
+        "mempool_status",
+        Versioned::new(MempoolStatus {
+            pending: 4,
+            capacity: 10000,
+        }),
+    );
+}
+
+#[test]
+fn test_subscription_items_match_golden_json() {
+    check("subscribe_messages", NodeEvent::Message(received_message()));
+    check(
+        "subscribe_peer_events",
+        vec![
+            NodeEvent::PeerConnected { peer: peer() },
+            NodeEvent::PeerDisconnected { peer: peer() },
+        ],
+    );
+    check(
+        "subscribe_logs",
+        LogRecord {
+            at: 1_700_000_000_000,
+            level: "INFO".to_string(),
+            target: "sigil::node".to_string(),
+            message: "Connected".to_string(),
+            fields: BTreeMap::from([("peer".to_string(), PEER.to_string())]),
+        },
+    );
+}
+
+#[test]
+fn test_every_node_event_matches_golden_json() {
+    let peer = peer();
+    check(
+        "node_events",
+        vec![
+            NodeEvent::Message(received_message()),
+            NodeEvent::PeerConnected { peer },
+            NodeEvent::PrivateMessage {
+                peer,
+                id: "direct-3".to_string(),
+                received_at: 1_700_000_000_000,
+                payload: Bytes::from_static(b"hi"),
+            },
+            NodeEvent::PeerDisconnected { peer },
+            NodeEvent::BlobOffered {
+                peer,
+                id: 1,
+                name: "snapshot.tar".to_string(),
+                size: 4096,
+            },
+            NodeEvent::BlobProgress {
+                peer,
+                id: 1,
+                received: 1024,
+                size: 4096,
+            },
+            NodeEvent::BlobReceived {
+                peer,
+                id: 1,
+                size: 4096,
+            },
+            NodeEvent::BlobSent { peer, id: 1 },
+            NodeEvent::BatchReceived {
+                peer,
+                number: 5,
+                hash: BlockHash([7; 32]),
+                transactions: 2,
+            },
+            NodeEvent::SyncProgress {
+                peer,
+                received: 10,
+                total: 20,
+            },
+            NodeEvent::SyncFinished {
+                peer,
+                error: Some("peer went away".to_string()),
+            },
+            NodeEvent::ClockSkewed {
+                peer,
+                skew_millis: -2500,
+            },
+            NodeEvent::HolePunched { peer },
+            NodeEvent::HolePunchFailed {
+                peer,
+                error: "no addresses".to_string(),
+            },
+            NodeEvent::BootstrapChanged {
+                status: BootstrapStatus::Done,
+            },
+            NodeEvent::EventsMissed { missed: 12 },
+        ],
+    );
+}
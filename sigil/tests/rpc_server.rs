@@ -0,0 +1,92 @@
+use jsonrpsee::server::ServerHandle;
+use jsonrpsee::types::error::METHOD_NOT_FOUND_CODE;
+use prometheus_client::registry::Registry;
+use sigil::config::Config;
+use sigil::log_stream::LogStream;
+use sigil::reload::Reloader;
+use sigil::rpc::client::{self, AdminApiClient, Error, MetaApiClient};
+use sigil::rpc::server;
+use sigil::testing::{TestNetwork, TestNode};
+use std::fs;
+use std::net::{Ipv4Addr, TcpListener};
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_admin_methods_are_not_served_without_a_token() {
+    let network = TestNetwork::start(1).expect("Failed to start the network");
+    let node = &network.nodes()[0];
+    let mut config = node.config.clone();
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    config.rpc.listen = (Ipv4Addr::LOCALHOST, port).into();
+    config.rpc.auth = Default::default();
+    let reloader = Arc::new(Reloader::new(
+        None,
+        Arc::new(config.clone()),
+        node.client.clone(),
+    ));
+    let _handle = server::start(
+        &config.rpc,
+        node.client.clone(),
+        reloader,
+        &mut Registry::default(),
+        &config.telemetry.rpc_duration_buckets,
+        LogStream::default(),
+    )
+    .await
+    .expect("Failed to start the RPC server");
+
+    let rpc = client::http(&format!("http://127.0.0.1:{port}"), None).unwrap();
+    assert!(rpc.api_version().await.is_ok());
+    match rpc.shutdown().await {
+        Err(Error::Call(error)) => assert_eq!(error.code(), METHOD_NOT_FOUND_CODE),
+        result => panic!("admin_shutdown was served without a token: {result:?}"),
+    }
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_ipc_socket_is_private_and_never_taken_over() {
+    let network = TestNetwork::start(1).expect("Failed to start the network");
+    let node = &network.nodes()[0];
+    let dir = std::env::temp_dir().join(format!("sigil-ipc-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("sigil.sock");
+    let mut config = node.config.clone();
+    config.rpc.listen = (Ipv4Addr::LOCALHOST, 0).into();
+    config.rpc.ipc_path = Some(path.clone());
+    let reloader = Arc::new(Reloader::new(
+        None,
+        Arc::new(config.clone()),
+        node.client.clone(),
+    ));
+    let _handle = start(&config, node, reloader.clone())
+        .await
+        .expect("Failed to start the RPC server");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(start(&config, node, reloader).await.is_err());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    network.shutdown().await;
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+async fn start(
+    config: &Config,
+    node: &TestNode,
+    reloader: Arc<Reloader>,
+) -> Result<ServerHandle, Box<dyn std::error::Error>> {
+    server::start(
+        &config.rpc,
+        node.client.clone(),
+        reloader,
+        &mut Registry::default(),
+        &config.telemetry.rpc_duration_buckets,
+        LogStream::default(),
+    )
+    .await
+}
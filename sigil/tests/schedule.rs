@@ -0,0 +1,20 @@
+use sigil::schedule::{Job, Scheduler};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_scheduler_returns_jobs_as_they_fall_due() {
+    let mut scheduler = Scheduler::default();
+    scheduler.every(Job::SaveSnapshot, Duration::from_secs(3600));
+    scheduler.every(Job::RetryDeliveries, Duration::from_millis(10));
+    assert_eq!(
+        scheduler.jobs().collect::<Vec<_>>(),
+        [Job::SaveSnapshot, Job::RetryDeliveries]
+    );
+
+    // Jobs are not due until their first period has passed.
+    let first = tokio::time::timeout(Duration::from_millis(5), scheduler.next()).await;
+    assert!(first.is_err());
+    for _ in 0..3 {
+        assert_eq!(scheduler.next().await, Job::RetryDeliveries);
+    }
+}
@@ -0,0 +1,61 @@
+use sigil::event::NodeEvent;
+use sigil::testing::TestNetwork;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::test(start_paused = true)]
+async fn test_simulated_nodes_bootstrap_through_one_peer() {
+    // Every node joins through the first, and finds the rest by bootstrapping the DHT, which
+    // happens every minute of simulated time.
+    let network = TestNetwork::simulate_with(5, |_, config| {
+        config.network.peers.truncate(1);
+        config.schedule.kad_bootstrap_interval_secs = 60;
+    })
+    .expect("Failed to start the network");
+    sleep(Duration::from_secs(300)).await;
+
+    for node in network.nodes() {
+        let routing_table = node.client.routing_table_peers().await.unwrap();
+        for other in network.nodes() {
+            assert!(
+                other.peer_id == node.peer_id || routing_table.contains_key(&other.peer_id),
+                "{} never found {}",
+                node.peer_id,
+                other.peer_id
+            );
+        }
+    }
+    network.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_event_log_holds_events_from_before_subscribing() {
+    let network = TestNetwork::simulate(2).expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+
+    // The nodes connected before anything subscribed to their events, which the event log still
+    // holds once its task has caught up.
+    sleep(Duration::from_millis(100)).await;
+    let [first, second] = network.nodes() else {
+        unreachable!()
+    };
+    let logged = first.client.recent_events(100, None);
+    assert!(logged.iter().any(|logged| matches!(
+        logged.event,
+        NodeEvent::PeerConnected { peer } if peer == second.peer_id
+    )));
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_simulated_peer_ids_follow_node_indexes() {
+    let first = TestNetwork::simulate(2).expect("Failed to start the network");
+    let second = TestNetwork::simulate(2).expect("Failed to start the network");
+    for (a, b) in first.nodes().iter().zip(second.nodes()) {
+        assert_eq!(a.peer_id, b.peer_id);
+        assert_ne!(a.address, b.address);
+    }
+}
@@ -0,0 +1,27 @@
+use libp2p::{Multiaddr, PeerId};
+use sigil::snapshot::{NetworkSnapshot, SnapshotPeer};
+use std::fs;
+
+#[test]
+fn test_snapshot_round_trips_through_its_file() {
+    let path = std::env::temp_dir().join(format!("sigil-snapshot-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    assert_eq!(NetworkSnapshot::load(&path).unwrap(), None);
+
+    let address: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+    let snapshot = NetworkSnapshot {
+        peers: vec![SnapshotPeer {
+            peer_id: PeerId::random(),
+            addresses: vec![address.clone()],
+        }],
+        relays: Vec::new(),
+        external_addresses: vec![address],
+        topics: vec!["sigil".to_string()],
+    };
+    snapshot.save(&path).unwrap();
+    assert_eq!(NetworkSnapshot::load(&path).unwrap(), Some(snapshot));
+
+    fs::write(&path, "{").unwrap();
+    assert!(NetworkSnapshot::load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,28 @@
+use libp2p::gossipsub::IdentTopic;
+use libp2p::PeerId;
+use prometheus_client::registry::Registry;
+use sigil::stats::{GossipStats, MAX_TOPIC_PEERS};
+use std::time::Instant;
+
+#[test]
+fn test_topic_stats_keep_the_peers_that_forwarded_the_most() {
+    let mut stats = GossipStats::new(&mut Registry::default());
+    let topic = IdentTopic::new("test-net").hash();
+    let now = Instant::now();
+    let busy = PeerId::random();
+    for _ in 0..3 {
+        stats.record_message(&topic, busy, 10, now);
+    }
+    for _ in 0..MAX_TOPIC_PEERS * 2 {
+        stats.record_message(&topic, PeerId::random(), 10, now);
+    }
+
+    let snapshot = stats.snapshot(now);
+    assert_eq!(snapshot[0].messages, 3 + MAX_TOPIC_PEERS as u64 * 2);
+    assert_eq!(snapshot[0].peers.len(), MAX_TOPIC_PEERS);
+    assert_eq!(snapshot[0].peers[&busy], 3);
+
+    stats.forget_peer(&busy);
+    let snapshot = stats.snapshot(now);
+    assert!(!snapshot[0].peers.contains_key(&busy));
+}
@@ -0,0 +1,45 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+use sigil::telemetry::otlp_metrics;
+
+#[test]
+fn test_openmetrics_convert_to_otlp() {
+    let mut registry = Registry::with_prefix("sigil");
+    let counter: Counter = Counter::default();
+    counter.inc_by(3);
+    registry.register("messages", "Messages seen", counter);
+    let histogram = Histogram::new([0.1, 1.0].into_iter());
+    histogram.observe(0.05);
+    histogram.observe(0.5);
+    histogram.observe(5.0);
+    registry.register("call_duration_seconds", "Call durations", histogram);
+    let mut text = String::new();
+    encode(&mut text, &registry).unwrap();
+
+    let resource = [("service.name".to_string(), "sigil".to_string())];
+    let body = otlp_metrics(&text, &resource, 1, 2);
+    let resource_metrics = &body["resourceMetrics"][0];
+    assert_eq!(
+        resource_metrics["resource"]["attributes"][0]["value"]["stringValue"],
+        "sigil"
+    );
+    let metrics = resource_metrics["scopeMetrics"][0]["metrics"]
+        .as_array()
+        .unwrap();
+    assert_eq!(metrics.len(), 2);
+
+    let sum = &metrics[0]["sum"];
+    assert_eq!(metrics[0]["name"], "sigil_messages");
+    assert_eq!(metrics[0]["description"], "Messages seen.");
+    assert_eq!(sum["isMonotonic"], true);
+    assert_eq!(sum["dataPoints"][0]["asDouble"], 3.0);
+    assert_eq!(sum["dataPoints"][0]["timeUnixNano"], "2");
+
+    let point = &metrics[1]["histogram"]["dataPoints"][0];
+    assert_eq!(point["count"], "3");
+    assert_eq!(point["sum"], 5.55);
+    assert_eq!(point["explicitBounds"], serde_json::json!([0.1, 1.0]));
+    assert_eq!(point["bucketCounts"], serde_json::json!(["1", "1", "1"]));
+}
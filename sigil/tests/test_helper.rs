@@ -0,0 +1,655 @@
+//! Helpers for the integration tests, which run nodes in Docker, from the `sigil:dev` image unless
+//! a test picks another. Every node sits on networks made for its test alone and has mDNS off, so
+//! nodes of tests running in parallel never find each other.
+//! Each test binary uses some of them.
+#![allow(dead_code)]
+
+use anyhow::{bail, Context};
+use libp2p::{Multiaddr, PeerId};
+use sigil::event::NodeEvent;
+use sigil::journal::ReceivedMessage;
+use sigil::rpc::client::{self, HttpClient, MyApiClient};
+use sigil::rpc::DebugApiClient;
+use std::future::Future;
+use std::net::IpAddr;
+use std::process::Command as StdCommand;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use testcontainers::{
+    core::{ContainerAsync, IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    GenericImage, ImageExt,
+};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+
+/// The port nodes serve their RPC on inside their containers.
+pub const RPC_PORT: u16 = 3030;
+
+/// The RPC token every node requires, without which the `admin` namespace is not served.
+const RPC_TOKEN: &str = "sigil-test";
+
+/// Where a node's generated configuration file is put in its container.
+const CONFIG_PATH: &str = "/etc/sigil/sigil.toml";
+
+/// The image NAT routers run, which has `iptables` and `ip`, built from `tests/nat`.
+const NAT_IMAGE: &str = "sigil-nat:dev";
+
+/// How often `SigilTestInstance::wait_for` checks its condition, and `expect_message` the
+/// messages received.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run `docker` with `args`, returning what it printed.
+async fn docker(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run docker")?;
+    if !output.status.success() {
+        bail!(
+            "docker {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The address of container `id` on `network`.
+async fn container_ip(id: &str, network: &DockerNetwork) -> anyhow::Result<IpAddr> {
+    let format = format!(
+        "{{{{(index .NetworkSettings.Networks \"{}\").IPAddress}}}}",
+        network.name()
+    );
+    let ip = docker(&["inspect", "--format", &format, id]).await?;
+    ip.parse()
+        .with_context(|| format!("Container is not on {}: {ip:?}", network.name()))
+}
+
+/// A Docker bridge network made for one test, removed when dropped. Containers on different
+/// networks cannot reach each other.
+pub struct DockerNetwork {
+    name: String,
+}
+
+impl DockerNetwork {
+    pub async fn create() -> anyhow::Result<Self> {
+        let name = format!("sigil-test-{:016x}", rand::random::<u64>());
+        docker(&["network", "create", &name]).await?;
+        Ok(Self { name })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The network's addresses, such as `172.18.0.0/16`.
+    pub async fn subnet(&self) -> anyhow::Result<String> {
+        docker(&[
+            "network",
+            "inspect",
+            "--format",
+            "{{(index .IPAM.Config 0).Subnet}}",
+            &self.name,
+        ])
+        .await
+    }
+}
+
+impl Drop for DockerNetwork {
+    fn drop(&mut self) {
+        let _ = StdCommand::new("docker")
+            .args(["network", "rm", &self.name])
+            .output();
+    }
+}
+
+/// A node running in a container. The container sits on a network of its own, through which its
+/// RPC port is published, so that it keeps answering the test however its peer networks change.
+pub struct SigilTestInstance {
+    pub container: ContainerAsync<GenericImage>,
+    pub client: HttpClient,
+    pub peer_id: PeerId,
+    tcp_port: u16,
+
+    /// The network the RPC port is published through, which no other container joins.
+    control: DockerNetwork,
+
+    /// Every message the node has received since it started, as streamed by `listener`.
+    messages: Arc<Mutex<Vec<ReceivedMessage>>>,
+    listener: JoinHandle<()>,
+
+    /// Text that exempts a line from `log_failures`.
+    allowed_logs: Vec<String>,
+}
+
+/// Configures a node before starting it. Its settings are rendered into a configuration file
+/// that is copied into the container, on top of the test profile.
+pub struct SigilTestInstanceBuilder {
+    settings: toml::Table,
+    image: (String, String),
+    tcp_port: u16,
+    networks: Vec<String>,
+    env: Vec<(String, String)>,
+    allowed_logs: Vec<String>,
+}
+
+impl SigilTestInstanceBuilder {
+    /// Run the node from the image `name:tag`.
+    pub fn image(mut self, name: impl Into<String>, tag: impl Into<String>) -> Self {
+        self.image = (name.into(), tag.into());
+        self
+    }
+
+    /// Only log the lines matching `filter`, given in `RUST_LOG` syntax such as
+    /// `sigil=debug,libp2p_gossipsub=trace`.
+    pub fn log_filter(self, filter: impl Into<String>) -> Self {
+        self.env("RUST_LOG", filter)
+    }
+
+    /// The TCP port the node listens on for peers, 9000 unless set. Every container has an
+    /// address of its own, so nodes may share a port.
+    pub fn port(mut self, tcp_port: u16) -> Self {
+        self.tcp_port = tcp_port;
+        self
+    }
+
+    /// Peers for the node to dial at startup, each by one address.
+    pub fn peers(self, addresses: impl IntoIterator<Item = Multiaddr>) -> Self {
+        let peers = addresses
+            .into_iter()
+            .map(|address| {
+                let mut peer = toml::Table::new();
+                peer.insert("addresses".to_string(), vec![address.to_string()].into());
+                toml::Value::Table(peer)
+            })
+            .collect::<Vec<_>>();
+        self.setting("network.peers", peers)
+    }
+
+    pub fn is_relay(self, relay: bool) -> Self {
+        self.setting("network.relay", relay)
+    }
+
+    /// Set the setting at `path`, such as `rpc.auth.enabled`, to `value`.
+    pub fn setting(mut self, path: &str, value: impl Into<toml::Value>) -> Self {
+        let mut table = &mut self.settings;
+        let mut segments = path.split('.').peekable();
+        while let Some(segment) = segments.next() {
+            if segments.peek().is_none() {
+                table.insert(segment.to_string(), value.into());
+                break;
+            }
+            let entry = table
+                .entry(segment)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            table = entry.as_table_mut().expect("settings are nested in tables");
+        }
+        self
+    }
+
+    /// Let the node reach the nodes on `network`.
+    pub fn network(mut self, network: &DockerNetwork) -> Self {
+        self.networks.push(network.name().to_string());
+        self
+    }
+
+    /// Set an environment variable in the container, such as a `SIGIL_` override.
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    /// Let the node log lines containing `text` without failing the test, for tests that
+    /// provoke the errors.
+    pub fn allow_log(mut self, text: impl Into<String>) -> Self {
+        self.allowed_logs.push(text.into());
+        self
+    }
+
+    /// The configuration file the node is started with.
+    pub fn config(&self) -> String {
+        let mut settings = self.settings.clone();
+        let network = settings
+            .entry("network")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if let Some(network) = network.as_table_mut() {
+            network.insert("tcp_port".to_string(), i64::from(self.tcp_port).into());
+        }
+        toml::to_string(&settings).expect("settings are valid TOML")
+    }
+
+    pub async fn build(self) -> anyhow::Result<SigilTestInstance> {
+        let control = DockerNetwork::create().await?;
+        let (name, tag) = &self.image;
+        let mut image = GenericImage::new(name, tag)
+            .with_exposed_port(RPC_PORT.tcp())
+            .with_wait_for(WaitFor::message_on_stdout("Sigil is alive."))
+            .with_network(control.name())
+            .with_copy_to(CONFIG_PATH, self.config().into_bytes())
+            .with_cmd(["--config", CONFIG_PATH]);
+        for (name, value) in &self.env {
+            image = image.with_env_var(name, value);
+        }
+        let container = image
+            .start()
+            .await
+            .context("Failed to start sigil container")?;
+        let host_port = container
+            .get_host_port_ipv4(RPC_PORT)
+            .await
+            .context("Failed to get host port")?;
+        let client = client::http(&format!("http://localhost:{host_port}"), Some(RPC_TOKEN))
+            .context("Failed to build client")?;
+        let peer_id = client
+            .node_info()
+            .await
+            .context("Failed to read node info")?
+            .result
+            .peer_id;
+        let (messages, listener) = listen(&format!("ws://localhost:{host_port}")).await?;
+        for network in &self.networks {
+            docker(&["network", "connect", network, container.id()]).await?;
+        }
+        Ok(SigilTestInstance {
+            container,
+            client,
+            peer_id,
+            tcp_port: self.tcp_port,
+            control,
+            messages,
+            listener,
+            allowed_logs: self.allowed_logs,
+        })
+    }
+}
+
+/// Subscribe to every message the node at `url` receives, collecting them in the background until
+/// the returned task is aborted. This is done before the node joins the networks its peers are
+/// on, so nothing is missed.
+async fn listen(url: &str) -> anyhow::Result<(Arc<Mutex<Vec<ReceivedMessage>>>, JoinHandle<()>)> {
+    let ws = client::ws(url, Some(RPC_TOKEN))
+        .await
+        .context("Failed to build WebSocket client")?;
+    let mut subscription = ws
+        .subscribe_messages(None)
+        .await
+        .context("Failed to subscribe to messages")?;
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let received = messages.clone();
+    let listener = tokio::spawn(async move {
+        // The subscription ends with the client, which must live as long as it does.
+        let _ws = ws;
+        while let Some(Ok(event)) = subscription.next().await {
+            if let NodeEvent::Message(message) = event {
+                received.lock().unwrap().push(message);
+            }
+        }
+    });
+    Ok((messages, listener))
+}
+
+impl SigilTestInstance {
+    pub fn builder() -> SigilTestInstanceBuilder {
+        let mut settings = toml::Table::new();
+        settings.insert("profile".to_string(), "test".into());
+        let auth = toml::Table::from_iter([("token".to_string(), RPC_TOKEN.into())]);
+        let rpc = toml::Table::from_iter([("auth".to_string(), auth.into())]);
+        settings.insert("rpc".to_string(), rpc.into());
+        SigilTestInstanceBuilder {
+            settings,
+            image: ("sigil".to_string(), "dev".to_string()),
+            tcp_port: 9000,
+            networks: Vec::new(),
+            env: Vec::new(),
+            allowed_logs: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        self.container.id()
+    }
+
+    /// Join `network`, reaching the nodes on it.
+    pub async fn connect(&self, network: &DockerNetwork) -> anyhow::Result<()> {
+        docker(&["network", "connect", network.name(), self.id()]).await?;
+        Ok(())
+    }
+
+    /// Leave `network`, cutting the node off from the nodes on it.
+    pub async fn disconnect(&self, network: &DockerNetwork) -> anyhow::Result<()> {
+        docker(&["network", "disconnect", network.name(), self.id()]).await?;
+        Ok(())
+    }
+
+    /// The node's address on `network`.
+    pub async fn ip(&self, network: &DockerNetwork) -> anyhow::Result<IpAddr> {
+        container_ip(self.id(), network).await
+    }
+
+    /// The address peers on `network` dial the node at over TCP.
+    pub async fn address(&self, network: &DockerNetwork) -> anyhow::Result<Multiaddr> {
+        Ok(format!(
+            "/ip4/{}/tcp/{}/p2p/{}",
+            self.ip(network).await?,
+            self.tcp_port,
+            self.peer_id
+        )
+        .parse()?)
+    }
+
+    /// Poll the node's RPC until `check` holds, failing after `timeout` with the last error
+    /// `check` returned, if any. `what` describes the condition for the failure.
+    pub async fn wait_for<F, Fut>(
+        &self,
+        what: &str,
+        timeout: Duration,
+        mut check: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnMut(HttpClient) -> Fut,
+        Fut: Future<Output = anyhow::Result<bool>>,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let error = match check(self.client.clone()).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => None,
+                Err(e) => Some(e),
+            };
+            if Instant::now() >= deadline {
+                return match error {
+                    Some(e) => Err(e.context(format!("Timed out waiting for {what}"))),
+                    None => bail!("Timed out after {timeout:?} waiting for {what}"),
+                };
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Wait until the node is connected to at least `peers` peers.
+    pub async fn wait_for_connected(&self, peers: usize, timeout: Duration) -> anyhow::Result<()> {
+        self.wait_for(&format!("{peers} peers"), timeout, |client| async move {
+            Ok(client.connected_peers().await?.len() >= peers)
+        })
+        .await
+    }
+
+    /// Wait until the node is connected to `peer_id`.
+    pub async fn wait_for_peer(&self, peer_id: PeerId, timeout: Duration) -> anyhow::Result<()> {
+        self.wait_for(&format!("{peer_id}"), timeout, |client| async move {
+            Ok(client.connected_peers().await?.contains(&peer_id))
+        })
+        .await
+    }
+
+    /// Wait until `peer_id` is in the node's gossip mesh for `topic`, so that what the node
+    /// publishes there reaches it.
+    pub async fn wait_for_mesh(
+        &self,
+        topic: &str,
+        peer_id: PeerId,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        self.wait_for(
+            &format!("{peer_id} in the mesh of {topic}"),
+            timeout,
+            |client| async move {
+                let dump = client.dump_state().await?.result;
+                Ok(dump
+                    .node
+                    .mesh
+                    .get(topic)
+                    .is_some_and(|peers| peers.contains(&peer_id)))
+            },
+        )
+        .await
+    }
+
+    /// Publish `data` on `topic`, returning the message's id.
+    pub async fn publish(&self, topic: &str, data: &str) -> anyhow::Result<String> {
+        self.client
+            .gossipsub_publish(topic.to_string(), data.to_string())
+            .await
+            .with_context(|| format!("Failed to publish on {topic}"))
+    }
+
+    /// Wait until the node has received `data` on `topic`, at any time since it started, failing
+    /// after `timeout` with what it received on `topic` instead.
+    pub async fn expect_message(
+        &self,
+        topic: &str,
+        data: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<ReceivedMessage> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let received = self.received(topic);
+            if let Some(message) = received
+                .iter()
+                .find(|message| message.entry.payload == data.as_bytes())
+            {
+                return Ok(message.clone());
+            }
+            if Instant::now() >= deadline {
+                let payloads: Vec<_> = received
+                    .iter()
+                    .map(|message| String::from_utf8_lossy(&message.entry.payload))
+                    .collect();
+                bail!(
+                    "Timed out after {timeout:?} waiting for {data:?} on {topic}, having received \
+                     {payloads:?}"
+                );
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// The messages the node has received on `topic` since it started, oldest first.
+    pub fn received(&self, topic: &str) -> Vec<ReceivedMessage> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.topic == topic)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn logs(&self) -> String {
+        match self.container.stdout_to_vec().await {
+            Ok(log_bytes) => String::from_utf8_lossy(&log_bytes).into_owned(),
+            Err(e) => format!("Failed to retrieve container logs: {}", e),
+        }
+    }
+}
+
+impl Drop for SigilTestInstance {
+    /// Remove the container, failing the test if the node logged a failure, which the node may
+    /// survive without its RPC showing it, as when a task other than the RPC server's panics. A
+    /// test that has already failed is not failed again.
+    fn drop(&mut self) {
+        self.listener.abort();
+        let logs = if thread::panicking() {
+            None
+        } else {
+            StdCommand::new("docker")
+                .args(["logs", self.container.id()])
+                .output()
+                .ok()
+                .map(|output| {
+                    String::from_utf8_lossy(&output.stdout).into_owned()
+                        + &String::from_utf8_lossy(&output.stderr)
+                })
+        };
+        // The container must be gone before its control network can be removed.
+        let _ = StdCommand::new("docker")
+            .args(["rm", "--force", "--volumes", self.container.id()])
+            .output();
+        if let Some(logs) = logs {
+            let failures = log_failures(&logs, &self.allowed_logs);
+            if !failures.is_empty() {
+                panic!(
+                    "Node {} logged failures:\n{}\nIts logs:\n{logs}",
+                    self.peer_id,
+                    failures.join("\n")
+                );
+            }
+        }
+    }
+}
+
+/// The lines of `logs` reporting a panic, an error, or a message dropped because a queue was
+/// full, except those containing any of `allowed`. Colours are stripped from what is returned.
+pub fn log_failures(logs: &str, allowed: &[String]) -> Vec<String> {
+    logs.lines()
+        .map(strip_ansi)
+        .filter(|line| {
+            line.contains("panicked at")
+                || line.contains("queue full")
+                || line.split_whitespace().any(|word| word == "ERROR")
+                || line.contains("\"level\":\"ERROR\"")
+        })
+        .filter(|line| !allowed.iter().any(|text| line.contains(text.as_str())))
+        .collect()
+}
+
+/// `line` without the ANSI escape sequences that colour the node's log levels on stdout.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // A sequence ends with its first letter, as in `\x1b[31m`.
+            chars.by_ref().find(char::is_ascii_alphabetic);
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Nodes split off from a shared network onto one of their own, where they only reach each other,
+/// until the partition is healed.
+pub struct Partition<'a> {
+    shared: &'a DockerNetwork,
+    island: DockerNetwork,
+    instances: Vec<&'a SigilTestInstance>,
+}
+
+impl<'a> Partition<'a> {
+    /// Cut `instances` off from the rest of `shared`.
+    pub async fn split(
+        shared: &'a DockerNetwork,
+        instances: &[&'a SigilTestInstance],
+    ) -> anyhow::Result<Self> {
+        let island = DockerNetwork::create().await?;
+        for instance in instances {
+            instance.connect(&island).await?;
+            instance.disconnect(shared).await?;
+        }
+        Ok(Self {
+            shared,
+            island,
+            instances: instances.to_vec(),
+        })
+    }
+
+    /// Return the nodes to the shared network. Their addresses on it may have changed.
+    pub async fn heal(self) -> anyhow::Result<()> {
+        for instance in &self.instances {
+            instance.connect(self.shared).await?;
+            instance.disconnect(&self.island).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A router between a private network of its own and a shared one, translating the addresses of
+/// the nodes behind it as a home router does. They reach the shared network through it, but
+/// nothing there can open a connection to them: only replies get through, including those to the
+/// connections that hole punching opens from both sides at once.
+pub struct Nat {
+    // The router must be gone before its private network can be removed.
+    router: Router,
+    lan: DockerNetwork,
+
+    /// The router's address on the private network.
+    gateway: IpAddr,
+    shared_subnet: String,
+}
+
+/// The container routing for a `Nat`, removed when dropped.
+struct Router(String);
+
+impl Drop for Router {
+    fn drop(&mut self) {
+        let _ = StdCommand::new("docker")
+            .args(["rm", "--force", &self.0])
+            .output();
+    }
+}
+
+impl Nat {
+    /// Start a router between a new private network and `shared`.
+    pub async fn create(shared: &DockerNetwork) -> anyhow::Result<Self> {
+        let lan = DockerNetwork::create().await?;
+        let router = Router(
+            docker(&[
+                "run",
+                "--detach",
+                "--cap-add",
+                "NET_ADMIN",
+                "--sysctl",
+                "net.ipv4.ip_forward=1",
+                "--network",
+                lan.name(),
+                NAT_IMAGE,
+            ])
+            .await?,
+        );
+        docker(&["network", "connect", shared.name(), &router.0]).await?;
+        let shared_subnet = shared.subnet().await?;
+        let rules = format!(
+            "iptables -t nat -A POSTROUTING -s {lan} -d {shared_subnet} -j MASQUERADE && \
+             iptables -A FORWARD -m conntrack --ctstate ESTABLISHED,RELATED -j ACCEPT && \
+             iptables -A FORWARD -s {lan} -j ACCEPT && \
+             iptables -A FORWARD -j DROP",
+            lan = lan.subnet().await?,
+        );
+        docker(&["exec", &router.0, "sh", "-c", &rules]).await?;
+        Ok(Self {
+            gateway: container_ip(&router.0, &lan).await?,
+            router,
+            lan,
+            shared_subnet,
+        })
+    }
+
+    /// Put `instance`, which must not be on the shared network itself, behind the router.
+    pub async fn attach(&self, instance: &SigilTestInstance) -> anyhow::Result<()> {
+        instance.connect(&self.lan).await?;
+        // The node's image has no `ip`, so the route is added from a container sharing its
+        // network namespace. The default route stays on its control network.
+        docker(&[
+            "run",
+            "--rm",
+            "--network",
+            &format!("container:{}", instance.id()),
+            "--cap-add",
+            "NET_ADMIN",
+            NAT_IMAGE,
+            "ip",
+            "route",
+            "add",
+            &self.shared_subnet,
+            "via",
+            &self.gateway.to_string(),
+        ])
+        .await?;
+        Ok(())
+    }
+}
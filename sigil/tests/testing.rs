@@ -0,0 +1,106 @@
+use sigil::event::NodeEvent;
+use sigil::testing::TestNetwork;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn test_network_nodes_connect_and_gossip() {
+    let network = TestNetwork::start(3).expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+
+    let topic = network.nodes()[0].config.topics.application[0].clone();
+    let clients = network.clients();
+    let mut events = clients[2].subscribe_events();
+    // Subscriptions are exchanged just after connecting, so the first publishes may find no peers.
+    timeout(Duration::from_secs(10), async {
+        while clients[0]
+            .gossipsub_publish(&topic, b"hello".to_vec())
+            .await
+            .is_err()
+        {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Never found peers to publish to");
+
+    let received = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(NodeEvent::Message(message)) = events.recv().await {
+                return message;
+            }
+        }
+    })
+    .await
+    .expect("The message never arrived");
+    assert_eq!(received.topic, topic);
+    assert_eq!(received.entry.payload, &b"hello"[..]);
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_nodes_join_and_leave_the_network() {
+    let mut network = TestNetwork::simulate(2).expect("Failed to start the network");
+    let left = network.leave(0).await;
+    let joined = network.join().expect("Failed to start a node").peer_id;
+    assert_ne!(joined, left);
+    assert_eq!(
+        network.nodes()[1].config.node_name.as_deref(),
+        Some("node-2")
+    );
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("The new node did not connect");
+
+    let peers = network.nodes()[0].client.connected_peers().await.unwrap();
+    assert!(!peers.contains(&left));
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_node_status_counts_the_connected_peers() {
+    let network = TestNetwork::simulate(3).expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+
+    let status = network.nodes()[0].client.node_status().await.unwrap();
+    assert_eq!(status.connected_peers, 2);
+    assert!(status.mesh_peers <= 2);
+    assert_eq!(status.relays, 0);
+    network.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_private_messages_reach_the_recipients_subscribers() {
+    let network = TestNetwork::simulate(2).expect("Failed to start the network");
+    network
+        .wait_for_connections(Duration::from_secs(10))
+        .await
+        .expect("Nodes did not connect");
+
+    let (sender, recipient) = (&network.nodes()[0], &network.nodes()[1]);
+    let mut events = recipient.client.subscribe_events();
+    sender
+        .client
+        .send_private(recipient.peer_id, b"secret".to_vec())
+        .await
+        .expect("The message was not delivered");
+    let (peer, payload) = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(NodeEvent::PrivateMessage { peer, payload, .. }) = events.recv().await {
+                return (peer, payload);
+            }
+        }
+    })
+    .await
+    .expect("The message never reached the subscriber");
+    assert_eq!(peer, sender.peer_id);
+    assert_eq!(payload, &b"secret"[..]);
+    network.shutdown().await;
+}
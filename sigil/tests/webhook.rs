@@ -0,0 +1,10 @@
+use sigil::webhook::sign;
+
+#[test]
+fn test_webhook_signature_is_hmac_sha256() {
+    // RFC 4231, test case 2.
+    assert_eq!(
+        sign(b"Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
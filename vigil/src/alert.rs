@@ -0,0 +1,182 @@
+use crate::config::{AlertConfig, Rule};
+use crate::poll::NodeSample;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A notification that a node started or stopped breaking a rule.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    /// The kind of the broken rule, such as `peer_count`.
+    pub rule: String,
+    pub node: String,
+    pub status: AlertStatus,
+
+    /// What was observed, for a human to read.
+    pub message: String,
+
+    /// When the alert was raised, in seconds since the Unix epoch.
+    pub at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl Alert {
+    /// A line describing the alert, as it is logged and sent to Slack.
+    pub fn summary(&self) -> String {
+        let status = match self.status {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Resolved => "RESOLVED",
+        };
+        format!(
+            "[{status}] {} on {}: {}",
+            self.rule, self.node, self.message
+        )
+    }
+}
+
+impl Rule {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Rule::Unreachable => "unreachable",
+            Rule::PeerCount { .. } => "peer_count",
+            Rule::HolepunchSuccessRate { .. } => "holepunch_success_rate",
+        }
+    }
+
+    /// Whether the node is breaking the rule, with what was observed, or `None` if the poll could
+    /// not tell, such as when the node is down or does not report the numbers the rule needs.
+    pub fn check(&self, sample: Option<&NodeSample>) -> Option<(bool, String)> {
+        match self {
+            Rule::Unreachable => Some(match sample {
+                Some(_) => (false, "the node answers its polls".to_string()),
+                None => (true, "the node did not answer its poll".to_string()),
+            }),
+            Rule::PeerCount { min } => {
+                let peers = sample?.connected_peers;
+                Some((
+                    peers < *min,
+                    format!("{peers} peers connected, expecting {min}"),
+                ))
+            }
+            Rule::HolepunchSuccessRate {
+                min_percent,
+                min_attempts,
+            } => {
+                let stats = sample?.stats.as_ref()?;
+                let attempts = stats.holepunches_succeeded + stats.holepunches_failed;
+                if attempts < *min_attempts {
+                    return None;
+                }
+                let percent = stats.holepunches_succeeded as f64 * 100.0 / attempts as f64;
+                Some((
+                    percent < *min_percent,
+                    format!(
+                        "{percent:.1}% of {attempts} hole punches succeeded, expecting \
+                         {min_percent}%"
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct RuleState {
+    /// How many polls in a row have broken the rule.
+    broken_polls: u32,
+    firing: bool,
+}
+
+/// Holds every node to the configured rules, deciding from each poll which alerts fire or resolve.
+pub struct Alerts {
+    rules: Vec<Rule>,
+    after_polls: u32,
+
+    /// The state of each rule, by its index, for each node.
+    states: HashMap<(usize, String), RuleState>,
+}
+
+impl Alerts {
+    pub fn new(config: &AlertConfig) -> Self {
+        Self {
+            rules: config.rules.clone(),
+            after_polls: config.after_polls.max(1),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Check a poll of `node`, which is `None` if the node did not answer, returning the alerts
+    /// that fire or resolve because of it.
+    pub fn evaluate(&mut self, node: &str, sample: Option<&NodeSample>) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some((broken, message)) = rule.check(sample) else {
+                continue;
+            };
+            let state = self.states.entry((index, node.to_string())).or_default();
+            state.broken_polls = if broken { state.broken_polls + 1 } else { 0 };
+            let status = match (state.firing, state.broken_polls >= self.after_polls) {
+                (false, true) => AlertStatus::Firing,
+                (true, false) => AlertStatus::Resolved,
+                _ => continue,
+            };
+            state.firing = status == AlertStatus::Firing;
+            alerts.push(Alert {
+                rule: rule.kind().to_string(),
+                node: node.to_string(),
+                status,
+                message,
+                at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default(),
+            });
+        }
+        alerts
+    }
+}
+
+/// Delivers alerts to the configured webhooks and Slack channels.
+pub struct Notifier {
+    client: reqwest::Client,
+    webhooks: Vec<String>,
+    slack_webhooks: Vec<String>,
+}
+
+impl Notifier {
+    pub fn new(config: &AlertConfig, timeout: Duration) -> Result<Self, reqwest::Error> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            webhooks: config.webhooks.clone(),
+            slack_webhooks: config.slack_webhooks.clone(),
+        })
+    }
+
+    /// Log `alert` and post it to every endpoint in the background. Failed posts are logged but
+    /// not retried.
+    pub fn notify(&self, alert: &Alert) {
+        println!("{}", alert.summary());
+        let slack = json!({ "text": alert.summary() });
+        let posts = self
+            .webhooks
+            .iter()
+            .map(|url| (url, json!(alert)))
+            .chain(self.slack_webhooks.iter().map(|url| (url, slack.clone())));
+        for (url, body) in posts {
+            let request = self.client.post(url).json(&body);
+            let url = url.clone();
+            tokio::spawn(async move {
+                if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                    println!("Failed to post alert to {url}: {e}");
+                }
+            });
+        }
+    }
+}
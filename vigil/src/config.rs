@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+/// The configuration of a vigil agent. Every field has a default, so an empty file is valid.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The sigil nodes to watch.
+    pub targets: Vec<Target>,
+
+    /// Where the exported metrics are served for Prometheus to scrape, at `/metrics`.
+    pub listen: SocketAddr,
+
+    /// How often every node is polled.
+    pub poll_interval_secs: u64,
+
+    /// How long a node may take to answer a call before it is counted as down.
+    pub timeout_secs: u64,
+
+    pub alerts: AlertConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            listen: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 9400)),
+            poll_interval_secs: 15,
+            timeout_secs: 5,
+            alerts: AlertConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Read the TOML configuration at `path`, or the defaults when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config {}: {e}", path.display()))?;
+        Self::parse(&contents).map_err(|e| format!("invalid config {}: {e}", path.display()).into())
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs.max(1))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.max(1))
+    }
+}
+
+/// A sigil node's RPC endpoint.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// The name the node's metrics are labelled with, which defaults to its URL.
+    pub name: Option<String>,
+
+    /// The node's HTTP JSON-RPC endpoint, such as `http://10.0.0.1:3030`.
+    pub url: String,
+
+    /// The node's WebSocket JSON-RPC endpoint, for streaming its logs and events. Defaults to
+    /// `url` with a WebSocket scheme, as sigil serves both on one port unless told otherwise.
+    pub ws_url: Option<String>,
+
+    /// The bearer token of the node's RPC, if its authentication is on. Without it, the metrics
+    /// drawn from `debug_dumpState`, such as mesh sizes, are not exported for the node.
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+}
+
+impl Target {
+    /// A target at `url`, named by its URL and without a token.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            name: None,
+            url: url.into(),
+            ws_url: None,
+            token: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    pub fn ws_url(&self) -> String {
+        if let Some(ws_url) = &self.ws_url {
+            return ws_url.clone();
+        }
+        match self.url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{rest}"),
+            Some(("http", rest)) => format!("ws://{rest}"),
+            _ => self.url.clone(),
+        }
+    }
+}
+
+/// Rules that every polled node is held to, and where to notify when one starts or stops being
+/// broken.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    pub rules: Vec<Rule>,
+
+    /// How many polls in a row a rule must be broken for before its alert fires, so that a single
+    /// slow poll does not page anyone.
+    pub after_polls: u32,
+
+    /// Endpoints that every alert is posted to as JSON.
+    pub webhooks: Vec<String>,
+
+    /// Slack incoming webhooks that every alert is posted to as a message.
+    pub slack_webhooks: Vec<String>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            after_polls: 2,
+            webhooks: Vec::new(),
+            slack_webhooks: Vec::new(),
+        }
+    }
+}
+
+/// A condition of a healthy node.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Rule {
+    /// The node answers its polls, alerting when it does not.
+    Unreachable,
+
+    /// The node is connected to at least `min` peers.
+    PeerCount { min: u64 },
+
+    /// At least `min_percent` of the node's hole punches since it started have succeeded, once it
+    /// has attempted at least `min_attempts`.
+    HolepunchSuccessRate { min_percent: f64, min_attempts: u64 },
+}
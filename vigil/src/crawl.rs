@@ -0,0 +1,161 @@
+use crate::config::Target;
+use crate::poll::{Node, NodeView};
+use crate::topology::{EdgeKind, Topology, TopologyPeer};
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+/// How far a crawl reaches beyond the configured nodes.
+#[derive(Clone, Debug)]
+pub struct CrawlOptions {
+    /// The port peers serve their RPC on. When set, the crawl follows each peer it finds to the
+    /// RPC endpoint at that port on the hosts it listens on; otherwise only the configured nodes
+    /// are crawled, and their peers are mapped as they see them.
+    pub rpc_port: Option<u16>,
+
+    /// The most nodes to crawl, configured ones included.
+    pub max_nodes: usize,
+    pub timeout: Duration,
+}
+
+/// A node to crawl: the endpoints it may answer at, tried in turn, and its peer id if known.
+struct Pending {
+    peer_id: Option<String>,
+    targets: Vec<Target>,
+}
+
+/// Map the network by crawling `targets` and, as `options` allow, the peers they know.
+pub async fn crawl(targets: &[Target], options: &CrawlOptions) -> Topology {
+    let mut topology = Topology::default();
+    let mut queue: VecDeque<Pending> = targets
+        .iter()
+        .map(|target| Pending {
+            peer_id: None,
+            targets: vec![target.clone()],
+        })
+        .collect();
+    let mut queued: HashSet<String> = HashSet::new();
+    let mut crawled = 0;
+    while crawled < options.max_nodes {
+        let Some(pending) = queue.pop_front() else {
+            break;
+        };
+        if pending
+            .peer_id
+            .is_some_and(|peer_id| is_crawled(&topology, &peer_id))
+        {
+            continue;
+        }
+        let Some((target, view)) = inspect(&pending.targets, options.timeout).await else {
+            continue;
+        };
+        // Configured endpoints may lead to the same node, or to one already crawled as a peer.
+        if is_crawled(&topology, &view.info.peer_id) {
+            continue;
+        }
+        crawled += 1;
+        queued.insert(view.info.peer_id.clone());
+        record(&mut topology, &target, &view);
+
+        let Some(rpc_port) = options.rpc_port else {
+            continue;
+        };
+        for peer in &view.peers {
+            if !queued.insert(peer.peer_id.clone()) {
+                continue;
+            }
+            let targets: Vec<Target> = peer
+                .listen_addresses
+                .iter()
+                .filter_map(|address| rpc_url(address, rpc_port))
+                .map(Target::new)
+                .collect();
+            if !targets.is_empty() {
+                queue.push_back(Pending {
+                    peer_id: Some(peer.peer_id.clone()),
+                    targets,
+                });
+            }
+        }
+    }
+    topology
+}
+
+fn is_crawled(topology: &Topology, peer_id: &str) -> bool {
+    topology
+        .peers
+        .get(peer_id)
+        .is_some_and(|peer| peer.rpc.is_some())
+}
+
+/// Inspect the first of `targets` that answers.
+async fn inspect(targets: &[Target], timeout: Duration) -> Option<(Target, NodeView)> {
+    for target in targets {
+        let node = match Node::new(target, timeout) {
+            Ok(node) => node,
+            Err(e) => {
+                eprintln!("Skipping {}: {e}", target.url);
+                continue;
+            }
+        };
+        match node.inspect().await {
+            Ok(view) => return Some((target.clone(), view)),
+            Err(e) => eprintln!("Failed to crawl {}: {e}", target.url),
+        }
+    }
+    None
+}
+
+/// Add what a crawled node reported to the topology.
+fn record(topology: &mut Topology, target: &Target, view: &NodeView) {
+    let peer_id = view.info.peer_id.as_str();
+    topology.add_peer(
+        peer_id,
+        TopologyPeer {
+            node_name: view.info.node_name.clone(),
+            agent_version: Some(view.info.agent_version.clone()),
+            listen_addresses: view.info.listen_addresses.clone(),
+            rpc: Some(target.url.clone()),
+        },
+    );
+    for peer in &view.peers {
+        topology.add_peer(
+            &peer.peer_id,
+            TopologyPeer {
+                node_name: peer.node_name.clone(),
+                agent_version: peer.agent_version.clone(),
+                listen_addresses: peer.listen_addresses.clone(),
+                rpc: None,
+            },
+        );
+        topology.add_edge(peer_id, &peer.peer_id, EdgeKind::Connection, None);
+    }
+    for peer in &view.routing_table {
+        topology.add_edge(peer_id, peer, EdgeKind::RoutingTable, None);
+    }
+    let Some(state) = &view.state else {
+        return;
+    };
+    for (topic, peers) in &state.mesh {
+        for peer in peers {
+            topology.add_edge(peer_id, peer, EdgeKind::Mesh, Some(topic));
+        }
+    }
+    for relay in &state.relays {
+        if relay.status == "accepted" {
+            topology.add_edge(peer_id, &relay.peer_id, EdgeKind::Relay, None);
+        }
+    }
+}
+
+/// The RPC endpoint at `port` on the host of the multiaddress `address`, if it names a host that
+/// can be reached from elsewhere.
+pub fn rpc_url(address: &str, port: u16) -> Option<String> {
+    let mut parts = address.split('/').skip(1);
+    let (protocol, host) = (parts.next()?, parts.next()?);
+    match protocol {
+        "ip4" if host != "127.0.0.1" && host != "0.0.0.0" => Some(format!("http://{host}:{port}")),
+        "ip6" if host != "::1" && host != "::" => Some(format!("http://[{host}]:{port}")),
+        "dns" | "dns4" | "dns6" => Some(format!("http://{host}:{port}")),
+        _ => None,
+    }
+}
@@ -0,0 +1,8 @@
+pub mod alert;
+pub mod config;
+pub mod crawl;
+pub mod metrics;
+pub mod poll;
+pub mod serve;
+pub mod timeline;
+pub mod topology;
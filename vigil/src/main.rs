@@ -0,0 +1,275 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use prometheus_client::registry::Registry;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use vigil::alert::{Alerts, Notifier};
+use vigil::config::{Config, Target};
+use vigil::crawl::{self, CrawlOptions};
+use vigil::metrics::Metrics;
+use vigil::poll::Node;
+use vigil::serve;
+use vigil::timeline::{self, Timeline};
+
+/// Watches sigil nodes over their RPC and exports their state as Prometheus metrics.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Path to a TOML configuration file; defaults are used when omitted.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// The RPC endpoint of a sigil node to watch, besides those in the configuration. May be
+    /// given more than once.
+    #[arg(long = "target", global = true)]
+    targets: Vec<String>,
+
+    /// Where to serve the metrics, in place of the configured address.
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Map the network's peers, connections, routing tables, meshes and relays once, starting
+    /// from the configured nodes.
+    Crawl(CrawlArgs),
+
+    /// Stream the configured nodes' logs and peer events, merged into one timeline ordered by
+    /// time, until stopped.
+    Timeline(TimelineArgs),
+}
+
+#[derive(clap::Args)]
+struct CrawlArgs {
+    /// The port peers serve their RPC on, to crawl past the configured nodes to each peer they
+    /// know. Without it, only the configured nodes are crawled.
+    #[arg(long)]
+    rpc_port: Option<u16>,
+
+    /// The most nodes to crawl.
+    #[arg(long, default_value_t = 100)]
+    max_nodes: usize,
+
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// A file to write the topology to, in place of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct TimelineArgs {
+    /// Only stream the log lines matching these `RUST_LOG` style directives, such as
+    /// `sigil=info,libp2p_dcutr=debug`.
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// How long to hold each entry, in milliseconds, for entries from other nodes that happened
+    /// before it but arrive after it to be put in their place.
+    #[arg(long, default_value_t = 2000)]
+    window_ms: u64,
+
+    #[arg(long, value_enum, default_value_t = TimelineFormat::Text)]
+    format: TimelineFormat,
+
+    /// A file to write the timeline to, in place of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// How a timeline is written out.
+#[derive(Clone, Copy, ValueEnum)]
+enum TimelineFormat {
+    /// One line of text per entry.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// How a topology is written out.
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    /// Graphviz's DOT language, for drawing.
+    Dot,
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut config = Config::load(args.config.as_deref())?;
+    config
+        .targets
+        .extend(args.targets.into_iter().map(Target::new));
+    if let Some(listen) = args.listen {
+        config.listen = listen;
+    }
+    if config.targets.is_empty() {
+        return Err("no sigil nodes to watch; give --target or list targets in the config".into());
+    }
+    match args.command {
+        Some(Command::Crawl(crawl)) => map(&config, crawl).await,
+        Some(Command::Timeline(timeline)) => follow(&config, timeline).await,
+        None => export(&config).await,
+    }
+}
+
+/// Poll every node on an interval, serving what is found as metrics and alerting on broken rules
+/// until stopped.
+async fn export(config: &Config) -> Result<(), Box<dyn Error>> {
+    let nodes = config
+        .targets
+        .iter()
+        .map(|target| Node::new(target, config.timeout()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut registry = Registry::with_prefix("vigil");
+    let mut metrics = Metrics::new(&mut registry);
+    let mut alerts = Alerts::new(&config.alerts);
+    let notifier = Notifier::new(&config.alerts, config.timeout())?;
+    let listener = TcpListener::bind(config.listen)
+        .await
+        .map_err(|e| format!("failed to serve metrics on {}: {e}", config.listen))?;
+    println!("Serving metrics on http://{}/metrics", config.listen);
+    tokio::spawn(serve::serve(listener, Arc::new(registry)));
+
+    // Poll every node at once, so that one slow node does not delay the others' metrics.
+    let mut interval = tokio::time::interval(config.poll_interval());
+    loop {
+        interval.tick().await;
+        let mut polls = JoinSet::new();
+        for node in &nodes {
+            let node = node.clone();
+            polls.spawn(async move {
+                let sample = node.poll().await;
+                (node, sample)
+            });
+        }
+        while let Some(polled) = polls.join_next().await {
+            let Ok((node, sample)) = polled else {
+                continue;
+            };
+            let sample = match sample {
+                Ok(sample) => {
+                    metrics.record(node.name(), &sample);
+                    Some(sample)
+                }
+                Err(e) => {
+                    println!("Failed to poll {}: {e}", node.name());
+                    metrics.record_down(node.name());
+                    None
+                }
+            };
+            for alert in alerts.evaluate(node.name(), sample.as_ref()) {
+                notifier.notify(&alert);
+            }
+        }
+    }
+}
+
+/// Crawl the network once and write out its topology.
+async fn map(config: &Config, args: CrawlArgs) -> Result<(), Box<dyn Error>> {
+    let options = CrawlOptions {
+        rpc_port: args.rpc_port,
+        max_nodes: args.max_nodes,
+        timeout: config.timeout(),
+    };
+    let topology = crawl::crawl(&config.targets, &options).await;
+    let crawled = topology
+        .peers
+        .values()
+        .filter(|peer| peer.rpc.is_some())
+        .count();
+    if crawled == 0 {
+        return Err("none of the targets could be crawled".into());
+    }
+    eprintln!(
+        "Crawled {crawled} nodes, seeing {} peers and {} edges",
+        topology.peers.len(),
+        topology.edges.len()
+    );
+    let rendered = match args.format {
+        Format::Json => serde_json::to_string_pretty(&topology)? + "\n",
+        Format::Dot => topology.to_dot(),
+    };
+    match args.output {
+        Some(path) => fs::write(&path, rendered)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?,
+        None => print!("{rendered}"),
+    }
+    Ok(())
+}
+
+/// Write the nodes' merged logs and events until every stream ends or vigil is interrupted.
+async fn follow(config: &Config, args: TimelineArgs) -> Result<(), Box<dyn Error>> {
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(
+            File::create(path).map_err(|e| format!("failed to create {}: {e}", path.display()))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    let (sender, mut entries) = mpsc::channel(1024);
+    for target in &config.targets {
+        let target = target.clone();
+        let filter = args.filter.clone();
+        let timeout = config.timeout();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = timeline::follow(&target, filter.as_deref(), timeout, sender).await {
+                eprintln!("Stopped following {}: {e}", target.name());
+            }
+        });
+    }
+    drop(sender);
+
+    let mut timeline = Timeline::new(Duration::from_millis(args.window_ms));
+    let mut release = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        tokio::select! {
+            entry = entries.recv() => match entry {
+                Some(entry) => timeline.push(entry),
+                None => break,
+            },
+            _ = release.tick() => {
+                write_entries(&mut output, timeline.release(timeline::unix_millis()), args.format)?;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    write_entries(&mut output, timeline.finish(), args.format)
+}
+
+fn write_entries(
+    output: &mut dyn Write,
+    entries: Vec<timeline::Entry>,
+    format: TimelineFormat,
+) -> Result<(), Box<dyn Error>> {
+    for entry in &entries {
+        match format {
+            TimelineFormat::Text => writeln!(output, "{}", entry.to_line())?,
+            TimelineFormat::Json => writeln!(output, "{}", serde_json::to_string(entry)?)?,
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use crate::poll::NodeSample;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::{family::Family, gauge::Gauge};
+use prometheus_client::registry::Registry;
+use std::collections::{BTreeSet, HashMap};
+
+/// The statuses a relay reservation can be in, each exported even when no relay is in it.
+const RELAY_STATUSES: [&str; 3] = ["pending", "accepted", "closed"];
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct NodeLabels {
+    node: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TopicLabels {
+    node: String,
+    topic: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RelayLabels {
+    node: String,
+    status: String,
+}
+
+/// The metrics exported for every watched node, each labelled with the node's name. A node's
+/// totals are gauges rather than counters, as they restart from zero whenever the node does.
+pub struct Metrics {
+    up: Family<NodeLabels, Gauge>,
+    connected_peers: Family<NodeLabels, Gauge>,
+    routing_table_peers: Family<NodeLabels, Gauge>,
+    mesh_peers: Family<TopicLabels, Gauge>,
+    relays: Family<RelayLabels, Gauge>,
+    pending_dials: Family<NodeLabels, Gauge>,
+    pending_dht_queries: Family<NodeLabels, Gauge>,
+    pending_deliveries: Family<NodeLabels, Gauge>,
+    uptime: Family<NodeLabels, Gauge>,
+    connections: Family<NodeLabels, Gauge>,
+    messages_published: Family<NodeLabels, Gauge>,
+    messages_received: Family<NodeLabels, Gauge>,
+    holepunches_succeeded: Family<NodeLabels, Gauge>,
+    holepunches_failed: Family<NodeLabels, Gauge>,
+
+    /// The topics each node last had a mesh on, so that meshes it has left stop being exported.
+    mesh_topics: HashMap<String, BTreeSet<String>>,
+}
+
+impl Metrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let metrics = Self {
+            up: Family::default(),
+            connected_peers: Family::default(),
+            routing_table_peers: Family::default(),
+            mesh_peers: Family::default(),
+            relays: Family::default(),
+            pending_dials: Family::default(),
+            pending_dht_queries: Family::default(),
+            pending_deliveries: Family::default(),
+            uptime: Family::default(),
+            connections: Family::default(),
+            messages_published: Family::default(),
+            messages_received: Family::default(),
+            holepunches_succeeded: Family::default(),
+            holepunches_failed: Family::default(),
+            mesh_topics: HashMap::new(),
+        };
+        registry.register(
+            "up",
+            "Whether the node answered its last poll",
+            metrics.up.clone(),
+        );
+        registry.register(
+            "connected_peers",
+            "Peers the node is connected to",
+            metrics.connected_peers.clone(),
+        );
+        registry.register(
+            "routing_table_peers",
+            "Peers in the node's Kademlia routing table",
+            metrics.routing_table_peers.clone(),
+        );
+        registry.register(
+            "mesh_peers",
+            "Peers in the node's gossipsub mesh for each topic",
+            metrics.mesh_peers.clone(),
+        );
+        registry.register(
+            "relays",
+            "Relay reservations the node holds, by status",
+            metrics.relays.clone(),
+        );
+        registry.register(
+            "pending_dials",
+            "Dials the node is waiting on",
+            metrics.pending_dials.clone(),
+        );
+        registry.register(
+            "pending_dht_queries",
+            "DHT queries the node is waiting on",
+            metrics.pending_dht_queries.clone(),
+        );
+        registry.register(
+            "pending_deliveries",
+            "Reliable deliveries awaiting acknowledgement",
+            metrics.pending_deliveries.clone(),
+        );
+        registry.register(
+            "uptime_seconds",
+            "How long the node has been running",
+            metrics.uptime.clone(),
+        );
+        registry.register(
+            "connections",
+            "Connections the node has established since it started",
+            metrics.connections.clone(),
+        );
+        registry.register(
+            "messages_published",
+            "Gossipsub messages the node has published since it started",
+            metrics.messages_published.clone(),
+        );
+        registry.register(
+            "messages_received",
+            "Gossipsub messages the node has received since it started",
+            metrics.messages_received.clone(),
+        );
+        registry.register(
+            "holepunches_succeeded",
+            "Hole punches the node has completed since it started",
+            metrics.holepunches_succeeded.clone(),
+        );
+        registry.register(
+            "holepunches_failed",
+            "Hole punches the node has failed since it started",
+            metrics.holepunches_failed.clone(),
+        );
+        metrics
+    }
+
+    /// Export what a poll of `node` found.
+    pub fn record(&mut self, node: &str, sample: &NodeSample) {
+        let labels = NodeLabels {
+            node: node.to_string(),
+        };
+        let set = |family: &Family<NodeLabels, Gauge>, value: u64| {
+            family.get_or_create(&labels).set(value as i64);
+        };
+        set(&self.up, 1);
+        set(&self.connected_peers, sample.connected_peers);
+        if let Some(peers) = sample.routing_table_peers {
+            set(&self.routing_table_peers, peers);
+        }
+        if let Some(stats) = &sample.stats {
+            set(&self.uptime, stats.uptime_secs);
+            set(&self.connections, stats.connections);
+            set(&self.messages_published, stats.messages_published);
+            set(&self.messages_received, stats.messages_received);
+            set(&self.holepunches_succeeded, stats.holepunches_succeeded);
+            set(&self.holepunches_failed, stats.holepunches_failed);
+        }
+        let Some(state) = &sample.state else {
+            return;
+        };
+        set(&self.pending_dials, state.pending_dials);
+        set(&self.pending_dht_queries, state.pending_dht_queries);
+        set(&self.pending_deliveries, state.pending_deliveries);
+        for status in RELAY_STATUSES {
+            let relays = state
+                .relays
+                .iter()
+                .filter(|relay| relay.status == status)
+                .count();
+            let labels = RelayLabels {
+                node: node.to_string(),
+                status: status.to_string(),
+            };
+            self.relays.get_or_create(&labels).set(relays as i64);
+        }
+
+        let topics: BTreeSet<String> = state.mesh.keys().cloned().collect();
+        let previous = self.mesh_topics.insert(node.to_string(), topics);
+        for topic in previous.unwrap_or_default() {
+            if !state.mesh.contains_key(&topic) {
+                self.mesh_peers.remove(&TopicLabels {
+                    node: node.to_string(),
+                    topic,
+                });
+            }
+        }
+        for (topic, peers) in &state.mesh {
+            let labels = TopicLabels {
+                node: node.to_string(),
+                topic: topic.clone(),
+            };
+            self.mesh_peers
+                .get_or_create(&labels)
+                .set(peers.len() as i64);
+        }
+    }
+
+    /// Record that `node` did not answer its poll. Its other metrics keep their last values.
+    pub fn record_down(&mut self, node: &str) {
+        let labels = NodeLabels {
+            node: node.to_string(),
+        };
+        self.up.get_or_create(&labels).set(0);
+    }
+}
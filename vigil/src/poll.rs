@@ -0,0 +1,179 @@
+use crate::config::Target;
+use jsonrpsee::core::client::{ClientT, Error as ClientError};
+use jsonrpsee::http_client::{HeaderMap, HeaderValue, HttpClient, HttpClientBuilder};
+use jsonrpsee::rpc_params;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::time::Duration;
+
+/// How many peers are asked for in each page of a node's peers.
+const PAGE_SIZE: usize = 1000;
+
+/// A node's totals since it started, as its `stats` method reports them.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SessionStats {
+    pub uptime_secs: u64,
+    pub connections: u64,
+    pub messages_published: u64,
+    pub messages_received: u64,
+    pub holepunches_succeeded: u64,
+    pub holepunches_failed: u64,
+}
+
+/// The parts of a node's `debug_dumpState` that are exported.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StateDump {
+    /// The peers in the node's mesh for each subscribed topic.
+    pub mesh: BTreeMap<String, Vec<String>>,
+    pub relays: Vec<RelayState>,
+    pub pending_dials: u64,
+    pub pending_dht_queries: u64,
+    pub pending_deliveries: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RelayState {
+    pub peer_id: String,
+
+    /// Whether the reservation is `pending`, `accepted` or `closed`.
+    pub status: String,
+}
+
+/// A node's identity, as its `node_info` method reports it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NodeInfo {
+    pub peer_id: String,
+    #[serde(default)]
+    pub node_name: Option<String>,
+    pub agent_version: String,
+    pub listen_addresses: Vec<String>,
+}
+
+/// A peer a node is connected to, as its `peers` method reports it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PeerInfo {
+    pub peer_id: String,
+    pub node_name: Option<String>,
+    pub agent_version: Option<String>,
+    pub listen_addresses: Vec<String>,
+}
+
+/// One page of a paged listing.
+#[derive(Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+/// Everything a node reports about its place in the network, for mapping its topology.
+#[derive(Clone, Debug)]
+pub struct NodeView {
+    pub info: NodeInfo,
+    pub peers: Vec<PeerInfo>,
+
+    /// The peers in the node's routing table, or none if its DHT is compiled out.
+    pub routing_table: Vec<String>,
+
+    /// The node's meshes and relays, if its debug namespace is open to us.
+    pub state: Option<StateDump>,
+}
+
+/// What one poll of a node found. Only the connected peers must be readable for the node to count
+/// as up; each other part is missing when the node does not offer it to us, such as when its DHT
+/// is compiled out or its debug namespace needs a token we lack.
+#[derive(Clone, Debug, Default)]
+pub struct NodeSample {
+    pub connected_peers: u64,
+    pub routing_table_peers: Option<u64>,
+    pub stats: Option<SessionStats>,
+    pub state: Option<StateDump>,
+}
+
+/// A sigil node, polled over its JSON-RPC API.
+#[derive(Clone, Debug)]
+pub struct Node {
+    name: String,
+    client: HttpClient,
+}
+
+impl Node {
+    pub fn new(target: &Target, timeout: Duration) -> Result<Self, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &target.token {
+            headers.insert(
+                "authorization",
+                HeaderValue::from_str(&format!("Bearer {token}"))?,
+            );
+        }
+        let client = HttpClientBuilder::default()
+            .set_headers(headers)
+            .request_timeout(timeout)
+            .build(&target.url)
+            .map_err(|e| format!("invalid target {}: {e}", target.url))?;
+        Ok(Self {
+            name: target.name().to_string(),
+            client,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the node's identity, connections, routing table and meshes.
+    pub async fn inspect(&self) -> Result<NodeView, ClientError> {
+        let info: NodeInfo = self.client.request("node_info", rpc_params![]).await?;
+        let mut peers = Vec::new();
+        let mut cursor = None;
+        loop {
+            let query = json!({ "cursor": cursor, "limit": PAGE_SIZE });
+            let page: Page<PeerInfo> = self.client.request("peers", rpc_params![query]).await?;
+            peers.extend(page.items);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        let routing_table: HashMap<String, Vec<String>> = self
+            .client
+            .request("kademlia_routing_table_peers", rpc_params![])
+            .await
+            .unwrap_or_default();
+        Ok(NodeView {
+            info,
+            peers,
+            routing_table: routing_table.into_keys().collect(),
+            state: self
+                .client
+                .request("debug_dumpState", rpc_params![])
+                .await
+                .ok(),
+        })
+    }
+
+    pub async fn poll(&self) -> Result<NodeSample, ClientError> {
+        let peers: Vec<String> = self
+            .client
+            .request("connected_peers", rpc_params![])
+            .await?;
+        let routing_table: Option<HashMap<String, Vec<String>>> = self
+            .client
+            .request("kademlia_routing_table_peers", rpc_params![])
+            .await
+            .ok();
+        Ok(NodeSample {
+            connected_peers: peers.len() as u64,
+            routing_table_peers: routing_table.map(|peers| peers.len() as u64),
+            stats: self.client.request("stats", rpc_params![]).await.ok(),
+            state: self
+                .client
+                .request("debug_dumpState", rpc_params![])
+                .await
+                .ok(),
+        })
+    }
+}
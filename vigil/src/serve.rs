@@ -0,0 +1,59 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The largest scrape request we read, which is far more than Prometheus sends.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Serve the registry's metrics at `/metrics` to every connection on `listener`.
+pub async fn serve(listener: TcpListener, registry: Arc<Registry>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                println!("Failed to accept metrics connection: {e}");
+                continue;
+            }
+        };
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = scrape(stream, &registry).await {
+                println!("Metrics connection failed: {e}");
+            }
+        });
+    }
+}
+
+/// Answer one HTTP request, with the metrics if it asks for `/metrics`.
+async fn scrape(mut stream: TcpStream, registry: &Registry) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = line.split(|&b| b == b' ');
+    let (method, path) = (parts.next(), parts.next());
+    let path = path.map(|path| path.split(|&b| b == b'?').next().unwrap_or_default());
+    let response = match (method, path) {
+        (Some(b"GET"), Some(b"/metrics")) => {
+            let mut body = String::new();
+            encode(&mut body, registry).map_err(std::io::Error::other)?;
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
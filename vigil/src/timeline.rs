@@ -0,0 +1,182 @@
+use crate::config::Target;
+use jsonrpsee::core::client::SubscriptionClientT;
+use jsonrpsee::http_client::{HeaderMap, HeaderValue};
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// One line of a node's log or one of its events, as merged into the timeline.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When it happened, in milliseconds since the Unix epoch. Log lines carry the node's own
+    /// clock, while events are stamped when they reach us, as sigil does not time them.
+    pub at: u64,
+    pub node: String,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Record {
+    Log {
+        level: String,
+        target: String,
+        message: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        fields: BTreeMap<String, String>,
+    },
+    /// A peer connecting or disconnecting, as the node's `subscribe_peer_events` streams it.
+    Event { event: Value },
+}
+
+/// A log line as the node's `subscribe_logs` streams it.
+#[derive(Deserialize)]
+struct LogLine {
+    at: u64,
+    level: String,
+    target: String,
+    message: String,
+    #[serde(default)]
+    fields: BTreeMap<String, String>,
+}
+
+impl Entry {
+    /// The entry as one line of text, led by its time in seconds since the Unix epoch.
+    pub fn to_line(&self) -> String {
+        let mut line = format!("{}.{:03} {}", self.at / 1000, self.at % 1000, self.node);
+        match &self.record {
+            Record::Log {
+                level,
+                target,
+                message,
+                fields,
+            } => {
+                let _ = write!(line, " {level:>5} {target}: {message}");
+                for (name, value) in fields {
+                    let _ = write!(line, " {name}={value}");
+                }
+            }
+            Record::Event { event } => {
+                let _ = write!(line, " EVENT {event}");
+            }
+        }
+        line
+    }
+}
+
+/// Merges the entries of several nodes into one timeline, ordered by time. Entries arrive from
+/// each node a little late and out of step with the others, so each is held for `window` before
+/// it is released, letting any earlier entries still on their way take their place before it.
+pub struct Timeline {
+    window: u64,
+    received: u64,
+
+    /// Held entries by time, then by arrival to keep entries with the same time in order.
+    held: BTreeMap<(u64, u64), Entry>,
+}
+
+impl Timeline {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.as_millis() as u64,
+            received: 0,
+            held: BTreeMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.held.insert((entry.at, self.received), entry);
+        self.received += 1;
+    }
+
+    /// The entries that have been held for the window by `now`, in milliseconds since the Unix
+    /// epoch, oldest first.
+    pub fn release(&mut self, now: u64) -> Vec<Entry> {
+        let held = self
+            .held
+            .split_off(&(now.saturating_sub(self.window) + 1, 0));
+        std::mem::replace(&mut self.held, held)
+            .into_values()
+            .collect()
+    }
+
+    /// Every entry still held, oldest first, for when no more will arrive.
+    pub fn finish(&mut self) -> Vec<Entry> {
+        std::mem::take(&mut self.held).into_values().collect()
+    }
+}
+
+/// Stream `target`'s log lines matching `filter` and its peer events into `entries`, until the
+/// node closes either stream or the receiver is dropped.
+pub async fn follow(
+    target: &Target,
+    filter: Option<&str>,
+    timeout: Duration,
+    entries: mpsc::Sender<Entry>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = &target.token {
+        headers.insert(
+            "authorization",
+            HeaderValue::from_str(&format!("Bearer {token}"))?,
+        );
+    }
+    let client = WsClientBuilder::default()
+        .set_headers(headers)
+        .connection_timeout(timeout)
+        .request_timeout(timeout)
+        .build(target.ws_url())
+        .await?;
+    let mut logs = client
+        .subscribe::<LogLine, _>("subscribe_logs", rpc_params![filter], "unsubscribe_logs")
+        .await?;
+    let mut events = client
+        .subscribe::<Value, _>(
+            "subscribe_peer_events",
+            rpc_params![],
+            "unsubscribe_peer_events",
+        )
+        .await?;
+    let node = target.name().to_string();
+    loop {
+        let entry = tokio::select! {
+            log = logs.next() => {
+                let log = log.ok_or("the node closed its log stream")??;
+                Entry {
+                    at: log.at,
+                    node: node.clone(),
+                    record: Record::Log {
+                        level: log.level,
+                        target: log.target,
+                        message: log.message,
+                        fields: log.fields,
+                    },
+                }
+            }
+            event = events.next() => Entry {
+                at: unix_millis(),
+                node: node.clone(),
+                record: Record::Event {
+                    event: event.ok_or("the node closed its event stream")??,
+                },
+            },
+        };
+        if entries.send(entry).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// A map of a sigil network: the peers seen by the crawled nodes, and how they are linked.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    /// Every peer seen, by peer id.
+    pub peers: BTreeMap<String, TopologyPeer>,
+    pub edges: BTreeSet<Edge>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopologyPeer {
+    pub node_name: Option<String>,
+    pub agent_version: Option<String>,
+    pub listen_addresses: Vec<String>,
+
+    /// The RPC endpoint the peer was crawled through, or `None` if it was only seen by others.
+    pub rpc: Option<String>,
+}
+
+/// A link from one peer to another, as the first reported it.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+
+    /// The topic of a mesh edge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// The peers have an open connection.
+    Connection,
+    /// The second peer is in the first's Kademlia routing table.
+    RoutingTable,
+    /// The second peer is in the first's gossipsub mesh for a topic.
+    Mesh,
+    /// The first peer holds a reservation on the second, a relay.
+    Relay,
+}
+
+impl Topology {
+    /// Record `peer_id`, merging what is known of it with what was seen before.
+    pub fn add_peer(&mut self, peer_id: &str, peer: TopologyPeer) {
+        let known = self.peers.entry(peer_id.to_string()).or_default();
+        known.node_name = peer.node_name.or(known.node_name.take());
+        known.agent_version = peer.agent_version.or(known.agent_version.take());
+        for address in peer.listen_addresses {
+            if !known.listen_addresses.contains(&address) {
+                known.listen_addresses.push(address);
+            }
+        }
+        known.rpc = peer.rpc.or(known.rpc.take());
+    }
+
+    /// Record an edge, and its ends as peers if they are not known yet. Connections have no
+    /// direction, so both ends reporting one records a single edge.
+    pub fn add_edge(&mut self, from: &str, to: &str, kind: EdgeKind, topic: Option<&str>) {
+        let (from, to) = match kind {
+            EdgeKind::Connection if to < from => (to, from),
+            _ => (from, to),
+        };
+        for peer_id in [from, to] {
+            self.peers.entry(peer_id.to_string()).or_default();
+        }
+        self.edges.insert(Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind,
+            topic: topic.map(str::to_string),
+        });
+    }
+
+    /// The topology in Graphviz's DOT language. Peers are labelled by name where they have one,
+    /// and crawled peers are drawn filled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph sigil {\n");
+        for (peer_id, peer) in &self.peers {
+            let label = peer
+                .node_name
+                .as_deref()
+                .unwrap_or_else(|| short_id(peer_id));
+            let style = match peer.rpc {
+                Some(_) => ", style=filled",
+                None => "",
+            };
+            let _ = writeln!(dot, "  \"{peer_id}\" [label=\"{}\"{style}];", escape(label));
+        }
+        for edge in &self.edges {
+            let attributes = match (edge.kind, &edge.topic) {
+                (EdgeKind::Connection, _) => "dir=none".to_string(),
+                (EdgeKind::RoutingTable, _) => "style=dashed, color=gray".to_string(),
+                (EdgeKind::Mesh, topic) => format!(
+                    "color=blue, label=\"{}\"",
+                    escape(topic.as_deref().unwrap_or_default())
+                ),
+                (EdgeKind::Relay, _) => "style=dotted, color=red".to_string(),
+            };
+            let _ = writeln!(
+                dot,
+                "  \"{}\" -> \"{}\" [{attributes}];",
+                edge.from, edge.to
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// The end of a peer id, which is enough to tell peers apart in a drawing.
+fn short_id(peer_id: &str) -> &str {
+    let start = peer_id.len().saturating_sub(8);
+    peer_id.get(start..).unwrap_or(peer_id)
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
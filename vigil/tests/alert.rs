@@ -0,0 +1,78 @@
+use vigil::alert::{AlertStatus, Alerts};
+use vigil::config::{Config, Rule};
+use vigil::poll::{NodeSample, SessionStats};
+
+fn sample(peers: u64) -> NodeSample {
+    NodeSample {
+        connected_peers: peers,
+        ..NodeSample::default()
+    }
+}
+
+#[test]
+fn test_alerts_fire_after_consecutive_broken_polls_and_resolve() {
+    let config = Config::parse(
+        r#"
+        [alerts]
+        after_polls = 2
+        webhooks = ["http://alerts.example/hook"]
+
+        [[alerts.rules]]
+        kind = "peer_count"
+        min = 3
+
+        [[alerts.rules]]
+        kind = "unreachable"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.alerts.rules[0], Rule::PeerCount { min: 3 });
+    let mut alerts = Alerts::new(&config.alerts);
+
+    // One broken poll is tolerated, and a healthy one starts the count again.
+    assert!(alerts.evaluate("alpha", Some(&sample(1))).is_empty());
+    assert!(alerts.evaluate("alpha", Some(&sample(5))).is_empty());
+    assert!(alerts.evaluate("alpha", Some(&sample(1))).is_empty());
+    let fired = alerts.evaluate("alpha", Some(&sample(2)));
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].rule, "peer_count");
+    assert_eq!(fired[0].status, AlertStatus::Firing);
+    assert_eq!(fired[0].message, "2 peers connected, expecting 3");
+
+    // A node that is down cannot be judged on its peers, so only its reachability alerts.
+    assert!(alerts.evaluate("alpha", None).is_empty());
+    let fired = alerts.evaluate("alpha", None);
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].rule, "unreachable");
+
+    let resolved = alerts.evaluate("alpha", Some(&sample(4)));
+    assert_eq!(resolved.len(), 2);
+    assert!(resolved
+        .iter()
+        .all(|alert| alert.status == AlertStatus::Resolved));
+
+    // Rules are tracked for each node apart.
+    assert!(alerts.evaluate("bravo", Some(&sample(1))).is_empty());
+}
+
+#[test]
+fn test_holepunch_success_rate_waits_for_enough_attempts() {
+    let rule = Rule::HolepunchSuccessRate {
+        min_percent: 50.0,
+        min_attempts: 10,
+    };
+    let with_holepunches = |succeeded, failed| NodeSample {
+        stats: Some(SessionStats {
+            holepunches_succeeded: succeeded,
+            holepunches_failed: failed,
+            ..SessionStats::default()
+        }),
+        ..NodeSample::default()
+    };
+    assert_eq!(rule.check(Some(&with_holepunches(1, 5))), None);
+    let (broken, message) = rule.check(Some(&with_holepunches(3, 9))).unwrap();
+    assert!(broken);
+    assert_eq!(message, "25.0% of 12 hole punches succeeded, expecting 50%");
+    assert!(!rule.check(Some(&with_holepunches(9, 3))).unwrap().0);
+    assert!(Config::parse("[[alerts.rules]]\nkind = \"peer_count\"\nmax = 3").is_err());
+}
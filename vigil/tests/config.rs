@@ -0,0 +1,24 @@
+use vigil::config::Config;
+
+#[test]
+fn test_targets_are_named_by_their_url_by_default() {
+    let config = Config::parse(
+        r#"
+        poll_interval_secs = 30
+
+        [[targets]]
+        url = "http://10.0.0.1:3030"
+
+        [[targets]]
+        name = "relay"
+        url = "http://10.0.0.2:3030"
+        token = "secret"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.poll_interval().as_secs(), 30);
+    assert_eq!(config.targets[0].name(), "http://10.0.0.1:3030");
+    assert_eq!(config.targets[1].name(), "relay");
+    assert_eq!(config.listen, Config::default().listen);
+    assert!(Config::parse("targets = 1").is_err());
+}
@@ -0,0 +1,60 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use vigil::metrics::Metrics;
+use vigil::poll::{NodeSample, StateDump};
+
+fn exported(registry: &Registry) -> String {
+    let mut text = String::new();
+    encode(&mut text, registry).unwrap();
+    text
+}
+
+#[test]
+fn test_samples_are_exported_per_node() {
+    let mut registry = Registry::with_prefix("vigil");
+    let mut metrics = Metrics::new(&mut registry);
+
+    // The dump is read as sigil serves it, with fields vigil does not export.
+    let state: StateDump = serde_json::from_str(
+        r#"{
+            "api_version": "1.0",
+            "info": {"peer_id": "12D3KooW"},
+            "connections": [],
+            "relays": [{"peer_id": "12D3KooW", "status": "accepted"}],
+            "mesh": {"test-net": ["a", "b"], "mempool": ["a"]},
+            "pending_dials": 1,
+            "pending_dht_queries": 2,
+            "pending_deliveries": 0
+        }"#,
+    )
+    .unwrap();
+    let sample = NodeSample {
+        connected_peers: 3,
+        routing_table_peers: None,
+        stats: None,
+        state: Some(state.clone()),
+    };
+    metrics.record("alpha", &sample);
+    let text = exported(&registry);
+    assert!(text.contains(r#"vigil_up{node="alpha"} 1"#));
+    assert!(text.contains(r#"vigil_connected_peers{node="alpha"} 3"#));
+    assert!(text.contains(r#"vigil_mesh_peers{node="alpha",topic="test-net"} 2"#));
+    assert!(text.contains(r#"vigil_relays{node="alpha",status="accepted"} 1"#));
+    assert!(text.contains(r#"vigil_relays{node="alpha",status="closed"} 0"#));
+    assert!(!text.contains("vigil_routing_table_peers{"));
+
+    // Meshes the node has left stop being exported, and a failed poll marks it down.
+    let mut state = state;
+    state.mesh.remove("mempool");
+    metrics.record(
+        "alpha",
+        &NodeSample {
+            state: Some(state),
+            ..sample
+        },
+    );
+    metrics.record_down("beta");
+    let text = exported(&registry);
+    assert!(!text.contains(r#"topic="mempool""#));
+    assert!(text.contains(r#"vigil_up{node="beta"} 0"#));
+}
@@ -0,0 +1,85 @@
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use vigil::config::Target;
+use vigil::timeline::{Entry, Record, Timeline};
+
+fn log(at: u64, node: &str, message: &str) -> Entry {
+    Entry {
+        at,
+        node: node.to_string(),
+        record: Record::Log {
+            level: "INFO".to_string(),
+            target: "sigil".to_string(),
+            message: message.to_string(),
+            fields: BTreeMap::new(),
+        },
+    }
+}
+
+#[test]
+fn test_timeline_orders_entries_from_every_node_by_time() {
+    let mut timeline = Timeline::new(Duration::from_millis(500));
+    timeline.push(log(1_000, "alpha", "dialing bravo"));
+    timeline.push(log(1_300, "alpha", "hole punch failed"));
+    // Bravo's line arrives late but happened in between.
+    timeline.push(log(1_200, "bravo", "inbound hole punch"));
+    timeline.push(log(1_200, "alpha", "same time, arrived later"));
+
+    // Entries are only released once held for the whole window.
+    assert!(timeline.release(1_499).is_empty());
+    let released: Vec<_> = timeline
+        .release(1_700)
+        .into_iter()
+        .map(|entry| entry.at)
+        .collect();
+    assert_eq!(released, [1_000, 1_200, 1_200]);
+    let finished = timeline.finish();
+    assert_eq!(finished, [log(1_300, "alpha", "hole punch failed")]);
+    assert!(timeline.finish().is_empty());
+}
+
+#[test]
+fn test_timeline_entries_render_as_lines_and_json() {
+    let mut entry = log(1_700_000_000_042, "alpha", "dialing");
+    if let Record::Log { fields, .. } = &mut entry.record {
+        fields.insert("peer".to_string(), "12D3KooWBravo".to_string());
+    }
+    assert_eq!(
+        entry.to_line(),
+        "1700000000.042 alpha  INFO sigil: dialing peer=12D3KooWBravo"
+    );
+    let event = Entry {
+        at: 5,
+        node: "bravo".to_string(),
+        record: Record::Event {
+            event: json!({ "type": "peer_connected", "peer": "12D3KooWAlpha" }),
+        },
+    };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        json!({
+            "at": 5,
+            "node": "bravo",
+            "source": "event",
+            "event": { "type": "peer_connected", "peer": "12D3KooWAlpha" },
+        })
+    );
+}
+
+#[test]
+fn test_target_ws_url_defaults_to_its_rpc_url() {
+    assert_eq!(
+        Target::new("http://10.0.0.1:3030").ws_url(),
+        "ws://10.0.0.1:3030"
+    );
+    assert_eq!(
+        Target::new("https://node.example").ws_url(),
+        "wss://node.example"
+    );
+    let target = Target {
+        ws_url: Some("ws://10.0.0.1:3031".to_string()),
+        ..Target::new("http://10.0.0.1:3030")
+    };
+    assert_eq!(target.ws_url(), "ws://10.0.0.1:3031");
+}
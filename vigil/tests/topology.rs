@@ -0,0 +1,56 @@
+use vigil::crawl::rpc_url;
+use vigil::topology::{EdgeKind, Topology, TopologyPeer};
+
+#[test]
+fn test_topology_merges_what_each_node_reports() {
+    let mut topology = Topology::default();
+    topology.add_peer(
+        "12D3KooWAlpha",
+        TopologyPeer {
+            node_name: Some("alpha".to_string()),
+            rpc: Some("http://10.0.0.1:3030".to_string()),
+            ..TopologyPeer::default()
+        },
+    );
+    // Both ends report their connection, which is recorded once.
+    topology.add_edge("12D3KooWAlpha", "12D3KooWBravo", EdgeKind::Connection, None);
+    topology.add_edge("12D3KooWBravo", "12D3KooWAlpha", EdgeKind::Connection, None);
+    topology.add_edge(
+        "12D3KooWAlpha",
+        "12D3KooWBravo",
+        EdgeKind::Mesh,
+        Some("test-net"),
+    );
+    assert_eq!(topology.peers.len(), 2);
+    assert_eq!(topology.edges.len(), 2);
+
+    // Peers seen again keep what was learned of them before.
+    topology.add_peer("12D3KooWAlpha", TopologyPeer::default());
+    assert_eq!(
+        topology.peers["12D3KooWAlpha"].node_name.as_deref(),
+        Some("alpha")
+    );
+
+    let dot = topology.to_dot();
+    assert!(dot.contains(r#""12D3KooWAlpha" [label="alpha", style=filled];"#));
+    assert!(dot.contains(r#""12D3KooWBravo" [label="ooWBravo"];"#));
+    assert!(dot.contains(r#""12D3KooWAlpha" -> "12D3KooWBravo" [color=blue, label="test-net"];"#));
+}
+
+#[test]
+fn test_peers_are_followed_to_rpc_on_reachable_hosts() {
+    assert_eq!(
+        rpc_url("/ip4/10.0.0.2/tcp/4001", 3030).as_deref(),
+        Some("http://10.0.0.2:3030")
+    );
+    assert_eq!(
+        rpc_url("/ip6/fd00::2/udp/4001/quic-v1", 3030).as_deref(),
+        Some("http://[fd00::2]:3030")
+    );
+    assert_eq!(
+        rpc_url("/dns4/sigil-1/tcp/4001", 3030).as_deref(),
+        Some("http://sigil-1:3030")
+    );
+    assert_eq!(rpc_url("/ip4/127.0.0.1/tcp/4001", 3030), None);
+    assert_eq!(rpc_url("/p2p-circuit", 3030), None);
+}